@@ -0,0 +1,49 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use git_introspection::bench::{Fixture, GRAPH_WORKLOADS, WORKLOADS};
+
+/// Sizes of the generated repositories, in commits.
+const SIZES: [usize; 2] = [1_000, 10_000];
+
+fn workloads(c: &mut Criterion) {
+    for workload in &WORKLOADS {
+        let mut group = c.benchmark_group(workload.name);
+        // A scan of 10,000 commits diffs all of them, a few samples are enough
+        group.sample_size(10);
+        for commits in SIZES {
+            let fixture = Fixture::generate(commits).expect("generating the fixture");
+            let db = fixture.connect().expect("registering the tables");
+            let rows = workload.run(&db, &fixture).expect("running the workload");
+            group.throughput(Throughput::Elements(rows as u64));
+            group.bench_function(BenchmarkId::from_parameter(commits), |b| {
+                b.iter(|| workload.run(&db, &fixture).expect("running the workload"))
+            });
+        }
+        group.finish();
+    }
+}
+
+/// The revwalks and ancestry checks of the largest fixture, without and with its commit-graph.
+fn commit_graph(c: &mut Criterion) {
+    let fixture = Fixture::generate(SIZES[SIZES.len() - 1]).expect("generating the fixture");
+    for workload in &GRAPH_WORKLOADS {
+        let mut group = c.benchmark_group(format!("commit_graph/{}", workload.name));
+        for enabled in [false, true] {
+            fixture
+                .set_commit_graph(enabled)
+                .expect("writing the commit-graph");
+            let db = fixture.connect().expect("registering the tables");
+            let name = if enabled { "with" } else { "without" };
+            group.bench_function(name, |b| {
+                b.iter(|| workload.run(&db, &fixture).expect("running the workload"))
+            });
+        }
+        group.finish();
+    }
+    // The other benchmarks read the fixture without it
+    fixture
+        .set_commit_graph(false)
+        .expect("removing the commit-graph");
+}
+
+criterion_group!(benches, workloads, commit_graph);
+criterion_main!(benches);
@@ -0,0 +1,245 @@
+use crate::CustomError;
+use arrow_array::builder::{
+    BinaryBuilder, BooleanBuilder, Float64Builder, Int64Builder, StringBuilder,
+    TimestampSecondBuilder,
+};
+use arrow_array::RecordBatch;
+use arrow_schema::{ArrowError, DataType, Field, Schema, TimeUnit};
+use chrono::{DateTime, FixedOffset};
+use itertools::Itertools;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use rusqlite::types::Value;
+use rusqlite::Statement;
+use std::io::Write;
+use std::sync::Arc;
+
+const BATCH_SIZE: usize = 8192;
+
+/// Writes the result of `stmt` as a snappy compressed parquet file.
+///
+/// Column types come from the declared type of the column and fall back to the values of the
+/// first batch for expressions, DATETIME columns become UTC timestamps. Rows are read and written
+/// in batches so the result set is never held in memory as a whole.
+pub fn execute_and_write_parquet<W: Write + Send>(
+    stmt: &mut Statement,
+    out: W,
+) -> Result<(), CustomError> {
+    let columns = stmt
+        .columns()
+        .iter()
+        .map(|c| (c.name().to_string(), c.decl_type().and_then(declared_type)))
+        .collect_vec();
+    let mut rows = stmt.raw_query();
+
+    let mut batch = next_batch(&mut rows, columns.len())?;
+    let fields = columns
+        .iter()
+        .enumerate()
+        .map(|(i, (name, declared))| {
+            let data_type = declared
+                .clone()
+                .unwrap_or_else(|| inferred_type(batch.iter().map(|row| &row[i])));
+            Field::new(name, data_type, true)
+        })
+        .collect_vec();
+    let schema = Arc::new(Schema::new(fields));
+
+    let props = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let mut writer = ArrowWriter::try_new(out, schema.clone(), Some(props))?;
+    loop {
+        writer.write(&to_record_batch(&schema, &batch)?)?;
+        if batch.len() < BATCH_SIZE {
+            break;
+        }
+        batch = next_batch(&mut rows, columns.len())?;
+    }
+    writer.close()?;
+
+    Ok(())
+}
+
+fn next_batch(rows: &mut rusqlite::Rows, col_count: usize) -> rusqlite::Result<Vec<Vec<Value>>> {
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    while batch.len() < BATCH_SIZE {
+        match rows.next()? {
+            Some(row) => batch.push(
+                (0..col_count)
+                    .map(|i| row.get_ref(i).map(Value::from))
+                    .try_collect()?,
+            ),
+            None => break,
+        }
+    }
+    Ok(batch)
+}
+
+fn declared_type(decl: &str) -> Option<DataType> {
+    let decl = decl.to_ascii_uppercase();
+    if decl.contains("DATETIME") || decl.contains("TIMESTAMP") {
+        Some(DataType::Timestamp(TimeUnit::Second, Some("UTC".into())))
+    } else if decl.contains("BOOL") {
+        Some(DataType::Boolean)
+    } else if decl.contains("INT") {
+        Some(DataType::Int64)
+    } else if decl.contains("REAL") || decl.contains("FLOA") || decl.contains("DOUB") {
+        Some(DataType::Float64)
+    } else if decl.contains("CHAR") || decl.contains("CLOB") || decl.contains("TEXT") {
+        Some(DataType::Utf8)
+    } else if decl.contains("BLOB") {
+        Some(DataType::Binary)
+    } else {
+        None
+    }
+}
+
+fn inferred_type<'a>(values: impl Iterator<Item = &'a Value>) -> DataType {
+    let kinds = values
+        .filter(|v| !matches!(v, Value::Null))
+        .map(std::mem::discriminant)
+        .unique()
+        .collect_vec();
+    let is = |v: Value| kinds.contains(&std::mem::discriminant(&v));
+    match kinds.len() {
+        1 if is(Value::Integer(0)) => DataType::Int64,
+        1 if is(Value::Real(0.0)) => DataType::Float64,
+        2 if is(Value::Integer(0)) && is(Value::Real(0.0)) => DataType::Float64,
+        1 if is(Value::Blob(vec![])) => DataType::Binary,
+        _ => DataType::Utf8,
+    }
+}
+
+fn to_record_batch(schema: &Arc<Schema>, rows: &[Vec<Value>]) -> Result<RecordBatch, ArrowError> {
+    let columns = schema
+        .fields()
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            let mut builder = ColumnBuilder::new(field.data_type(), rows.len());
+            rows.iter().try_for_each(|row| {
+                builder.append(&row[i]).map_err(|value| {
+                    ArrowError::InvalidArgumentError(format!(
+                        "column {} has type {} but contains {:?}, use CAST to pick one type",
+                        field.name(),
+                        field.data_type(),
+                        value
+                    ))
+                })
+            })?;
+            Ok(builder.finish())
+        })
+        .collect::<Result<Vec<_>, ArrowError>>()?;
+    RecordBatch::try_new(schema.clone(), columns)
+}
+
+enum ColumnBuilder {
+    Int64(Int64Builder),
+    Float64(Float64Builder),
+    Boolean(BooleanBuilder),
+    Utf8(StringBuilder),
+    Binary(BinaryBuilder),
+    Timestamp(TimestampSecondBuilder),
+}
+
+impl ColumnBuilder {
+    fn new(data_type: &DataType, capacity: usize) -> Self {
+        match data_type {
+            DataType::Int64 => ColumnBuilder::Int64(Int64Builder::with_capacity(capacity)),
+            DataType::Float64 => ColumnBuilder::Float64(Float64Builder::with_capacity(capacity)),
+            DataType::Boolean => ColumnBuilder::Boolean(BooleanBuilder::with_capacity(capacity)),
+            DataType::Binary => ColumnBuilder::Binary(BinaryBuilder::new()),
+            DataType::Timestamp(_, _) => ColumnBuilder::Timestamp(
+                TimestampSecondBuilder::with_capacity(capacity).with_timezone("UTC"),
+            ),
+            _ => ColumnBuilder::Utf8(StringBuilder::new()),
+        }
+    }
+
+    /// Appends `value`, returning it back when it can't be represented in this column.
+    fn append<'a>(&mut self, value: &'a Value) -> Result<(), &'a Value> {
+        match (self, value) {
+            (ColumnBuilder::Int64(b), Value::Null) => b.append_null(),
+            (ColumnBuilder::Int64(b), Value::Integer(i)) => b.append_value(*i),
+            (ColumnBuilder::Float64(b), Value::Null) => b.append_null(),
+            (ColumnBuilder::Float64(b), Value::Integer(i)) => b.append_value(*i as f64),
+            (ColumnBuilder::Float64(b), Value::Real(f)) => b.append_value(*f),
+            (ColumnBuilder::Boolean(b), Value::Null) => b.append_null(),
+            (ColumnBuilder::Boolean(b), Value::Integer(i)) => b.append_value(*i != 0),
+            (ColumnBuilder::Utf8(b), Value::Null) => b.append_null(),
+            (ColumnBuilder::Utf8(b), Value::Integer(i)) => b.append_value(i.to_string()),
+            (ColumnBuilder::Utf8(b), Value::Real(f)) => b.append_value(f.to_string()),
+            (ColumnBuilder::Utf8(b), Value::Text(t)) => b.append_value(t),
+            (ColumnBuilder::Utf8(b), Value::Blob(bytes)) => {
+                b.append_value(String::from_utf8_lossy(bytes))
+            }
+            (ColumnBuilder::Binary(b), Value::Null) => b.append_null(),
+            (ColumnBuilder::Binary(b), Value::Text(t)) => b.append_value(t),
+            (ColumnBuilder::Binary(b), Value::Blob(bytes)) => b.append_value(bytes),
+            (ColumnBuilder::Timestamp(b), Value::Null) => b.append_null(),
+            (ColumnBuilder::Timestamp(b), Value::Integer(seconds)) => b.append_value(*seconds),
+            (ColumnBuilder::Timestamp(b), Value::Text(t)) => {
+                let parsed = t
+                    .parse::<DateTime<FixedOffset>>()
+                    .or_else(|_| DateTime::parse_from_str(t, "%F %T%.f%:z"));
+                match parsed {
+                    Ok(when) => b.append_value(when.timestamp()),
+                    Err(_) => return Err(value),
+                }
+            }
+            _ => return Err(value),
+        };
+        Ok(())
+    }
+
+    fn finish(&mut self) -> arrow_array::ArrayRef {
+        match self {
+            ColumnBuilder::Int64(b) => Arc::new(b.finish()),
+            ColumnBuilder::Float64(b) => Arc::new(b.finish()),
+            ColumnBuilder::Boolean(b) => Arc::new(b.finish()),
+            ColumnBuilder::Utf8(b) => Arc::new(b.finish()),
+            ColumnBuilder::Binary(b) => Arc::new(b.finish()),
+            ColumnBuilder::Timestamp(b) => Arc::new(b.finish()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::arrow_export::execute_and_write_parquet;
+    use arrow_schema::{DataType, TimeUnit};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use rusqlite::Connection;
+
+    #[test]
+    fn parquet_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch(
+            r#"
+            CREATE TABLE t(n INTEGER, msg TEXT, at DATETIME);
+            INSERT INTO t VALUES (1, 'one', '2022-07-01 17:55:57+00:00'), (NULL, 'two', NULL);
+            "#,
+        )?;
+        let path = std::env::temp_dir().join("sqlitegit_parquet_roundtrip.parquet");
+        let mut stmt = db.prepare("SELECT n, msg, at, 1.5 AS r FROM t")?;
+        execute_and_write_parquet(&mut stmt, std::fs::File::create(&path)?)?;
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(&path)?)?;
+        let schema = reader.schema().clone();
+        let rows: usize = reader.build()?.map(|b| b.unwrap().num_rows()).sum();
+        std::fs::remove_file(&path)?;
+
+        assert_eq!(rows, 2);
+        assert_eq!(schema.field(0).data_type(), &DataType::Int64);
+        assert_eq!(schema.field(1).data_type(), &DataType::Utf8);
+        assert_eq!(
+            schema.field(2).data_type(),
+            &DataType::Timestamp(TimeUnit::Second, Some("UTC".into()))
+        );
+        assert_eq!(schema.field(3).data_type(), &DataType::Float64);
+
+        Ok(())
+    }
+}
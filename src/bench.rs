@@ -0,0 +1,248 @@
+use crate::{commit_graph, CustomError, SqliteGit};
+use git2::Repository;
+use rusqlite::Connection;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// A query measured by `sqlitegit bench` and the criterion benchmarks. It selects a single count,
+/// the number of rows it read.
+pub struct Workload {
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+/// The commits scan, the stats join and a point lookup of a single commit, `?1` is bound to the
+/// hash of a commit in the middle of the history.
+pub const WORKLOADS: [Workload; 3] = [
+    Workload {
+        name: "commits_scan",
+        sql: "SELECT count(message) FROM commits",
+    },
+    Workload {
+        name: "stats_join",
+        sql: "SELECT count(s.file_name) FROM commits c JOIN stats s ON s.hash = c.hash",
+    },
+    Workload {
+        name: "point_lookup",
+        sql: "SELECT count(message) FROM commits WHERE ref = ?1",
+    },
+];
+
+/// A revwalk of the whole history and an ancestry check across half of it, which libgit2 answers
+/// from the commit-graph when the repository has one. `?1` is bound like in [`WORKLOADS`].
+pub const GRAPH_WORKLOADS: [Workload; 2] = [
+    Workload {
+        name: "rev_count",
+        sql: "SELECT git_rev_count('HEAD')",
+    },
+    Workload {
+        name: "is_ancestor",
+        sql: "SELECT git_is_ancestor(?1, 'HEAD')",
+    },
+];
+
+impl Workload {
+    /// Runs the query once against `fixture`, returns the rows it read.
+    pub fn run(&self, db: &Connection, fixture: &Fixture) -> Result<i64, CustomError> {
+        let mut stmt = db.prepare_cached(self.sql)?;
+        if stmt.parameter_count() > 0 {
+            stmt.raw_bind_parameter(1, &fixture.middle)?;
+        }
+        let rows = stmt.raw_query().next()?.map(|row| row.get(0)).transpose()?;
+        Ok(rows.unwrap_or_default())
+    }
+}
+
+/// A generated repository with a linear history of `commits` commits, every 100th of them a merge.
+/// Each commit changes two of 17 files, and is authored by one of five people.
+pub struct Fixture {
+    pub path: PathBuf,
+    pub commits: usize,
+    /// The commit half-way down the history
+    pub middle: String,
+}
+
+impl Fixture {
+    /// Generates the fixture in the temp directory with `git fast-import`. A fixture of the same
+    /// size that was generated before is reused.
+    pub fn generate(commits: usize) -> Result<Fixture, CustomError> {
+        let path = std::env::temp_dir().join(format!("sqlitegit_bench_{}", commits));
+        let repo = match Repository::open(&path) {
+            // HEAD is only set once the import has finished
+            Ok(repo) if repo.head().is_ok() => repo,
+            _ => {
+                let _ = std::fs::remove_dir_all(&path);
+                import(Repository::init(&path)?, commits)?
+            }
+        };
+        let middle = repo
+            .revparse_single(&format!("HEAD~{}", commits / 2))?
+            .id()
+            .to_string();
+        Ok(Fixture {
+            path,
+            commits,
+            middle,
+        })
+    }
+
+    /// A connection with the git tables over the fixture. The stats aren't cached, every scan
+    /// diffs the commits again.
+    pub fn connect(&self) -> Result<Connection, CustomError> {
+        let db = Connection::open_in_memory()?;
+        SqliteGit::new()
+            .with_all()
+            .repository(&self.path)
+            .register(&db)?;
+        Ok(db)
+    }
+
+    /// Writes the commit-graph of the fixture, or removes it. Connections opened afterwards read
+    /// the history with it or without it.
+    pub fn set_commit_graph(&self, enabled: bool) -> Result<(), CustomError> {
+        let repo = Repository::open(&self.path)?;
+        let path = commit_graph::path(&repo);
+        match enabled {
+            true => {
+                commit_graph::write(&repo)?;
+            }
+            false if path.exists() => std::fs::remove_file(path)?,
+            false => {}
+        }
+        Ok(())
+    }
+}
+
+fn import(repo: Repository, commits: usize) -> Result<Repository, CustomError> {
+    let workdir = repo.workdir().unwrap_or_else(|| repo.path()).to_path_buf();
+    let mut import = Command::new("git")
+        .args(["fast-import", "--quiet"])
+        .current_dir(&workdir)
+        .stdin(Stdio::piped())
+        .spawn()?;
+    {
+        let stdin = import.stdin.take().ok_or_else(|| {
+            CustomError::Io(std::io::Error::other("git fast-import has no stdin"))
+        })?;
+        let mut stream = std::io::BufWriter::new(stdin);
+        for i in 1..=commits {
+            let when = 1_600_000_000 + i * 60;
+            let message = format!("Change {}\n\nTouches two of the files.\n", i);
+            writeln!(stream, "commit refs/heads/main\nmark :{}", i)?;
+            writeln!(
+                stream,
+                "author Developer {0} <developer{0}@example.com> {1} +0000",
+                i % 5,
+                when
+            )?;
+            writeln!(stream, "committer CI <ci@example.com> {} +0000", when)?;
+            write!(stream, "data {}\n{}", message.len(), message)?;
+            if i > 1 {
+                writeln!(stream, "from :{}", i - 1)?;
+            }
+            if i > 2 && i % 100 == 0 {
+                writeln!(stream, "merge :{}", i - 2)?;
+            }
+            for file in [format!("src/{}.rs", i % 10), format!("docs/{}.md", i % 7)] {
+                let content = format!("{}\n", i);
+                write!(
+                    stream,
+                    "M 644 inline {}\ndata {}\n{}",
+                    file,
+                    content.len(),
+                    content
+                )?;
+            }
+        }
+        stream.flush()?;
+    }
+    let status = import.wait()?;
+    if !status.success() {
+        return Err(CustomError::Io(std::io::Error::other(format!(
+            "git fast-import failed with {}",
+            status
+        ))));
+    }
+    repo.set_head("refs/heads/main")?;
+    Ok(repo)
+}
+
+/// Runs every workload `iterations` times against fixtures of each size in `sizes`, and prints the
+/// median time and throughput of each.
+pub fn run(sizes: &[usize], iterations: usize) -> Result<(), CustomError> {
+    println!(
+        "{:>8}  {:<12}  {:>8}  {:>10}  {:>12}",
+        "commits", "workload", "rows", "median", "rows/s"
+    );
+    for &commits in sizes {
+        let fixture = Fixture::generate(commits)?;
+        let db = fixture.connect()?;
+        for workload in &WORKLOADS {
+            let mut rows = 0;
+            let mut times = vec![];
+            for _ in 0..iterations.max(1) {
+                let start = Instant::now();
+                rows = workload.run(&db, &fixture)?;
+                times.push(start.elapsed());
+            }
+            times.sort();
+            let median = times[times.len() / 2];
+            println!(
+                "{:>8}  {:<12}  {:>8}  {:>10}  {:>12.0}",
+                commits,
+                workload.name,
+                rows,
+                format!("{:.1?}", median),
+                rows as f64 / median.max(Duration::from_nanos(1)).as_secs_f64()
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::bench::{Fixture, GRAPH_WORKLOADS, WORKLOADS};
+
+    #[test]
+    fn workloads_read_the_generated_history() -> Result<(), Box<dyn std::error::Error>> {
+        let fixture = Fixture::generate(250)?;
+        let db = fixture.connect()?;
+        let rows = WORKLOADS
+            .iter()
+            .map(|workload| workload.run(&db, &fixture))
+            .collect::<Result<Vec<_>, _>>()?;
+        let reused = Fixture::generate(250)?;
+        std::fs::remove_dir_all(&fixture.path)?;
+
+        // The root commit has no parent to diff against
+        assert_eq!(rows, [250, 2 * 249, 1]);
+        assert_eq!(reused.middle, fixture.middle);
+
+        Ok(())
+    }
+
+    #[test]
+    fn graph_workloads_read_the_same_with_a_commit_graph() -> Result<(), Box<dyn std::error::Error>>
+    {
+        let fixture = Fixture::generate(120)?;
+        let run = |enabled| -> Result<Vec<i64>, Box<dyn std::error::Error>> {
+            fixture.set_commit_graph(enabled)?;
+            let db = fixture.connect()?;
+            let rows = GRAPH_WORKLOADS
+                .iter()
+                .map(|workload| workload.run(&db, &fixture));
+            Ok(rows.collect::<Result<_, _>>()?)
+        };
+        let without = run(false)?;
+        let with = run(true)?;
+        std::fs::remove_dir_all(&fixture.path)?;
+
+        assert_eq!(without, [120, 1]);
+        assert_eq!(with, without);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,296 @@
+use crate::writable::{required, text, Row, Table};
+use crate::CustomError;
+use git2::{Branch, BranchType, Reference, Repository};
+use rusqlite::types::Value;
+use std::path::PathBuf;
+
+/// `branches([repo])`, the local branches of a repository, with how far they are ahead of and
+/// behind their upstream. INSERT creates a branch at `target`, a revision, HEAD when it's NULL.
+/// UPDATE renames a branch, moves it to another `target` or sets its `upstream`, DELETE removes
+/// it. Branches checked out in the repository or one of its worktrees can't be moved or deleted.
+pub(crate) static BRANCHES: Table = Table {
+    name: "branches",
+    columns: &[
+        ("name", "text"),
+        ("target", "text"),
+        ("upstream", "text"),
+        ("ahead", "integer"),
+        ("behind", "integer"),
+        ("is_head", "integer"),
+    ],
+    rows,
+    insert,
+    update: Some(update),
+    delete,
+};
+
+const NAME: usize = 0;
+const TARGET: usize = 1;
+const UPSTREAM: usize = 2;
+
+fn rows(repo: &Repository) -> Result<Vec<Row>, CustomError> {
+    let mut rows = vec![];
+    for branch in repo.branches(Some(BranchType::Local))? {
+        let (branch, _) = branch?;
+        let Some(name) = branch.get().shorthand() else {
+            continue;
+        };
+        let target = branch.get().target();
+        let upstream = branch.upstream().ok();
+        let upstream_target = upstream
+            .as_ref()
+            .and_then(|upstream| upstream.get().target());
+        let (ahead, behind) = match (target, upstream_target) {
+            (Some(target), Some(upstream)) => {
+                let (ahead, behind) = repo.graph_ahead_behind(target, upstream)?;
+                (Value::Integer(ahead as i64), Value::Integer(behind as i64))
+            }
+            _ => (Value::Null, Value::Null),
+        };
+        let upstream = upstream.and_then(|upstream| upstream.get().shorthand().map(String::from));
+        rows.push(vec![
+            Value::Text(name.to_string()),
+            target.map_or(Value::Null, |oid| Value::Text(oid.to_string())),
+            upstream.map_or(Value::Null, Value::Text),
+            ahead,
+            behind,
+            Value::Integer(branch.is_head().into()),
+        ]);
+    }
+    Ok(rows)
+}
+
+fn insert(repo: &Repository, row: &Row) -> Result<(), CustomError> {
+    let name = required(&BRANCHES, row, NAME)?;
+    let target = repo.revparse_single(text(&BRANCHES, row, TARGET)?.unwrap_or("HEAD"))?;
+    let mut branch = repo.branch(name, &target.peel_to_commit()?, false)?;
+    if let Some(upstream) = text(&BRANCHES, row, UPSTREAM)? {
+        branch.set_upstream(Some(upstream))?;
+    }
+    Ok(())
+}
+
+fn update(repo: &Repository, old: &Row, new: &Row) -> Result<(), CustomError> {
+    // The counts and is_head follow from the other columns
+    if old[UPSTREAM + 1..] != new[UPSTREAM + 1..] {
+        return Err(CustomError::InvalidArgument(
+            "only the name, target and upstream of branches can be set".to_string(),
+        ));
+    }
+    let name = required(&BRANCHES, old, NAME)?;
+    let mut branch = repo.find_branch(name, BranchType::Local)?;
+    let (new_target, new_upstream) = (
+        text(&BRANCHES, new, TARGET)?,
+        text(&BRANCHES, new, UPSTREAM)?,
+    );
+    if new_target != text(&BRANCHES, old, TARGET)? {
+        let target = repo.revparse_single(new_target.unwrap_or("HEAD"))?;
+        refuse_checked_out(repo, &branch, "moved")?;
+        let log = format!("branches: move {} to {}", name, target.id());
+        branch = Branch::wrap((branch.get_mut()).set_target(target.peel_to_commit()?.id(), &log)?);
+    }
+    if new_upstream != text(&BRANCHES, old, UPSTREAM)? {
+        branch.set_upstream(new_upstream)?;
+    }
+    let new_name = required(&BRANCHES, new, NAME)?;
+    if new_name != name {
+        // A rename moves HEAD along, but not the HEAD of another worktree
+        if let Some(worktree) = checked_out_elsewhere(repo, branch.get())? {
+            return Err(checked_out_error(name, "renamed", worktree));
+        }
+        branch.rename(new_name, false)?;
+    }
+    Ok(())
+}
+
+fn delete(repo: &Repository, row: &Row) -> Result<(), CustomError> {
+    let name = required(&BRANCHES, row, NAME)?;
+    let mut branch = repo.find_branch(name, BranchType::Local)?;
+    refuse_checked_out(repo, &branch, "deleted")?;
+    Ok(branch.delete()?)
+}
+
+/// Fails when `branch` is the HEAD of the repository or of one of its worktrees.
+fn refuse_checked_out(repo: &Repository, branch: &Branch, change: &str) -> Result<(), CustomError> {
+    let name = branch.get().shorthand().unwrap_or_default();
+    if head_is(repo, branch.get())? {
+        let path = repo.workdir().unwrap_or_else(|| repo.path());
+        return Err(checked_out_error(name, change, path.to_path_buf()));
+    }
+    match checked_out_elsewhere(repo, branch.get())? {
+        Some(worktree) => Err(checked_out_error(name, change, worktree)),
+        None => Ok(()),
+    }
+}
+
+/// The path of the worktree other than the repository itself that has `branch` checked out.
+fn checked_out_elsewhere(
+    repo: &Repository,
+    branch: &Reference,
+) -> Result<Option<PathBuf>, CustomError> {
+    for name in repo.worktrees()?.iter().flatten() {
+        let worktree = repo.find_worktree(name)?;
+        // A worktree whose directory is gone is pruned by git, it has nothing checked out
+        let Ok(opened) = Repository::open_from_worktree(&worktree) else {
+            continue;
+        };
+        if head_is(&opened, branch)? {
+            return Ok(Some(worktree.path().to_path_buf()));
+        }
+    }
+    Ok(None)
+}
+
+fn head_is(repo: &Repository, branch: &Reference) -> Result<bool, CustomError> {
+    let head = repo.find_reference("HEAD")?;
+    Ok(head.symbolic_target_bytes() == Some(branch.name_bytes()))
+}
+
+fn checked_out_error(name: &str, change: &str, worktree: PathBuf) -> CustomError {
+    CustomError::InvalidArgument(format!(
+        "{} is checked out at {} and can't be {}",
+        name,
+        worktree.display(),
+        change
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test::{commit_file, temp_repository};
+    use crate::SqliteGit;
+    use git2::{BranchType, WorktreeAddOptions};
+    use rusqlite::Connection;
+
+    type Branch = (
+        String,
+        String,
+        Option<String>,
+        Option<i64>,
+        Option<i64>,
+        bool,
+    );
+
+    fn branches(db: &Connection) -> rusqlite::Result<Vec<Branch>> {
+        let mut stmt = db.prepare(
+            "SELECT name, target, upstream, ahead, behind, is_head FROM branches ORDER BY name",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+                row.get(5)?,
+            ))
+        })?;
+        rows.collect()
+    }
+
+    #[test]
+    fn creates_renames_moves_and_deletes_branches() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("branches")?;
+        let first = commit_file(&repo, "file.txt", "one\n", "first")?;
+        let second = commit_file(&repo, "file.txt", "two\n", "second")?;
+        let main = repo.head()?.shorthand().unwrap_or_default().to_string();
+
+        let db = Connection::open_in_memory()?;
+        SqliteGit::new()
+            .with_branches()
+            .repository(&path)
+            .register(&db)?;
+        db.execute_batch(&format!(
+            "BEGIN;
+             INSERT INTO branches(name, target, upstream) VALUES ('topic', 'HEAD~1', '{0}');
+             INSERT INTO branches(name) VALUES ('stale');
+             COMMIT;",
+            main
+        ))?;
+        let created = branches(&db)?;
+        db.execute_batch(
+            "BEGIN;
+             UPDATE branches SET name = 'feature', target = 'HEAD' WHERE name = 'topic';
+             DELETE FROM branches WHERE name = 'stale';
+             COMMIT;",
+        )?;
+        let changed = branches(&db)?;
+        let head = db.execute("DELETE FROM branches WHERE is_head", []);
+        let counts = db.execute("UPDATE branches SET ahead = 3 WHERE name = 'feature'", []);
+        std::fs::remove_dir_all(&path)?;
+
+        let branch = |name: &str, target, upstream: Option<&str>, counts, is_head| {
+            let (ahead, behind) = match counts {
+                Some((ahead, behind)) => (Some(ahead), Some(behind)),
+                None => (None, None),
+            };
+            let upstream = upstream.map(String::from);
+            let name = name.to_string();
+            (
+                name,
+                git2::Oid::to_string(&target),
+                upstream,
+                ahead,
+                behind,
+                is_head,
+            )
+        };
+        assert_eq!(
+            created,
+            vec![
+                branch(&main, second, None, None, true),
+                branch("stale", second, None, None, false),
+                branch("topic", first, Some(&main), Some((0, 1)), false),
+            ]
+        );
+        assert_eq!(
+            changed,
+            vec![
+                branch("feature", second, Some(&main), Some((0, 0)), false),
+                branch(&main, second, None, None, true),
+            ]
+        );
+        assert!(head.unwrap_err().to_string().contains("is checked out at"));
+        assert!(counts.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_branches_checked_out_in_a_worktree() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("branches_worktree")?;
+        let head = commit_file(&repo, "file.txt", "one\n", "first")?;
+        let branch = repo.branch("topic", &repo.find_commit(head)?, false)?;
+        let worktree_path = std::env::temp_dir().join("sqlitegit_branches_worktree_topic");
+        let _ = std::fs::remove_dir_all(&worktree_path);
+        let mut options = WorktreeAddOptions::new();
+        options.reference(Some(branch.get()));
+        repo.worktree("topic", &worktree_path, Some(&options))?;
+
+        let db = Connection::open_in_memory()?;
+        SqliteGit::new()
+            .with_branches()
+            .repository(&path)
+            .register(&db)?;
+        let deleted = db.execute("DELETE FROM branches WHERE name = 'topic'", []);
+        let renamed = db.execute(
+            "UPDATE branches SET name = 'other' WHERE name = 'topic'",
+            [],
+        );
+        let kept = repo.find_branch("topic", BranchType::Local).is_ok();
+        std::fs::remove_dir_all(&path)?;
+        std::fs::remove_dir_all(&worktree_path)?;
+
+        let error = deleted.unwrap_err().to_string();
+        assert!(error.contains("topic is checked out at"), "{}", error);
+        assert!(
+            error.contains("sqlitegit_branches_worktree_topic"),
+            "{}",
+            error
+        );
+        assert!(renamed.is_err());
+        assert!(kept);
+
+        Ok(())
+    }
+}
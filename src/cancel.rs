@@ -0,0 +1,121 @@
+use crate::Interrupt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// How often a pressed Ctrl-C is looked for.
+const POLL: Duration = Duration::from_millis(20);
+
+/// Set by the SIGINT handler, storing to an atomic is all a signal handler may safely do.
+static PRESSED: AtomicBool = AtomicBool::new(false);
+
+/// While it lives Ctrl-C interrupts the running statements instead of ending the process.
+pub(crate) struct CtrlC {
+    #[cfg(unix)]
+    previous: Option<nix::sys::signal::SigAction>,
+    done: Arc<AtomicBool>,
+    watcher: Option<thread::JoinHandle<()>>,
+}
+
+impl CtrlC {
+    pub(crate) fn interrupting(interrupt: &Interrupt) -> CtrlC {
+        PRESSED.store(false, Ordering::SeqCst);
+        let done = Arc::new(AtomicBool::new(false));
+        let watcher = {
+            let (done, interrupt) = (done.clone(), interrupt.clone());
+            thread::spawn(move || {
+                while !done.load(Ordering::SeqCst) {
+                    if PRESSED.swap(false, Ordering::SeqCst) {
+                        interrupt.interrupt();
+                    }
+                    thread::sleep(POLL);
+                }
+            })
+        };
+        CtrlC {
+            #[cfg(unix)]
+            previous: on_sigint(nix::sys::signal::SigHandler::Handler(pressed)),
+            done,
+            watcher: Some(watcher),
+        }
+    }
+}
+
+impl Drop for CtrlC {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(previous) = self.previous.take() {
+            on_sigint(previous.handler());
+        }
+        self.done.store(true, Ordering::SeqCst);
+        if let Some(watcher) = self.watcher.take() {
+            let _ = watcher.join();
+        }
+    }
+}
+
+#[cfg(unix)]
+extern "C" fn pressed(_: std::os::raw::c_int) {
+    PRESSED.store(true, Ordering::SeqCst);
+}
+
+/// Installs `handler` for SIGINT, returns the action it replaced.
+#[cfg(unix)]
+fn on_sigint(handler: nix::sys::signal::SigHandler) -> Option<nix::sys::signal::SigAction> {
+    use nix::sys::signal::{sigaction, SaFlags, SigAction, SigSet, Signal};
+    let action = SigAction::new(handler, SaFlags::SA_RESTART, SigSet::empty());
+    // Safe as `pressed` only stores to an atomic
+    unsafe { sigaction(Signal::SIGINT, &action) }.ok()
+}
+
+/// Interrupts the running statements once the timeout has passed, unless it's dropped before.
+pub(crate) struct Deadline {
+    _cancel: Sender<()>,
+    expired: Arc<AtomicBool>,
+}
+
+impl Deadline {
+    pub(crate) fn start(interrupt: &Interrupt, timeout: Duration) -> Deadline {
+        let (cancel, cancelled) = channel();
+        let expired = Arc::new(AtomicBool::new(false));
+        let (interrupt, expire) = (interrupt.clone(), expired.clone());
+        thread::spawn(move || {
+            if let Err(RecvTimeoutError::Timeout) = cancelled.recv_timeout(timeout) {
+                expire.store(true, Ordering::SeqCst);
+                interrupt.interrupt();
+            }
+        });
+        Deadline {
+            _cancel: cancel,
+            expired,
+        }
+    }
+
+    pub(crate) fn expired(&self) -> bool {
+        self.expired.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cancel::Deadline;
+    use crate::Interrupt;
+    use std::time::Duration;
+
+    #[test]
+    fn deadline_interrupts_unless_dropped() {
+        let interrupt = Interrupt::default();
+        let checkpoint = interrupt.checkpoint();
+        drop(Deadline::start(&interrupt, Duration::from_millis(10)));
+        std::thread::sleep(Duration::from_millis(50));
+        let dropped = checkpoint.check();
+        let deadline = Deadline::start(&interrupt, Duration::from_millis(10));
+        std::thread::sleep(Duration::from_millis(50));
+
+        assert!(dropped.is_ok());
+        assert!(deadline.expired());
+        assert!(checkpoint.check().is_err());
+    }
+}
@@ -0,0 +1,88 @@
+use crate::output::OutputOptions;
+use crate::params::Params;
+use crate::utils::execute_into_table;
+use crate::CustomError;
+use itertools::Itertools;
+use rusqlite::{Batch, Connection, Statement};
+
+/// Runs every statement in `sql` as a rule, a rule is violated when its statement returns rows.
+///
+/// The `--` comment lines in front of a statement name the rule, statements without a comment
+/// are named by their first line. Each violated rule is printed with the offending rows, the
+/// number of violated rules is returned. Statements without columns are executed silently so a
+/// rules file can set up helper tables or views first.
+pub fn run(db: &Connection, sql: &str, params: &Params) -> Result<usize, CustomError> {
+    let mut batch = Batch::new(db, sql);
+    let mut rules = 0;
+    let mut violations = 0;
+    while let Some(mut stmt) = batch.next()? {
+        params.bind(&mut stmt)?;
+        if stmt.column_count() == 0 {
+            stmt.raw_execute()?;
+            continue;
+        }
+
+        rules += 1;
+        let name = rule_name(&stmt);
+        let (table, row_count) = execute_into_table(&mut stmt, &OutputOptions::default())?;
+        if row_count == 0 {
+            println!("ok   {}", name);
+            continue;
+        }
+
+        violations += 1;
+        println!("FAIL {} ({} rows)", name, row_count);
+        (table.lines().iter()).for_each(|line| println!("     {}", line));
+    }
+
+    println!(
+        "\n{} rules, {} passed, {} failed",
+        rules,
+        rules - violations,
+        violations
+    );
+    Ok(violations)
+}
+
+fn rule_name(stmt: &Statement) -> String {
+    let sql = stmt.expanded_sql().unwrap_or_default();
+    let lines = sql
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect_vec();
+    let comments = lines
+        .iter()
+        .take_while(|line| line.starts_with("--"))
+        .map(|line| line.trim_start_matches('-').trim())
+        .join(" ");
+    if comments.is_empty() {
+        lines.first().unwrap_or(&"").to_string()
+    } else {
+        comments
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::check::run;
+    use crate::params::Params;
+    use rusqlite::Connection;
+
+    #[test]
+    fn counts_violated_rules() -> Result<(), Box<dyn std::error::Error>> {
+        let db = Connection::open_in_memory()?;
+        let rules = r#"
+            CREATE TEMP TABLE t(n INTEGER);
+            INSERT INTO t VALUES (1), (2), (3);
+            -- no negative numbers
+            SELECT n FROM t WHERE n < 0;
+            -- nothing above two
+            SELECT n FROM t WHERE n > 2;
+            SELECT n FROM t WHERE n = 1;
+        "#;
+        assert_eq!(run(&db, rules, &Params::default())?, 2);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,333 @@
+use crate::output::{ColumnWidth, DateFormat, OutputMode, TimeZoneChoice, DEFAULT_MAX_WIDTH};
+use crate::params::Param;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::io::IsTerminal;
+use std::path::PathBuf;
+
+/// Query git repositories with SQL
+#[derive(Parser, Debug)]
+#[command(name = "sqlitegit", version, about)]
+pub struct Cli {
+    /// Read the repository at REPO and its .sqlitegit.toml instead of the current directory's,
+    /// the paths of the other arguments stay relative to the current directory
+    #[arg(long, global = true, value_name = "REPO")]
+    pub repo: Option<PathBuf>,
+
+    /// Read the config from FILE instead of .sqlitegit.toml
+    #[arg(long, global = true, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Don't create the recent_commits, author_summary, merge_commits and weekly_activity views
+    #[arg(long, global = true)]
+    pub no_views: bool,
+
+    /// Read the history into memory in the background at startup, so the queries of `repl` and
+    /// `serve` don't walk it again
+    #[arg(long, global = true)]
+    pub warm_index: bool,
+
+    /// Walk at most N commits from HEAD when a query of commits or merges passes no revision, 0
+    /// walks them all. Overrides scan_limit from the config
+    #[arg(long, global = true, value_name = "N")]
+    pub scan_limit: Option<usize>,
+
+    /// Write the query plan, timings and the filter calls of the git tables to stderr after
+    /// each statement of query, run and repl
+    #[arg(long, global = true)]
+    pub profile: bool,
+
+    /// Print the results of query and run to stdout instead of paging them with
+    /// $SQLITEGIT_PAGER or $PAGER, less by default, when they are printed to a terminal
+    #[arg(long, global = true)]
+    pub no_pager: bool,
+
+    /// Don't show the commits walked, the diffs computed and the time spent on stderr while
+    /// query, run and export wait for their first rows
+    #[arg(long, global = true)]
+    pub no_progress: bool,
+
+    /// Color tables: a bold header, dim NULLs and striped rows. auto colors them when stdout is
+    /// a terminal and NO_COLOR isn't set
+    #[arg(long, global = true, value_enum, value_name = "WHEN", default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
+
+    /// The time zone DATETIME columns are printed and exported in, original keeps the offset
+    /// a value was returned with. Overrides tz from the config, utc by default
+    #[arg(long, global = true, value_enum, value_name = "ZONE")]
+    pub tz: Option<TimeZoneChoice>,
+
+    /// Print and export DATETIME columns in the strftime FORMAT, e.g. "%d %b %Y %H:%M".
+    /// Overrides the date_formats of the config
+    #[arg(long, global = true, value_name = "FORMAT")]
+    pub date_format: Option<DateFormat>,
+
+    /// Log query plans, revwalk sizes and timings to stderr, repeat for more detail
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Execute SQL statements and print each result set
+    ///
+    /// The statements are read from SQL, from FILE, or from stdin when neither is given.
+    Query(QueryArgs),
+    /// Start an interactive SQL prompt
+    Repl,
+    /// Start the terminal user interface
+    #[cfg(feature = "tui")]
+    Tui(TuiArgs),
+    /// Execute a SQL statement and write the result to a file or a SQLite database
+    Export(ExportArgs),
+    /// Answer read-only SQL queries sent to POST /query with JSON rows
+    ///
+    /// The queries read only the repository sqlitegit was started in, repository arguments are
+    /// rejected.
+    Serve(ServeArgs),
+    /// Run the SQL rules in FILE and fail when any of them returns rows
+    ///
+    /// Every statement is a rule that selects its violations, the `--` comment in front of a
+    /// statement names the rule. Exits with status 1 when a rule is violated.
+    Check(CheckArgs),
+    /// Run a named query from the config file
+    ///
+    /// The query's parameters are passed as --NAME VALUE after the query name, parameters that
+    /// are left out use the defaults from the config.
+    Run(RunArgs),
+    /// Build or refresh the full-text index over commit messages in the SQLite database FILE
+    ///
+    /// Only commits that aren't indexed yet are added. Search it after attaching FILE, e.g.
+    /// `ATTACH 'FILE' AS idx; SELECT * FROM idx.commit_messages_fts WHERE commit_messages_fts
+    /// MATCH 'fix'`.
+    Index(IndexArgs),
+    /// Write the repository's commit-graph file if it doesn't have one yet
+    ///
+    /// libgit2 reads the commit-graph when it's there, walks and ancestry checks then use its
+    /// pre-parsed parents and generation numbers instead of reading every commit.
+    CommitGraph(CommitGraphArgs),
+    /// Keep real commits, stats and refs tables in the SQLite database FILE up to date
+    ///
+    /// Only the commits added since the last sync are diffed and ingested, the commits of
+    /// rewritten history are deleted. Query the tables directly, e.g. `sqlite3 FILE 'SELECT
+    /// count(*) FROM commits'`.
+    Sync(SyncArgs),
+    /// Measure the commits scan, the stats join and point lookups against generated repositories
+    ///
+    /// The repositories are generated in the temp directory with git fast-import and reused by
+    /// later runs. Prints the median time and the rows read per second of each query.
+    Bench(BenchArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct QueryArgs {
+    /// The SQL statements to execute
+    #[arg(conflicts_with = "file")]
+    pub sql: Option<String>,
+    /// Read the SQL statements from FILE, use - for stdin
+    #[arg(short, long, value_name = "FILE")]
+    pub file: Option<PathBuf>,
+    /// Bind a parameter, NAME=VALUE for :NAME, N=VALUE for ?N or VALUE for the next ?
+    #[arg(short, long = "param", value_name = "PARAM")]
+    pub params: Vec<Param>,
+    /// How to print the result sets
+    #[arg(long, value_enum, default_value_t = OutputMode::Table)]
+    pub format: OutputMode,
+    /// Print every column of a row on a line of its own, short for --format expanded
+    #[arg(short = 'x', long, conflicts_with = "format")]
+    pub expanded: bool,
+    /// Leave out the header row of csv and tsv output
+    #[arg(long)]
+    pub no_header: bool,
+    /// Cut off table values wider than N columns of the terminal, 0 doesn't cut them off
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_MAX_WIDTH)]
+    pub max_width: usize,
+    /// Cut off the values of the table column NAME at WIDTH instead, 0 doesn't cut them off
+    #[arg(long = "width", value_name = "NAME=WIDTH")]
+    pub widths: Vec<ColumnWidth>,
+    /// Print at most N rows of a result set to a terminal and count the rest, row_limit from
+    /// the config or 1000 by default, 0 prints them all
+    #[arg(long, value_name = "N")]
+    pub limit: Option<usize>,
+    /// Print every row to a terminal too
+    #[arg(long, conflicts_with = "limit")]
+    pub no_limit: bool,
+    /// Re-run the statements whenever the repository changes
+    #[arg(short, long)]
+    pub watch: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct ExportArgs {
+    /// The SQL statement to execute
+    pub sql: String,
+    /// File to write the result to, defaults to stdout
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+    /// Bind a parameter, NAME=VALUE for :NAME, N=VALUE for ?N or VALUE for the next ?
+    #[arg(short, long = "param", value_name = "PARAM")]
+    pub params: Vec<Param>,
+    /// Format of the written result, parquet requires --output
+    #[arg(long, value_enum, default_value_t = ExportFormat::Csv)]
+    pub format: ExportFormat,
+    /// Leave out the header row of csv and tsv output
+    #[arg(long)]
+    pub no_header: bool,
+    /// Write the result into a table of the SQLite database FILE instead
+    #[arg(long, value_name = "FILE", requires = "table", conflicts_with_all = ["output", "format"])]
+    pub db: Option<PathBuf>,
+    /// Name of the table created by --db
+    #[arg(long, value_name = "NAME", requires = "db")]
+    pub table: Option<String>,
+    /// Replace the table if it already exists
+    #[arg(long, requires = "db")]
+    pub replace: bool,
+}
+
+#[cfg(feature = "tui")]
+#[derive(Args, Debug)]
+pub struct TuiArgs {
+    /// Start with the dashboard, the panels of [[tui.panels]] in the config
+    #[arg(long)]
+    pub dashboard: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct CheckArgs {
+    /// File with the rules, use - for stdin
+    #[arg(value_name = "FILE")]
+    pub file: PathBuf,
+    /// Bind a parameter, NAME=VALUE for :NAME, N=VALUE for ?N or VALUE for the next ?
+    #[arg(short, long = "param", value_name = "PARAM")]
+    pub params: Vec<Param>,
+}
+
+#[derive(Args, Debug)]
+pub struct RunArgs {
+    /// Name of the query, lists the available queries when left out
+    pub name: Option<String>,
+    /// How to print the result sets
+    #[arg(long, value_enum, default_value_t = OutputMode::Table)]
+    pub format: OutputMode,
+    /// Print every column of a row on a line of its own, short for --format expanded
+    #[arg(short = 'x', long, conflicts_with = "format")]
+    pub expanded: bool,
+    /// Leave out the header row of csv and tsv output
+    #[arg(long)]
+    pub no_header: bool,
+    /// Cut off table values wider than N columns of the terminal, 0 doesn't cut them off
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_MAX_WIDTH)]
+    pub max_width: usize,
+    /// Cut off the values of the table column NAME at WIDTH instead, 0 doesn't cut them off
+    #[arg(long = "width", value_name = "NAME=WIDTH")]
+    pub widths: Vec<ColumnWidth>,
+    /// Print at most N rows of a result set to a terminal and count the rest, row_limit from
+    /// the config or 1000 by default, 0 prints them all
+    #[arg(long, value_name = "N")]
+    pub limit: Option<usize>,
+    /// Print every row to a terminal too
+    #[arg(long, conflicts_with = "limit")]
+    pub no_limit: bool,
+    /// The query's parameters as --NAME VALUE or --NAME=VALUE
+    #[arg(
+        trailing_var_arg = true,
+        allow_hyphen_values = true,
+        value_name = "PARAMS"
+    )]
+    pub params: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct ServeArgs {
+    /// Address to listen on
+    #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:8080")]
+    pub bind: String,
+
+    /// Stop queries that run longer than SECONDS
+    #[arg(long, value_name = "SECONDS")]
+    pub timeout: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether output printed to stdout is colored.
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Auto => {
+                let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+                std::io::stdout().is_terminal() && !no_color
+            }
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    Csv,
+    Tsv,
+    Json,
+    Ndjson,
+    Table,
+    Markdown,
+    Html,
+    Dot,
+    Parquet,
+}
+
+impl ExportFormat {
+    /// The text output mode writing this format, None for binary formats.
+    pub fn output_mode(self) -> Option<OutputMode> {
+        match self {
+            ExportFormat::Csv => Some(OutputMode::Csv),
+            ExportFormat::Tsv => Some(OutputMode::Tsv),
+            ExportFormat::Json => Some(OutputMode::Json),
+            ExportFormat::Ndjson => Some(OutputMode::Ndjson),
+            ExportFormat::Table => Some(OutputMode::Table),
+            ExportFormat::Markdown => Some(OutputMode::Markdown),
+            ExportFormat::Html => Some(OutputMode::Html),
+            ExportFormat::Dot => Some(OutputMode::Dot),
+            ExportFormat::Parquet => None,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct IndexArgs {
+    /// The SQLite database the index is kept in, created if it doesn't exist
+    #[arg(value_name = "FILE")]
+    pub db: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct CommitGraphArgs {
+    /// Rewrite the commit-graph even if there is one, e.g. to add the commits since it was written
+    #[arg(long)]
+    pub force: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct SyncArgs {
+    /// The SQLite database the tables are kept in, created if it doesn't exist
+    #[arg(value_name = "FILE")]
+    pub db: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct BenchArgs {
+    /// Number of commits of a generated repository, repeat for several sizes
+    #[arg(long = "commits", value_name = "N", default_values_t = [1_000, 10_000])]
+    pub sizes: Vec<usize>,
+    /// How many times each query runs
+    #[arg(long, value_name = "N", default_value_t = 5)]
+    pub iterations: usize,
+}
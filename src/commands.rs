@@ -0,0 +1,448 @@
+use crate::arrow_export::execute_and_write_parquet;
+use crate::cancel::CtrlC;
+use crate::cli::{
+    BenchArgs, CheckArgs, Cli, Command, CommitGraphArgs, ExportArgs, IndexArgs, QueryArgs, RunArgs,
+    SyncArgs,
+};
+use crate::config::Config;
+use crate::materialize::execute_and_materialize;
+use crate::output::{OutputMode, OutputOptions, RowLimit, DEFAULT_ROW_LIMIT};
+use crate::pager::Output;
+use crate::params::{Param, Params};
+use crate::progress::Spinner;
+use crate::sync::sync_repository;
+use crate::utils::{execute_all_and_write, execute_and_write};
+use crate::{
+    bench, check, commit_graph, refresh_message_index, register_modules, repl, serve, watch,
+    CustomError, Profiler, Progress,
+};
+use git2::Repository;
+use itertools::Itertools;
+use rusqlite::Connection;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+/// libgit2 takes a few times the cached size, scans over a huge history stay in a few hundred
+/// megabytes with this instead of the default 256 MiB.
+const OBJECT_CACHE_LIMIT: usize = 64 * 1024 * 1024;
+
+/// The diffs of a scan find the trees of the previous commit parsed, root trees of big
+/// repositories included.
+const TREE_CACHE_LIMIT: usize = 4 * 1024 * 1024;
+
+/// Runs the `sqlitegit` command described by `cli`.
+pub fn run(cli: Cli) -> Result<ExitCode, CustomError> {
+    // --repo picks the default repository and the repository's config, the paths of the other
+    // arguments stay relative to where sqlitegit was started
+    let repository = cli.repo.clone().unwrap_or_else(|| PathBuf::from("."));
+    let config_path = cli.config.clone();
+
+    crate::set_object_cache_limit(OBJECT_CACHE_LIMIT);
+    crate::set_tree_cache_limit(TREE_CACHE_LIMIT);
+    let config = Config::load(config_path.as_deref(), &repository)?;
+    // --scan-limit 0 turns the limit of the config off
+    let scan_limit = cli
+        .scan_limit
+        .or(config.scan_limit)
+        .filter(|&commits| commits > 0);
+    let db = Connection::open_in_memory()?;
+    let serve = matches!(cli.command, Command::Serve(_));
+    // The queries serve answers don't make the server clone whatever URL they name
+    let mirrors = if serve { None } else { mirror_dir() };
+    let views = !cli.no_views;
+    let git = register_modules(
+        &db,
+        &repository,
+        cli.warm_index,
+        scan_limit,
+        mirrors,
+        serve,
+        views,
+    )?;
+    let interrupt = git.interrupt();
+    let profiler = cli.profile.then(|| git.profiler());
+    let progress = (!cli.no_progress).then(|| git.progress());
+
+    // Ctrl-C stops the statements of the commands that run to completion, the long-running
+    // ones are ended by it
+    let _ctrl_c = match &cli.command {
+        Command::Query(QueryArgs { watch: false, .. })
+        | Command::Export(_)
+        | Command::Check(_)
+        | Command::Run(_) => Some(CtrlC::interrupting(&interrupt)),
+        _ => None,
+    };
+    // What the output of every command shares
+    let defaults = OutputOptions {
+        color: cli.color.enabled(),
+        timezone: cli.tz.or(config.tz).unwrap_or_default(),
+        date_format: cli.date_format,
+        date_formats: config.date_formats.clone(),
+        ..OutputOptions::default()
+    };
+    match cli.command {
+        Command::Query(args) if args.watch => {
+            let (sql, params, output) = query_options(&config, args, &defaults)?;
+            watch::run(&db, &repository, &sql, &params, &output)?
+        }
+        Command::Query(args) => {
+            let (profiler, progress) = (profiler.as_ref(), progress.as_ref());
+            query(
+                &db,
+                &config,
+                args,
+                profiler,
+                progress,
+                !cli.no_pager,
+                &defaults,
+            )?
+        }
+        Command::Repl => {
+            let limit = config.row_limit.unwrap_or(DEFAULT_ROW_LIMIT);
+            repl::run(
+                &db,
+                &interrupt,
+                &git.profiler(),
+                cli.profile,
+                defaults,
+                limit,
+            )?
+        }
+        #[cfg(feature = "tui")]
+        Command::Tui(args) => {
+            let config_path = config_path.as_deref();
+            let dashboard = args.dashboard;
+            crate::tui::run(
+                &db,
+                &interrupt,
+                &config,
+                config_path,
+                &repository,
+                dashboard,
+            )?
+        }
+        Command::Export(args) => export(&db, args, &defaults, spinner(progress.as_ref()))?,
+        Command::Serve(args) => {
+            let timeout = args.timeout.map(std::time::Duration::from_secs);
+            serve::run(&db, &args.bind, timeout, &interrupt)?
+        }
+        Command::Check(args) => return check(&db, args),
+        Command::Run(args) => {
+            let spinner = spinner(progress.as_ref());
+            let profiler = profiler.as_ref();
+            run_template(
+                &db,
+                &config,
+                args,
+                profiler,
+                spinner,
+                !cli.no_pager,
+                &defaults,
+            )?
+        }
+        Command::Index(args) => index(&db, args)?,
+        Command::CommitGraph(args) => commit_graph(&repository, args)?,
+        Command::Sync(args) => sync(&db, &repository, args)?,
+        Command::Bench(BenchArgs { sizes, iterations }) => bench::run(&sizes, iterations)?,
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn query(
+    db: &Connection,
+    config: &Config,
+    args: QueryArgs,
+    profiler: Option<&Profiler>,
+    progress: Option<&Progress>,
+    page: bool,
+    defaults: &OutputOptions,
+) -> Result<(), CustomError> {
+    let (sql, params, output) = query_options(config, args, defaults)?;
+    print_paged(page, spinner(progress), |out| {
+        execute_all_and_write(db, &sql, &params, &output, profiler, out)
+            .map_err(|e| e.diagnose(db, &sql))
+    })
+}
+
+/// The statements of `query`, their parameters and how their results are printed.
+fn query_options(
+    config: &Config,
+    args: QueryArgs,
+    defaults: &OutputOptions,
+) -> Result<(String, Params, OutputOptions), CustomError> {
+    let sql = match (args.sql, args.file) {
+        (Some(sql), _) => sql,
+        (None, Some(path)) => read_sql(&path)?,
+        (None, None) => std::io::read_to_string(std::io::stdin())?,
+    };
+    let params = Params::from(args.params);
+    let output = OutputOptions {
+        mode: match args.expanded {
+            true => OutputMode::Expanded,
+            false => args.format,
+        },
+        headers: !args.no_header,
+        max_width: args.max_width,
+        widths: args.widths,
+        limit: row_limit(config, args.limit, args.no_limit),
+        ..defaults.clone()
+    };
+    Ok((sql, params, output))
+}
+
+/// The SQL in the file at `path`, or on stdin for `-`. The error of a file that can't be read
+/// names it.
+fn read_sql(path: &Path) -> Result<String, CustomError> {
+    if path.as_os_str() == "-" {
+        return Ok(std::io::read_to_string(std::io::stdin())?);
+    }
+    std::fs::read_to_string(path).map_err(|e| path_error(path, e))
+}
+
+/// `e` with the path it's about, io errors leave it out.
+fn path_error(path: &Path, e: std::io::Error) -> CustomError {
+    CustomError::Io(std::io::Error::new(
+        e.kind(),
+        format!("{}: {}", path.display(), e),
+    ))
+}
+
+/// The rows of a result set printed to a terminal, everything is written to files and pipes.
+fn row_limit(config: &Config, limit: Option<usize>, no_limit: bool) -> Option<RowLimit> {
+    if no_limit || !std::io::stdout().is_terminal() {
+        return None;
+    }
+    let rows = limit.or(config.row_limit).unwrap_or(DEFAULT_ROW_LIMIT);
+    (rows > 0).then_some(RowLimit {
+        rows,
+        hint: "--no-limit",
+    })
+}
+
+/// Where the mirrors of the repositories read by URL are kept, `sqlitegit/mirrors` in the
+/// user's cache directory.
+fn mirror_dir() -> Option<PathBuf> {
+    let cache = match std::env::var_os("XDG_CACHE_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".cache"),
+    };
+    Some(cache.join("sqlitegit").join("mirrors"))
+}
+
+/// The spinner showing `progress` while a statement runs, none without progress.
+fn spinner(progress: Option<&Progress>) -> Spinner {
+    progress.map_or_else(Spinner::none, Spinner::start)
+}
+
+/// Runs `print` with the output of [`Output::start`] and waits for the pager. The spinner is
+/// stopped once there's output.
+fn print_paged(
+    page: bool,
+    mut spinner: Spinner,
+    print: impl FnOnce(&mut dyn Write) -> Result<(), CustomError>,
+) -> Result<(), CustomError> {
+    let mut out = Output::start(page);
+    let printed = print(&mut spinner.stopped_by(&mut out));
+    spinner.stop();
+    let finished = out.finish().map_err(CustomError::from);
+    // Quitting the pager before the end closes the pipe, that's not an error
+    match printed.and(finished) {
+        Err(CustomError::Io(e)) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        result => result,
+    }
+}
+
+fn check(db: &Connection, args: CheckArgs) -> Result<ExitCode, CustomError> {
+    let sql = read_sql(&args.file)?;
+    match check::run(db, &sql, &Params::from(args.params))? {
+        0 => Ok(ExitCode::SUCCESS),
+        _ => Ok(ExitCode::FAILURE),
+    }
+}
+
+fn run_template(
+    db: &Connection,
+    config: &Config,
+    args: RunArgs,
+    profiler: Option<&Profiler>,
+    spinner: Spinner,
+    page: bool,
+    defaults: &OutputOptions,
+) -> Result<(), CustomError> {
+    let name = match args.name {
+        Some(name) => name,
+        None => {
+            for (name, template) in &config.queries {
+                println!("{}", name);
+                if let Some(description) = &template.description {
+                    println!("    {}", description);
+                }
+                for (param, default) in &template.params {
+                    println!("    --{} (default {})", param, default);
+                }
+            }
+            return Ok(());
+        }
+    };
+    let template = config.queries.get(&name).ok_or_else(|| {
+        CustomError::Io(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!(
+                "there is no query named {}, known queries are: {}",
+                name,
+                config.queries.keys().join(", ")
+            ),
+        ))
+    })?;
+
+    let mut params = template
+        .params
+        .iter()
+        .map(|(name, value)| Param::Named(name.to_string(), value.to_string()))
+        .collect_vec();
+    let mut args_iter = args.params.into_iter();
+    while let Some(arg) = args_iter.next() {
+        let param = match arg.strip_prefix("--").map(|arg| arg.split_once('=')) {
+            Some(Some((name, value))) => Param::Named(name.to_string(), value.to_string()),
+            Some(None) => match args_iter.next() {
+                Some(value) => Param::Named(arg[2..].to_string(), value),
+                None => return Err(invalid_template_args(format!("{} needs a value", arg))),
+            },
+            None => {
+                return Err(invalid_template_args(format!(
+                    "unexpected argument {}",
+                    arg
+                )))
+            }
+        };
+        params.push(param);
+    }
+
+    let output = OutputOptions {
+        mode: match args.expanded {
+            true => OutputMode::Expanded,
+            false => args.format,
+        },
+        headers: !args.no_header,
+        max_width: args.max_width,
+        widths: args.widths,
+        limit: row_limit(config, args.limit, args.no_limit),
+        ..defaults.clone()
+    };
+    let params = Params::from(params);
+    print_paged(page, spinner, |out| {
+        execute_all_and_write(db, &template.sql, &params, &output, profiler, out)
+            .map_err(|e| e.diagnose(db, &template.sql))
+    })
+}
+
+fn invalid_template_args(message: String) -> CustomError {
+    CustomError::Io(std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        format!("{}, pass parameters as --NAME VALUE", message),
+    ))
+}
+
+/// Exports the result, the spinner runs until it's written to a file or until the first rows are
+/// written to stdout.
+fn export(
+    db: &Connection,
+    args: ExportArgs,
+    defaults: &OutputOptions,
+    mut spinner: Spinner,
+) -> Result<(), CustomError> {
+    let mut stmt = db.prepare(&args.sql)?;
+    Params::from(args.params).bind(&mut stmt)?;
+
+    if let (Some(path), Some(table)) = (&args.db, &args.table) {
+        let count = execute_and_materialize(&mut stmt, path, table, args.replace)?;
+        spinner.stop();
+        eprintln!("wrote {} rows to {} in {}", count, table, path.display());
+        return Ok(());
+    }
+
+    match (args.format.output_mode(), args.output) {
+        (Some(mode), output) => {
+            let options = OutputOptions {
+                mode,
+                headers: !args.no_header,
+                color: false,
+                ..defaults.clone()
+            };
+            match output {
+                Some(path) => {
+                    let file = std::fs::File::create(&path).map_err(|e| path_error(&path, e))?;
+                    let mut file = std::io::BufWriter::new(file);
+                    execute_and_write(&mut stmt, &mut file, &options)?;
+                    file.flush()?;
+                }
+                None => {
+                    let stdout = &mut std::io::stdout().lock();
+                    execute_and_write(&mut stmt, &mut spinner.stopped_by(stdout), &options)?
+                }
+            }
+        }
+        (None, Some(path)) => {
+            let file = std::fs::File::create(&path).map_err(|e| path_error(&path, e))?;
+            execute_and_write_parquet(&mut stmt, file)?
+        }
+        (None, None) => {
+            return Err(CustomError::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "binary formats need a file, pass --output",
+            )))
+        }
+    }
+
+    Ok(())
+}
+
+fn index(db: &Connection, args: IndexArgs) -> Result<(), CustomError> {
+    db.execute(
+        "ATTACH DATABASE ? AS message_index",
+        [args.db.to_string_lossy()],
+    )?;
+    let added = refresh_message_index(db, "message_index")?;
+    eprintln!("indexed {} new commits in {}", added, args.db.display());
+    Ok(())
+}
+
+fn sync(db: &Connection, repository: &Path, args: SyncArgs) -> Result<(), CustomError> {
+    db.execute("ATTACH DATABASE ? AS synced", [args.db.to_string_lossy()])?;
+    let synced = sync_repository(db, "synced", &Repository::open(repository)?)?;
+    eprintln!(
+        "synced {} new commits into {}, removed {}",
+        synced.added,
+        args.db.display(),
+        synced.removed
+    );
+    Ok(())
+}
+
+fn commit_graph(repository: &Path, args: CommitGraphArgs) -> Result<(), CustomError> {
+    let repo = Repository::open(repository)?;
+    let path = commit_graph::path(&repo);
+    if path.exists() && !args.force {
+        eprintln!(
+            "{} already exists, pass --force to rewrite it",
+            path.display()
+        );
+        return Ok(());
+    }
+    commit_graph::write(&repo)?;
+    if !commit_graph::loaded(&repo) {
+        return Err(CustomError::Io(std::io::Error::other(format!(
+            "libgit2 can't read the commit-graph it wrote to {}",
+            path.display()
+        ))));
+    }
+    eprintln!("wrote {}", path.display());
+    // libgit2 reads the file either way, git only while core.commitGraph isn't turned off. The
+    // setting is the user's to change
+    if repo.config()?.get_bool("core.commitGraph").ok() == Some(false) {
+        eprintln!("warning: core.commitGraph is false, git ignores the commit-graph");
+    }
+    Ok(())
+}
@@ -0,0 +1,186 @@
+use crate::CustomError;
+use git2::Repository;
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int};
+#[cfg(feature = "cli")]
+use std::os::raw::{c_float, c_uint};
+use std::path::Path;
+#[cfg(feature = "cli")]
+use std::path::PathBuf;
+use std::ptr;
+
+/// Where the commit-graph of a repository is, below its git directory. libgit2 loads it with the
+/// object database, its revwalks and ancestry checks then read the parents and generation numbers
+/// from it instead of parsing every commit.
+#[cfg(feature = "cli")]
+const FILE: &str = "objects/info/commit-graph";
+
+/// libgit2's `git_commit_graph`, opaque.
+#[repr(C)]
+struct CommitGraph {
+    _private: [u8; 0],
+}
+
+/// libgit2's `git_commit_graph_writer`, opaque.
+#[cfg(feature = "cli")]
+#[repr(C)]
+struct Writer {
+    _private: [u8; 0],
+}
+
+/// libgit2's `git_commit_graph_writer_options`.
+#[cfg(feature = "cli")]
+#[repr(C)]
+struct WriterOptions {
+    version: c_uint,
+    split_strategy: c_int,
+    size_multiple: c_float,
+    max_commits: usize,
+}
+
+#[cfg(feature = "cli")]
+const WRITER_OPTIONS_VERSION: c_uint = 1;
+
+// The commit-graph API of `git2/sys/commit_graph.h`, which libgit2-sys doesn't bind
+extern "C" {
+    fn git_commit_graph_open(out: *mut *mut CommitGraph, objects_dir: *const c_char) -> c_int;
+    fn git_commit_graph_free(cgraph: *mut CommitGraph);
+}
+
+#[cfg(feature = "cli")]
+extern "C" {
+    fn git_commit_graph_writer_new(out: *mut *mut Writer, objects_info_dir: *const c_char)
+        -> c_int;
+    fn git_commit_graph_writer_free(writer: *mut Writer);
+    fn git_commit_graph_writer_add_revwalk(
+        writer: *mut Writer,
+        walk: *mut libgit2_sys::git_revwalk,
+    ) -> c_int;
+    fn git_commit_graph_writer_options_init(options: *mut WriterOptions, version: c_uint) -> c_int;
+    fn git_commit_graph_writer_commit(writer: *mut Writer, options: *mut WriterOptions) -> c_int;
+}
+
+/// A libgit2 object freed when dropped.
+struct Owned<T>(*mut T, unsafe extern "C" fn(*mut T));
+
+impl<T> Drop for Owned<T> {
+    fn drop(&mut self) {
+        if !self.0.is_null() {
+            unsafe { (self.1)(self.0) }
+        }
+    }
+}
+
+/// The commit-graph file of `repo`, which may not exist.
+#[cfg(feature = "cli")]
+pub(crate) fn path(repo: &Repository) -> PathBuf {
+    repo.path().join(FILE)
+}
+
+/// Whether `repo` has a commit-graph libgit2 can read, the object database of a repository opened
+/// from now on uses it.
+pub(crate) fn loaded(repo: &Repository) -> bool {
+    let Ok(objects) = c_path(&repo.path().join("objects")) else {
+        return false;
+    };
+    libgit2_sys::init();
+    let mut cgraph = ptr::null_mut();
+    let opened = unsafe { git_commit_graph_open(&mut cgraph, objects.as_ptr()) };
+    let _cgraph = Owned(cgraph, git_commit_graph_free);
+    opened == 0
+}
+
+/// Writes the commit-graph of the commits reachable from the refs and HEAD of `repo`, like
+/// `git commit-graph write --reachable`, and has `repo` load it.
+#[cfg(feature = "cli")]
+pub(crate) fn write(repo: &Repository) -> Result<PathBuf, CustomError> {
+    let info = repo.path().join("objects").join("info");
+    std::fs::create_dir_all(&info)?;
+    let (git_dir, info_dir) = (c_path(repo.path())?, c_path(&info)?);
+    libgit2_sys::init();
+    unsafe {
+        let mut handle = ptr::null_mut();
+        check(libgit2_sys::git_repository_open(
+            &mut handle,
+            git_dir.as_ptr(),
+        ))?;
+        let handle = Owned(handle, libgit2_sys::git_repository_free);
+        let mut walk = ptr::null_mut();
+        check(libgit2_sys::git_revwalk_new(&mut walk, handle.0))?;
+        let walk = Owned(walk, libgit2_sys::git_revwalk_free);
+        // Every ref, refs that point to anything but a commit are skipped
+        let refs = CString::new("refs/*").expect("no NUL in the glob");
+        check(libgit2_sys::git_revwalk_push_glob(walk.0, refs.as_ptr()))?;
+        // An unborn HEAD has no commits to add
+        if libgit2_sys::git_revwalk_push_head(walk.0) < 0 {
+            libgit2_sys::git_error_clear();
+        }
+
+        let mut writer = ptr::null_mut();
+        check(git_commit_graph_writer_new(&mut writer, info_dir.as_ptr()))?;
+        let writer = Owned(writer, git_commit_graph_writer_free);
+        check(git_commit_graph_writer_add_revwalk(writer.0, walk.0))?;
+        let mut options = WriterOptions {
+            version: 0,
+            split_strategy: 0,
+            size_multiple: 0.0,
+            max_commits: 0,
+        };
+        check(git_commit_graph_writer_options_init(
+            &mut options,
+            WRITER_OPTIONS_VERSION,
+        ))?;
+        check(git_commit_graph_writer_commit(writer.0, &mut options))?;
+    }
+    // The object database of an open repository looks for a new commit-graph when refreshed
+    repo.odb()?.refresh()?;
+    Ok(path(repo))
+}
+
+#[cfg(feature = "cli")]
+fn check(code: c_int) -> Result<(), git2::Error> {
+    match code {
+        0.. => Ok(()),
+        _ => Err(git2::Error::last_error(code)
+            .unwrap_or_else(|| git2::Error::from_str("libgit2 failed without an error"))),
+    }
+}
+
+fn c_path(path: &Path) -> Result<CString, CustomError> {
+    let path = path.to_str().ok_or_else(|| {
+        CustomError::InvalidArgument(format!("{} isn't valid UTF-8", path.display()))
+    })?;
+    CString::new(path)
+        .map_err(|_| CustomError::InvalidArgument(format!("{} contains a NUL byte", path)))
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod test {
+    use crate::commit_graph;
+    use crate::test::{commit_file, temp_repository};
+
+    #[test]
+    fn writes_a_commit_graph_libgit2_loads() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("commit_graph")?;
+        let first = commit_file(&repo, "file.txt", "one\n", "first")?;
+        let second = commit_file(&repo, "file.txt", "two\n", "second")?;
+        let head = commit_file(&repo, "file.txt", "three\n", "third")?;
+
+        let before = commit_graph::loaded(&repo);
+        let written = commit_graph::write(&repo)?;
+        let after = commit_graph::loaded(&repo);
+        let mut walk = repo.revwalk()?;
+        walk.push(head)?;
+        let walked = walk.collect::<Result<Vec<_>, _>>()?;
+        let descendant = repo.graph_descendant_of(head, first)?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert!(!before);
+        assert_eq!(written, path.join(".git/objects/info/commit-graph"));
+        assert!(after);
+        assert_eq!(walked, vec![head, second, first]);
+        assert!(descendant);
+
+        Ok(())
+    }
+}
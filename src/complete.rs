@@ -0,0 +1,352 @@
+use itertools::Itertools;
+use rusqlite::Connection;
+use std::collections::BTreeMap;
+
+/// Keywords that follow a table without being its alias.
+const NOT_ALIASES: [&str; 21] = [
+    "CROSS",
+    "EXCEPT",
+    "FULL",
+    "GROUP",
+    "HAVING",
+    "INDEXED",
+    "INNER",
+    "INTERSECT",
+    "JOIN",
+    "LEFT",
+    "LIMIT",
+    "NATURAL",
+    "NOT",
+    "ON",
+    "ORDER",
+    "OUTER",
+    "RIGHT",
+    "UNION",
+    "USING",
+    "WHERE",
+    "WINDOW",
+];
+
+/// Keywords followed by a table.
+const BEFORE_TABLES: [&str; 5] = ["FROM", "JOIN", "INTO", "UPDATE", "TABLE"];
+
+/// The names a statement can use, read from the database: the tables, the views and the git
+/// tables with their columns, and the functions.
+#[derive(Debug, Default)]
+pub(crate) struct Schema {
+    /// The columns of the tables by the lowercase name of the table
+    tables: BTreeMap<String, Table>,
+    functions: Vec<Function>,
+}
+
+#[derive(Debug)]
+struct Table {
+    name: String,
+    columns: Vec<Column>,
+}
+
+#[derive(Debug)]
+struct Column {
+    name: String,
+    /// The hidden columns of the git tables are the arguments they're called with, in order,
+    /// e.g. `commits(repository, ref)`
+    hidden: bool,
+}
+
+#[derive(Debug)]
+struct Function {
+    name: String,
+    /// The least and the most arguments, None when it takes any number of them
+    args: Option<(i64, i64)>,
+}
+
+/// A name a word can be completed to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct Completion {
+    /// What replaces the word
+    pub(crate) text: String,
+    /// How the completion is listed, with the arguments of a table or a function
+    pub(crate) display: String,
+}
+
+impl Schema {
+    /// Reads the tables and views of the schema, the git tables and the other table-valued
+    /// functions, and the functions. The git tables declare their arguments as hidden columns.
+    pub(crate) fn read(db: &Connection) -> rusqlite::Result<Schema> {
+        let mut stmt = db.prepare(
+            "SELECT name FROM sqlite_schema WHERE type IN ('table', 'view')
+             UNION SELECT name FROM pragma_module_list",
+        )?;
+        let names: Vec<String> = stmt.query_map([], |row| row.get(0))?.try_collect()?;
+        let mut stmt = db.prepare("SELECT name, hidden FROM pragma_table_xinfo(?)")?;
+        let mut tables = BTreeMap::new();
+        for name in names {
+            // Modules that aren't tables by themselves, like fts5, have no columns or can't be
+            // connected without arguments
+            let columns = stmt.query_map([&name], |row| {
+                Ok(Column {
+                    name: row.get(0)?,
+                    hidden: row.get::<_, i64>(1)? != 0,
+                })
+            });
+            let columns: Vec<Column> = columns.and_then(Iterator::collect).unwrap_or_default();
+            if !columns.is_empty() {
+                tables.insert(name.to_lowercase(), Table { name, columns });
+            }
+        }
+
+        let mut stmt = db.prepare(
+            "SELECT name, min(narg), max(narg), min(narg) < 0 FROM pragma_function_list
+             GROUP BY name ORDER BY name",
+        )?;
+        let functions = stmt
+            .query_map([], |row| {
+                let any: bool = row.get(3)?;
+                Ok(Function {
+                    name: row.get(0)?,
+                    args: (!any).then_some((row.get(1)?, row.get(2)?)),
+                })
+            })?
+            .try_collect()?;
+        Ok(Schema { tables, functions })
+    }
+
+    /// Where the word that ends at the byte `pos` of `sql` starts and what it completes to.
+    /// After `FROM` and `JOIN` the word is a table, after `alias.` a column of the aliased
+    /// table, anywhere else a column of the tables of the statement or a function.
+    pub(crate) fn complete(&self, sql: &str, pos: usize) -> (usize, Vec<Completion>) {
+        let before = &sql[..pos];
+        let start = before.rfind(|c: char| !is_name(c)).map_or(0, |at| {
+            at + before[at..].chars().next().map_or(1, char::len_utf8)
+        });
+        let word = &before[start..];
+        let matches =
+            |name: &str| name.len() >= word.len() && name[..word.len()].eq_ignore_ascii_case(word);
+
+        // The statement the cursor is in, its tables may come after the cursor
+        let statement_start = before.rfind(';').map_or(0, |at| at + 1);
+        let statement_end = sql[pos..].find(';').map_or(sql.len(), |at| pos + at);
+        let tokens = tokens(&sql[statement_start..statement_end]);
+        let tables = self.tables_of(&tokens);
+
+        if let Some(qualifier) = before[..start].strip_suffix('.') {
+            let qualifier = qualifier
+                .rsplit(|c: char| !is_name(c))
+                .next()
+                .unwrap_or_default();
+            let table = (tables.iter())
+                .find(|(_, alias)| alias.eq_ignore_ascii_case(qualifier))
+                .map(|(table, _)| *table)
+                .or_else(|| self.tables.get(&qualifier.to_lowercase()));
+            let columns = table.map_or(vec![], |table| {
+                let columns = table.columns.iter().filter(|column| matches(&column.name));
+                columns.map(Column::completion).collect()
+            });
+            return (start, columns);
+        }
+
+        let previous = before[..start].split_whitespace().next_back();
+        let after_keyword =
+            previous.is_some_and(|word| BEFORE_TABLES.iter().any(|k| k.eq_ignore_ascii_case(word)));
+        let tables_named = (self.tables.values())
+            .filter(|table| matches(&table.name))
+            .map(Table::completion);
+        if after_keyword {
+            return (start, tables_named.collect());
+        }
+        // Every name would be listed
+        if word.is_empty() {
+            return (start, vec![]);
+        }
+        let columns = (tables.iter())
+            .flat_map(|(table, _)| &table.columns)
+            .filter(|column| matches(&column.name))
+            .map(Column::completion)
+            .unique();
+        let functions = (self.functions.iter())
+            .filter(|function| matches(&function.name))
+            .map(Function::completion);
+        let completions = columns.chain(functions).chain(tables_named);
+        (start, completions.collect())
+    }
+
+    /// The tables named by the tokens of a statement, with their alias, or their own name.
+    fn tables_of<'s>(&'s self, tokens: &[&'s str]) -> Vec<(&'s Table, &'s str)> {
+        let mut tables = vec![];
+        for (i, token) in tokens.iter().enumerate() {
+            let Some(table) = self.tables.get(&token.to_lowercase()) else {
+                continue;
+            };
+            // Columns of the table are qualified with its name, `commits.hash`
+            if tokens.get(i + 1) == Some(&".") {
+                continue;
+            }
+            let mut next = i + 1;
+            // The arguments of a table-valued function
+            if tokens.get(next) == Some(&"(") {
+                let mut depth = 0;
+                for token in &tokens[next..] {
+                    next += 1;
+                    match *token {
+                        "(" => depth += 1,
+                        ")" if depth == 1 => break,
+                        ")" => depth -= 1,
+                        _ => {}
+                    }
+                }
+            }
+            if tokens
+                .get(next)
+                .is_some_and(|t| t.eq_ignore_ascii_case("AS"))
+            {
+                next += 1;
+            }
+            let alias = match tokens.get(next) {
+                Some(alias)
+                    if alias.chars().all(is_name)
+                        && !NOT_ALIASES.iter().any(|k| k.eq_ignore_ascii_case(alias)) =>
+                {
+                    alias
+                }
+                _ => token,
+            };
+            tables.push((table, *alias));
+        }
+        tables
+    }
+}
+
+impl Table {
+    /// Tables with arguments are listed like they're called, `commits(repository, ref, sample)`.
+    fn completion(&self) -> Completion {
+        let args = (self.columns.iter())
+            .filter(|column| column.hidden)
+            .map(|column| &column.name)
+            .join(", ");
+        Completion {
+            text: self.name.clone(),
+            display: match args.is_empty() {
+                true => self.name.clone(),
+                false => format!("{}({})", self.name, args),
+            },
+        }
+    }
+}
+
+impl Column {
+    fn completion(&self) -> Completion {
+        Completion {
+            text: self.name.clone(),
+            display: match self.hidden {
+                true => format!("{} (hidden)", self.name),
+                false => self.name.clone(),
+            },
+        }
+    }
+}
+
+impl Function {
+    fn completion(&self) -> Completion {
+        let args = match self.args {
+            None => "...".to_string(),
+            Some((least, most)) if least == most => least.to_string(),
+            Some((least, most)) => format!("{}-{}", least, most),
+        };
+        Completion {
+            text: format!("{}(", self.name),
+            display: format!("{}({} args)", self.name, args),
+        }
+    }
+}
+
+fn is_name(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// The names, strings, numbers and punctuation of a statement, without the whitespace.
+fn tokens(sql: &str) -> Vec<&str> {
+    let mut tokens = vec![];
+    let mut rest = sql.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let len = match c {
+            c if is_name(c) => rest.find(|c: char| !is_name(c)).unwrap_or(rest.len()),
+            // Strings and quoted names, a string that isn't closed yet runs to the end
+            '\'' | '"' | '`' => rest[1..].find(c).map_or(rest.len(), |at| at + 2),
+            c => c.len_utf8(),
+        };
+        let (token, after) = rest.split_at(len);
+        // Quoted names are names too
+        tokens.push(match c {
+            '"' | '`' => token.trim_matches(c),
+            _ => token,
+        });
+        rest = after.trim_start();
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod test {
+    use crate::complete::{Completion, Schema};
+    use crate::SqliteGit;
+    use rusqlite::Connection;
+
+    #[test]
+    fn completes_tables_columns_and_functions() -> Result<(), rusqlite::Error> {
+        let db = Connection::open_in_memory()?;
+        SqliteGit::new().with_all().register(&db)?;
+        db.execute("CREATE TABLE releases(tag text, released_at text)", [])?;
+        let schema = Schema::read(&db)?;
+        let complete = |sql: &str| {
+            let (start, completions) = schema.complete(sql, sql.len());
+            let texts = completions.into_iter().map(|c| c.text).collect::<Vec<_>>();
+            (start, texts)
+        };
+        let display = |sql: &str, text: &str| {
+            let (_, completions) = schema.complete(sql, sql.len());
+            completions
+                .into_iter()
+                .find(|completion| completion.text == text)
+                .map(|completion| completion.display)
+        };
+
+        assert_eq!(
+            complete("SELECT * FROM com"),
+            (14, vec!["commits".to_string()])
+        );
+        assert_eq!(
+            display("SELECT * FROM com", "commits").as_deref(),
+            Some("commits(repository, ref, sample)")
+        );
+        assert_eq!(complete("select * from rel").1, ["releases"]);
+        // Columns of the aliased table, hidden ones too
+        let sql = "SELECT c.re FROM commits('HEAD') AS c";
+        let columns = schema.complete(sql, 11).1.into_iter().map(|c| c.text);
+        assert_eq!(columns.collect::<Vec<_>>(), ["repository", "ref"]);
+        let (start, columns) = complete("SELECT * FROM commits c JOIN stats s ON s.");
+        assert_eq!(start, 42);
+        assert!(columns.contains(&"file_name".to_string()));
+        assert_eq!(
+            schema.complete("SELECT s.h FROM stats s", 10).1,
+            [Completion {
+                text: "hash".to_string(),
+                display: "hash (hidden)".to_string()
+            }]
+        );
+        // The tables may come after the cursor, functions follow the columns
+        let (_, names) = complete("SELECT * FROM releases; SELECT author_n");
+        assert!(names.is_empty());
+        assert_eq!(
+            schema.complete("SELECT author_n FROM commits", 15).1[0].text,
+            "author_name"
+        );
+        assert_eq!(complete("SELECT git_sho").1, ["git_short("]);
+        assert_eq!(
+            display("SELECT git_sho", "git_short(").as_deref(),
+            Some("git_short(1-3 args)")
+        );
+        assert!(complete("SELECT ").1.is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,318 @@
+use crate::output::{DateFormat, OutputMode, TimeZoneChoice};
+use crate::CustomError;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Name of the config file, looked up in the repository and in the home directory.
+const CONFIG_FILE: &str = ".sqlitegit.toml";
+
+/// Settings read from `.sqlitegit.toml`.
+///
+/// ```toml
+/// scan_limit = 10000
+/// row_limit = 500
+/// tz = "local"
+///
+/// [date_formats]
+/// table = "%d %b %Y %H:%M"
+/// csv = "%Y-%m-%dT%H:%M:%S%:z"
+///
+/// [queries.churn]
+/// description = "Lines changed per file"
+/// sql = "SELECT file_name, sum(additions + deletions) FROM stats, commits WHERE ..."
+/// params = { since = "1970-01-01" }
+///
+/// [tui]
+/// theme = "no-color"
+/// keys = { quit = "ctrl-x", run = ["f5", "ctrl-e"] }
+///
+/// [[tui.panels]]
+/// title = "Recent commits"
+/// sql = "SELECT author_when, author_name, message FROM commits LIMIT 20"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Commits walked from HEAD when a query of `commits` or `merges` passes no revision
+    #[serde(default)]
+    pub scan_limit: Option<usize>,
+    /// Rows of a result set printed to a terminal, 0 prints them all
+    #[serde(default)]
+    pub row_limit: Option<usize>,
+    /// The time zone DATETIME columns are printed in: `local`, `utc` or `original`
+    #[serde(default)]
+    pub tz: Option<TimeZoneChoice>,
+    /// The strftime formats DATETIME columns are printed in by output format, e.g.
+    /// `table = "%d %b %Y"`
+    #[serde(default)]
+    pub date_formats: BTreeMap<OutputMode, DateFormat>,
+    #[serde(default)]
+    pub queries: BTreeMap<String, QueryTemplate>,
+    #[cfg(feature = "tui")]
+    #[serde(default)]
+    pub tui: TuiConfig,
+    /// Builds without the tui accept its settings and leave them alone
+    #[cfg(not(feature = "tui"))]
+    #[serde(default, rename = "tui")]
+    _tui: Option<serde::de::IgnoredAny>,
+}
+
+/// Settings of `sqlitegit tui`.
+#[cfg(feature = "tui")]
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TuiConfig {
+    /// `default`, or `no-color` for text styles only, which is also used when NO_COLOR is set
+    #[serde(default)]
+    pub theme: Option<String>,
+    /// Colors replacing the ones of the default theme, e.g. `focused = "green"`
+    #[serde(default)]
+    pub colors: BTreeMap<String, String>,
+    /// Keys replacing the default ones of an action, e.g. `run = ["f5", "ctrl-e"]`
+    #[serde(default)]
+    pub keys: BTreeMap<String, KeyList>,
+    /// The panels of the dashboard, in the order they're laid out
+    #[serde(default)]
+    pub panels: Vec<Panel>,
+}
+
+/// A panel of the dashboard, the result of its query is refreshed when the repository changes.
+#[cfg(feature = "tui")]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Panel {
+    pub title: String,
+    pub sql: String,
+    /// Values of the `:NAME` parameters of the query
+    #[serde(default)]
+    pub params: BTreeMap<String, String>,
+}
+
+/// A key or a list of keys.
+#[cfg(feature = "tui")]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum KeyList {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[cfg(feature = "tui")]
+impl KeyList {
+    pub fn keys(&self) -> &[String] {
+        match self {
+            KeyList::One(key) => std::slice::from_ref(key),
+            KeyList::Many(keys) => keys,
+        }
+    }
+}
+
+/// A named, parameterized query run with `sqlitegit run NAME --PARAM VALUE`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QueryTemplate {
+    /// The statements to execute, `:NAME` parameters are filled in from the command line
+    pub sql: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Default values of the parameters
+    #[serde(default)]
+    pub params: BTreeMap<String, String>,
+}
+
+impl Config {
+    /// Reads `path` when given. Otherwise `~/.sqlitegit.toml` and `.sqlitegit.toml` in the
+    /// directory `repository` are read if they exist, the repository's settings and queries win
+    /// over the ones from the home directory.
+    pub fn load(path: Option<&Path>, repository: &Path) -> Result<Config, CustomError> {
+        if let Some(path) = path {
+            return Config::read(path);
+        }
+
+        let mut config = Config::default();
+        let candidates = [
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(CONFIG_FILE)),
+            Some(repository.join(CONFIG_FILE)),
+        ];
+        for path in candidates.iter().flatten().filter(|path| path.is_file()) {
+            let read = Config::read(path)?;
+            config.scan_limit = read.scan_limit.or(config.scan_limit);
+            config.row_limit = read.row_limit.or(config.row_limit);
+            config.tz = read.tz.or(config.tz);
+            config.date_formats.extend(read.date_formats);
+            config.queries.extend(read.queries);
+            #[cfg(feature = "tui")]
+            {
+                config.tui.theme = read.tui.theme.or(config.tui.theme);
+                config.tui.colors.extend(read.tui.colors);
+                config.tui.keys.extend(read.tui.keys);
+                // The repository's dashboard replaces the one of the home directory
+                if !read.tui.panels.is_empty() {
+                    config.tui.panels = read.tui.panels;
+                }
+            }
+        }
+        Ok(config)
+    }
+
+    /// The file queries are saved to: `path` when given, otherwise `.sqlitegit.toml` in the
+    /// directory `repository` if there is one and `~/.sqlitegit.toml` if not.
+    #[cfg(feature = "tui")]
+    pub fn save_path(path: Option<&Path>, repository: &Path) -> PathBuf {
+        let home = std::env::var_os("HOME").map(|home| PathBuf::from(home).join(CONFIG_FILE));
+        let local = repository.join(CONFIG_FILE);
+        match path {
+            Some(path) => path.to_path_buf(),
+            None if local.is_file() => local,
+            None => home.unwrap_or(local),
+        }
+    }
+
+    /// Writes `template` to the config file at `path` as `[queries.NAME]`, replacing the query
+    /// of that name. The rest of the file is left as it is, comments included. The file is
+    /// created when it doesn't exist, one that can't be loaded is not written to.
+    #[cfg(feature = "tui")]
+    pub fn save_query(
+        path: &Path,
+        name: &str,
+        template: &QueryTemplate,
+    ) -> Result<(), CustomError> {
+        use toml_edit::{value, DocumentMut, InlineTable, Item, Table};
+
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        toml::from_str::<Config>(&content)
+            .map_err(|e| CustomError::Config(path.to_path_buf(), e))?;
+        let mut document = content
+            .parse::<DocumentMut>()
+            .map_err(|e| CustomError::InvalidArgument(format!("{}: {}", path.display(), e)))?;
+
+        let mut query = Table::new();
+        if let Some(description) = &template.description {
+            query["description"] = value(description);
+        }
+        query["sql"] = value(&template.sql);
+        if !template.params.is_empty() {
+            let params = template.params.iter().collect::<InlineTable>();
+            query["params"] = value(params);
+        }
+        if document.as_table().is_empty() {
+            // The comments of a file without settings would end up after the query
+            let comments = document.trailing().as_str().unwrap_or_default().to_string();
+            query.decor_mut().set_prefix(comments);
+            document.set_trailing("");
+        }
+        let queries = document
+            .entry("queries")
+            .or_insert_with(|| {
+                let mut queries = Table::new();
+                queries.set_implicit(true);
+                Item::Table(queries)
+            })
+            .as_table_mut()
+            .ok_or_else(|| {
+                CustomError::InvalidArgument(format!("{}: queries is not a table", path.display()))
+            })?;
+        queries.insert(name, Item::Table(query));
+        std::fs::write(path, document.to_string())?;
+        Ok(())
+    }
+
+    fn read(path: &Path) -> Result<Config, CustomError> {
+        let content = std::fs::read_to_string(path)?;
+        toml::from_str(&content).map_err(|e| CustomError::Config(path.to_path_buf(), e))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::Config;
+
+    #[test]
+    fn parse_queries() -> Result<(), toml::de::Error> {
+        let config: Config = toml::from_str(
+            r#"
+            scan_limit = 5000
+
+            [queries.churn]
+            sql = "SELECT :since"
+            params = { since = "2024-01-01" }
+
+            [queries.authors]
+            description = "Everyone who committed"
+            sql = "SELECT DISTINCT author_name FROM commits"
+
+            [tui]
+            theme = "no-color"
+            keys = { quit = "ctrl-x", run = ["f5", "ctrl-e"] }
+
+            [[tui.panels]]
+            title = "Authors"
+            sql = "SELECT author_name, count(*) FROM commits GROUP BY 1"
+            "#,
+        )?;
+
+        assert_eq!(config.scan_limit, Some(5000));
+        assert_eq!(config.queries.len(), 2);
+        assert_eq!(config.queries["churn"].params["since"], "2024-01-01");
+        assert_eq!(
+            config.queries["authors"].description.as_deref(),
+            Some("Everyone who committed")
+        );
+        #[cfg(feature = "tui")]
+        {
+            assert_eq!(config.tui.theme.as_deref(), Some("no-color"));
+            assert_eq!(config.tui.keys["quit"].keys(), ["ctrl-x"]);
+            assert_eq!(config.tui.keys["run"].keys(), ["f5", "ctrl-e"]);
+            assert_eq!(config.tui.panels[0].title, "Authors");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "tui")]
+    fn save_query() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::config::QueryTemplate;
+        use std::collections::BTreeMap;
+
+        let path = std::env::temp_dir().join("sqlitegit_save_query.toml");
+        std::fs::write(
+            &path,
+            "# Settings of the team\nscan_limit = 5000\n\n[queries.authors]\nsql = \"SELECT 1\"\n",
+        )?;
+        let template = QueryTemplate {
+            sql: "SELECT :since\nFROM commits".to_string(),
+            description: Some("Since a date".to_string()),
+            params: BTreeMap::from([("since".to_string(), "2024-01-01".to_string())]),
+        };
+        Config::save_query(&path, "since", &template)?;
+        Config::save_query(
+            &path,
+            "authors",
+            &QueryTemplate {
+                sql: "SELECT 2".to_string(),
+                ..template.clone()
+            },
+        )?;
+        let content = std::fs::read_to_string(&path)?;
+        let config = Config::load(Some(&path), std::path::Path::new("."))?;
+        std::fs::remove_file(&path)?;
+
+        assert!(
+            content.starts_with("# Settings of the team\n"),
+            "{}",
+            content
+        );
+        assert_eq!(config.scan_limit, Some(5000));
+        assert_eq!(config.queries["since"].sql, template.sql);
+        assert_eq!(config.queries["since"].params, template.params);
+        assert_eq!(config.queries["authors"].sql, "SELECT 2");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,261 @@
+use git2::{Config, Cred, CredentialType};
+use std::path::PathBuf;
+use tracing::debug;
+
+/// The environment variable holding a token for https remotes, e.g. a personal access token of
+/// GitHub or GitLab.
+const TOKEN_VARIABLE: &str = "SQLITEGIT_TOKEN";
+
+/// The environment variable naming the host the token is for, like `github.com`. The token is
+/// offered to no other host.
+const TOKEN_HOST_VARIABLE: &str = "SQLITEGIT_TOKEN_HOST";
+
+/// The keys below `~/.ssh` tried when the ssh agent has none the remote accepts, in the order of
+/// `ssh`.
+const SSH_KEYS: [&str; 3] = ["id_ed25519", "id_ecdsa", "id_rsa"];
+
+/// One way of authenticating to a remote.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Attempt {
+    /// The user name alone, which ssh asks for before the key
+    Username(String),
+    SshAgent(String),
+    SshKey(String, PathBuf),
+    /// The token of `SQLITEGIT_TOKEN`
+    Token(String),
+    /// The credential helpers of the git config, like `git` itself uses
+    CredentialHelper,
+    /// The credentials of the logged in user, for NTLM and Negotiate
+    Default,
+}
+
+/// Answers the credential requests of a clone or fetch like `git` would: with the ssh agent and
+/// the default keys for ssh, with `SQLITEGIT_TOKEN` or the configured credential helper for
+/// https. The token is only sent over https to the host of `SQLITEGIT_TOKEN_HOST`. Each way is
+/// tried once, libgit2 asks again when the remote rejects an answer.
+pub(crate) struct Credentials {
+    /// The git config the credential helpers are read from
+    config: Option<Config>,
+    home: Option<PathBuf>,
+    /// The token and the host it's for
+    token: Option<(String, String)>,
+    tried: Vec<Attempt>,
+}
+
+impl Credentials {
+    /// Credentials from the environment, with the credential helpers of `config`.
+    pub(crate) fn new(config: Option<Config>) -> Credentials {
+        let config = config.or_else(|| Config::open_default().ok());
+        let var = |name| std::env::var(name).ok().filter(|value| !value.is_empty());
+        let token = match (var(TOKEN_VARIABLE), var(TOKEN_HOST_VARIABLE)) {
+            (Some(token), Some(host)) => Some((token, host)),
+            (Some(_), None) => {
+                debug!(
+                    "{} is ignored without {}",
+                    TOKEN_VARIABLE, TOKEN_HOST_VARIABLE
+                );
+                None
+            }
+            (None, _) => None,
+        };
+        let home = std::env::var_os("HOME").map(PathBuf::from);
+        Credentials {
+            config,
+            home,
+            token,
+            tried: vec![],
+        }
+    }
+
+    /// The credentials to try next for `url`, an error once every way was tried.
+    pub(crate) fn next(
+        &mut self,
+        url: &str,
+        username: Option<&str>,
+        allowed: CredentialType,
+    ) -> Result<Cred, git2::Error> {
+        let Some(attempt) = self.attempt(url, username, allowed) else {
+            return Err(git2::Error::from_str(&format!(
+                "no credentials {} accepts, tried {}",
+                url,
+                self.tried_names()
+            )));
+        };
+        debug!(url, ?attempt, "authenticating");
+        self.tried.push(attempt.clone());
+        match attempt {
+            Attempt::Username(user) => Cred::username(&user),
+            Attempt::SshAgent(user) => Cred::ssh_key_from_agent(&user),
+            Attempt::SshKey(user, key) => Cred::ssh_key(&user, None, &key, None),
+            Attempt::Token(user) => {
+                let token = self.token.as_ref().map_or("", |(token, _)| token);
+                Cred::userpass_plaintext(&user, token)
+            }
+            Attempt::CredentialHelper => match &self.config {
+                Some(config) => Cred::credential_helper(config, url, username),
+                None => Err(git2::Error::from_str(
+                    "no git config to read credential helpers from",
+                )),
+            },
+            Attempt::Default => Cred::default(),
+        }
+    }
+
+    /// The first way of authenticating the remote allows that wasn't tried yet.
+    fn attempt(
+        &self,
+        url: &str,
+        username: Option<&str>,
+        allowed: CredentialType,
+    ) -> Option<Attempt> {
+        let user = username.unwrap_or("git").to_string();
+        let mut attempts = vec![];
+        if allowed.contains(CredentialType::USERNAME) {
+            attempts.push(Attempt::Username(user.clone()));
+        }
+        if allowed.contains(CredentialType::SSH_KEY) {
+            attempts.push(Attempt::SshAgent(user.clone()));
+            let ssh = self.home.iter().map(|home| home.join(".ssh"));
+            let keys = ssh.flat_map(|ssh| SSH_KEYS.map(|key| ssh.join(key)));
+            let keys = keys.filter(|key| key.exists());
+            attempts.extend(keys.map(|key| Attempt::SshKey(user.clone(), key)));
+        }
+        if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) {
+            if self.token_for(url) {
+                // GitHub and GitLab take any user name with a token
+                let user = username.unwrap_or("x-access-token").to_string();
+                attempts.push(Attempt::Token(user));
+            }
+            attempts.push(Attempt::CredentialHelper);
+        }
+        if allowed.contains(CredentialType::DEFAULT) {
+            attempts.push(Attempt::Default);
+        }
+        attempts
+            .into_iter()
+            .find(|attempt| !self.tried.contains(attempt))
+    }
+
+    /// Whether the token may be sent to `url`, only over https and to its host.
+    fn token_for(&self, url: &str) -> bool {
+        let Some((_, token_host)) = &self.token else {
+            return false;
+        };
+        let Some(rest) = url.strip_prefix("https://") else {
+            return false;
+        };
+        let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+        let host = authority
+            .rsplit_once('@')
+            .map_or(authority, |(_, host)| host);
+        host.eq_ignore_ascii_case(token_host)
+    }
+
+    fn tried_names(&self) -> String {
+        if self.tried.is_empty() {
+            return "nothing".to_string();
+        }
+        let names = self.tried.iter().map(|attempt| match attempt {
+            Attempt::Username(user) => format!("the user name {}", user),
+            Attempt::SshAgent(_) => "the ssh agent".to_string(),
+            Attempt::SshKey(_, key) => key.display().to_string(),
+            Attempt::Token(_) => TOKEN_VARIABLE.to_string(),
+            Attempt::CredentialHelper => "the credential helpers".to_string(),
+            Attempt::Default => "the default credentials".to_string(),
+        });
+        names.collect::<Vec<_>>().join(", ")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::credentials::{Attempt, Credentials};
+    use git2::CredentialType;
+    use std::path::PathBuf;
+
+    fn credentials(home: Option<PathBuf>) -> Credentials {
+        Credentials {
+            config: None,
+            home,
+            token: Some(("secret".to_string(), "host".to_string())),
+            tried: vec![],
+        }
+    }
+
+    #[test]
+    fn tries_each_way_once() -> Result<(), Box<dyn std::error::Error>> {
+        let home = std::env::temp_dir().join("sqlitegit_credentials");
+        std::fs::create_dir_all(home.join(".ssh"))?;
+        std::fs::write(home.join(".ssh").join("id_rsa"), "")?;
+        let mut credentials = credentials(Some(home.clone()));
+        let mut attempts = |url, username, allowed| {
+            let mut attempts = vec![];
+            while let Some(attempt) = credentials.attempt(url, username, allowed) {
+                credentials.tried.push(attempt.clone());
+                attempts.push(attempt);
+            }
+            attempts
+        };
+
+        let ssh = attempts("ssh://host/repo", Some("git"), CredentialType::SSH_KEY);
+        let https = attempts(
+            "https://host/repo",
+            None,
+            CredentialType::USER_PASS_PLAINTEXT,
+        );
+        let failed = credentials
+            .next(
+                "https://host/repo",
+                None,
+                CredentialType::USER_PASS_PLAINTEXT,
+            )
+            .err()
+            .map(|e| e.message().to_string());
+        std::fs::remove_dir_all(&home)?;
+
+        let key = home.join(".ssh").join("id_rsa");
+        assert_eq!(
+            ssh,
+            vec![
+                Attempt::SshAgent("git".to_string()),
+                Attempt::SshKey("git".to_string(), key.clone()),
+            ]
+        );
+        assert_eq!(
+            https,
+            vec![
+                Attempt::Token("x-access-token".to_string()),
+                Attempt::CredentialHelper
+            ]
+        );
+        assert_eq!(
+            failed,
+            Some(format!(
+                "no credentials https://host/repo accepts, tried the ssh agent, {}, \
+                 SQLITEGIT_TOKEN, the credential helpers",
+                key.display()
+            ))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn withholds_the_token_from_other_hosts() {
+        let first = |url| credentials(None).attempt(url, None, CredentialType::USER_PASS_PLAINTEXT);
+
+        assert_eq!(
+            first("https://user@HOST/repo"),
+            Some(Attempt::Token("x-access-token".to_string()))
+        );
+        for url in [
+            "https://attacker.example/x",
+            "https://host.attacker.example/x",
+            "https://attacker.example/host",
+            "https://host:8443/repo",
+            "http://host/repo",
+        ] {
+            assert_eq!(first(url), Some(Attempt::CredentialHelper), "{}", url);
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use crate::{git_error_code, CustomError};
+use itertools::Itertools;
+use rusqlite::{ffi, Connection};
+use std::fmt::{Display, Formatter};
+use unicode_width::UnicodeWidthStr;
+
+/// An error of a statement with the spot SQLite complained about pointed out, and hints at the
+/// mistakes that commonly cause it.
+#[derive(Debug)]
+pub struct Diagnostic {
+    error: CustomError,
+    snippet: Option<Snippet>,
+    hints: Vec<String>,
+}
+
+/// The line of the SQL the error is about, with a caret under the offending part.
+#[derive(Debug, PartialEq)]
+struct Snippet {
+    /// Counted from 1
+    line_number: usize,
+    line: String,
+    /// Where the caret starts and how wide it is, in columns of the terminal
+    column: usize,
+    width: usize,
+}
+
+impl CustomError {
+    /// The error of executing `sql` on `db` with its diagnostic, errors that aren't about the
+    /// statement or the repository are left alone.
+    pub(crate) fn diagnose(self, db: &Connection, sql: &str) -> CustomError {
+        let (code, message) = match &self {
+            CustomError::Sqlite(rusqlite::Error::SqliteFailure(e, message)) => {
+                let message = message.clone().unwrap_or_else(|| e.to_string());
+                (e.extended_code & 0xff, message)
+            }
+            CustomError::Sqlite(rusqlite::Error::ModuleError(message)) => {
+                (ffi::SQLITE_ERROR, message.clone())
+            }
+            CustomError::Git(e) => (git_error_code(e), e.message().to_string()),
+            _ => return self,
+        };
+        CustomError::Diagnostic(Box::new(Diagnostic {
+            snippet: snippet(sql, &message),
+            hints: hints(db, code, &message),
+            error: self,
+        }))
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.error)?;
+        if let Some(snippet) = &self.snippet {
+            let gutter = " ".repeat(snippet.line_number.to_string().len());
+            write!(f, "\n{} |\n", gutter)?;
+            writeln!(f, "{} | {}", snippet.line_number, snippet.line)?;
+            write!(
+                f,
+                "{} | {}{}",
+                gutter,
+                " ".repeat(snippet.column),
+                "^".repeat(snippet.width.max(1))
+            )?;
+        }
+        for hint in &self.hints {
+            write!(f, "\nhint: {}", hint)?;
+        }
+        Ok(())
+    }
+}
+
+/// Points at the name SQLite's `message` is about, or at the end of an incomplete statement.
+fn snippet(sql: &str, message: &str) -> Option<Snippet> {
+    let name = [
+        "no such column: ",
+        "no such table: ",
+        "no such function: ",
+        "ambiguous column name: ",
+    ]
+    .iter()
+    .find_map(|prefix| message.strip_prefix(prefix))
+    .or_else(|| {
+        let near = message.strip_prefix("near \"")?;
+        near.split_once("\": syntax error").map(|(token, _)| token)
+    })
+    .or_else(|| {
+        let table = message.strip_prefix("too many arguments on ")?;
+        table.split_once("()").map(|(table, _)| table)
+    });
+    let (start, len) = match name {
+        Some(name) => {
+            // `main.releases` is written `releases`
+            let unqualified = name.rsplit('.').next().unwrap_or(name);
+            match find_name(sql, name) {
+                Some(start) => (start, name.len()),
+                None => (find_name(sql, unqualified)?, unqualified.len()),
+            }
+        }
+        None if message == "incomplete input" => (sql.trim_end().len(), 0),
+        None => return None,
+    };
+
+    let line_start = sql[..start].rfind('\n').map_or(0, |at| at + 1);
+    let line_end = sql[start..].find('\n').map_or(sql.len(), |at| start + at);
+    // Tabs would make the caret miss
+    let untabbed = |text: &str| text.replace('\t', " ");
+    Some(Snippet {
+        line_number: sql[..start].matches('\n').count() + 1,
+        line: untabbed(sql[line_start..line_end].trim_end()),
+        column: untabbed(&sql[line_start..start]).width(),
+        width: untabbed(&sql[start..start + len]).width(),
+    })
+}
+
+/// The byte offset of the first `name` in `sql` that isn't part of a longer name.
+fn find_name(sql: &str, name: &str) -> Option<usize> {
+    let is_name = |c: char| c.is_alphanumeric() || c == '_';
+    let (lower, name) = (sql.to_lowercase(), name.to_lowercase());
+    // Lowercasing keeps the offsets of ASCII, others don't get a caret
+    if lower.len() != sql.len() || name.is_empty() {
+        return None;
+    }
+    lower.match_indices(&name).map(|(at, _)| at).find(|&at| {
+        let before = lower[..at].chars().next_back();
+        let after = lower[at + name.len()..].chars().next();
+        !before.is_some_and(is_name) && !after.is_some_and(is_name)
+    })
+}
+
+/// What commonly causes an error with the result `code` and `message`.
+fn hints(db: &Connection, code: i32, message: &str) -> Vec<String> {
+    let mut hints = vec![];
+    if message.starts_with("unable to parse OID") {
+        hints.push(
+            "the ref argument of commits, merges and stats is a full commit hash, resolve \
+             branches and tags with git_rev_parse, e.g. stats('.', git_rev_parse('main'))"
+                .to_string(),
+        );
+    }
+    if let Some(table) = (message.strip_prefix("too many arguments on "))
+        .and_then(|rest| rest.split_once("()"))
+        .map(|(table, _)| table)
+    {
+        let args = table_arguments(db, table);
+        if !args.is_empty() {
+            hints.push(format!("{} takes {}({})", table, table, args.join(", ")));
+        }
+    }
+    if message.starts_with("no such table: ") {
+        hints.push(
+            "the git tables are commits, merges and stats, .tables in the repl lists the others"
+                .to_string(),
+        );
+    }
+    let git = match code {
+        ffi::SQLITE_CANTOPEN => {
+            "the repository is the first argument of the git tables, the revision comes second, \
+             e.g. commits('.', hash), and --repo sets the repository queries read by default"
+        }
+        ffi::SQLITE_BUSY => {
+            "another git process holds a lock on the repository, try again once it's done"
+        }
+        ffi::SQLITE_AUTH => "the remote refused the credentials, check the ones git uses for it",
+        ffi::SQLITE_CORRUPT => "the repository may be corrupt, git fsck checks it",
+        ffi::SQLITE_IOERR => "reading the repository failed, check that it exists and is readable",
+        _ => return hints,
+    };
+    hints.push(git.to_string());
+    hints
+}
+
+/// The hidden columns of `table` that it takes as arguments, in order.
+fn table_arguments(db: &Connection, table: &str) -> Vec<String> {
+    let arguments = db
+        .prepare("SELECT name FROM pragma_table_xinfo(?) WHERE hidden")
+        .and_then(|mut stmt| stmt.query_map([table], |row| row.get(0))?.try_collect());
+    arguments.unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test::{commit_file, temp_repository};
+    use crate::SqliteGit;
+    use rusqlite::Connection;
+
+    #[test]
+    fn points_at_the_mistake_and_hints_at_the_fix() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("diagnostic")?;
+        commit_file(&repo, "file.txt", "one\n", "first")?;
+        let db = Connection::open_in_memory()?;
+        SqliteGit::new()
+            .with_all()
+            .repository(&path)
+            .register(&db)?;
+        let diagnose = |sql: &str| {
+            let error = db
+                .prepare(sql)
+                .and_then(|mut stmt| stmt.query([])?.next().map(drop));
+            error.map_err(|e| crate::CustomError::from(e).diagnose(&db, sql).to_string())
+        };
+
+        assert_eq!(
+            diagnose("SELECT hash\nFROM commits\nWHERE hsh = 1"),
+            Err("no such column: hsh\n  |\n3 | WHERE hsh = 1\n  |       ^^^".to_string())
+        );
+        assert_eq!(
+            diagnose("SELECT (1"),
+            Err("incomplete input\n  |\n1 | SELECT (1\n  |          ^".to_string())
+        );
+        let error = diagnose("SELECT * FROM stats WHERE hash = 'main'").unwrap_err();
+        assert!(error.contains("git_rev_parse('main')"), "{}", error);
+        let error = diagnose("SELECT * FROM commits(NULL, NULL, 1, 2)").unwrap_err();
+        assert!(
+            error.ends_with("^^^^^^^\nhint: commits takes commits(repository, ref, sample)"),
+            "{}",
+            error
+        );
+        let error = diagnose("SELECT * FROM commits('HEAD')").unwrap_err();
+        assert!(error.contains("hint: the repository is the first argument"));
+        std::fs::remove_dir_all(&path)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,291 @@
+use crate::intern::Interner;
+use crate::interrupt::Checkpoint;
+use crate::stats_cache::FileStats;
+use crate::{CustomError, DiffSettings, GitStatsCursor};
+use git2::{Oid, Repository};
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{Debug, Formatter};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{sync_channel, SyncSender};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+/// How many commits of a `commits` walk may wait for the workers, and how many of their diffs are
+/// kept for `stats`.
+const AHEAD: usize = 2048;
+
+/// How often `stats` checks for an interrupt while it waits for a worker.
+const WAIT: Duration = Duration::from_millis(100);
+
+/// Diffs the commits `commits` walks on a pool of threads, for the `stats` cursors of the same
+/// statement, so a scan like `commits JOIN stats` doesn't diff one commit at a time on the SQLite
+/// thread.
+///
+/// SQLite opens the cursors of every table of a statement before it filters any of them. While a
+/// `stats` cursor is open the walks of `commits` hand each commit to the workers before returning
+/// it, and `stats` takes the diff from them, waiting when a worker is still at it. A walk never
+/// waits for the workers, the commits they have no room for are diffed by `stats` itself. The
+/// diffs are dropped once the last `stats` cursor closes.
+#[derive(Clone, Default)]
+pub(crate) struct DiffAhead {
+    shared: Arc<Shared>,
+}
+
+#[derive(Default)]
+struct Shared {
+    /// The open `stats` cursors
+    readers: AtomicUsize,
+    diffs: Mutex<Diffs>,
+    /// Notified when a worker is done with a commit
+    settled: Condvar,
+}
+
+/// A commit of a repository, diffed with some options.
+type Key = (PathBuf, Oid, String);
+
+#[derive(Default)]
+struct Diffs {
+    /// The commits handed to the workers, `None` until they're diffed
+    slots: HashMap<Key, Option<FileStats>>,
+    /// The diffed commits, oldest first
+    done: VecDeque<Key>,
+}
+
+impl DiffAhead {
+    /// Makes the walks feed the workers until the returned guard, held by a `stats` cursor, is
+    /// dropped.
+    pub(crate) fn reader(&self) -> AheadReader {
+        self.shared.readers.fetch_add(1, Ordering::SeqCst);
+        AheadReader {
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Feeds the commits of a walk of `repo` to the workers, which diff them with `settings`.
+    pub(crate) fn feed(&self, repo: &Repository, settings: &DiffSettings) -> AheadFeed {
+        AheadFeed {
+            shared: self.shared.clone(),
+            path: repo.path().to_path_buf(),
+            settings: settings.clone(),
+            work: None,
+        }
+    }
+
+    /// The diff of `commit` with `settings` a worker computed, `None` when no worker was handed the
+    /// commit or the diff failed.
+    pub(crate) fn take(
+        &self,
+        repo: &Repository,
+        commit: Oid,
+        settings: &DiffSettings,
+        checkpoint: &Checkpoint,
+    ) -> Result<Option<FileStats>, CustomError> {
+        let key = (repo.path().to_path_buf(), commit, settings.cache_key());
+        let mut diffs = self.shared.diffs();
+        loop {
+            match diffs.slots.get(&key) {
+                None => return Ok(None),
+                Some(Some(_)) => return Ok(diffs.slots.remove(&key).flatten()),
+                Some(None) => {
+                    checkpoint.check()?;
+                    let settled = self.shared.settled.wait_timeout(diffs, WAIT);
+                    diffs = settled.unwrap_or_else(PoisonError::into_inner).0;
+                }
+            }
+        }
+    }
+}
+
+impl Shared {
+    fn diffs(&self) -> MutexGuard<'_, Diffs> {
+        self.diffs.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Stores the diff of a worker, or forgets the commit when the diff failed or no `stats`
+    /// cursor is open anymore.
+    fn settle(&self, key: Key, stats: Option<FileStats>) {
+        let mut diffs = self.diffs();
+        match stats {
+            Some(stats) => {
+                diffs.slots.insert(key.clone(), Some(stats));
+                diffs.done.push_back(key);
+                while diffs.done.len() > AHEAD {
+                    let Some(oldest) = diffs.done.pop_front() else {
+                        break;
+                    };
+                    if let Some(Some(_)) = diffs.slots.get(&oldest) {
+                        diffs.slots.remove(&oldest);
+                    }
+                }
+            }
+            None => {
+                diffs.slots.remove(&key);
+            }
+        }
+        self.settled.notify_all();
+    }
+}
+
+impl Debug for DiffAhead {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DiffAhead")
+            .field("readers", &self.shared.readers.load(Ordering::Relaxed))
+            .field("diffs", &self.shared.diffs().slots.len())
+            .finish()
+    }
+}
+
+/// Held by a `stats` cursor while it's open.
+pub(crate) struct AheadReader {
+    shared: Arc<Shared>,
+}
+
+impl Drop for AheadReader {
+    fn drop(&mut self) {
+        if self.shared.readers.fetch_sub(1, Ordering::SeqCst) == 1 {
+            let mut diffs = self.shared.diffs();
+            diffs.slots.retain(|_, stats| stats.is_none());
+            diffs.done.clear();
+        }
+    }
+}
+
+/// The commits of one walk, for the workers of [`DiffAhead`]. The workers are started by the
+/// first commit walked while a `stats` cursor is open and stop when the feed is dropped.
+pub(crate) struct AheadFeed {
+    shared: Arc<Shared>,
+    path: PathBuf,
+    settings: DiffSettings,
+    work: Option<SyncSender<Oid>>,
+}
+
+impl AheadFeed {
+    /// Hands `commit` to the workers, unless no `stats` cursor is open or the workers are busy.
+    pub(crate) fn push(&mut self, commit: Oid) {
+        if self.work.is_none() && self.shared.readers.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+        let work = (self.work)
+            .get_or_insert_with(|| spawn_ahead(&self.shared, &self.path, &self.settings));
+        let key = (self.path.clone(), commit, self.settings.cache_key());
+        let mut diffs = self.shared.diffs();
+        // The workers settle the commit under the lock, only once it's in `slots`
+        if !diffs.slots.contains_key(&key) && work.try_send(commit).is_ok() {
+            diffs.slots.insert(key, None);
+        }
+    }
+}
+
+/// Starts the workers of a feed, they diff the commits of the returned queue.
+fn spawn_ahead(shared: &Arc<Shared>, path: &Path, settings: &DiffSettings) -> SyncSender<Oid> {
+    let workers = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    let (work_tx, work_rx) = sync_channel::<Oid>(AHEAD);
+    let work_rx = Arc::new(Mutex::new(work_rx));
+    for _ in 0..workers {
+        let (shared, work_rx) = (shared.clone(), work_rx.clone());
+        let (path, settings) = (path.to_path_buf(), settings.clone());
+        thread::spawn(move || {
+            let repo = Repository::open(&path);
+            let mut interner = Interner::default();
+            let options = settings.cache_key();
+            loop {
+                let next = work_rx
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .recv();
+                let Ok(oid) = next else {
+                    return;
+                };
+                // The statement that wanted the diffs is done
+                let wanted = shared.readers.load(Ordering::SeqCst) > 0;
+                let stats = wanted.then(|| diff(&repo, oid, &settings, &mut interner).ok());
+                shared.settle((path.clone(), oid, options.clone()), stats.flatten());
+            }
+        });
+    }
+    work_tx
+}
+
+/// The stats of `oid`, with the repository a worker opened.
+pub(crate) fn diff(
+    repo: &Result<Repository, git2::Error>,
+    oid: Oid,
+    settings: &DiffSettings,
+    interner: &mut Interner,
+) -> Result<FileStats, CustomError> {
+    match repo {
+        Ok(repo) => {
+            let hash = oid.to_string();
+            let checkpoint = Checkpoint::default();
+            GitStatsCursor::compute_diff(repo, &hash, settings, interner, &checkpoint, None)
+        }
+        Err(e) => Err(git2::Error::from_str(e.message()).into()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diff_ahead::DiffAhead;
+    use crate::interrupt::Checkpoint;
+    use crate::test::{commit_file, temp_repository};
+    use crate::{DiffSettings, SqliteGit};
+    use rusqlite::Connection;
+
+    #[test]
+    fn diffs_the_walked_commits_while_stats_is_open() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("diff_ahead")?;
+        let first = commit_file(&repo, "file.txt", "one\n", "first")?;
+        let second = commit_file(&repo, "file.txt", "two\n", "second")?;
+
+        let (ahead, settings, checkpoint) = (
+            DiffAhead::default(),
+            DiffSettings::default(),
+            Checkpoint::default(),
+        );
+        ahead.feed(&repo, &settings).push(first);
+        let unread = ahead.take(&repo, first, &settings, &checkpoint)?;
+        let reader = ahead.reader();
+        let mut feed = ahead.feed(&repo, &settings);
+        feed.push(first);
+        feed.push(second);
+        let diffed = ahead.take(&repo, second, &settings, &checkpoint)?;
+        let taken = ahead.take(&repo, second, &settings, &checkpoint)?;
+        drop(reader);
+        let closed = ahead.take(&repo, first, &settings, &checkpoint)?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!(unread, None);
+        assert_eq!(diffed, Some(vec![("file.txt".into(), 1, 1)]));
+        assert_eq!(taken, None);
+        assert_eq!(closed, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn stats_of_a_history_scan_are_diffed_ahead() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("stats_diffed_ahead")?;
+        for i in 1..=20 {
+            commit_file(&repo, "file.txt", &"line\n".repeat(i), "grow")?;
+        }
+
+        let db = Connection::open_in_memory()?;
+        SqliteGit::new()
+            .with_all()
+            .repository(&path)
+            .register(&db)?;
+        let sql = "SELECT count(*), sum(additions) FROM commits c JOIN stats s ON s.hash = c.hash";
+        let counted = db.query_row(sql, [], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?))
+        })?;
+        std::fs::remove_dir_all(&path)?;
+
+        // The root commit has no parent to differ from
+        assert_eq!(counted, (19, 19));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,109 @@
+use crate::stats_cache::FileStats;
+use crate::CustomError;
+use git2::{Oid, Repository};
+use std::fmt::{Debug, Formatter};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// How many diffs stay around for the next cursor.
+const CAPACITY: usize = 32;
+
+/// The diffs computed last by the cursors of a connection, so a statement that reads the same
+/// commit's diff more than once, e.g. `stats` in two correlated subqueries or joined with itself,
+/// diffs it once.
+///
+/// The cursors of a nested loop ask for the same commit at about the same time, keeping the few
+/// most recent diffs is enough. Diffs are keyed by git directory, commit and diff options.
+#[derive(Clone, Default)]
+pub(crate) struct DiffCache {
+    /// The recent diffs, least recently used first
+    diffs: Arc<Mutex<Vec<Recent>>>,
+}
+
+/// A diff and what it was computed for.
+type Recent = (Key, Arc<FileStats>);
+
+#[derive(PartialEq)]
+struct Key {
+    git_dir: PathBuf,
+    commit: Oid,
+    options: String,
+}
+
+impl DiffCache {
+    /// The diff of `commit` with `options`, computed with `compute` when it isn't one of the
+    /// recent diffs. The cache isn't locked while `compute` diffs.
+    pub(crate) fn get_or_compute(
+        &self,
+        repo: &Repository,
+        commit: Oid,
+        options: &str,
+        compute: impl FnOnce() -> Result<FileStats, CustomError>,
+    ) -> Result<Arc<FileStats>, CustomError> {
+        let key = Key {
+            git_dir: repo.path().to_path_buf(),
+            commit,
+            options: options.to_string(),
+        };
+        {
+            let mut diffs = self.diffs();
+            if let Some(i) = diffs.iter().position(|(recent, _)| *recent == key) {
+                let entry = diffs.remove(i);
+                let stats = entry.1.clone();
+                diffs.push(entry);
+                return Ok(stats);
+            }
+        }
+        let stats = Arc::new(compute()?);
+        let mut diffs = self.diffs();
+        if diffs.len() >= CAPACITY {
+            diffs.remove(0);
+        }
+        diffs.push((key, stats.clone()));
+        Ok(stats)
+    }
+
+    fn diffs(&self) -> MutexGuard<'_, Vec<Recent>> {
+        self.diffs.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Debug for DiffCache {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.diffs().iter().map(|(key, _)| key.commit))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diff_cache::DiffCache;
+    use crate::test::{commit_file, temp_repository};
+    use std::cell::Cell;
+    use std::sync::Arc;
+
+    #[test]
+    fn diffs_each_recent_commit_once() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("diff_cache")?;
+        let first = commit_file(&repo, "file.txt", "one\n", "first")?;
+        let second = commit_file(&repo, "file.txt", "two\n", "second")?;
+
+        let cache = DiffCache::default();
+        let computed = Cell::new(0);
+        let compute = || {
+            computed.set(computed.get() + 1);
+            Ok(vec![(Arc::from("file.txt"), 1, 1)])
+        };
+        let once = cache.get_or_compute(&repo, second, "", compute)?;
+        let again = cache.get_or_compute(&repo, second, "", compute)?;
+        cache.get_or_compute(&repo, second, "context_lines=0", compute)?;
+        cache.get_or_compute(&repo, first, "", compute)?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert!(Arc::ptr_eq(&once, &again));
+        assert_eq!(computed.get(), 3);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,316 @@
+use crate::mirrors::{callbacks, is_url};
+use crate::{trace_index_info, CustomError, TableConfig};
+use git2::{FetchOptions, Oid, Repository};
+use itertools::Itertools;
+use rusqlite::types::{Type, ValueRef};
+use rusqlite::vtab::{
+    sqlite3_vtab, sqlite3_vtab_cursor, Context, IndexConstraintOp, IndexInfo, VTab, VTabConnection,
+    VTabCursor, Values,
+};
+use std::os::raw::c_int;
+use std::time::{Duration, Instant};
+use tracing::{debug, debug_span, info};
+
+/// The hidden columns `fetch` takes as arguments, in order, and the bit of each in `idx_num`.
+const ARGUMENTS: [(c_int, c_int); 3] = [(REMOTE, 1), (REFSPEC, 2), (REPO, 4)];
+const REMOTE: c_int = 4;
+const REFSPEC: c_int = 5;
+const REPO: c_int = 6;
+
+/// A ref the fetch moved: its name, where it pointed before, where it points now and whether
+/// that's a fast-forward.
+type UpdatedRef = (String, Option<Oid>, Option<Oid>, Option<bool>);
+
+/// `fetch(remote [, refspec, repo])`, fetches from a remote of the repository and returns the
+/// refs that were updated. The remote is a name like `origin` or a URL, the refspec defaults to
+/// the remote's own. A URL without a repository fetches the mirror of the URL, when the
+/// repositories read by URL are mirrored, and clones it first when there is none yet.
+#[repr(C)]
+pub(crate) struct GitFetch {
+    base: sqlite3_vtab,
+    config: TableConfig,
+}
+
+unsafe impl<'a> VTab<'a> for GitFetch {
+    type Aux = TableConfig;
+    type Cursor = GitFetchCursor;
+
+    fn connect(
+        _db: &mut VTabConnection,
+        aux: Option<&Self::Aux>,
+        _args: &[&[u8]],
+    ) -> rusqlite::Result<(String, Self)> {
+        Ok((
+            "create table fetch(ref_name text, old_hash text, new_hash text, is_fast_forward integer, remote hidden, refspec hidden, repo hidden)"
+                .to_string(),
+            GitFetch {
+                base: sqlite3_vtab::default(),
+                config: aux.cloned().unwrap_or_default(),
+            },
+        ))
+    }
+
+    fn best_index(&self, info: &mut IndexInfo) -> rusqlite::Result<()> {
+        trace_index_info("fetch", info);
+        let mut constrained = [None; ARGUMENTS.len()];
+        for (i, constraint) in info.constraints().enumerate() {
+            let argument = ARGUMENTS
+                .iter()
+                .position(|(column, _)| *column == constraint.column());
+            if let (Some(argument), true, IndexConstraintOp::SQLITE_INDEX_CONSTRAINT_EQ) =
+                (argument, constraint.is_usable(), constraint.operator())
+            {
+                constrained[argument] = Some(i);
+            }
+        }
+        let mut idx_num = 0;
+        let passed = constrained
+            .iter()
+            .zip(ARGUMENTS)
+            .filter_map(|(i, (_, bit))| Some(((*i)?, bit)));
+        for (argv_index, (i, bit)) in passed.enumerate() {
+            let mut usage = info.constraint_usage(i);
+            usage.set_argv_index(argv_index as c_int + 1);
+            usage.set_omit(true);
+            idx_num |= bit;
+        }
+        debug!(table = "fetch", idx_num, "best_index");
+        info.set_idx_num(idx_num);
+        // Without a remote there is nothing to fetch, any plan passing one wins
+        info.set_estimated_cost(if idx_num & 1 == 1 { 1000.0 } else { 1e12 });
+        Ok(())
+    }
+
+    fn open(&mut self) -> rusqlite::Result<GitFetchCursor> {
+        Ok(GitFetchCursor {
+            base: Default::default(),
+            config: self.config.clone(),
+            updated: vec![],
+            i: 0,
+            args: [None, None, None],
+            scan: None,
+        })
+    }
+}
+
+#[repr(C)]
+pub(crate) struct GitFetchCursor {
+    base: sqlite3_vtab_cursor,
+    config: TableConfig,
+    updated: Vec<UpdatedRef>,
+    i: usize,
+    /// The remote, the refspec and the repository passed
+    args: [Option<String>; 3],
+    /// The scan of `--profile` the rows count towards
+    scan: Option<usize>,
+}
+
+impl GitFetchCursor {
+    fn init(&mut self, idx_num: c_int, vals: &[ValueRef]) -> Result<(), CustomError> {
+        self.i = 0;
+        self.updated.clear();
+        let mut vals = vals.iter();
+        for (arg, (column, bit)) in self.args.iter_mut().zip(ARGUMENTS) {
+            let value = match idx_num & bit {
+                0 => None,
+                _ => vals.next(),
+            };
+            *arg = match value.map(|value| (value.data_type(), value.as_str())) {
+                None | Some((Type::Null, _)) => None,
+                Some((_, Ok(text))) => Some(text.to_string()),
+                Some((data_type, Err(_))) => {
+                    let name = ["remote", "refspec", "repository"][(column - REMOTE) as usize];
+                    return Err(CustomError::InvalidArgument(format!(
+                        "the {} must be TEXT, got {}",
+                        name, data_type
+                    )));
+                }
+            };
+        }
+        let [remote, refspec, repo] = &self.args;
+        let Some(remote) = remote else {
+            // fetch(NULL) fetches nothing, like a NULL revision lists no commits
+            return match idx_num & 1 {
+                1 => Ok(()),
+                _ => Err(CustomError::InvalidArgument(
+                    "fetch needs a remote, e.g. fetch('origin')".to_string(),
+                )),
+            };
+        };
+        let refspec = refspec.as_deref();
+        self.updated = match (repo, &self.config.mirrors) {
+            // The mirror of a URL fetches from the URL, which is its origin
+            (None, Some(mirrors)) if is_url(remote) => {
+                let mirror = mirrors.cloned(remote, &self.config.interrupt.checkpoint())?;
+                let repo = self.config.repositories.open(&mirror)?;
+                fetch(&repo, "origin", refspec, &self.config)?
+            }
+            _ => {
+                let repo = self.config.open_repository(repo.as_deref())?;
+                fetch(&repo, remote, refspec, &self.config)?
+            }
+        };
+        Ok(())
+    }
+}
+
+/// Fetches `refspec`, or the configured refspecs, from `remote` into `repo` and returns the refs
+/// that moved.
+fn fetch(
+    repo: &Repository,
+    remote: &str,
+    refspec: Option<&str>,
+    config: &TableConfig,
+) -> Result<Vec<UpdatedRef>, CustomError> {
+    let mut remote = match repo.find_remote(remote) {
+        Ok(remote) => remote,
+        Err(_) if is_url(remote) => repo.remote_anonymous(remote)?,
+        Err(e) => return Err(e.into()),
+    };
+    let checkpoint = config.interrupt.checkpoint();
+    let mut moved = vec![];
+    let mut callbacks = callbacks(&checkpoint, repo.config().ok());
+    callbacks.update_tips(|name, old, new| {
+        let oid = |oid: Oid| (!oid.is_zero()).then_some(oid);
+        moved.push((name.to_string(), oid(old), oid(new)));
+        true
+    });
+    let mut options = FetchOptions::new();
+    options.remote_callbacks(callbacks);
+    let refspecs = refspec.into_iter().collect_vec();
+    info!(remote = remote.name().or(remote.url()), ?refspecs, "fetch");
+    let fetched = remote.fetch(&refspecs, Some(&mut options), None);
+    // An interrupted transfer fails with a generic error
+    checkpoint.check()?;
+    fetched?;
+    drop(options);
+
+    let updated = moved.into_iter().map(|(name, old, new)| {
+        // Refs that were created or deleted, and tags of other objects, have nothing to compare
+        let fast_forward = match (old, new) {
+            (Some(old), Some(new)) => repo.graph_descendant_of(new, old).ok(),
+            _ => None,
+        };
+        (name, old, new, fast_forward)
+    });
+    Ok(updated.collect())
+}
+
+unsafe impl VTabCursor for GitFetchCursor {
+    fn filter(
+        &mut self,
+        idx_num: c_int,
+        _idx_str: Option<&str>,
+        args: &Values<'_>,
+    ) -> rusqlite::Result<()> {
+        let vals = args.iter().collect_vec();
+        let _span = debug_span!("filter", table = "fetch", idx_num, ?vals).entered();
+        let start = Instant::now();
+        self.init(idx_num, &vals)?;
+        info!(updated = self.updated.len(), elapsed = ?start.elapsed(), "fetched");
+        let row = !self.updated.is_empty();
+        self.scan = (self.config.profiler).filter("fetch", &vals, start.elapsed(), row);
+        Ok(())
+    }
+
+    fn next(&mut self) -> rusqlite::Result<()> {
+        self.i += 1;
+        let row = self.i < self.updated.len();
+        self.config.profiler.next(self.scan, Duration::ZERO, row);
+        Ok(())
+    }
+
+    fn eof(&self) -> bool {
+        self.i >= self.updated.len()
+    }
+
+    fn column(&self, ctx: &mut Context, i: c_int) -> rusqlite::Result<()> {
+        let (name, old, new, fast_forward) = &self.updated[self.i];
+        let hash = |oid: &Option<Oid>| oid.map(|oid| oid.to_string());
+        match i {
+            0 => ctx.set_result(name),
+            1 => ctx.set_result(&hash(old)),
+            2 => ctx.set_result(&hash(new)),
+            3 => ctx.set_result(fast_forward),
+            REMOTE | REFSPEC | REPO => ctx.set_result(&self.args[(i - REMOTE) as usize]),
+            _ => Ok(()),
+        }
+    }
+
+    fn rowid(&self) -> rusqlite::Result<i64> {
+        Ok(self.i as i64)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test::{commit_file, temp_repository};
+    use crate::SqliteGit;
+    use git2::{BranchType, Repository};
+    use rusqlite::Connection;
+
+    type Updated = (String, Option<String>, String, Option<bool>);
+
+    #[test]
+    fn fetches_and_returns_the_updated_refs() -> Result<(), Box<dyn std::error::Error>> {
+        let (upstream_path, upstream) = temp_repository("fetch_upstream")?;
+        let first = commit_file(&upstream, "file.txt", "one\n", "first")?;
+        let (path, _) = temp_repository("fetch_clone")?;
+        std::fs::remove_dir_all(&path)?;
+        let repo = Repository::clone(upstream_path.to_str().unwrap(), &path)?;
+        let second = commit_file(&upstream, "file.txt", "two\n", "second")?;
+        let branch = upstream.head()?.shorthand().unwrap_or_default().to_string();
+        upstream.branch("topic", &upstream.find_commit(first)?, false)?;
+
+        let db = Connection::open_in_memory()?;
+        SqliteGit::new()
+            .with_all()
+            .with_fetch()
+            .repository(&path)
+            .register(&db)?;
+        let sql = "SELECT ref_name, old_hash, new_hash, is_fast_forward FROM fetch('origin')
+                   ORDER BY ref_name";
+        let fetch = || -> rusqlite::Result<Vec<Updated>> {
+            let mut stmt = db.prepare(sql)?;
+            let rows = stmt.query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })?;
+            rows.collect()
+        };
+        let fetched = fetch()?;
+        let again = fetch()?;
+        let tracking = repo
+            .find_branch(&format!("origin/{}", branch), BranchType::Remote)?
+            .get()
+            .target();
+        let missing = db.query_row("SELECT count(*) FROM fetch", [], |row| row.get::<_, i64>(0));
+        std::fs::remove_dir_all(&upstream_path)?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!(
+            fetched,
+            vec![
+                (
+                    format!("refs/remotes/origin/{}", branch),
+                    Some(first.to_string()),
+                    second.to_string(),
+                    Some(true)
+                ),
+                (
+                    "refs/remotes/origin/topic".to_string(),
+                    None,
+                    first.to_string(),
+                    None
+                ),
+            ]
+        );
+        assert_eq!(again, vec![]);
+        assert_eq!(tracking, Some(second));
+        assert!(missing
+            .unwrap_err()
+            .to_string()
+            .contains("fetch needs a remote"));
+
+        Ok(())
+    }
+}
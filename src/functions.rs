@@ -0,0 +1,1747 @@
+use crate::repository_cache::CachedRepository;
+use crate::{semver, to_utc, CustomError, TableConfig};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use git2::{
+    Blame, BlameOptions, Commit, DescribeOptions, DiffFormat, DiffOptions, ErrorClass, ErrorCode,
+    Mailmap, Object, ObjectType, Oid, Repository, RevparseMode, Revwalk, Signature, Sort, Time,
+};
+use rusqlite::functions::{Context, FunctionFlags};
+use rusqlite::types::{ToSql, Value, ValueRef};
+use rusqlite::Connection;
+use serde_json::{json, Value as JsonValue};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::io::Read;
+use std::ops::RangeInclusive;
+use std::os::raw::c_int;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// A scalar function, the repository arguments are opened with the defaults in the config.
+type ScalarFunction<T> = fn(&Context, &TableConfig) -> Result<T, CustomError>;
+
+/// Registers the scalar `git_*` functions and the `SEMVER` and `MAILMAP` collations.
+pub fn register(db: &Connection, config: &TableConfig) -> rusqlite::Result<()> {
+    db.create_collation("SEMVER", semver::compare)?;
+    let mailmap = MailmapCollation::new(config);
+    db.create_collation("MAILMAP", move |a, b| mailmap.compare(a, b))?;
+    register_function(db, "git_rev_parse", 1..=2, config, rev_parse)?;
+    register_function(db, "git_merge_base", 2..=3, config, merge_base)?;
+    register_function(db, "git_is_ancestor", 2..=3, config, is_ancestor)?;
+    register_function(db, "git_patch_id", 1..=2, config, patch_id)?;
+    register_function(db, "git_blob_content", 2..=3, config, blob_content)?;
+    register_function(db, "git_short", 1..=3, config, short)?;
+    register_function(db, "git_diff_text", 2..=4, config, diff_text)?;
+    register_function(db, "git_blame_line", 2..=4, config, blame_line)?;
+    register_function(db, "git_blame_line_json", 2..=4, config, blame_line_json)?;
+    register_function(db, "git_blame_json", 1..=3, config, blame_json)?;
+    register_function(db, "git_exists_at", 2..=3, config, exists_at)?;
+    register_function(db, "git_commit_json", 1..=2, config, commit_json)?;
+    register_function(db, "git_check_ignore", 1..=2, config, check_ignore)?;
+    register_function(db, "git_rev_count", 1..=2, config, rev_count)?;
+    register_function(db, "git_conventional", 1..=1, config, conventional)?;
+    register_function(db, "git_trailer", 2..=3, config, trailer)?;
+    register_function(db, "git_canonical_author", 2..=3, config, canonical_author)?;
+    register_function(db, "git_week", 1..=1, config, week)?;
+    register_function(db, "git_month", 1..=1, config, month)?;
+    register_function(db, "git_iso_week", 1..=1, config, iso_week)?;
+    register_function(db, "git_symbolic_ref", 1..=2, config, symbolic_ref)?;
+    register_function(db, "git_current_branch", 0..=1, config, current_branch)?;
+    register_function(db, "git_object_size", 1..=2, config, object_size)?;
+    // Both read outside of the repository, the global config and any directory
+    if !config.pinned {
+        register_function(db, "git_config_get", 1..=2, config, config_get)?;
+        register_function(db, "git_repo_root", 0..=1, config, repo_root)?;
+    }
+
+    let describe = Arc::new(Describe::new(config));
+    for n_arg in 1..=2 {
+        let describe = describe.clone();
+        db.create_scalar_function(
+            "git_describe",
+            n_arg,
+            FunctionFlags::SQLITE_UTF8,
+            move |ctx| Ok(describe.describe(ctx)?),
+        )?;
+    }
+    let topo_order = Arc::new(TopoOrder::new(config));
+    for n_arg in 1..=3 {
+        let topo_order = topo_order.clone();
+        db.create_scalar_function(
+            "git_topo_order",
+            n_arg,
+            FunctionFlags::SQLITE_UTF8,
+            move |ctx| Ok(topo_order.position(ctx)?),
+        )?;
+    }
+    Ok(())
+}
+
+/// Registers `function` once per number of arguments it accepts, so SQLite rejects calls with
+/// the wrong number of arguments.
+fn register_function<T: ToSql + 'static>(
+    db: &Connection,
+    name: &str,
+    arities: RangeInclusive<c_int>,
+    config: &TableConfig,
+    function: ScalarFunction<T>,
+) -> rusqlite::Result<()> {
+    for n_arg in arities {
+        let config = config.clone();
+        db.create_scalar_function(name, n_arg, FunctionFlags::SQLITE_UTF8, move |ctx| {
+            Ok(function(ctx, &config)?)
+        })?;
+    }
+    Ok(())
+}
+
+/// The TEXT argument at `idx`, `None` for NULL or when the argument is left out so functions can
+/// pass NULL through.
+fn text_arg(ctx: &Context, idx: usize, name: &str) -> Result<Option<String>, CustomError> {
+    if idx >= ctx.len() {
+        return Ok(None);
+    }
+    match ctx.get_raw(idx) {
+        ValueRef::Null => Ok(None),
+        ValueRef::Text(text) => Ok(Some(String::from_utf8_lossy(text).to_string())),
+        other => Err(CustomError::InvalidArgument(format!(
+            "the {} must be TEXT, got {}",
+            name,
+            other.data_type()
+        ))),
+    }
+}
+
+/// The INTEGER argument at `idx`, `None` for NULL or when the argument is left out.
+fn int_arg(ctx: &Context, idx: usize, name: &str) -> Result<Option<i64>, CustomError> {
+    if idx >= ctx.len() {
+        return Ok(None);
+    }
+    match ctx.get_raw(idx) {
+        ValueRef::Null => Ok(None),
+        ValueRef::Integer(i) => Ok(Some(i)),
+        other => Err(CustomError::InvalidArgument(format!(
+            "the {} must be INTEGER, got {}",
+            name,
+            other.data_type()
+        ))),
+    }
+}
+
+/// The timestamp argument at `idx`, either the DATETIME text the tables return, RFC 3339, a date,
+/// or unix seconds. `None` for NULL.
+fn timestamp_arg(ctx: &Context, idx: usize) -> Result<Option<DateTime<Utc>>, CustomError> {
+    let invalid = |value: &dyn std::fmt::Display| {
+        CustomError::InvalidArgument(format!("{} is not a timestamp", value))
+    };
+    match ctx.get_raw(idx) {
+        ValueRef::Null => Ok(None),
+        ValueRef::Integer(seconds) => Ok(Utc.timestamp_opt(seconds, 0).single()),
+        ValueRef::Real(seconds) => Ok(Utc.timestamp_opt(seconds as i64, 0).single()),
+        ValueRef::Text(text) => {
+            let text = String::from_utf8_lossy(text);
+            let text = text.trim();
+            DateTime::parse_from_str(text, "%F %T%.f%:z")
+                .or_else(|_| DateTime::parse_from_rfc3339(text))
+                .map(|time| time.with_timezone(&Utc))
+                .or_else(|_| NaiveDateTime::parse_from_str(text, "%F %T%.f").map(|t| t.and_utc()))
+                .or_else(|_| {
+                    NaiveDate::parse_from_str(text, "%F")
+                        .map(|date| date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc())
+                })
+                .map(Some)
+                .map_err(|_| invalid(&text))
+        }
+        ValueRef::Blob(_) => Err(invalid(&"a BLOB")),
+    }
+}
+
+/// Opens the repository passed at `idx`, or the default one when the argument is left out.
+fn repo_arg(
+    ctx: &Context,
+    idx: usize,
+    config: &TableConfig,
+) -> Result<CachedRepository, CustomError> {
+    let path = text_arg(ctx, idx, "repository path")?;
+    config.open_repository(path.as_deref())
+}
+
+/// Turns a failed lookup into NULL, so a missing revision doesn't abort the whole query.
+fn not_found_as_none<T>(result: Result<T, git2::Error>) -> Result<Option<T>, CustomError> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(e) if e.code() == ErrorCode::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The text the tables return DATETIME columns as.
+fn sql_datetime(time: Time) -> String {
+    to_utc(time).format("%F %T%.f%:z").to_string()
+}
+
+/// The commit `rev` points to, `None` when it doesn't resolve.
+fn resolve_commit<'r>(repo: &'r Repository, rev: &str) -> Result<Option<Commit<'r>>, CustomError> {
+    let commit = repo
+        .revparse_single(rev)
+        .and_then(|object| object.peel_to_commit());
+    not_found_as_none(commit)
+}
+
+/// `git_rev_parse(expr [, repo])`, the full hash of the object `expr` resolves to.
+fn rev_parse(ctx: &Context, config: &TableConfig) -> Result<Option<String>, CustomError> {
+    let Some(expr) = text_arg(ctx, 0, "revision")? else {
+        return Ok(None);
+    };
+    let repo = repo_arg(ctx, 1, config)?;
+    let object = not_found_as_none(repo.revparse_single(&expr))?;
+    Ok(object.map(|object| object.id().to_string()))
+}
+
+/// `git_merge_base(a, b [, repo])`, the best common ancestor of two commits. NULL when the
+/// histories are unrelated.
+fn merge_base(ctx: &Context, config: &TableConfig) -> Result<Option<String>, CustomError> {
+    let (Some(a), Some(b)) = (
+        text_arg(ctx, 0, "first hash")?,
+        text_arg(ctx, 1, "second hash")?,
+    ) else {
+        return Ok(None);
+    };
+    let repo = repo_arg(ctx, 2, config)?;
+    let (Some(a), Some(b)) = (resolve_commit(&repo, &a)?, resolve_commit(&repo, &b)?) else {
+        return Ok(None);
+    };
+    let base = not_found_as_none(repo.merge_base(a.id(), b.id()))?;
+    Ok(base.map(|oid| oid.to_string()))
+}
+
+/// `git_is_ancestor(ancestor, descendant [, repo])`, whether `ancestor` is reachable from
+/// `descendant`. A commit counts as its own ancestor like in `git merge-base --is-ancestor`.
+fn is_ancestor(ctx: &Context, config: &TableConfig) -> Result<Option<bool>, CustomError> {
+    let (Some(ancestor), Some(descendant)) = (
+        text_arg(ctx, 0, "ancestor")?,
+        text_arg(ctx, 1, "descendant")?,
+    ) else {
+        return Ok(None);
+    };
+    let repo = repo_arg(ctx, 2, config)?;
+    let (Some(ancestor), Some(descendant)) = (
+        resolve_commit(&repo, &ancestor)?,
+        resolve_commit(&repo, &descendant)?,
+    ) else {
+        return Ok(None);
+    };
+    if ancestor.id() == descendant.id() {
+        return Ok(Some(true));
+    }
+    Ok(Some(
+        repo.graph_descendant_of(descendant.id(), ancestor.id())?,
+    ))
+}
+
+/// `git_patch_id(hash [, repo])`, the patch-id of the changes a commit makes to its first
+/// parent. Cherry-picks of a commit share its patch-id.
+fn patch_id(ctx: &Context, config: &TableConfig) -> Result<Option<String>, CustomError> {
+    let Some(rev) = text_arg(ctx, 0, "hash")? else {
+        return Ok(None);
+    };
+    let repo = repo_arg(ctx, 1, config)?;
+    let Some(commit) = resolve_commit(&repo, &rev)? else {
+        return Ok(None);
+    };
+    let parent_tree = match commit.parent_count() {
+        0 => None,
+        _ => Some(commit.parent(0)?.tree()?),
+    };
+    let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+    Ok(Some(diff.patchid(None)?.to_string()))
+}
+
+/// `git_blob_content(rev, path [, repo])`, the content of the file at `path` in the tree of
+/// `rev`. TEXT when the file is valid UTF-8, a BLOB otherwise and NULL when there is no such file.
+///
+/// The size is read from the object header first, files over the configured maximum fail the
+/// query without being loaded. Loose objects are streamed into the result, libgit2 reads packed
+/// ones whole.
+fn blob_content(ctx: &Context, config: &TableConfig) -> Result<Option<Value>, CustomError> {
+    let (Some(rev), Some(path)) = (text_arg(ctx, 0, "revision")?, text_arg(ctx, 1, "path")?) else {
+        return Ok(None);
+    };
+    let repo = repo_arg(ctx, 2, config)?;
+    let Some(commit) = resolve_commit(&repo, &rev)? else {
+        return Ok(None);
+    };
+    let tree = commit.tree()?;
+    let Some(entry) = not_found_as_none(tree.get_path(Path::new(&path)))? else {
+        return Ok(None);
+    };
+    if entry.kind() != Some(ObjectType::Blob) {
+        return Ok(None);
+    }
+
+    let odb = repo.odb()?;
+    let max_size = config.max_blob_size();
+    let check_size = |size: usize| match size as u64 > max_size {
+        true => Err(CustomError::InvalidArgument(format!(
+            "{} is {} bytes at {}, more than the maximum of {} bytes",
+            path, size, rev, max_size
+        ))),
+        false => Ok(()),
+    };
+    // Only the loose backend streams, a packed object fails to open a stream
+    let content = match odb.reader(entry.id()) {
+        Ok((mut reader, size, _)) => {
+            check_size(size)?;
+            // git2's reader counts the whole buffer as read, even at the end of the object. The
+            // loose backend fills the buffer, so one read of the object's size reads all of it
+            let mut content = vec![0; size];
+            reader.read_exact(&mut content)?;
+            content
+        }
+        Err(_) => {
+            let (size, _) = odb.read_header(entry.id())?;
+            check_size(size)?;
+            odb.read(entry.id())?.data().to_vec()
+        }
+    };
+    Ok(Some(match String::from_utf8(content) {
+        Ok(text) => Value::Text(text),
+        Err(e) => Value::Blob(e.into_bytes()),
+    }))
+}
+
+/// `git_short(hash [, len [, repo]])`, the shortest prefix of at least `len` (7 by default) characters
+/// that no other object in the repository starts with.
+fn short(ctx: &Context, config: &TableConfig) -> Result<Option<String>, CustomError> {
+    let Some(rev) = text_arg(ctx, 0, "hash")? else {
+        return Ok(None);
+    };
+    let min_len = int_arg(ctx, 1, "length")?.unwrap_or(7).clamp(4, 40) as usize;
+    let repo = repo_arg(ctx, 2, config)?;
+    let Some(object) = not_found_as_none(repo.revparse_single(&rev))? else {
+        return Ok(None);
+    };
+    let odb = repo.odb()?;
+    let hash = object.id().to_string();
+    for len in min_len..hash.len() {
+        let prefix = &hash[..len];
+        match odb.exists_prefix(Oid::from_str(prefix)?, len) {
+            Ok(_) => return Ok(Some(prefix.to_string())),
+            Err(e) if e.code() == ErrorCode::Ambiguous => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(Some(hash))
+}
+
+/// `git_diff_text(rev_a, rev_b [, path [, repo]])`, the unified diff from the tree of `rev_a` to
+/// the tree of `rev_b` like `git diff` prints it, optionally only for the files under `path`.
+/// Fails when the diff grows larger than the configured maximum.
+fn diff_text(ctx: &Context, config: &TableConfig) -> Result<Option<String>, CustomError> {
+    let (Some(a), Some(b)) = (text_arg(ctx, 0, "revision")?, text_arg(ctx, 1, "revision")?) else {
+        return Ok(None);
+    };
+    let path = text_arg(ctx, 2, "path")?;
+    let repo = repo_arg(ctx, 3, config)?;
+    let (Some(a), Some(b)) = (resolve_commit(&repo, &a)?, resolve_commit(&repo, &b)?) else {
+        return Ok(None);
+    };
+
+    let mut options = DiffOptions::new();
+    if let Some(path) = &path {
+        options.pathspec(path);
+    }
+    let diff = repo.diff_tree_to_tree(Some(&a.tree()?), Some(&b.tree()?), Some(&mut options))?;
+    let checkpoint = config.interrupt.checkpoint();
+    let max_size = config.max_diff_size() as usize;
+    let mut patch = vec![];
+    let printed = diff.print(DiffFormat::Patch, |_, _, line| {
+        if let '+' | '-' | ' ' = line.origin() {
+            patch.push(line.origin() as u8);
+        }
+        patch.extend_from_slice(line.content());
+        patch.len() <= max_size && checkpoint.check().is_ok()
+    });
+    checkpoint.check()?;
+    if patch.len() > max_size {
+        return Err(CustomError::InvalidArgument(format!(
+            "the diff from {} to {} is more than the maximum of {} bytes",
+            a.id(),
+            b.id(),
+            max_size
+        )));
+    }
+    printed?;
+    Ok(Some(String::from_utf8_lossy(&patch).to_string()))
+}
+
+/// `git_blame_line(path, line_no [, rev [, repo]])`, the hash of the commit that last changed line
+/// `line_no` (counting from 1) of the file at `path` in `rev`, HEAD by default.
+fn blame_line(ctx: &Context, config: &TableConfig) -> Result<Option<String>, CustomError> {
+    let line = blame_line_json(ctx, config)?;
+    Ok(line.map(|line| line["hash"].as_str().unwrap_or_default().to_string()))
+}
+
+/// `git_blame_line_json(path, line_no [, rev [, repo]])`, like `git_blame_line` but returns a JSON
+/// object with the hash, the author, the date and the line number in that commit.
+fn blame_line_json(ctx: &Context, config: &TableConfig) -> Result<Option<JsonValue>, CustomError> {
+    let (Some(path), Some(line_no)) = (text_arg(ctx, 0, "path")?, int_arg(ctx, 1, "line")?) else {
+        return Ok(None);
+    };
+    let rev = text_arg(ctx, 2, "revision")?.unwrap_or_else(|| "HEAD".to_string());
+    let repo = repo_arg(ctx, 3, config)?;
+    let Some(blame) = blame_file(&repo, &path, &rev)? else {
+        return Ok(None);
+    };
+    let Some(hunk) = usize::try_from(line_no)
+        .ok()
+        .and_then(|line_no| blame.get_line(line_no))
+    else {
+        return Ok(None);
+    };
+
+    let author = hunk.final_signature();
+    let line_in_commit = hunk.orig_start_line() + (line_no as usize - hunk.final_start_line());
+    Ok(Some(json!({
+        "hash": hunk.final_commit_id().to_string(),
+        "author_name": String::from_utf8_lossy(author.name_bytes()),
+        "author_email": String::from_utf8_lossy(author.email_bytes()),
+        "author_when": sql_datetime(author.when()),
+        "line": line_in_commit,
+    })))
+}
+
+/// `git_blame_json(path [, rev [, repo]])`, the blame of the whole file at `path` in `rev`, HEAD
+/// by default, as a JSON array of hunks. Each hunk has the hash, the author and the date of the
+/// commit that last changed its lines, the line it starts at (counting from 1) and how many lines
+/// it has. One blame instead of one `git_blame_line` per line.
+fn blame_json(ctx: &Context, config: &TableConfig) -> Result<Option<JsonValue>, CustomError> {
+    let Some(path) = text_arg(ctx, 0, "path")? else {
+        return Ok(None);
+    };
+    let rev = text_arg(ctx, 1, "revision")?.unwrap_or_else(|| "HEAD".to_string());
+    let repo = repo_arg(ctx, 2, config)?;
+    let Some(blame) = blame_file(&repo, &path, &rev)? else {
+        return Ok(None);
+    };
+    let hunks = blame.iter().map(|hunk| {
+        let author = hunk.final_signature();
+        json!({
+            "hash": hunk.final_commit_id().to_string(),
+            "author_name": String::from_utf8_lossy(author.name_bytes()),
+            "author_email": String::from_utf8_lossy(author.email_bytes()),
+            "author_when": sql_datetime(author.when()),
+            "start_line": hunk.final_start_line(),
+            "lines": hunk.lines_in_hunk(),
+        })
+    });
+    Ok(Some(JsonValue::Array(hunks.collect())))
+}
+
+/// The blame of the file at `path` in `rev`, None when either doesn't exist.
+fn blame_file<'r>(
+    repo: &'r Repository,
+    path: &str,
+    rev: &str,
+) -> Result<Option<Blame<'r>>, CustomError> {
+    let Some(commit) = resolve_commit(repo, rev)? else {
+        return Ok(None);
+    };
+    let mut options = BlameOptions::new();
+    options.newest_commit(commit.id());
+    not_found_as_none(repo.blame_file(Path::new(path), Some(&mut options)))
+}
+
+/// `git_exists_at(rev, path [, repo])`, whether the tree of `rev` has a file or directory at
+/// `path`.
+fn exists_at(ctx: &Context, config: &TableConfig) -> Result<Option<bool>, CustomError> {
+    let (Some(rev), Some(path)) = (text_arg(ctx, 0, "revision")?, text_arg(ctx, 1, "path")?) else {
+        return Ok(None);
+    };
+    let repo = repo_arg(ctx, 2, config)?;
+    let Some(commit) = resolve_commit(&repo, &rev)? else {
+        return Ok(None);
+    };
+    let entry = not_found_as_none(commit.tree()?.get_path(Path::new(&path)))?;
+    Ok(Some(entry.is_some()))
+}
+
+/// `git_commit_json(hash [, repo])`, everything about a commit as a JSON object: the tree, all
+/// parents, author and committer with their UTC offsets, the message split into summary and
+/// body, the trailers and the raw signature of signed commits.
+fn commit_json(ctx: &Context, config: &TableConfig) -> Result<Option<JsonValue>, CustomError> {
+    let Some(rev) = text_arg(ctx, 0, "hash")? else {
+        return Ok(None);
+    };
+    let repo = repo_arg(ctx, 1, config)?;
+    let Some(commit) = resolve_commit(&repo, &rev)? else {
+        return Ok(None);
+    };
+
+    let message = String::from_utf8_lossy(commit.message_bytes()).to_string();
+    let mut trailers = serde_json::Map::new();
+    for (key, value) in message_trailers(&message)? {
+        let values = trailers.entry(key).or_insert_with(|| json!([]));
+        if let JsonValue::Array(values) = values {
+            values.push(JsonValue::String(value));
+        }
+    }
+    let signature = not_found_as_none(repo.extract_signature(&commit.id(), None))?
+        .map(|(signature, _)| String::from_utf8_lossy(&signature).to_string());
+
+    Ok(Some(json!({
+        "hash": commit.id().to_string(),
+        "tree": commit.tree_id().to_string(),
+        "parents": commit.parent_ids().map(|id| id.to_string()).collect::<Vec<_>>(),
+        "author": signature_json(&commit.author()),
+        "committer": signature_json(&commit.committer()),
+        "summary": commit.summary_bytes().map(|s| String::from_utf8_lossy(s).to_string()),
+        "body": commit.body_bytes().map(|s| String::from_utf8_lossy(s).to_string()),
+        "message": message,
+        "trailers": trailers,
+        "encoding": commit.message_encoding(),
+        "signature": signature,
+    })))
+}
+
+fn signature_json(signature: &Signature) -> JsonValue {
+    json!({
+        "name": String::from_utf8_lossy(signature.name_bytes()),
+        "email": String::from_utf8_lossy(signature.email_bytes()),
+        "when": sql_datetime(signature.when()),
+        "offset_minutes": signature.when().offset_minutes(),
+    })
+}
+
+/// The `Key: value` trailers at the end of a commit message, in the order they appear.
+fn message_trailers(message: &str) -> Result<Vec<(String, String)>, CustomError> {
+    let trailers = git2::message_trailers_strs(message)?;
+    Ok(trailers
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect())
+}
+
+/// `git_config_get(key [, repo])`, the value of a config variable like `git config --get` reads
+/// it, the repository's config wins over the global and the system config. NULL when unset.
+fn config_get(ctx: &Context, config: &TableConfig) -> Result<Option<String>, CustomError> {
+    let Some(key) = text_arg(ctx, 0, "key")? else {
+        return Ok(None);
+    };
+    let repo = repo_arg(ctx, 1, config)?;
+    let value = repo.config()?.snapshot()?.get_string(&key);
+    not_found_as_none(value)
+}
+
+/// `git_check_ignore(path [, repo])`, whether the `.gitignore` files, `.git/info/exclude` and
+/// `core.excludesFile` ignore `path`, relative to the root of the working tree.
+fn check_ignore(ctx: &Context, config: &TableConfig) -> Result<Option<bool>, CustomError> {
+    let Some(path) = text_arg(ctx, 0, "path")? else {
+        return Ok(None);
+    };
+    let repo = repo_arg(ctx, 1, config)?;
+    Ok(Some(repo.is_path_ignored(&path)?))
+}
+
+/// `git_rev_count(range [, repo])`, the number of commits in a revision or range like
+/// `git rev-list --count` counts them: `main` counts the history of main, `main..topic` the
+/// commits only on topic and `main...topic` the commits on either side since they diverged.
+fn rev_count(ctx: &Context, config: &TableConfig) -> Result<Option<i64>, CustomError> {
+    let Some(range) = text_arg(ctx, 0, "range")? else {
+        return Ok(None);
+    };
+    let repo = repo_arg(ctx, 1, config)?;
+    let Some(mut walk) = walk_range(&repo, &range)? else {
+        return Ok(None);
+    };
+    let checkpoint = config.interrupt.checkpoint();
+    let count = walk.try_fold(0, |count, oid| {
+        checkpoint.check()?;
+        oid.map(|_| count + 1).map_err(CustomError::from)
+    })?;
+    Ok(Some(count))
+}
+
+/// A revwalk over a revision or range the way `git rev-list` walks it. The commits a range
+/// excludes are hidden, the walk never visits them. None when the revision doesn't resolve.
+pub(crate) fn walk_range<'r>(
+    repo: &'r Repository,
+    range: &str,
+) -> Result<Option<Revwalk<'r>>, CustomError> {
+    let Some(tips) = range_tips(repo, range)? else {
+        return Ok(None);
+    };
+    Ok(Some(walk_tips(repo, &tips)?))
+}
+
+fn walk_tips<'r>(
+    repo: &'r Repository,
+    (pushed, hidden): &Tips,
+) -> Result<Revwalk<'r>, git2::Error> {
+    let mut walk = repo.revwalk()?;
+    for oid in pushed {
+        walk.push(*oid)?;
+    }
+    for oid in hidden {
+        walk.hide(*oid)?;
+    }
+    Ok(walk)
+}
+
+/// The commits a walk of a revision or range starts from, and the commits it hides.
+type Tips = (Vec<Oid>, Vec<Oid>);
+
+/// The tips of [`walk_range`], which identify the commits it walks. None when the revision doesn't
+/// resolve.
+fn range_tips(repo: &Repository, range: &str) -> Result<Option<Tips>, CustomError> {
+    let Some(spec) = not_found_as_none(repo.revparse(range))? else {
+        return Ok(None);
+    };
+    let commit_id = |object: Option<&Object>| match object {
+        Some(object) => Ok(Some(object.peel_to_commit()?.id())),
+        None => Ok::<_, git2::Error>(None),
+    };
+    let (from, to) = (commit_id(spec.from())?, commit_id(spec.to())?);
+
+    Ok(Some(match (from, to) {
+        (Some(from), Some(to)) if spec.mode().contains(RevparseMode::MERGE_BASE) => {
+            let base = not_found_as_none(repo.merge_base(from, to))?;
+            (vec![from, to], base.into_iter().collect())
+        }
+        (Some(from), Some(to)) => (vec![to], vec![from]),
+        (Some(single), None) | (None, Some(single)) => (vec![single], vec![]),
+        (None, None) => return Ok(None),
+    }))
+}
+
+/// `git_conventional(message)`, the parts of a conventional commit subject like
+/// `feat(parser)!: accept tabs` as a JSON object with `type`, `scope`, `breaking` and
+/// `description`. A `BREAKING CHANGE:` footer marks the commit as breaking too. NULL when the
+/// subject doesn't follow the convention.
+fn conventional(ctx: &Context, _: &TableConfig) -> Result<Option<JsonValue>, CustomError> {
+    let Some(message) = text_arg(ctx, 0, "message")? else {
+        return Ok(None);
+    };
+    let subject = message.lines().next().unwrap_or_default();
+    let Some((head, description)) = subject.split_once(':') else {
+        return Ok(None);
+    };
+    let description = description.trim();
+    let (head, bang) = match head.strip_suffix('!') {
+        Some(head) => (head, true),
+        None => (head, false),
+    };
+    let (kind, scope) = match head.split_once('(') {
+        Some((kind, scope)) => match scope.strip_suffix(')') {
+            Some(scope) => (kind, Some(scope)),
+            None => return Ok(None),
+        },
+        None => (head, None),
+    };
+    let is_word = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_alphanumeric() || c == '-');
+    if !is_word(kind) || description.is_empty() || scope.is_some_and(|s| s.trim().is_empty()) {
+        return Ok(None);
+    }
+
+    let breaking = bang
+        || message.lines().skip(1).any(|line| {
+            line.starts_with("BREAKING CHANGE:") || line.starts_with("BREAKING-CHANGE:")
+        });
+    Ok(Some(json!({
+        "type": kind.to_lowercase(),
+        "scope": scope,
+        "breaking": breaking,
+        "description": description,
+    })))
+}
+
+/// `git_trailer(hash, key [, repo])`, the value of the `key` trailer in the commit's message, the
+/// key is matched case-insensitively. Values of a trailer that appears more than once are
+/// separated by newlines, NULL when the message has no such trailer.
+fn trailer(ctx: &Context, config: &TableConfig) -> Result<Option<String>, CustomError> {
+    let (Some(rev), Some(key)) = (text_arg(ctx, 0, "hash")?, text_arg(ctx, 1, "key")?) else {
+        return Ok(None);
+    };
+    let repo = repo_arg(ctx, 2, config)?;
+    let Some(commit) = resolve_commit(&repo, &rev)? else {
+        return Ok(None);
+    };
+    let message = String::from_utf8_lossy(commit.message_bytes()).to_string();
+    let values = message_trailers(&message)?
+        .into_iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case(&key))
+        .map(|(_, value)| value)
+        .collect::<Vec<_>>();
+    if values.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(values.join("\n")))
+}
+
+/// `git_canonical_author(name, email [, repo])`, the identity `.mailmap` maps an author to, as
+/// `Name <email>`.
+fn canonical_author(ctx: &Context, config: &TableConfig) -> Result<Option<String>, CustomError> {
+    let (Some(name), Some(email)) = (text_arg(ctx, 0, "name")?, text_arg(ctx, 1, "email")?) else {
+        return Ok(None);
+    };
+    let repo = repo_arg(ctx, 2, config)?;
+    Ok(Some(resolve_identity(&repo.mailmap()?, &name, &email)?))
+}
+
+fn resolve_identity(mailmap: &Mailmap, name: &str, email: &str) -> Result<String, CustomError> {
+    // Signatures can't have an empty name, the email stands in for it
+    let signature = Signature::now(if name.is_empty() { email } else { name }, email);
+    // Nor a name or email with angle brackets, which a .mailmap can't map either
+    let Ok(signature) = signature else {
+        return Ok(format!("{} <{}>", name, email));
+    };
+    let resolved = mailmap.resolve_signature(&signature)?;
+    Ok(format!(
+        "{} <{}>",
+        String::from_utf8_lossy(resolved.name_bytes()),
+        String::from_utf8_lossy(resolved.email_bytes())
+    ))
+}
+
+/// The `MAILMAP` collation, compares `Name <email>` identities by the identity the `.mailmap` of
+/// the default repository maps them to, ignoring case. Use it to group the aliases of an author:
+/// `GROUP BY author_name || ' <' || author_email || '>' COLLATE MAILMAP`.
+struct MailmapCollation {
+    config: TableConfig,
+    /// Resolved identities, up to [`MAILMAP_CACHE`] of them
+    canonical: Mutex<HashMap<String, String>>,
+}
+
+/// Identities the `MAILMAP` collation keeps resolved, it starts over when it has seen more.
+const MAILMAP_CACHE: usize = 100_000;
+
+impl MailmapCollation {
+    fn new(config: &TableConfig) -> Self {
+        MailmapCollation {
+            config: config.clone(),
+            canonical: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn compare(&self, a: &str, b: &str) -> Ordering {
+        self.canonical(a).cmp(&self.canonical(b))
+    }
+
+    /// Collations can't fail, identities the mailmap can't be read for are compared as they are.
+    fn canonical(&self, identity: &str) -> String {
+        let mut cache = self
+            .canonical
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(canonical) = cache.get(identity) {
+            return canonical.clone();
+        }
+
+        let (name, email) = match identity.rsplit_once('<') {
+            Some((name, email)) => (name.trim(), email.trim_end().trim_end_matches('>')),
+            None if identity.contains('@') => ("", identity.trim()),
+            None => (identity.trim(), ""),
+        };
+        let canonical = self
+            .config
+            .open_repository(None)
+            .and_then(|repo| resolve_identity(&repo.mailmap()?, name, email))
+            .unwrap_or_else(|_| identity.to_string())
+            .to_lowercase();
+        if cache.len() >= MAILMAP_CACHE {
+            cache.clear();
+        }
+        cache.insert(identity.to_string(), canonical.clone());
+        canonical
+    }
+}
+
+/// `git_week(ts)`, the Monday starting the week of `ts` as `YYYY-MM-DD`, in UTC.
+fn week(ctx: &Context, _: &TableConfig) -> Result<Option<String>, CustomError> {
+    Ok(timestamp_arg(ctx, 0)?.map(|ts| {
+        let monday = ts.date_naive() - Duration::days(ts.weekday().num_days_from_monday().into());
+        monday.format("%F").to_string()
+    }))
+}
+
+/// `git_month(ts)`, the month of `ts` as `YYYY-MM`, in UTC.
+fn month(ctx: &Context, _: &TableConfig) -> Result<Option<String>, CustomError> {
+    Ok(timestamp_arg(ctx, 0)?.map(|ts| ts.format("%Y-%m").to_string()))
+}
+
+/// `git_iso_week(ts)`, the ISO 8601 week of `ts` as `YYYY-Www`, in UTC. The year is the ISO
+/// year, so the last days of December can belong to week 1 of the next year.
+fn iso_week(ctx: &Context, _: &TableConfig) -> Result<Option<String>, CustomError> {
+    Ok(timestamp_arg(ctx, 0)?.map(|ts| {
+        let week = ts.iso_week();
+        format!("{}-W{:02}", week.year(), week.week())
+    }))
+}
+
+/// `git_symbolic_ref(name [, repo])`, the ref a symbolic ref like `HEAD` or
+/// `refs/remotes/origin/HEAD` points to, e.g. `refs/heads/main`. Short names like `origin/HEAD`
+/// are looked up like git does. NULL for refs that aren't symbolic or don't exist.
+fn symbolic_ref(ctx: &Context, config: &TableConfig) -> Result<Option<String>, CustomError> {
+    let Some(name) = text_arg(ctx, 0, "name")? else {
+        return Ok(None);
+    };
+    let repo = repo_arg(ctx, 1, config)?;
+    for prefix in ["", "refs/", "refs/tags/", "refs/heads/", "refs/remotes/"] {
+        match repo.find_reference(&format!("{prefix}{name}")) {
+            Ok(reference) => return Ok(reference.symbolic_target().map(str::to_string)),
+            // Names like `main` aren't valid without a prefix
+            Err(e) if matches!(e.code(), ErrorCode::NotFound | ErrorCode::InvalidSpec) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(None)
+}
+
+/// `git_current_branch([repo])`, the short name of the checked out branch, also before its first
+/// commit. NULL when HEAD is detached.
+fn current_branch(ctx: &Context, config: &TableConfig) -> Result<Option<String>, CustomError> {
+    let repo = repo_arg(ctx, 0, config)?;
+    let head = repo.find_reference("HEAD")?;
+    let branch = head
+        .symbolic_target()
+        .map(|target| target.strip_prefix("refs/heads/").unwrap_or(target));
+    Ok(branch.map(str::to_string))
+}
+
+/// `git_repo_root([path])`, the root of the working tree of the repository `path` is in, searching
+/// upwards from `path` like git does. The git directory of bare repositories, NULL outside of a
+/// repository.
+fn repo_root(ctx: &Context, config: &TableConfig) -> Result<Option<String>, CustomError> {
+    let start = match text_arg(ctx, 0, "path")? {
+        Some(path) => PathBuf::from(path),
+        None => config
+            .repository
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(".")),
+    };
+    let Some(repo) = not_found_as_none(Repository::discover(&start))? else {
+        return Ok(None);
+    };
+    let root = repo.workdir().unwrap_or_else(|| repo.path());
+    let root = root.canonicalize()?;
+    Ok(Some(root.to_string_lossy().to_string()))
+}
+
+/// `git_object_size(oid [, repo])`, the uncompressed size in bytes of any object, read from the
+/// object header without loading the object. NULL when there is no such object.
+fn object_size(ctx: &Context, config: &TableConfig) -> Result<Option<i64>, CustomError> {
+    let Some(rev) = text_arg(ctx, 0, "oid")? else {
+        return Ok(None);
+    };
+    let repo = repo_arg(ctx, 1, config)?;
+    // Full hashes are looked up directly, resolving a revision loads the object
+    let oid = match Oid::from_str(&rev) {
+        Ok(oid) if rev.len() == 40 => oid,
+        _ => match not_found_as_none(repo.revparse_single(&rev))? {
+            Some(object) => object.id(),
+            None => return Ok(None),
+        },
+    };
+    let header = not_found_as_none(repo.odb()?.read_header(oid))?;
+    Ok(header.map(|(size, _)| size as i64))
+}
+
+/// Repositories `git_describe` remembers descriptions of.
+const DESCRIBED_REPOSITORIES: usize = 4;
+
+/// Descriptions `git_describe` remembers per repository.
+const DESCRIPTIONS: usize = 100_000;
+
+type Descriptions = HashMap<Oid, Option<String>>;
+
+/// `git_describe(hash [, repo])`, the nearest tag, the commits since it and the abbreviated hash
+/// like `git describe --tags` prints them, e.g. `v1.2-3-g1a2b3c4`. NULL when no tag is reachable.
+///
+/// Describing walks the history back to a tag, release reports call it for every row. The
+/// descriptions are remembered per repository until a tag is added, moved or deleted.
+struct Describe {
+    config: TableConfig,
+    /// The descriptions per repository argument, with the fingerprint of the tags they were
+    /// computed for
+    described: Mutex<HashMap<Option<String>, (u64, Descriptions)>>,
+}
+
+impl Describe {
+    fn new(config: &TableConfig) -> Self {
+        Describe {
+            config: config.clone(),
+            described: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn describe(&self, ctx: &Context) -> Result<Option<String>, CustomError> {
+        let Some(rev) = text_arg(ctx, 0, "hash")? else {
+            return Ok(None);
+        };
+        let repo_param = text_arg(ctx, 1, "repository path")?;
+        let repo = self.config.open_repository(repo_param.as_deref())?;
+        let Some(commit) = resolve_commit(&repo, &rev)? else {
+            return Ok(None);
+        };
+        let tags = tags_fingerprint(&repo)?;
+        {
+            let described = self
+                .described
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            let cached = (described.get(&repo_param))
+                .filter(|(fingerprint, _)| *fingerprint == tags)
+                .and_then(|(_, descriptions)| descriptions.get(&commit.id()));
+            if let Some(description) = cached {
+                return Ok(description.clone());
+            }
+        }
+
+        let describe = commit
+            .as_object()
+            .describe(DescribeOptions::new().describe_tags());
+        let description = match describe {
+            Ok(describe) => Some(describe.format(None)?),
+            // libgit2 reports a commit without reachable tags as a generic describe error
+            Err(e) if e.class() == ErrorClass::Describe => None,
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut described = self
+            .described
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if !described.contains_key(&repo_param) && described.len() >= DESCRIBED_REPOSITORIES {
+            described.clear();
+        }
+        let (fingerprint, descriptions) = described.entry(repo_param).or_default();
+        if *fingerprint != tags || descriptions.len() >= DESCRIPTIONS {
+            *fingerprint = tags;
+            descriptions.clear();
+        }
+        descriptions.insert(commit.id(), description.clone());
+        Ok(description)
+    }
+}
+
+/// A hash of the names and targets of the tags, it changes when a tag is added, moved or deleted.
+fn tags_fingerprint(repo: &Repository) -> Result<u64, CustomError> {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    for reference in repo.references_glob("refs/tags/*")? {
+        let reference = reference?;
+        (reference.name_bytes(), reference.target()).hash(&mut hasher);
+    }
+    Ok(hasher.finish())
+}
+
+type Positions = Arc<HashMap<Oid, i64>>;
+
+/// The repository argument and the revision of a walk `git_topo_order` keeps the order of.
+type WalkKey = (Option<String>, String);
+
+/// Walks `git_topo_order` keeps the order of.
+const TOPO_ORDERS: usize = 4;
+
+/// `git_topo_order(hash [, rev [, repo]])`, the position of a commit in the topological order of
+/// the commits `rev` walks, HEAD when omitted, counting from 0 at the oldest. `rev` is a revision
+/// or a range like `commits` takes, pass the one the commits were listed with. Parents always come
+/// before their children, so `ORDER BY git_topo_order(hash, rev)` follows the DAG even when commit
+/// dates are skewed. NULL for commits the walk doesn't reach.
+///
+/// The order is computed once per repository and walk, not for every call, and again when a ref
+/// of the walk moves. It takes memory for every commit walked, the orders of at most
+/// [`TOPO_ORDERS`] walks are kept.
+struct TopoOrder {
+    config: TableConfig,
+    /// The positions per repository argument and revision, with the tips they were computed for
+    orders: Mutex<HashMap<WalkKey, (Tips, Positions)>>,
+}
+
+impl TopoOrder {
+    fn new(config: &TableConfig) -> Self {
+        TopoOrder {
+            config: config.clone(),
+            orders: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn position(&self, ctx: &Context) -> Result<Option<i64>, CustomError> {
+        let Some(hash) = text_arg(ctx, 0, "hash")? else {
+            return Ok(None);
+        };
+        let rev = match ctx.len() {
+            1 => Some("HEAD".to_string()),
+            _ => text_arg(ctx, 1, "revision")?,
+        };
+        let Some(rev) = rev else {
+            return Ok(None);
+        };
+        let repo_param = text_arg(ctx, 2, "repository path")?;
+        let repo = self.config.open_repository(repo_param.as_deref())?;
+        let Some(commit) = resolve_commit(&repo, &hash)? else {
+            return Ok(None);
+        };
+        let Some(order) = self.order(&repo, (repo_param, rev))? else {
+            return Ok(None);
+        };
+        Ok(order.get(&commit.id()).copied())
+    }
+
+    /// The positions of the commits the revision of `key` walks, None when it doesn't resolve.
+    fn order(&self, repo: &Repository, key: WalkKey) -> Result<Option<Positions>, CustomError> {
+        let Some(tips) = range_tips(repo, &key.1)? else {
+            return Ok(None);
+        };
+        let mut orders = self.orders.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some((cached_tips, order)) = orders.get(&key) {
+            if *cached_tips == tips {
+                return Ok(Some(order.clone()));
+            }
+        }
+
+        let checkpoint = self.config.interrupt.checkpoint();
+        let mut walk = walk_tips(repo, &tips)?;
+        walk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+        let order = walk
+            .enumerate()
+            .map(|(i, oid)| {
+                checkpoint.check()?;
+                Ok((oid?, i as i64))
+            })
+            .collect::<Result<HashMap<_, _>, CustomError>>()?;
+        let order = Arc::new(order);
+        if orders.len() >= TOPO_ORDERS {
+            orders.clear();
+        }
+        orders.insert(key, (tips, order.clone()));
+        Ok(Some(order))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test::{commit_file, temp_repository};
+    use crate::SqliteGit;
+    use rusqlite::Connection;
+    use std::path::Path;
+    use std::process::Command;
+
+    fn functions_db(repository: &Path) -> rusqlite::Result<Connection> {
+        let db = Connection::open_in_memory()?;
+        SqliteGit::new()
+            .with_functions()
+            .repository(repository)
+            .register(&db)?;
+        Ok(db)
+    }
+
+    #[test]
+    fn rev_parse() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("functions_rev_parse")?;
+        let first = commit_file(&repo, "file.txt", "one\n", "first")?;
+        let second = commit_file(&repo, "file.txt", "two\n", "second")?;
+
+        let db = functions_db(&path)?;
+        let (head, parent, missing, null): (String, String, Option<String>, Option<String>) = db
+            .query_row(
+                "SELECT git_rev_parse('HEAD'), git_rev_parse('HEAD~1', ?), git_rev_parse('nope'), git_rev_parse(NULL)",
+                [path.to_str()],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!(head, second.to_string());
+        assert_eq!(parent, first.to_string());
+        assert_eq!(missing, None);
+        assert_eq!(null, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn describe() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("functions_describe")?;
+        let first = commit_file(&repo, "file.txt", "one\n", "first")?;
+        let untagged: Option<String> = functions_db(&path)?.query_row(
+            "SELECT git_describe(?)",
+            [first.to_string()],
+            |row| row.get(0),
+        )?;
+        repo.tag_lightweight("v1.0", &repo.find_object(first, None)?, false)?;
+        let second = commit_file(&repo, "file.txt", "two\n", "second")?;
+
+        let db = functions_db(&path)?;
+        let (tagged, later): (String, String) = db.query_row(
+            "SELECT git_describe(?), git_describe(?)",
+            [first.to_string(), second.to_string()],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!(untagged, None);
+        assert_eq!(tagged, "v1.0");
+        assert_eq!(later, format!("v1.0-1-g{}", &second.to_string()[..7]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn describe_follows_new_tags() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("functions_describe_follows_new_tags")?;
+        let first = commit_file(&repo, "file.txt", "one\n", "first")?;
+        repo.tag_lightweight("v1.0", &repo.find_object(first, None)?, false)?;
+        let second = commit_file(&repo, "file.txt", "two\n", "second")?;
+
+        let db = functions_db(&path)?;
+        let describe = || {
+            db.query_row("SELECT git_describe(?)", [second.to_string()], |row| {
+                row.get::<_, String>(0)
+            })
+        };
+        let before = describe()?;
+        let remembered = describe()?;
+        repo.tag_lightweight("v1.1", &repo.find_object(second, None)?, false)?;
+        let tagged = describe()?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!(before, format!("v1.0-1-g{}", &second.to_string()[..7]));
+        assert_eq!(remembered, before);
+        assert_eq!(tagged, "v1.1");
+
+        Ok(())
+    }
+
+    #[test]
+    fn merge_base() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("functions_merge_base")?;
+        let base = commit_file(&repo, "file.txt", "one\n", "base")?;
+        let main = commit_file(&repo, "file.txt", "two\n", "main")?;
+        repo.branch("topic", &repo.find_commit(base)?, false)?;
+        repo.set_head("refs/heads/topic")?;
+        let topic = commit_file(&repo, "other.txt", "three\n", "topic")?;
+
+        let db = functions_db(&path)?;
+        let (merge_base, same): (String, String) = db.query_row(
+            "SELECT git_merge_base(?1, ?2), git_merge_base(?1, ?1)",
+            [main.to_string(), topic.to_string()],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!(merge_base, base.to_string());
+        assert_eq!(same, main.to_string());
+
+        Ok(())
+    }
+
+    #[test]
+    fn is_ancestor() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("functions_is_ancestor")?;
+        let first = commit_file(&repo, "file.txt", "one\n", "first")?;
+        let second = commit_file(&repo, "file.txt", "two\n", "second")?;
+
+        let db = functions_db(&path)?;
+        let (forward, backward, same): (bool, bool, bool) = db.query_row(
+            "SELECT git_is_ancestor(?1, ?2), git_is_ancestor(?2, ?1), git_is_ancestor(?1, ?1)",
+            [first.to_string(), second.to_string()],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert!(forward);
+        assert!(!backward);
+        assert!(same);
+
+        Ok(())
+    }
+
+    #[test]
+    fn patch_id() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("functions_patch_id")?;
+        let base = commit_file(&repo, "file.txt", "one\n", "base")?;
+        let picked = commit_file(&repo, "other.txt", "two\n", "add other")?;
+        let changed = commit_file(&repo, "file.txt", "three\n", "change file")?;
+        repo.branch("topic", &repo.find_commit(base)?, false)?;
+        repo.set_head("refs/heads/topic")?;
+        repo.checkout_head(Some(git2::build::CheckoutBuilder::new().force()))?;
+        let cherry_pick = commit_file(&repo, "other.txt", "two\n", "add other again")?;
+
+        let db = functions_db(&path)?;
+        let ids: Vec<String> = db
+            .prepare("SELECT git_patch_id(value) FROM json_each(?)")?
+            .query_map(
+                [format!(
+                    r#"["{}", "{}", "{}"]"#,
+                    picked, cherry_pick, changed
+                )],
+                |row| row.get(0),
+            )?
+            .collect::<Result<_, _>>()?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!(ids[0], ids[1]);
+        assert_ne!(ids[0], ids[2]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn blob_content() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("functions_blob_content")?;
+        let first = commit_file(&repo, "dir/file.txt", "one\n", "first")?;
+        commit_file(&repo, "dir/file.txt", "two\n", "second")?;
+
+        let db = functions_db(&path)?;
+        let (old, new, missing, dir): (String, String, Option<String>, Option<String>) = db
+            .query_row(
+            "SELECT git_blob_content(?, 'dir/file.txt'), git_blob_content('HEAD', 'dir/file.txt'),
+                        git_blob_content('HEAD', 'nope.txt'), git_blob_content('HEAD', 'dir')",
+            [first.to_string()],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )?;
+        let limited = Connection::open_in_memory()?;
+        SqliteGit::new()
+            .with_functions()
+            .repository(&path)
+            .max_blob_size(3)
+            .register(&limited)?;
+        let too_large = limited.query_row(
+            "SELECT git_blob_content('HEAD', 'dir/file.txt')",
+            [],
+            |row| row.get::<_, String>(0),
+        );
+        // Packed objects can't be streamed and are read whole
+        let packed = Command::new("git")
+            .args(["gc", "-q"])
+            .current_dir(&path)
+            .status()?;
+        let (packed_old, packed_new): (String, String) = db.query_row(
+            "SELECT git_blob_content(?, 'dir/file.txt'), git_blob_content('HEAD', 'dir/file.txt')",
+            [first.to_string()],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        let packed_too_large = limited.query_row(
+            "SELECT git_blob_content('HEAD', 'dir/file.txt')",
+            [],
+            |row| row.get::<_, String>(0),
+        );
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!(old, "one\n");
+        assert_eq!(new, "two\n");
+        assert_eq!(missing, None);
+        assert_eq!(dir, None);
+        assert!(too_large.is_err());
+        assert!(packed.success());
+        assert_eq!(
+            (packed_old.as_str(), packed_new.as_str()),
+            ("one\n", "two\n")
+        );
+        assert!(packed_too_large.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn short() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("functions_short")?;
+        let commit = commit_file(&repo, "file.txt", "one\n", "first")?.to_string();
+
+        let db = functions_db(&path)?;
+        let (default, longer, shortest): (String, String, String) = db.query_row(
+            "SELECT git_short(?1), git_short(?1, 12), git_short(?1, 1)",
+            [&commit],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!(default, commit[..7]);
+        assert_eq!(longer, commit[..12]);
+        assert_eq!(shortest, commit[..4]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn semver_collation() -> Result<(), Box<dyn std::error::Error>> {
+        let db = functions_db(Path::new("."))?;
+        let tags: Vec<String> = db
+            .prepare(
+                "SELECT value FROM json_each('[\"v1.10.0\", \"v1.9.0\", \"v1.9.0-rc.1\"]')
+                 ORDER BY value COLLATE SEMVER DESC",
+            )?
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+
+        assert_eq!(tags, vec!["v1.10.0", "v1.9.0", "v1.9.0-rc.1"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn diff_text() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("functions_diff_text")?;
+        let first = commit_file(&repo, "file.txt", "one\n", "first")?;
+        commit_file(&repo, "file.txt", "two\n", "second")?;
+        commit_file(&repo, "other.txt", "three\n", "third")?;
+
+        let db = functions_db(&path)?;
+        let (all, restricted): (String, String) = db.query_row(
+            "SELECT git_diff_text(?1, 'HEAD'), git_diff_text(?1, 'HEAD', 'file.txt')",
+            [first.to_string()],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        // The diff has a limit of its own, the one of git_blob_content doesn't apply
+        let limited = |diff_size| -> rusqlite::Result<rusqlite::Result<String>> {
+            let db = Connection::open_in_memory()?;
+            SqliteGit::new()
+                .with_functions()
+                .repository(&path)
+                .max_blob_size(3)
+                .max_diff_size(diff_size)
+                .register(&db)?;
+            Ok(db.query_row(
+                "SELECT git_diff_text(?1, 'HEAD', 'file.txt')",
+                [first.to_string()],
+                |row| row.get(0),
+            ))
+        };
+        let (fits, too_large) = (limited(1024)?, limited(10)?);
+        std::fs::remove_dir_all(&path)?;
+
+        assert!(all.contains("+++ b/other.txt\n"));
+        assert!(!restricted.contains("other.txt"));
+        assert!(restricted.starts_with("diff --git a/file.txt b/file.txt\n"));
+        assert!(restricted.ends_with("@@ -1 +1 @@\n-one\n+two\n"));
+        assert_eq!(fits?, restricted);
+        assert!(too_large
+            .unwrap_err()
+            .to_string()
+            .contains("more than the maximum of 10 bytes"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn blame_line() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("functions_blame_line")?;
+        let first = commit_file(&repo, "file.txt", "one\ntwo\n", "first")?;
+        let second = commit_file(&repo, "file.txt", "zero\none\nTWO\n", "second")?;
+
+        let db = functions_db(&path)?;
+        let (line_1, line_2, old, missing): (String, String, String, Option<String>) = db
+            .query_row(
+                "SELECT git_blame_line('file.txt', 1), git_blame_line('file.txt', 2),
+                        git_blame_line('file.txt', 2, ?), git_blame_line('file.txt', 4)",
+                [first.to_string()],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )?;
+        let (author, line): (String, i64) = db.query_row(
+            "SELECT json_extract(blame, '$.author_name'), json_extract(blame, '$.line')
+             FROM (SELECT git_blame_line_json('file.txt', 2) AS blame)",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!(line_1, second.to_string());
+        assert_eq!(line_2, first.to_string());
+        assert_eq!(old, first.to_string());
+        assert_eq!(missing, None);
+        assert_eq!(author, "Someone");
+        assert_eq!(line, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn blame_json() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("functions_blame_json")?;
+        let first = commit_file(&repo, "file.txt", "one\ntwo\n", "first")?;
+        let second = commit_file(&repo, "file.txt", "zero\none\ntwo\n", "second")?;
+
+        let db = functions_db(&path)?;
+        let mut stmt = db.prepare(
+            "SELECT json_extract(value, '$.hash'), json_extract(value, '$.start_line'),
+                    json_extract(value, '$.lines')
+             FROM json_each(git_blame_json('file.txt'))",
+        )?;
+        let hunks = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .collect::<rusqlite::Result<Vec<(String, i64, i64)>>>()?;
+        let missing: Option<String> =
+            db.query_row("SELECT git_blame_json('missing.txt')", [], |row| row.get(0))?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!(
+            hunks,
+            vec![(second.to_string(), 1, 1), (first.to_string(), 2, 2)]
+        );
+        assert_eq!(missing, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn exists_at() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("functions_exists_at")?;
+        let first = commit_file(&repo, "file.txt", "one\n", "first")?;
+        commit_file(&repo, "dir/other.txt", "two\n", "second")?;
+
+        let db = functions_db(&path)?;
+        let (before, after, dir, file): (bool, bool, bool, bool) = db.query_row(
+            "SELECT git_exists_at(?, 'dir/other.txt'), git_exists_at('HEAD', 'dir/other.txt'),
+                    git_exists_at('HEAD', 'dir'), git_exists_at('HEAD', 'file.txt')",
+            [first.to_string()],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert!(!before);
+        assert!(after);
+        assert!(dir);
+        assert!(file);
+
+        Ok(())
+    }
+
+    #[test]
+    fn commit_json() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("functions_commit_json")?;
+        let first = commit_file(&repo, "file.txt", "one\n", "first")?;
+        let message = "Second\n\nMore details.\n\nReviewed-by: A <a@example.com>\nReviewed-by: B <b@example.com>\n";
+        let second = commit_file(&repo, "file.txt", "two\n", message)?;
+
+        let db = functions_db(&path)?;
+        let (parent, body, reviewers, signature): (String, String, String, Option<String>) = db
+            .query_row(
+                "SELECT json_extract(c, '$.parents[0]'), json_extract(c, '$.body'),
+                        json_extract(c, '$.trailers.Reviewed-by'), json_extract(c, '$.signature')
+                 FROM (SELECT git_commit_json(?) AS c)",
+                [second.to_string()],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!(parent, first.to_string());
+        assert!(body.starts_with("More details."));
+        assert_eq!(reviewers, r#"["A <a@example.com>","B <b@example.com>"]"#);
+        assert_eq!(signature, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn config_get() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("functions_config_get")?;
+        repo.config()?.set_str("push.default", "simple")?;
+
+        let db = functions_db(&path)?;
+        let (value, missing): (String, Option<String>) = db.query_row(
+            "SELECT git_config_get('push.default'), git_config_get('sqlitegit.missing')",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!(value, "simple");
+        assert_eq!(missing, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_ignore() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("functions_check_ignore")?;
+        commit_file(
+            &repo,
+            ".gitignore",
+            "target/\n*.log\n",
+            "ignore build output",
+        )?;
+
+        let db = functions_db(&path)?;
+        let (dir, log, source): (bool, bool, bool) = db.query_row(
+            "SELECT git_check_ignore('target/debug/app'), git_check_ignore('logs/run.log'),
+                    git_check_ignore('src/main.rs')",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert!(dir);
+        assert!(log);
+        assert!(!source);
+
+        Ok(())
+    }
+
+    #[test]
+    fn rev_count() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("functions_rev_count")?;
+        let base = commit_file(&repo, "file.txt", "one\n", "base")?;
+        commit_file(&repo, "file.txt", "two\n", "main 1")?;
+        commit_file(&repo, "file.txt", "three\n", "main 2")?;
+        repo.branch("main", &repo.head()?.peel_to_commit()?, true)?;
+        repo.branch("topic", &repo.find_commit(base)?, false)?;
+        repo.set_head("refs/heads/topic")?;
+        commit_file(&repo, "other.txt", "four\n", "topic 1")?;
+
+        let db = functions_db(&path)?;
+        let counts: (i64, i64, i64, i64) = db.query_row(
+            "SELECT git_rev_count('main'), git_rev_count('main..topic'),
+                    git_rev_count('topic..main'), git_rev_count('main...topic')",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!(counts, (3, 1, 2, 3));
+
+        Ok(())
+    }
+
+    #[test]
+    fn conventional() -> Result<(), Box<dyn std::error::Error>> {
+        let db = functions_db(Path::new("."))?;
+        let parse = |message: &str| -> rusqlite::Result<Option<String>> {
+            db.query_row("SELECT git_conventional(?)", [message], |row| row.get(0))
+        };
+
+        assert_eq!(
+            parse("feat(parser)!: accept tabs\n\nbody")?.as_deref(),
+            Some(r#"{"type":"feat","scope":"parser","breaking":true,"description":"accept tabs"}"#)
+        );
+        assert_eq!(
+            parse("fix: crash\n\nBREAKING CHANGE: the config moved")?.as_deref(),
+            Some(r#"{"type":"fix","scope":null,"breaking":true,"description":"crash"}"#)
+        );
+        assert_eq!(
+            parse("docs: typo")?.as_deref(),
+            Some(r#"{"type":"docs","scope":null,"breaking":false,"description":"typo"}"#)
+        );
+        assert_eq!(parse("Merge branch 'main'")?, None);
+        assert_eq!(parse("fix(: nothing")?, None);
+        assert_eq!(parse("see http://example.com: docs")?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn trailer() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("functions_trailer")?;
+        let unsigned = commit_file(&repo, "file.txt", "one\n", "first")?;
+        let message =
+            "second\n\nSigned-off-by: A <a@example.com>\nsigned-off-by: B <b@example.com>\n";
+        let signed = commit_file(&repo, "file.txt", "two\n", message)?;
+
+        let db = functions_db(&path)?;
+        let (missing, values): (Option<String>, String) = db.query_row(
+            "SELECT git_trailer(?, 'Signed-off-by'), git_trailer(?, 'signed-off-by')",
+            [unsigned.to_string(), signed.to_string()],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!(missing, None);
+        assert_eq!(values, "A <a@example.com>\nB <b@example.com>");
+
+        Ok(())
+    }
+
+    #[test]
+    fn mailmap() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("functions_mailmap")?;
+        let mailmap = "Proper Name <proper@example.com> <old@example.com>\n";
+        commit_file(&repo, ".mailmap", mailmap, "add mailmap")?;
+
+        let db = functions_db(&path)?;
+        let (canonical, unmapped, bracketed): (String, String, String) = db.query_row(
+            "SELECT git_canonical_author('Old Name', 'old@example.com'),
+                    git_canonical_author('Other', 'other@example.com'),
+                    git_canonical_author('<Bot>', 'bot@example.com')",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+        let authors: i64 = db.query_row(
+            "SELECT count(DISTINCT value COLLATE MAILMAP) FROM json_each(?)",
+            [
+                r#"["Old Name <old@example.com>", "Proper Name <PROPER@example.com>",
+                 "Other <other@example.com>", "other <Other@Example.com>"]"#,
+            ],
+            |row| row.get(0),
+        )?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!(canonical, "Proper Name <proper@example.com>");
+        assert_eq!(unmapped, "Other <other@example.com>");
+        assert_eq!(bracketed, "<Bot> <bot@example.com>");
+        assert_eq!(authors, 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn time_buckets() -> Result<(), Box<dyn std::error::Error>> {
+        let db = functions_db(Path::new("."))?;
+        let buckets = |ts: &dyn rusqlite::ToSql| -> rusqlite::Result<(String, String, String)> {
+            db.query_row(
+                "SELECT git_week(?1), git_month(?1), git_iso_week(?1)",
+                [ts],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+        };
+        let expected = (
+            "2020-12-28".to_string(),
+            "2021-01".to_string(),
+            "2020-W53".to_string(),
+        );
+
+        assert_eq!(buckets(&"2021-01-03 17:55:57+00:00")?, expected);
+        assert_eq!(buckets(&"2021-01-03T19:55:57+02:00")?, expected);
+        assert_eq!(buckets(&"2021-01-03 17:55:57")?, expected);
+        assert_eq!(buckets(&"2021-01-03")?, expected);
+        assert_eq!(buckets(&1_609_696_557)?, expected);
+        assert!(buckets(&"yesterday").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn topo_order() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("functions_topo_order")?;
+        let first = commit_file(&repo, "file.txt", "one\n", "first")?;
+        let second = commit_file(&repo, "file.txt", "two\n", "second")?;
+
+        let db = functions_db(&path)?;
+        let positions = |db: &Connection| -> rusqlite::Result<(i64, i64)> {
+            db.query_row(
+                "SELECT git_topo_order(?), git_topo_order(?)",
+                [first.to_string(), second.to_string()],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+        };
+        let before = positions(&db)?;
+        let third = commit_file(&repo, "file.txt", "three\n", "third")?;
+        let after = positions(&db)?;
+        let latest: i64 = db.query_row("SELECT git_topo_order(?)", [third.to_string()], |row| {
+            row.get(0)
+        })?;
+        let main = repo.head()?.shorthand().unwrap_or_default().to_string();
+        repo.branch("topic", &repo.find_commit(third)?, false)?;
+        repo.set_head("refs/heads/topic")?;
+        let topic = commit_file(&repo, "file.txt", "four\n", "fourth")?;
+        repo.set_head(&format!("refs/heads/{}", main))?;
+        let on_topic: (Option<i64>, Option<i64>, Option<i64>, Option<i64>) = db.query_row(
+            "SELECT git_topo_order(?1), git_topo_order(?1, 'topic'),
+                    git_topo_order(?1, ?2 || '..topic'), git_topo_order(?1, NULL)",
+            [topic.to_string(), main.clone()],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!(before, (0, 1));
+        assert_eq!(after, (0, 1));
+        assert_eq!(latest, 2);
+        assert_eq!(on_topic, (None, Some(3), Some(0), None));
+
+        Ok(())
+    }
+
+    #[test]
+    fn symbolic_ref() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("functions_symbolic_ref")?;
+        let head = commit_file(&repo, "file.txt", "one\n", "first")?;
+        repo.reference("refs/remotes/origin/main", head, false, "fetch")?;
+        repo.reference_symbolic(
+            "refs/remotes/origin/HEAD",
+            "refs/remotes/origin/main",
+            false,
+            "clone",
+        )?;
+        let branch = repo.head()?.name().unwrap_or_default().to_string();
+
+        let db = functions_db(&path)?;
+        let refs: (String, String, Option<String>, Option<String>) = db.query_row(
+            "SELECT git_symbolic_ref('HEAD'), git_symbolic_ref('origin/HEAD'),
+                    git_symbolic_ref('origin/main'), git_symbolic_ref('nope')",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!(
+            refs,
+            (branch, "refs/remotes/origin/main".to_string(), None, None)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn current_branch() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("functions_current_branch")?;
+        repo.set_head("refs/heads/trunk")?;
+        let db = functions_db(&path)?;
+        let unborn: String = db.query_row("SELECT git_current_branch()", [], |row| row.get(0))?;
+        let head = commit_file(&repo, "file.txt", "one\n", "first")?;
+        let branch: String =
+            db.query_row("SELECT git_current_branch(?)", [path.to_str()], |row| {
+                row.get(0)
+            })?;
+        repo.set_head_detached(head)?;
+        let detached: Option<String> =
+            db.query_row("SELECT git_current_branch()", [], |row| row.get(0))?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!(unborn, "trunk");
+        assert_eq!(branch, "trunk");
+        assert_eq!(detached, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn repo_root() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("functions_repo_root")?;
+        commit_file(&repo, "dir/sub/file.txt", "one\n", "first")?;
+        let root = path.canonicalize()?.to_string_lossy().to_string();
+
+        let db = functions_db(&path)?;
+        let (default, nested, outside): (String, String, Option<String>) = db.query_row(
+            "SELECT git_repo_root(), git_repo_root(?), git_repo_root('/')",
+            [path.join("dir/sub").to_str()],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!(default, root);
+        assert_eq!(nested, root);
+        assert_eq!(outside, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn object_size() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("functions_object_size")?;
+        commit_file(&repo, "file.txt", "twelve bytes", "first")?;
+        let blob = repo.revparse_single("HEAD:file.txt")?.id();
+
+        let db = functions_db(&path)?;
+        let (blob_size, by_rev, missing): (i64, i64, Option<i64>) = db.query_row(
+            "SELECT git_object_size(?), git_object_size('HEAD:file.txt'),
+                    git_object_size('0000000000000000000000000000000000000001')",
+            [blob.to_string()],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!(blob_size, 12);
+        assert_eq!(by_rev, 12);
+        assert_eq!(missing, None);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,181 @@
+use crate::writable::{required, text, Row, Table};
+use crate::CustomError;
+use git2::{Config, ConfigLevel, Repository};
+use rusqlite::types::Value;
+
+/// `config([repo])`, the configuration of a repository, every value of every level from system
+/// to local. INSERT adds a value at `level`, `local` when it's NULL, like `git config --add`.
+/// UPDATE changes a value or moves it to another name or level, DELETE removes it. `global`
+/// writes to `~/.gitconfig`, so a statement can change the configuration of every repository.
+pub(crate) static CONFIG: Table = Table {
+    name: "config",
+    columns: &[("name", "text"), ("value", "text"), ("level", "text")],
+    rows,
+    insert,
+    update: Some(update),
+    delete,
+};
+
+const NAME: usize = 0;
+const VALUE: usize = 1;
+const LEVEL: usize = 2;
+
+/// The levels by the name of the column, from the lowest priority to the highest.
+const LEVELS: [(&str, ConfigLevel); 6] = [
+    ("programdata", ConfigLevel::ProgramData),
+    ("system", ConfigLevel::System),
+    ("xdg", ConfigLevel::XDG),
+    ("global", ConfigLevel::Global),
+    ("local", ConfigLevel::Local),
+    ("app", ConfigLevel::App),
+];
+
+fn rows(repo: &Repository) -> Result<Vec<Row>, CustomError> {
+    let config = repo.config()?;
+    let mut rows = vec![];
+    for entry in &config.entries(None)? {
+        let entry = entry?;
+        let level = LEVELS.iter().find(|(_, level)| *level == entry.level());
+        rows.push(vec![
+            Value::Text(String::from_utf8_lossy(entry.name_bytes()).to_string()),
+            Value::Text(String::from_utf8_lossy(entry.value_bytes()).to_string()),
+            level.map_or(Value::Null, |(name, _)| Value::Text(name.to_string())),
+        ]);
+    }
+    Ok(rows)
+}
+
+fn insert(repo: &Repository, row: &Row) -> Result<(), CustomError> {
+    let (name, value) = (
+        required(&CONFIG, row, NAME)?,
+        required(&CONFIG, row, VALUE)?,
+    );
+    let mut config = level(repo, row)?;
+    // Replaces the value when the name has it already, appends it otherwise
+    Ok(config.set_multivar(name, &exactly(value), value)?)
+}
+
+fn update(repo: &Repository, old: &Row, new: &Row) -> Result<(), CustomError> {
+    let (name, value) = (
+        required(&CONFIG, old, NAME)?,
+        required(&CONFIG, old, VALUE)?,
+    );
+    let new_name = required(&CONFIG, new, NAME)?;
+    if new_name == name && text(&CONFIG, new, LEVEL)? == text(&CONFIG, old, LEVEL)? {
+        let new_value = required(&CONFIG, new, VALUE)?;
+        let mut config = level(repo, old)?;
+        return Ok(config.set_multivar(name, &exactly(value), new_value)?);
+    }
+    insert(repo, new)?;
+    delete(repo, old)
+}
+
+fn delete(repo: &Repository, row: &Row) -> Result<(), CustomError> {
+    let (name, value) = (
+        required(&CONFIG, row, NAME)?,
+        required(&CONFIG, row, VALUE)?,
+    );
+    let mut config = level(repo, row)?;
+    Ok(config.remove_multivar(name, &exactly(value))?)
+}
+
+/// The configuration of the level of a row.
+fn level(repo: &Repository, row: &Row) -> Result<Config, CustomError> {
+    let name = text(&CONFIG, row, LEVEL)?.unwrap_or("local");
+    let Some((_, level)) = LEVELS.iter().find(|(level, _)| *level == name) else {
+        let levels = LEVELS.map(|(name, _)| name).join(", ");
+        return Err(CustomError::InvalidArgument(format!(
+            "{} isn't a config level, the levels are {}",
+            name, levels
+        )));
+    };
+    Ok(repo.config()?.open_level(*level)?)
+}
+
+/// A regular expression matching `value` and nothing else, the values of a name are picked
+/// with one.
+fn exactly(value: &str) -> String {
+    let mut pattern = String::from("^");
+    for c in value.chars() {
+        if "\\^$.|?*+()[]{}".contains(c) {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('$');
+    pattern
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test::temp_repository;
+    use crate::SqliteGit;
+    use git2::ConfigLevel;
+    use rusqlite::Connection;
+
+    #[test]
+    fn adds_changes_and_removes_local_values() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("git_config")?;
+
+        let db = Connection::open_in_memory()?;
+        SqliteGit::new()
+            .with_config()
+            .repository(&path)
+            .register(&db)?;
+        let values = || -> rusqlite::Result<Vec<(String, String)>> {
+            let mut stmt = db.prepare(
+                "SELECT name, value FROM config
+                 WHERE level = 'local' AND name LIKE 'sqlitegit.%' ORDER BY name, value",
+            )?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect()
+        };
+        db.execute_batch(
+            "BEGIN;
+             INSERT INTO config(name, value) VALUES ('sqlitegit.label', 'fast (1.5x)');
+             INSERT INTO config(name, value, level) VALUES ('sqlitegit.label', 'flaky', 'local');
+             INSERT INTO config(name, value) VALUES ('sqlitegit.owner', 'infra');
+             COMMIT;",
+        )?;
+        let added = values()?;
+        db.execute_batch(
+            "BEGIN;
+             UPDATE config SET value = 'stable' WHERE name = 'sqlitegit.label' AND value = 'flaky';
+             UPDATE config SET name = 'sqlitegit.team' WHERE name = 'sqlitegit.owner';
+             DELETE FROM config WHERE value = 'fast (1.5x)';
+             COMMIT;",
+        )?;
+        let changed = values()?;
+        let local = repo.config()?.open_level(ConfigLevel::Local)?;
+        let team = local.get_string("sqlitegit.team")?;
+        let level = db.execute(
+            "INSERT INTO config(name, value, level) VALUES ('sqlitegit.x', 'y', 'nowhere')",
+            [],
+        );
+        std::fs::remove_dir_all(&path)?;
+
+        let pair = |name: &str, value: &str| (name.to_string(), value.to_string());
+        assert_eq!(
+            added,
+            vec![
+                pair("sqlitegit.label", "fast (1.5x)"),
+                pair("sqlitegit.label", "flaky"),
+                pair("sqlitegit.owner", "infra"),
+            ]
+        );
+        assert_eq!(
+            changed,
+            vec![
+                pair("sqlitegit.label", "stable"),
+                pair("sqlitegit.team", "infra")
+            ]
+        );
+        assert_eq!(team, "infra");
+        assert!(level
+            .unwrap_err()
+            .to_string()
+            .contains("isn't a config level"));
+
+        Ok(())
+    }
+}
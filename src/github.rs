@@ -0,0 +1,647 @@
+use crate::mirrors::is_url;
+use crate::{set_timestamp, trace_index_info, CustomError, TableConfig};
+use chrono::{DateTime, Utc};
+use itertools::Itertools;
+use rusqlite::types::ValueRef;
+use rusqlite::vtab::{
+    sqlite3_vtab, sqlite3_vtab_cursor, Context, IndexConstraintOp, IndexInfo, VTab, VTabConnection,
+    VTabCursor, Values,
+};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::os::raw::c_int;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use tracing::{debug, debug_span, info};
+
+/// How long a listing read to the end is served again instead of asking GitHub, which keeps
+/// the rescans of a join, and the queries run in a row, from using up the rate limit.
+const LISTING_TTL: Duration = Duration::from_secs(60);
+
+/// The most items the API returns in a page.
+const PAGE_SIZE: usize = 100;
+
+/// The items of a listing read to the end, and when it was read.
+type Listing = (Instant, Arc<Vec<Value>>);
+
+/// How a column is read from an item of the API.
+#[derive(Debug, Clone, Copy)]
+enum Field {
+    /// A number or a string at the JSON pointer
+    Value(&'static str),
+    /// An ISO 8601 timestamp at the JSON pointer, a DATETIME like the ones of `commits`
+    Time(&'static str),
+    /// The names of the labels, separated by commas
+    Labels,
+}
+
+/// A listing of the GitHub API read by a table.
+#[derive(Debug)]
+pub(crate) struct Resource {
+    pub(crate) name: &'static str,
+    /// The path of the listing below `/repos/{owner}/{repo}`
+    path: &'static str,
+    columns: &'static [(&'static str, &'static str, Field)],
+    /// The issues listing has the pull requests too, `gh_issues` leaves them out
+    skip_pull_requests: bool,
+}
+
+/// `gh_pull_requests([repo])`, the pull requests of the GitHub repository of `repo`. Joins to
+/// `commits` on `merge_commit_sha` or `head_hash`, and to branches on `head_branch`.
+pub(crate) static PULL_REQUESTS: Resource = Resource {
+    name: "gh_pull_requests",
+    path: "pulls",
+    columns: &[
+        ("number", "integer", Field::Value("/number")),
+        ("title", "text", Field::Value("/title")),
+        ("state", "text", Field::Value("/state")),
+        ("author", "text", Field::Value("/user/login")),
+        ("created_at", "DATETIME", Field::Time("/created_at")),
+        ("updated_at", "DATETIME", Field::Time("/updated_at")),
+        ("closed_at", "DATETIME", Field::Time("/closed_at")),
+        ("merged_at", "DATETIME", Field::Time("/merged_at")),
+        (
+            "merge_commit_sha",
+            "text",
+            Field::Value("/merge_commit_sha"),
+        ),
+        ("head_branch", "text", Field::Value("/head/ref")),
+        ("head_hash", "text", Field::Value("/head/sha")),
+        ("base_branch", "text", Field::Value("/base/ref")),
+        ("is_draft", "bool", Field::Value("/draft")),
+        ("labels", "text", Field::Labels),
+        ("url", "text", Field::Value("/html_url")),
+        ("body", "text", Field::Value("/body")),
+    ],
+    skip_pull_requests: false,
+};
+
+/// `gh_issues([repo])`, the issues of the GitHub repository of `repo`, without its pull requests.
+pub(crate) static ISSUES: Resource = Resource {
+    name: "gh_issues",
+    path: "issues",
+    columns: &[
+        ("number", "integer", Field::Value("/number")),
+        ("title", "text", Field::Value("/title")),
+        ("state", "text", Field::Value("/state")),
+        ("author", "text", Field::Value("/user/login")),
+        ("created_at", "DATETIME", Field::Time("/created_at")),
+        ("updated_at", "DATETIME", Field::Time("/updated_at")),
+        ("closed_at", "DATETIME", Field::Time("/closed_at")),
+        ("comments", "integer", Field::Value("/comments")),
+        ("labels", "text", Field::Labels),
+        ("url", "text", Field::Value("/html_url")),
+        ("body", "text", Field::Value("/body")),
+    ],
+    skip_pull_requests: true,
+};
+
+/// The GitHub API the `gh_*` tables read, github.com unless `GITHUB_API_URL` and
+/// `GITHUB_SERVER_URL` point to GitHub Enterprise like they do in GitHub Actions.
+#[derive(Debug, Clone)]
+pub(crate) struct GitHub {
+    api: String,
+    /// The host of the remotes of the repositories on the server
+    host: String,
+    /// From `GH_TOKEN` or `GITHUB_TOKEN`, without one only public repositories can be read and
+    /// the rate limit is low
+    token: Option<String>,
+    /// The listings read to the end by URL, with the time they were read, shared by every clone
+    listings: Arc<Mutex<HashMap<String, Listing>>>,
+}
+
+impl GitHub {
+    pub(crate) fn from_env() -> GitHub {
+        let var = |name| std::env::var(name).ok().filter(|value| !value.is_empty());
+        let server = var("GITHUB_SERVER_URL").unwrap_or_else(|| "https://github.com".to_string());
+        let host = server.split_once("://").map_or(&*server, |(_, host)| host);
+        GitHub::new(
+            var("GITHUB_API_URL").unwrap_or_else(|| "https://api.github.com".to_string()),
+            host.trim_end_matches('/').to_string(),
+            var("GH_TOKEN").or_else(|| var("GITHUB_TOKEN")),
+        )
+    }
+
+    fn new(api: String, host: String, token: Option<String>) -> GitHub {
+        GitHub {
+            api: api.trim_end_matches('/').to_string(),
+            host,
+            token,
+            listings: Arc::default(),
+        }
+    }
+
+    /// The client of a scan, it keeps the connection to the API open between the pages.
+    fn agent() -> ureq::Agent {
+        ureq::AgentBuilder::new()
+            .user_agent(concat!("sqlitegit/", env!("CARGO_PKG_VERSION")))
+            .timeout(Duration::from_secs(30))
+            .build()
+    }
+
+    /// The `owner/name` of a remote URL of a repository on the server, like
+    /// `https://github.com/rust-lang/log` or `git@github.com:rust-lang/log.git`.
+    fn slug(&self, url: &str) -> Option<String> {
+        let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+        let rest = match rest.split_once('@') {
+            Some((user, rest)) if !user.contains('/') => rest,
+            _ => rest,
+        };
+        let (host, path) = rest.split_once(['/', ':'])?;
+        // Ports are left out, of the URL and of the server
+        let path = match path.split_once('/') {
+            Some((port, path)) if port.parse::<u16>().is_ok() => path,
+            _ => path,
+        };
+        if self.host.split(':').next() != Some(host) {
+            return None;
+        }
+        let path = path.trim_matches('/');
+        let path = path.strip_suffix(".git").unwrap_or(path);
+        match path.split('/').collect_vec()[..] {
+            [owner, name] if !owner.is_empty() && !name.is_empty() => Some(path.to_string()),
+            _ => None,
+        }
+    }
+
+    /// A listing read to the end less than [`LISTING_TTL`] ago.
+    fn cached(&self, url: &str) -> Option<Arc<Vec<Value>>> {
+        let listings = self.listings.lock().unwrap_or_else(PoisonError::into_inner);
+        match listings.get(url) {
+            Some((read, items)) if read.elapsed() < LISTING_TTL => Some(items.clone()),
+            _ => None,
+        }
+    }
+
+    fn cache(&self, url: String, items: Vec<Value>) {
+        let mut listings = self.listings.lock().unwrap_or_else(PoisonError::into_inner);
+        listings.insert(url, (Instant::now(), Arc::new(items)));
+    }
+
+    /// The items of the page at `url` and the URL of the next page, when there is one.
+    fn page(
+        &self,
+        agent: &ureq::Agent,
+        url: &str,
+    ) -> Result<(Vec<Value>, Option<String>), CustomError> {
+        let mut request = (agent.get(url))
+            .set("Accept", "application/vnd.github+json")
+            .set("X-GitHub-Api-Version", "2022-11-28");
+        if let Some(token) = &self.token {
+            request = request.set("Authorization", &format!("Bearer {}", token));
+        }
+        let response = request.call().map_err(|e| self.error(e))?;
+        let next = response.header("link").and_then(next_page);
+        let items = serde_json::from_reader(response.into_reader())
+            .map_err(|e| CustomError::GitHub(format!("unexpected answer of {}: {}", url, e)))?;
+        Ok((items, next))
+    }
+
+    fn error(&self, error: ureq::Error) -> CustomError {
+        let (status, response) = match error {
+            ureq::Error::Status(status, response) => (status, response),
+            ureq::Error::Transport(transport) => return CustomError::GitHub(transport.to_string()),
+        };
+        let url = response.get_url().to_string();
+        let body = serde_json::from_reader::<_, Value>(response.into_reader()).ok();
+        let message = body.as_ref().and_then(|body| body["message"].as_str());
+        let mut error = format!("{} answered {}", url, status);
+        if let Some(message) = message {
+            error = format!("{}: {}", error, message);
+        }
+        // GitHub hides private repositories from anonymous requests behind a 404
+        if matches!(status, 401 | 403 | 404) && self.token.is_none() {
+            error.push_str(", set GH_TOKEN or GITHUB_TOKEN to read private repositories");
+        }
+        CustomError::GitHub(error)
+    }
+}
+
+/// The URL of the `next` link of a `Link` header.
+fn next_page(link: &str) -> Option<String> {
+    link.split(',').find_map(|link| {
+        let (url, rel) = link.split_once(';')?;
+        let next = rel.split(';').any(|param| param.trim() == "rel=\"next\"");
+        let url = url.trim().strip_prefix('<')?.strip_suffix('>')?;
+        next.then(|| url.to_string())
+    })
+}
+
+#[repr(C)]
+pub(crate) struct GitHubTable {
+    base: sqlite3_vtab,
+    config: TableConfig,
+    resource: &'static Resource,
+}
+
+unsafe impl<'a> VTab<'a> for GitHubTable {
+    type Aux = (TableConfig, &'static Resource);
+    type Cursor = GitHubCursor;
+
+    fn connect(
+        _db: &mut VTabConnection,
+        aux: Option<&Self::Aux>,
+        _args: &[&[u8]],
+    ) -> rusqlite::Result<(String, Self)> {
+        let (config, resource) = aux
+            .cloned()
+            .unwrap_or((TableConfig::default(), &PULL_REQUESTS));
+        let columns = resource.columns.iter();
+        let mut columns = columns.map(|(name, declared, _)| format!("{} {}", name, declared));
+        let schema = format!(
+            "create table {}({}, repo hidden)",
+            resource.name,
+            columns.join(", ")
+        );
+        Ok((
+            schema,
+            GitHubTable {
+                base: sqlite3_vtab::default(),
+                config,
+                resource,
+            },
+        ))
+    }
+
+    fn best_index(&self, info: &mut IndexInfo) -> rusqlite::Result<()> {
+        trace_index_info(self.resource.name, info);
+        let repo = self.resource.columns.len() as c_int;
+        let usable = info.constraints().position(|constraint| {
+            constraint.column() == repo
+                && constraint.is_usable()
+                && constraint.operator() == IndexConstraintOp::SQLITE_INDEX_CONSTRAINT_EQ
+        });
+        let idx_num = match usable {
+            Some(i) => {
+                let mut usage = info.constraint_usage(i);
+                usage.set_argv_index(1);
+                usage.set_omit(true);
+                1
+            }
+            None => 0,
+        };
+        debug!(table = self.resource.name, idx_num, "best_index");
+        info.set_idx_num(idx_num);
+        // Every scan is a listing of the API, however the repository is picked
+        info.set_estimated_cost(1e6);
+        info.set_estimated_rows(1000);
+        Ok(())
+    }
+
+    fn open(&mut self) -> rusqlite::Result<GitHubCursor> {
+        Ok(GitHubCursor {
+            base: Default::default(),
+            config: self.config.clone(),
+            resource: self.resource,
+            agent: GitHub::agent(),
+            listing: String::new(),
+            items: Arc::default(),
+            read: vec![],
+            next: None,
+            i: 0,
+            repo_param: None,
+            scan: None,
+        })
+    }
+}
+
+#[repr(C)]
+pub(crate) struct GitHubCursor {
+    base: sqlite3_vtab_cursor,
+    config: TableConfig,
+    resource: &'static Resource,
+    agent: ureq::Agent,
+    /// The URL of the first page of the listing
+    listing: String,
+    /// The items of the pages read so far, or of the cached listing
+    items: Arc<Vec<Value>>,
+    /// Every item of the listing read, cached once the last page is
+    read: Vec<Value>,
+    /// The URL of the page after `items`
+    next: Option<String>,
+    i: usize,
+    repo_param: Option<String>,
+    /// The scan of `--profile` the rows count towards
+    scan: Option<usize>,
+}
+
+impl GitHubCursor {
+    fn init(&mut self, idx_num: c_int, vals: &[ValueRef]) -> Result<(), CustomError> {
+        self.i = 0;
+        self.read.clear();
+        self.items = Arc::default();
+        self.next = None;
+        self.repo_param = match (idx_num, vals.first()) {
+            (1, Some(ValueRef::Null)) => return Ok(()),
+            (1, Some(value)) => match value.as_str() {
+                Ok(repo) => Some(repo.to_string()),
+                Err(_) => {
+                    return Err(CustomError::InvalidArgument(format!(
+                        "the repository must be TEXT, got {}",
+                        value.data_type()
+                    )))
+                }
+            },
+            _ => None,
+        };
+        let Some(github) = &self.config.github else {
+            return Err(CustomError::InvalidArgument(
+                "the GitHub tables aren't enabled".into(),
+            ));
+        };
+        let slug = self.slug(github)?;
+        self.listing = format!(
+            "{}/repos/{}/{}?state=all&per_page={}",
+            github.api, slug, self.resource.path, PAGE_SIZE
+        );
+        match github.cached(&self.listing) {
+            Some(items) => self.items = items,
+            None => self.read_page(Some(self.listing.clone()))?,
+        }
+        self.settle()
+    }
+
+    /// The `owner/name` of the repository argument, a URL or the `origin` remote of a repository.
+    fn slug(&self, github: &GitHub) -> Result<String, CustomError> {
+        if let Some(url) = self.repo_param.as_deref().filter(|repo| is_url(repo)) {
+            return (github.slug(url)).ok_or_else(|| {
+                CustomError::InvalidArgument(format!(
+                    "{} isn't a repository on {}",
+                    url, github.host
+                ))
+            });
+        }
+        let repo = self.config.open_repository(self.repo_param.as_deref())?;
+        let remote = repo.find_remote("origin")?;
+        let url = remote.url().unwrap_or_default();
+        github.slug(url).ok_or_else(|| {
+            CustomError::InvalidArgument(format!(
+                "the origin of {} is {}, not a repository on {}",
+                repo.path().display(),
+                url,
+                github.host
+            ))
+        })
+    }
+
+    /// Reads the page at `url`, or caches the listing when there's none.
+    fn read_page(&mut self, url: Option<String>) -> Result<(), CustomError> {
+        let Some(github) = &self.config.github else {
+            return Ok(());
+        };
+        let Some(url) = url else {
+            github.cache(self.listing.clone(), std::mem::take(&mut self.read));
+            return Ok(());
+        };
+        self.config.interrupt.checkpoint().check()?;
+        let start = Instant::now();
+        let (items, next) = github.page(&self.agent, &url)?;
+        info!(table = self.resource.name, url, items = items.len(), elapsed = ?start.elapsed(), "page");
+        self.read.extend(items.iter().cloned());
+        self.items = Arc::new(items);
+        self.i = 0;
+        self.next = next;
+        if self.next.is_none() {
+            github.cache(self.listing.clone(), std::mem::take(&mut self.read));
+        }
+        Ok(())
+    }
+
+    /// Moves past the items the table leaves out, reading the next pages when the current one
+    /// runs out.
+    fn settle(&mut self) -> Result<(), CustomError> {
+        loop {
+            let skipped = |item: &Value| {
+                self.resource.skip_pull_requests && item.get("pull_request").is_some()
+            };
+            while self.items.get(self.i).is_some_and(skipped) {
+                self.i += 1;
+            }
+            if self.i < self.items.len() || self.next.is_none() {
+                return Ok(());
+            }
+            let next = self.next.take();
+            self.read_page(next)?;
+        }
+    }
+}
+
+unsafe impl VTabCursor for GitHubCursor {
+    fn filter(
+        &mut self,
+        idx_num: c_int,
+        _idx_str: Option<&str>,
+        args: &Values<'_>,
+    ) -> rusqlite::Result<()> {
+        let vals = args.iter().collect_vec();
+        let name = self.resource.name;
+        let _span = debug_span!("filter", table = name, idx_num, ?vals).entered();
+        let start = Instant::now();
+        self.init(idx_num, &vals)?;
+        let row = !self.eof();
+        self.scan = (self.config.profiler).filter(name, &vals, start.elapsed(), row);
+        Ok(())
+    }
+
+    fn next(&mut self) -> rusqlite::Result<()> {
+        let start = Instant::now();
+        self.i += 1;
+        self.settle()?;
+        let row = !self.eof();
+        self.config.profiler.next(self.scan, start.elapsed(), row);
+        Ok(())
+    }
+
+    fn eof(&self) -> bool {
+        self.i >= self.items.len()
+    }
+
+    fn column(&self, ctx: &mut Context, i: c_int) -> rusqlite::Result<()> {
+        let item = &self.items[self.i];
+        let Some((_, _, field)) = self.resource.columns.get(i as usize) else {
+            return ctx.set_result(&self.repo_param);
+        };
+        match field {
+            Field::Value(pointer) => match item.pointer(pointer) {
+                Some(Value::String(text)) => ctx.set_result(text),
+                Some(Value::Number(number)) => ctx.set_result(&number.as_i64()),
+                Some(Value::Bool(value)) => ctx.set_result(value),
+                _ => ctx.set_result(&Option::<i64>::None),
+            },
+            Field::Time(pointer) => {
+                let text = item.pointer(pointer).and_then(Value::as_str);
+                match text.and_then(|text| text.parse::<DateTime<Utc>>().ok()) {
+                    Some(when) => set_timestamp(ctx, &when),
+                    None => ctx.set_result(&Option::<i64>::None),
+                }
+            }
+            Field::Labels => {
+                let labels = item["labels"].as_array().into_iter().flatten();
+                let mut names = labels.filter_map(|label| label["name"].as_str());
+                ctx.set_result(&names.join(","))
+            }
+        }
+    }
+
+    fn rowid(&self) -> rusqlite::Result<i64> {
+        let number = self.items[self.i]["number"].as_i64();
+        Ok(number.unwrap_or(self.i as i64))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::github::GitHub;
+    use crate::test::{commit_file, temp_repository};
+    use crate::SqliteGit;
+    use rusqlite::Connection;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+
+    /// Answers the requests of the tables like the API would, and records their paths.
+    fn serve(listener: TcpListener, merge: String, requests: Arc<Mutex<Vec<String>>>) {
+        let address = listener.local_addr().unwrap();
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { return };
+            let mut lines = BufReader::new(&stream).lines().map_while(Result::ok);
+            let path = lines.next().unwrap_or_default();
+            let path = path.split(' ').nth(1).unwrap_or_default().to_string();
+            lines.take_while(|line| !line.is_empty()).for_each(drop);
+            requests.lock().unwrap().push(path.clone());
+            let next = format!("http://{}/repos/owner/name/pulls?page=2", address);
+            let (status, link, body) = match path.as_str() {
+                "/repos/owner/name/pulls?state=all&per_page=100" => (
+                    "200 OK",
+                    format!("<{}>; rel=\"next\", <{}>; rel=\"last\"", next, next),
+                    format!(
+                        r#"[{{"number": 2, "state": "closed", "merge_commit_sha": "{}",
+                             "merged_at": "2024-05-01T12:00:00Z", "head": {{"ref": "feature"}},
+                             "labels": [{{"name": "bug"}}, {{"name": "ui"}}]}}]"#,
+                        merge
+                    ),
+                ),
+                "/repos/owner/name/pulls?page=2" => (
+                    "200 OK",
+                    String::new(),
+                    r#"[{"number": 1, "state": "open", "head": {"ref": "draft"}, "labels": []}]"#
+                        .to_string(),
+                ),
+                "/repos/owner/name/issues?state=all&per_page=100" => (
+                    "200 OK",
+                    String::new(),
+                    r#"[{"number": 4, "pull_request": {}}, {"number": 3, "comments": 7}]"#
+                        .to_string(),
+                ),
+                _ => (
+                    "404 Not Found",
+                    String::new(),
+                    r#"{"message": "Not Found"}"#.to_string(),
+                ),
+            };
+            let link = match link.is_empty() {
+                true => String::new(),
+                false => format!("Link: {}\r\n", link),
+            };
+            let _ = write!(
+                stream,
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                link,
+                body.len(),
+                body
+            );
+        }
+    }
+
+    #[test]
+    fn lists_pull_requests_and_issues() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("github")?;
+        let merge = commit_file(&repo, "file.txt", "one\n", "Merge pull request #2")?;
+        repo.remote("origin", "git@github.com:owner/name.git")?;
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let api = format!("http://{}", listener.local_addr()?);
+        let requests = Arc::new(Mutex::new(vec![]));
+        let served = requests.clone();
+        let merge_sha = merge.to_string();
+        std::thread::spawn(move || serve(listener, merge_sha, served));
+
+        let mut git = SqliteGit::new().with_all().with_github().repository(&path);
+        let github = GitHub::new(api, "github.com".to_string(), None);
+        git.config.github = Some(github.clone());
+        let db = Connection::open_in_memory()?;
+        git.register(&db)?;
+        let pull_requests = || -> rusqlite::Result<Vec<String>> {
+            let mut stmt = db.prepare(
+                "SELECT number || ' ' || state || ' ' || head_branch || ' ' || labels
+                        || ' ' || ifnull(merged_at, '-')
+                 FROM gh_pull_requests ORDER BY number",
+            )?;
+            let rows = stmt.query_map([], |row| row.get(0))?;
+            rows.collect()
+        };
+        let listed = pull_requests()?;
+        let merged: String = db.query_row(
+            "SELECT c.message || ' #' || p.number
+             FROM commits c JOIN gh_pull_requests p ON p.merge_commit_sha = c.hash",
+            [],
+            |row| row.get(0),
+        )?;
+        let issues: String = db.query_row(
+            "SELECT group_concat(number || ' ' || comments) FROM gh_issues('https://github.com/owner/name')",
+            [],
+            |row| row.get(0),
+        )?;
+        let missing = db.query_row(
+            "SELECT count(*) FROM gh_issues('https://github.com/owner/missing')",
+            [],
+            |row| row.get::<_, i64>(0),
+        );
+        let elsewhere = db.query_row(
+            "SELECT count(*) FROM gh_issues('https://gitlab.com/owner/name')",
+            [],
+            |row| row.get::<_, i64>(0),
+        );
+        let requests = requests.lock().unwrap().clone();
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!(
+            listed,
+            vec![
+                "1 open draft  -".to_string(),
+                "2 closed feature bug,ui 2024-05-01 12:00:00+00:00".to_string(),
+            ]
+        );
+        assert_eq!(merged, "Merge pull request #2 #2");
+        assert_eq!(issues, "3 7");
+        let missing = missing.unwrap_err().to_string();
+        assert!(
+            missing.contains("answered 404: Not Found, set GH_TOKEN"),
+            "{}",
+            missing
+        );
+        let elsewhere = elsewhere.unwrap_err().to_string();
+        assert!(
+            elsewhere.contains("isn't a repository on github.com"),
+            "{}",
+            elsewhere
+        );
+        // The join reads the pull requests from the listing of the first query
+        assert_eq!(
+            requests,
+            vec![
+                "/repos/owner/name/pulls?state=all&per_page=100",
+                "/repos/owner/name/pulls?page=2",
+                "/repos/owner/name/issues?state=all&per_page=100",
+                "/repos/owner/missing/issues?state=all&per_page=100",
+            ]
+        );
+        assert_eq!(
+            github.slug("ssh://git@github.com:22/owner/name.git"),
+            Some("owner/name".to_string())
+        );
+        assert_eq!(github.slug("https://github.com/owner"), None);
+
+        Ok(())
+    }
+}
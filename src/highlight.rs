@@ -0,0 +1,188 @@
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use std::path::Path;
+use std::sync::OnceLock;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{FontStyle, Theme, ThemeSet};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+
+/// The syntect theme the code is colored with.
+const THEME: &str = "base16-eighties.dark";
+
+/// Lines highlighted per diff or file, the lines after them only get the colors of the diff.
+/// Highlighting takes about 20 milliseconds for every hundred lines, the preview follows the
+/// selected commit while it's moved.
+const MAX_HIGHLIGHTED_LINES: usize = 1_000;
+
+/// Longer lines, usually minified or generated code, aren't highlighted.
+const MAX_HIGHLIGHTED_LINE: usize = 1_000;
+
+const ADDED: Color = Color::Rgb(20, 60, 20);
+const REMOVED: Color = Color::Rgb(75, 20, 20);
+
+/// Syntax highlighting of the diffs and the files shown by the tui, with the syntaxes bundled
+/// with syntect. The syntax of a file is picked by its name.
+pub(crate) struct Highlighter {
+    syntaxes: SyntaxSet,
+    theme: Theme,
+}
+
+impl Highlighter {
+    /// The highlighter, the syntaxes are loaded the first time it's used.
+    pub(crate) fn get() -> &'static Highlighter {
+        static HIGHLIGHTER: OnceLock<Highlighter> = OnceLock::new();
+        HIGHLIGHTER.get_or_init(|| {
+            let mut themes = ThemeSet::load_defaults();
+            Highlighter {
+                syntaxes: SyntaxSet::load_defaults_nonewlines(),
+                theme: themes
+                    .themes
+                    .remove(THEME)
+                    .expect("syntect bundles the theme"),
+            }
+        })
+    }
+
+    /// The lines of the file at `path`.
+    pub(crate) fn file<'t>(
+        &self,
+        path: &str,
+        lines: impl Iterator<Item = &'t str>,
+    ) -> Vec<Line<'static>> {
+        let mut highlighter = HighlightLines::new(self.syntax(path), &self.theme);
+        let lines = lines.enumerate().map(|(i, line)| {
+            let line = line.replace('\t', "    ");
+            match i < MAX_HIGHLIGHTED_LINES {
+                true => Line::from(self.highlight(&mut highlighter, &line, Style::new())),
+                false => Line::raw(line),
+            }
+        });
+        lines.collect()
+    }
+
+    /// The lines of the unified diff `diff`, as `git_diff_text` prints it. The headers are bold,
+    /// added and removed lines have a green and a red background, and the code is highlighted
+    /// by the syntax of the file it is from.
+    pub(crate) fn diff(&self, diff: &str) -> Vec<Line<'static>> {
+        let mut highlighter = None;
+        let mut in_hunk = false;
+        let mut lines = vec![];
+        for (i, line) in diff.lines().enumerate() {
+            let line = line.replace('\t', "    ");
+            if let Some(paths) = line.strip_prefix("diff --git ") {
+                // The new path when the file was renamed, `a/old b/new`
+                let path = paths.rsplit_once(" b/").map_or(paths, |(_, path)| path);
+                highlighter = Some(HighlightLines::new(self.syntax(path), &self.theme));
+                in_hunk = false;
+            }
+            let (marker, background) = match line.chars().next() {
+                Some('@') if line.starts_with("@@") => {
+                    in_hunk = true;
+                    lines.push(Line::styled(line, Style::new().fg(Color::Cyan)));
+                    continue;
+                }
+                _ if !in_hunk => {
+                    let header = Style::new().add_modifier(Modifier::BOLD);
+                    lines.push(Line::styled(line, header));
+                    continue;
+                }
+                Some('+') => ("+", Style::new().bg(ADDED)),
+                Some('-') => ("-", Style::new().bg(REMOVED)),
+                Some(' ') => (" ", Style::new()),
+                // "\ No newline at end of file"
+                _ => {
+                    let note = Style::new().add_modifier(Modifier::DIM);
+                    lines.push(Line::styled(line, note));
+                    continue;
+                }
+            };
+            let code = &line[1..];
+            let mut spans = vec![Span::styled(marker.to_string(), background)];
+            match highlighter.as_mut() {
+                Some(highlighter) if i < MAX_HIGHLIGHTED_LINES => {
+                    spans.extend(self.highlight(highlighter, code, background));
+                }
+                _ => spans.push(Span::styled(code.to_string(), background)),
+            }
+            lines.push(Line::from(spans).style(background));
+        }
+        lines
+    }
+
+    fn syntax(&self, path: &str) -> &SyntaxReference {
+        let path = Path::new(path);
+        let by_extension = path.extension().and_then(|extension| {
+            self.syntaxes
+                .find_syntax_by_extension(&extension.to_string_lossy())
+        });
+        // Makefile, Dockerfile and the like are known by their name
+        let by_name = || {
+            let name = path.file_name()?.to_string_lossy();
+            self.syntaxes.find_syntax_by_extension(&name)
+        };
+        by_extension
+            .or_else(by_name)
+            .unwrap_or_else(|| self.syntaxes.find_syntax_plain_text())
+    }
+
+    /// The spans of `line` in the colors of its syntax, on the background of `base`.
+    fn highlight(
+        &self,
+        highlighter: &mut HighlightLines,
+        line: &str,
+        base: Style,
+    ) -> Vec<Span<'static>> {
+        let highlighted = match line.len() <= MAX_HIGHLIGHTED_LINE {
+            true => highlighter.highlight_line(line, &self.syntaxes).ok(),
+            false => None,
+        };
+        let Some(highlighted) = highlighted else {
+            return vec![Span::styled(line.to_string(), base)];
+        };
+        let spans = highlighted.into_iter().map(|(style, text)| {
+            let fg = style.foreground;
+            let mut span = base.fg(Color::Rgb(fg.r, fg.g, fg.b));
+            for (font, modifier) in [
+                (FontStyle::BOLD, Modifier::BOLD),
+                (FontStyle::ITALIC, Modifier::ITALIC),
+                (FontStyle::UNDERLINE, Modifier::UNDERLINED),
+            ] {
+                if style.font_style.contains(font) {
+                    span = span.add_modifier(modifier);
+                }
+            }
+            Span::styled(text.to_string(), span)
+        });
+        spans.collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::highlight::{Highlighter, ADDED, REMOVED};
+    use ratatui::style::Modifier;
+
+    #[test]
+    fn highlights_diffs_by_the_syntax_of_the_file() {
+        let diff = "diff --git a/src/main.rs b/src/main.rs\n\
+                    index 1111111..2222222 100644\n\
+                    --- a/src/main.rs\n\
+                    +++ b/src/main.rs\n\
+                    @@ -1 +1 @@\n\
+                    -fn main() {}\n\
+                    +fn main() { println!(\"hi\"); }\n\
+                    \\ No newline at end of file\n";
+        let lines = Highlighter::get().diff(diff);
+
+        assert_eq!(lines.len(), 8);
+        assert!(lines[0].style.add_modifier.contains(Modifier::BOLD));
+        assert_eq!(lines[5].style.bg, Some(REMOVED));
+        assert_eq!(lines[6].style.bg, Some(ADDED));
+        assert_eq!(lines[6].spans[0].content, "+");
+        // `fn` and `main` are colored differently
+        let colors = lines[6].spans[1..].iter().map(|span| span.style.fg);
+        assert!(colors.collect::<std::collections::HashSet<_>>().len() > 2);
+        assert_eq!(lines[6].to_string(), "+fn main() { println!(\"hi\"); }");
+        assert!(lines[7].style.add_modifier.contains(Modifier::DIM));
+    }
+}
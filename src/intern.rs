@@ -0,0 +1,43 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Interned strings kept at most, a long-lived cursor starts over when it has seen more.
+const CAPACITY: usize = 100_000;
+
+/// Hands out one shared copy of every distinct string, so the rows that repeat an author or a
+/// path share a single allocation instead of each allocating their own.
+#[derive(Debug, Default)]
+pub(crate) struct Interner {
+    strings: HashSet<Arc<str>>,
+}
+
+impl Interner {
+    pub(crate) fn intern(&mut self, s: &str) -> Arc<str> {
+        if let Some(interned) = self.strings.get(s) {
+            return interned.clone();
+        }
+        if self.strings.len() >= CAPACITY {
+            self.strings.clear();
+        }
+        let interned: Arc<str> = Arc::from(s);
+        self.strings.insert(interned.clone());
+        interned
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::intern::Interner;
+    use std::sync::Arc;
+
+    #[test]
+    fn shares_equal_strings() {
+        let mut interner = Interner::default();
+        let a = interner.intern("someone@example.com");
+        let b = interner.intern(&String::from("someone@example.com"));
+        let c = interner.intern("other@example.com");
+
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(!Arc::ptr_eq(&a, &c));
+    }
+}
@@ -0,0 +1,184 @@
+use crate::CustomError;
+use rusqlite::{Connection, InterruptHandle};
+use std::fmt::{Debug, Formatter};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, Weak};
+
+/// Stops the statements running on the connections the git tables are registered on, it may be
+/// used from any thread.
+///
+/// SQLite stops a statement between two of its steps. The tables and functions also check for an
+/// interrupt between the commits of a revwalk and the lines of a diff, so a statement stuck in a
+/// single step over the whole history stops too. It then fails with `interrupted`.
+#[derive(Clone, Default)]
+pub struct Interrupt {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    /// Bumped by every interrupt, the work started before stops
+    generation: AtomicU64,
+    /// The connections whose tables weren't dropped yet
+    connections: Mutex<Vec<Weak<InterruptHandle>>>,
+}
+
+/// Keeps a connection interruptible, the [`Interrupt`] forgets it once every clone is dropped.
+/// The tables and functions of the connection hold one, SQLite drops them when it's closed.
+#[derive(Clone)]
+pub(crate) struct Registration {
+    _handle: Arc<InterruptHandle>,
+}
+
+impl Debug for Registration {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Registration").finish_non_exhaustive()
+    }
+}
+
+impl Interrupt {
+    /// Interrupts everything running on the connections right now. Statements started afterwards
+    /// run normally.
+    pub fn interrupt(&self) {
+        self.inner.generation.fetch_add(1, Ordering::SeqCst);
+        let mut connections = self.connections();
+        connections.retain(|connection| match connection.upgrade() {
+            Some(connection) => {
+                connection.interrupt();
+                true
+            }
+            None => false,
+        });
+    }
+
+    /// Interrupts `db` too for as long as the returned registration is kept.
+    pub(crate) fn add_connection(&self, db: &Connection) -> Registration {
+        let handle = Arc::new(db.get_interrupt_handle());
+        let mut connections = self.connections();
+        connections.retain(|connection| connection.strong_count() > 0);
+        connections.push(Arc::downgrade(&handle));
+        Registration { _handle: handle }
+    }
+
+    fn connections(&self) -> MutexGuard<'_, Vec<Weak<InterruptHandle>>> {
+        (self.inner.connections.lock()).unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Taken when work starts, it fails the checks once the work is interrupted.
+    pub(crate) fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            inner: self.inner.clone(),
+            generation: self.inner.generation.load(Ordering::SeqCst),
+        }
+    }
+}
+
+impl Debug for Interrupt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Interrupt")
+            .field("generation", &self.inner.generation)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Checked in the loops of long-running work. The default one is never interrupted.
+#[derive(Clone, Default)]
+pub(crate) struct Checkpoint {
+    inner: Arc<Inner>,
+    generation: u64,
+}
+
+impl Checkpoint {
+    pub(crate) fn check(&self) -> Result<(), CustomError> {
+        match self.inner.generation.load(Ordering::SeqCst) == self.generation {
+            true => Ok(()),
+            false => Err(CustomError::Interrupted),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::intern::Interner;
+    use crate::test::{commit_file, temp_repository};
+    use crate::{CustomError, DiffSettings, GitStatsCursor, SqliteGit};
+    use rusqlite::{Connection, ErrorCode};
+
+    #[test]
+    fn stops_walks_and_later_statements_run() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("interrupt")?;
+        let mut head = None;
+        for i in 0..3 {
+            head = Some(commit_file(
+                &repo,
+                "file.txt",
+                &format!("{}\n", i),
+                "change",
+            )?);
+        }
+        let head = head.ok_or("no commits")?.to_string();
+        let git = SqliteGit::new().with_all().repository(&path);
+        let db = Connection::open_in_memory()?;
+        git.register(&db)?;
+        let interrupt = git.interrupt();
+
+        let checkpoint = interrupt.checkpoint();
+        interrupt.interrupt();
+        let stopped = checkpoint.check();
+        let settings = DiffSettings::default();
+        let diff = GitStatsCursor::compute_diff(
+            &repo,
+            &head,
+            &settings,
+            &mut Interner::default(),
+            &checkpoint,
+            None,
+        );
+        // Interrupts the statement from inside the walk, like Ctrl-C in the middle of it
+        let interrupt_from_sql = interrupt.clone();
+        db.create_scalar_function("interrupt", 0, Default::default(), move |_| {
+            interrupt_from_sql.interrupt();
+            Ok(true)
+        })?;
+        let interrupted = db.query_row(
+            "SELECT count(*) FROM commits c JOIN stats s ON s.hash = c.hash WHERE interrupt()",
+            [],
+            |row| row.get::<_, i64>(0),
+        );
+        let later: i64 = db.query_row("SELECT count(*) FROM commits", [], |row| row.get(0))?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert!(stopped.is_err());
+        assert!(matches!(diff, Err(CustomError::Interrupted)));
+        assert!(interrupt.checkpoint().check().is_ok());
+        assert!(matches!(
+            interrupted,
+            Err(rusqlite::Error::SqliteFailure(e, _)) if e.code == ErrorCode::OperationInterrupted
+        ));
+        assert_eq!(later, 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn forgets_closed_connections() -> Result<(), Box<dyn std::error::Error>> {
+        let git = SqliteGit::new().with_all().with_functions();
+        let interrupt = git.interrupt();
+        let open = Connection::open_in_memory()?;
+        git.register(&open)?;
+        for _ in 0..3 {
+            let closed = Connection::open_in_memory()?;
+            git.register(&closed)?;
+        }
+        let registered = interrupt.connections().len();
+        interrupt.interrupt();
+        let kept = interrupt.connections().len();
+        let still_open = open.query_row("SELECT 1", [], |row| row.get::<_, i64>(0))?;
+
+        assert_eq!(registered, 2);
+        assert_eq!(kept, 1);
+        assert_eq!(still_open, 1);
+
+        Ok(())
+    }
+}
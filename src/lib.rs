@@ -0,0 +1,3052 @@
+//! SQLite table-valued functions over git repositories, and the `sqlitegit` command line built
+//! on them.
+//!
+//! The command line is behind the default `cli` feature, build with `--no-default-features` to
+//! get only the tables.
+//!
+//! ```no_run
+//! let db = rusqlite::Connection::open_in_memory()?;
+//! git_introspection::SqliteGit::new()
+//!     .with_commits()
+//!     .with_stats()
+//!     .table_prefix("git_")
+//!     .register(&db)?;
+//! let commits: i64 = db.query_row("SELECT count(*) FROM git_commits", [], |row| row.get(0))?;
+//! # Ok::<(), rusqlite::Error>(())
+//! ```
+//!
+//! # Threads
+//!
+//! A [`SqliteGit`] may register the tables on many connections, e.g. every connection of a pool,
+//! and the connections may run on different threads. The connections share the state of the
+//! builder: open repositories, the stats cache, the warm index, [`Interrupt`] and [`Profiler`].
+//! All of it is behind locks, and a repository handle is only ever used by one statement at a
+//! time, two statements reading the same repository at once each open their own. A connection
+//! itself is used by one thread at a time, as with any rusqlite connection.
+
+#[cfg(feature = "cli")]
+mod arrow_export;
+#[cfg(feature = "cli")]
+pub mod bench;
+mod branches;
+#[cfg(feature = "cli")]
+mod cancel;
+#[cfg(feature = "cli")]
+mod check;
+#[cfg(feature = "cli")]
+pub mod cli;
+#[cfg(feature = "cli")]
+mod commands;
+mod commit_graph;
+#[cfg(feature = "cli")]
+mod complete;
+#[cfg(feature = "cli")]
+mod config;
+mod credentials;
+#[cfg(feature = "cli")]
+mod diagnostic;
+mod diff_ahead;
+mod diff_cache;
+mod fetch;
+mod functions;
+mod git_config;
+#[cfg(feature = "github")]
+mod github;
+#[cfg(feature = "tui")]
+mod highlight;
+mod intern;
+mod interrupt;
+#[cfg(feature = "cli")]
+mod materialize;
+mod message_index;
+mod mirrors;
+mod notes;
+#[cfg(feature = "cli")]
+mod output;
+#[cfg(feature = "cli")]
+mod pager;
+#[cfg(feature = "cli")]
+mod params;
+#[cfg(feature = "cli")]
+mod pipeline;
+mod prefetch;
+mod profile;
+mod progress;
+#[cfg(feature = "cli")]
+mod repl;
+mod repository_cache;
+mod semver;
+#[cfg(feature = "cli")]
+mod serve;
+mod stats_cache;
+#[cfg(feature = "cli")]
+mod sync;
+mod tags;
+#[cfg(feature = "tui")]
+mod tui;
+#[cfg(feature = "cli")]
+mod utils;
+mod warm_index;
+#[cfg(feature = "cli")]
+mod watch;
+mod writable;
+
+#[cfg(feature = "cli")]
+pub use crate::commands::run;
+pub use crate::interrupt::Interrupt;
+pub use crate::message_index::refresh_message_index;
+pub use crate::profile::{Profile, Profiler, Scan};
+pub use crate::progress::Progress;
+
+use chrono::{DateTime, Datelike, TimeZone, Timelike, Utc};
+use git2::{
+    Commit, DiffDelta, DiffHunk, DiffLine, DiffLineType, DiffOptions, Oid, Repository, Time,
+};
+use itertools::Itertools;
+use rusqlite::types::{Type, ValueRef};
+use rusqlite::vtab::{
+    eponymous_only_module, sqlite3_vtab, sqlite3_vtab_cursor, update_module, Context,
+    IndexConstraintOp, IndexInfo, VTab, VTabConnection, VTabCursor, Values,
+};
+use rusqlite::{ffi, Connection};
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::os::raw::c_int;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{debug, debug_span, info, trace, warn};
+
+use crate::diff_ahead::{AheadReader, DiffAhead};
+use crate::diff_cache::DiffCache;
+use crate::intern::Interner;
+use crate::interrupt::{Checkpoint, Registration};
+use crate::mirrors::{is_url, Mirrors};
+use crate::prefetch::Prefetch;
+use crate::repository_cache::{CachedRepository, RepositoryCache};
+use crate::stats_cache::{FileStats, StatsCache};
+use crate::warm_index::WarmIndex;
+use crate::writable::WritableTable;
+
+//  Shared -------------------------------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy)]
+enum RepoRevParam {
+    None = 0,
+    Rev = 1,
+    Repo = 2,
+    Both = 3,
+}
+
+impl From<RepoRevParam> for c_int {
+    fn from(param: RepoRevParam) -> Self {
+        param as c_int
+    }
+}
+
+impl RepoRevParam {
+    /// The bits of an `idx_num` holding the plan, tables may use the others for plans of their own.
+    const MASK: c_int = 0b11;
+
+    /// Number of `filter` arguments the plan takes.
+    fn args(self) -> usize {
+        match self {
+            RepoRevParam::None => 0,
+            RepoRevParam::Rev | RepoRevParam::Repo => 1,
+            RepoRevParam::Both => 2,
+        }
+    }
+}
+
+/// The hidden repository and revision arguments of a `filter` call.
+type RepoRevArgs = (Option<String>, Option<String>);
+
+/// Reads the hidden repository and revision arguments `filter` receives for the plan `idx_num`.
+/// `None` when one of them is NULL, as `= NULL` matches no rows.
+fn repo_rev_args(idx_num: c_int, vals: &[ValueRef]) -> Result<Option<RepoRevArgs>, CustomError> {
+    if vals.iter().any(|v| v.data_type() == Type::Null) {
+        return Ok(None);
+    }
+    let text = |i: usize, name: &str| match vals.get(i).map(|v| (v.data_type(), v.as_str())) {
+        Some((_, Ok(value))) => Ok(value.to_string()),
+        Some((data_type, Err(_))) => Err(CustomError::InvalidArgument(format!(
+            "the {} must be TEXT, got {}",
+            name, data_type
+        ))),
+        None => Err(CustomError::InvalidArgument(format!(
+            "the {} is missing",
+            name
+        ))),
+    };
+    let args = match idx_num & RepoRevParam::MASK {
+        1 => (None, Some(text(0, "revision")?)),
+        2 => (Some(text(0, "repository path")?), None),
+        3 => (
+            Some(text(0, "repository path")?),
+            Some(text(1, "revision")?),
+        ),
+        _ => (None, None),
+    };
+    Ok(Some(args))
+}
+
+/// Calls `visit` with the commits reachable from `rev`, or from HEAD when no revision is given,
+/// until it returns false.
+fn walk_commits(
+    repo: &Repository,
+    rev: Option<&str>,
+    mut visit: impl FnMut(Commit) -> Result<bool, CustomError>,
+) -> Result<(), CustomError> {
+    let mut walk = repo.revwalk()?;
+    match rev {
+        Some(rev) => walk.push(Oid::from_str(rev)?)?,
+        None => walk.push_head()?,
+    }
+    for oid in walk {
+        if !visit(repo.find_commit(oid?)?)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Counts the commits of a walk without a revision against the limit set with
+/// [`SqliteGit::scan_limit`].
+struct ScanLimit {
+    table: &'static str,
+    limit: Option<usize>,
+    walked: usize,
+}
+
+impl ScanLimit {
+    fn new(table: &'static str, limit: Option<usize>) -> ScanLimit {
+        ScanLimit {
+            table,
+            limit,
+            walked: 0,
+        }
+    }
+
+    /// Counts the next commit of the walk, false with a warning when it's past the limit.
+    fn allows_next(&mut self) -> bool {
+        if self.limit.is_some_and(|limit| self.walked >= limit) {
+            warn!(
+                table = self.table,
+                limit = self.walked,
+                "stopped at the scan limit, older commits are left out, pass a revision to read them"
+            );
+            return false;
+        }
+        self.walked += 1;
+        true
+    }
+}
+
+/// Caps the memory libgit2 keeps parsed objects in, across every repository of the process. A
+/// scan over the whole history reads every commit, the cache fills up to this limit, 256 MiB
+/// unless it's set.
+pub fn set_object_cache_limit(bytes: usize) {
+    libgit2_sys::init();
+    // Safe as the option takes a single size argument
+    unsafe {
+        libgit2_sys::git_libgit2_opts(
+            libgit2_sys::GIT_OPT_SET_CACHE_MAX_SIZE as c_int,
+            bytes as isize,
+        );
+    }
+}
+
+/// Caps the size of a single tree libgit2 keeps parsed, it only keeps trees up to 4 KiB unless
+/// it's set. Root trees of big repositories are larger, and a scan of the history diffs each of
+/// them twice: as the tree of a commit and as the tree of its child's parent. Like
+/// [`set_object_cache_limit`] it applies to every repository of the process.
+pub fn set_tree_cache_limit(bytes: usize) {
+    libgit2_sys::init();
+    // Safe as the option takes an object type and a size
+    unsafe {
+        libgit2_sys::git_libgit2_opts(
+            libgit2_sys::GIT_OPT_SET_CACHE_OBJECT_LIMIT as c_int,
+            libgit2_sys::GIT_OBJECT_TREE,
+            bytes,
+        );
+    }
+}
+
+/// Git timestamps out of chrono's range end up at the epoch instead of failing the query.
+fn to_utc(time: Time) -> DateTime<Utc> {
+    Utc.timestamp_opt(time.seconds(), 0)
+        .single()
+        .unwrap_or_default()
+}
+
+/// Length of a timestamp cell, `2022-06-23 16:00:00+00:00`.
+const TIMESTAMP_LEN: usize = 25;
+
+/// Sets `when` as the result of a cell, formatted like rusqlite formats a `DateTime<Utc>` but
+/// without allocating. Cells are read rows times columns times, some more than once.
+fn set_timestamp(ctx: &mut Context, when: &DateTime<Utc>) -> rusqlite::Result<()> {
+    let mut buf = [0; TIMESTAMP_LEN];
+    match format_timestamp(when, &mut buf) {
+        Some(text) => ctx.set_result(&text),
+        None => ctx.set_result(when),
+    }
+}
+
+/// Formats `when` into `buf`, None for years with more digits or a sign and for fractional
+/// seconds, those are left to rusqlite.
+fn format_timestamp<'a>(when: &DateTime<Utc>, buf: &'a mut [u8; TIMESTAMP_LEN]) -> Option<&'a str> {
+    if !(0..=9999).contains(&when.year()) || when.nanosecond() != 0 {
+        return None;
+    }
+    let mut out = &mut buf[..];
+    std::io::Write::write_fmt(
+        &mut out,
+        format_args!(
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}+00:00",
+            when.year(),
+            when.month(),
+            when.day(),
+            when.hour(),
+            when.minute(),
+            when.second()
+        ),
+    )
+    .ok()?;
+    std::str::from_utf8(buf).ok()
+}
+
+/// Defaults of the git tables, passed as the module's `Aux` when the tables are registered.
+#[derive(Debug, Clone, Default)]
+pub struct TableConfig {
+    /// Repository read when a query doesn't pass one, the current directory when unset
+    pub repository: Option<PathBuf>,
+    /// How `stats` diffs a commit against its parent
+    pub diff: DiffSettings,
+    /// Shared by every clone, so the tables and functions of a connection open each repository
+    /// once
+    repositories: RepositoryCache,
+    /// The diffs the cursors computed last, shared by every clone
+    diffs: DiffCache,
+    /// Diffs the commits walked by `commits` ahead of `stats`, shared by every clone
+    ahead: DiffAhead,
+    /// Where `stats` keeps the stats it computed, when enabled
+    stats_cache: Option<StatsCache>,
+    /// Keeps the connection the tables are registered on interruptible until they are dropped
+    _registration: Option<Registration>,
+    /// Largest file `git_blob_content` reads, [`DEFAULT_MAX_BLOB_SIZE`] when unset
+    max_blob_size: Option<u64>,
+    /// Largest diff `git_diff_text` prints, [`DEFAULT_MAX_DIFF_SIZE`] when unset
+    max_diff_size: Option<u64>,
+    /// The commits of the default repository read ahead of the queries, when enabled
+    warm_index: Option<WarmIndex>,
+    /// Commits walked from HEAD when a query passes no revision, all of them when unset
+    scan_limit: Option<usize>,
+    /// Stops the revwalks and diffs of the connections the tables are registered on
+    interrupt: Interrupt,
+    /// Records the scans of the tables while a statement is profiled
+    profiler: Profiler,
+    /// Counts the commits walked and the diffs computed
+    progress: Progress,
+    /// Rejects the repository arguments, only the default repository can be read
+    pinned: bool,
+    /// The mirrors of the remote repositories that are read by their URL, when enabled
+    mirrors: Option<Mirrors>,
+    /// The API the `gh_*` tables read, when enabled
+    #[cfg(feature = "github")]
+    github: Option<github::GitHub>,
+}
+
+// The tables of every connection the builder registers them on share its state, and rusqlite
+// connections move between threads
+const _: () = {
+    const fn shared_between_threads<T: Send + Sync>() {}
+    shared_between_threads::<TableConfig>();
+    shared_between_threads::<SqliteGit>();
+};
+
+impl TableConfig {
+    fn open_repository(&self, repo_param: Option<&str>) -> Result<CachedRepository, CustomError> {
+        if let (true, Some(repo)) = (self.pinned, repo_param) {
+            return Err(CustomError::InvalidArgument(format!(
+                "only the default repository can be read, not {}",
+                repo
+            )));
+        }
+        if let (Some(url), Some(mirrors)) = (repo_param.filter(|repo| is_url(repo)), &self.mirrors)
+        {
+            let mirror = mirrors.open(url, &self.interrupt.checkpoint())?;
+            return self.repositories.open(&mirror);
+        }
+        let path = match (repo_param, &self.repository) {
+            (Some(path), _) => Path::new(path),
+            (None, Some(path)) => path.as_path(),
+            (None, None) => Path::new("."),
+        };
+        self.repositories.open(path)
+    }
+
+    fn max_blob_size(&self) -> u64 {
+        self.max_blob_size.unwrap_or(DEFAULT_MAX_BLOB_SIZE)
+    }
+
+    fn max_diff_size(&self) -> u64 {
+        self.max_diff_size.unwrap_or(DEFAULT_MAX_DIFF_SIZE)
+    }
+}
+
+/// Largest file `git_blob_content` reads unless configured otherwise, 64 MiB.
+pub const DEFAULT_MAX_BLOB_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Largest diff `git_diff_text` prints unless configured otherwise, 64 MiB.
+pub const DEFAULT_MAX_DIFF_SIZE: u64 = 64 * 1024 * 1024;
+
+/// The diff options `stats` counts added and deleted lines with.
+#[derive(Debug, Clone)]
+pub struct DiffSettings {
+    pub context_lines: u32,
+    pub ignore_whitespace: bool,
+    pub ignore_blank_lines: bool,
+    pub ignore_filemode: bool,
+    pub ignore_submodules: bool,
+}
+
+impl Default for DiffSettings {
+    fn default() -> Self {
+        DiffSettings {
+            context_lines: 0,
+            ignore_whitespace: true,
+            ignore_blank_lines: true,
+            ignore_filemode: true,
+            ignore_submodules: true,
+        }
+    }
+}
+
+impl DiffSettings {
+    /// Identifies the settings in the stats cache, stats counted with other settings differ.
+    fn cache_key(&self) -> String {
+        format!(
+            "context_lines={} ignore_whitespace={} ignore_blank_lines={} ignore_filemode={} ignore_submodules={}",
+            self.context_lines,
+            self.ignore_whitespace,
+            self.ignore_blank_lines,
+            self.ignore_filemode,
+            self.ignore_submodules
+        )
+    }
+
+    fn to_diff_options(&self) -> DiffOptions {
+        let mut diff_options = DiffOptions::new();
+        diff_options
+            .ignore_blank_lines(self.ignore_blank_lines)
+            .ignore_filemode(self.ignore_filemode)
+            .context_lines(self.context_lines)
+            .ignore_whitespace(self.ignore_whitespace)
+            .ignore_submodules(self.ignore_submodules)
+            .ignore_whitespace_eol(self.ignore_whitespace)
+            .ignore_whitespace_change(self.ignore_whitespace);
+        diff_options
+    }
+}
+
+#[derive(Debug)]
+pub enum CustomError {
+    Git(git2::Error),
+    Sqlite(rusqlite::Error),
+    Io(std::io::Error),
+    #[cfg(feature = "cli")]
+    Readline(rustyline::error::ReadlineError),
+    #[cfg(feature = "cli")]
+    Arrow(arrow_schema::ArrowError),
+    #[cfg(feature = "cli")]
+    Parquet(parquet::errors::ParquetError),
+    #[cfg(feature = "cli")]
+    Notify(notify::Error),
+    #[cfg(feature = "cli")]
+    Config(PathBuf, toml::de::Error),
+    InvalidArgument(String),
+    /// The statement was stopped with [`Interrupt::interrupt`]
+    Interrupted,
+    /// An error of a statement with the spot it's about and hints at how to fix it
+    #[cfg(feature = "cli")]
+    Diagnostic(Box<crate::diagnostic::Diagnostic>),
+    /// A request of the `gh_*` tables that GitHub didn't answer with the listing
+    #[cfg(feature = "github")]
+    GitHub(String),
+}
+
+impl Display for CustomError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CustomError::Git(g) => write!(f, "{}", g.message()),
+            CustomError::Sqlite(s) => write!(f, "{}", s),
+            CustomError::Io(i) => write!(f, "{}", i),
+            #[cfg(feature = "cli")]
+            CustomError::Readline(r) => write!(f, "{}", r),
+            #[cfg(feature = "cli")]
+            CustomError::Arrow(a) => write!(f, "{}", a),
+            #[cfg(feature = "cli")]
+            CustomError::Parquet(p) => write!(f, "{}", p),
+            #[cfg(feature = "cli")]
+            CustomError::Notify(n) => write!(f, "{}", n),
+            #[cfg(feature = "cli")]
+            CustomError::Config(path, c) => write!(f, "{}: {}", path.display(), c),
+            CustomError::InvalidArgument(message) => write!(f, "{}", message),
+            CustomError::Interrupted => f.write_str("interrupted"),
+            #[cfg(feature = "cli")]
+            CustomError::Diagnostic(d) => write!(f, "{}", d),
+            #[cfg(feature = "github")]
+            CustomError::GitHub(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for CustomError {}
+
+impl From<CustomError> for rusqlite::Error {
+    fn from(e: CustomError) -> Self {
+        match e {
+            CustomError::Git(g) => sqlite_failure(git_error_code(&g), g.message()),
+            CustomError::Sqlite(s) => s,
+            CustomError::Io(i) => sqlite_failure(ffi::SQLITE_IOERR, &i.to_string()),
+            #[cfg(feature = "cli")]
+            CustomError::Readline(r) => rusqlite::Error::ModuleError(r.to_string()),
+            #[cfg(feature = "cli")]
+            CustomError::Arrow(a) => rusqlite::Error::ModuleError(a.to_string()),
+            #[cfg(feature = "cli")]
+            CustomError::Parquet(p) => rusqlite::Error::ModuleError(p.to_string()),
+            #[cfg(feature = "cli")]
+            CustomError::Notify(n) => rusqlite::Error::ModuleError(n.to_string()),
+            #[cfg(feature = "cli")]
+            CustomError::Config(path, c) => {
+                rusqlite::Error::ModuleError(format!("{}: {}", path.display(), c))
+            }
+            CustomError::InvalidArgument(message) => sqlite_failure(ffi::SQLITE_MISMATCH, &message),
+            CustomError::Interrupted => sqlite_failure(ffi::SQLITE_INTERRUPT, "interrupted"),
+            #[cfg(feature = "cli")]
+            CustomError::Diagnostic(d) => rusqlite::Error::ModuleError(d.to_string()),
+            #[cfg(feature = "github")]
+            CustomError::GitHub(message) => sqlite_failure(ffi::SQLITE_IOERR, &message),
+        }
+    }
+}
+
+fn sqlite_failure(code: c_int, message: &str) -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(ffi::Error::new(code), Some(message.to_string()))
+}
+
+/// The SQLite result code a failed git operation is reported with.
+fn git_error_code(e: &git2::Error) -> c_int {
+    match (e.code(), e.class()) {
+        (git2::ErrorCode::Locked, _) => ffi::SQLITE_BUSY,
+        (git2::ErrorCode::Auth | git2::ErrorCode::Certificate, _) => ffi::SQLITE_AUTH,
+        (
+            git2::ErrorCode::NotFound,
+            git2::ErrorClass::Repository | git2::ErrorClass::Os | git2::ErrorClass::Filesystem,
+        ) => ffi::SQLITE_CANTOPEN,
+        (_, git2::ErrorClass::Os | git2::ErrorClass::Filesystem) => ffi::SQLITE_IOERR,
+        (_, git2::ErrorClass::Zlib) => ffi::SQLITE_CORRUPT,
+        _ => ffi::SQLITE_ERROR,
+    }
+}
+
+impl From<rusqlite::Error> for CustomError {
+    fn from(e: rusqlite::Error) -> Self {
+        match e {
+            // The message of a statement SQLite rejects repeats the whole statement, the
+            // diagnostic points at the mistake instead
+            rusqlite::Error::SqlInputError { error, msg, .. } => {
+                CustomError::Sqlite(rusqlite::Error::SqliteFailure(error, Some(msg)))
+            }
+            e => CustomError::Sqlite(e),
+        }
+    }
+}
+
+impl From<git2::Error> for CustomError {
+    fn from(e: git2::Error) -> Self {
+        CustomError::Git(e)
+    }
+}
+
+impl From<std::io::Error> for CustomError {
+    fn from(e: std::io::Error) -> Self {
+        CustomError::Io(e)
+    }
+}
+
+#[cfg(feature = "cli")]
+impl From<rustyline::error::ReadlineError> for CustomError {
+    fn from(e: rustyline::error::ReadlineError) -> Self {
+        CustomError::Readline(e)
+    }
+}
+
+#[cfg(feature = "cli")]
+impl From<arrow_schema::ArrowError> for CustomError {
+    fn from(e: arrow_schema::ArrowError) -> Self {
+        CustomError::Arrow(e)
+    }
+}
+
+#[cfg(feature = "cli")]
+impl From<parquet::errors::ParquetError> for CustomError {
+    fn from(e: parquet::errors::ParquetError) -> Self {
+        CustomError::Parquet(e)
+    }
+}
+
+#[cfg(feature = "cli")]
+impl From<notify::Error> for CustomError {
+    fn from(e: notify::Error) -> Self {
+        CustomError::Notify(e)
+    }
+}
+
+fn trace_index_info(table: &str, info: &IndexInfo) {
+    for constraint in info.constraints() {
+        trace!(
+            table,
+            column = constraint.column(),
+            operator = ?constraint.operator(),
+            usable = constraint.is_usable(),
+            "constraint"
+        );
+    }
+}
+
+/// Estimated number of commits in a history, there's no cheap way to count them up front.
+const WALK_ROWS: f64 = 10_000.0;
+
+/// Estimated number of files a commit changes.
+const DIFF_ROWS: f64 = 10.0;
+
+/// Estimated number of commits walked from a revision before reaching a merge.
+const MERGE_DISTANCE: f64 = 10.0;
+
+/// Passes the usable `=` constraints on the hidden repository and revision columns to `filter`,
+/// the repository first, and returns the plan for them.
+fn plan_repo_rev(
+    table: &str,
+    info: &mut IndexInfo,
+    repo_column: c_int,
+    rev_column: c_int,
+) -> RepoRevParam {
+    trace_index_info(table, info);
+    let (mut repo, mut rev) = (None, None);
+    for (i, constraint) in info.constraints().enumerate() {
+        if !constraint.is_usable()
+            || constraint.operator() != IndexConstraintOp::SQLITE_INDEX_CONSTRAINT_EQ
+        {
+            continue;
+        }
+        if constraint.column() == repo_column {
+            repo = Some(i);
+        } else if constraint.column() == rev_column {
+            rev = Some(i);
+        }
+    }
+
+    for (argv_index, i) in [repo, rev].into_iter().flatten().enumerate() {
+        let mut usage = info.constraint_usage(i);
+        usage.set_argv_index(argv_index as c_int + 1);
+        usage.set_omit(true);
+    }
+    let plan = match (repo, rev) {
+        (Some(_), Some(_)) => RepoRevParam::Both,
+        (Some(_), None) => RepoRevParam::Repo,
+        (None, Some(_)) => RepoRevParam::Rev,
+        (None, None) => RepoRevParam::None,
+    };
+    debug!(table, ?plan, "best_index");
+    info.set_idx_num(plan.into());
+    plan
+}
+
+// COmmits --------------------------------------------------------------------------------------------------
+
+#[repr(C)]
+struct GitCommit {
+    base: sqlite3_vtab,
+    config: TableConfig,
+}
+
+unsafe impl<'a> VTab<'a> for GitCommit {
+    type Aux = TableConfig;
+    type Cursor = GitCommitCursor;
+
+    fn connect(
+        _db: &mut VTabConnection,
+        aux: Option<&Self::Aux>,
+        _args: &[&[u8]],
+    ) -> rusqlite::Result<(String, Self)> {
+        let sql = r#"
+        create table commits (
+            hash            text primary key,
+            message         text,
+            author_name     text,
+            author_email    text,
+            author_when     DATETIME,
+            committer_name  text,
+            committer_email text,
+            committer_when  DATETIME,
+            is_merge        bool,
+            parent_1        text,
+            parent_2        text,
+            repository      hidden,
+            ref             hidden,
+            sample          hidden
+        ) WITHOUT ROWID
+        "#;
+        Ok((
+            sql.to_owned(),
+            GitCommit {
+                base: sqlite3_vtab::default(),
+                config: aux.cloned().unwrap_or_default(),
+            },
+        ))
+    }
+
+    fn best_index(&self, info: &mut IndexInfo) -> rusqlite::Result<()> {
+        let plan = plan_repo_rev("commits", info, 11, 12);
+        let columns = CommitColumns::from_columns_used(info.col_used());
+        let sampled = Sample::plan(info, 13, plan.args());
+        let sample_plan = if sampled { Sample::PLANNED } else { 0 };
+        info.set_idx_num(c_int::from(plan) | columns.to_idx_num() | sample_plan);
+        // A revision looks up a single commit
+        let rows = match plan {
+            RepoRevParam::Rev | RepoRevParam::Both => 1.0,
+            RepoRevParam::Repo | RepoRevParam::None => WALK_ROWS,
+        };
+        info.set_estimated_cost(rows);
+        info.set_estimated_rows(rows as i64);
+
+        Ok(())
+    }
+
+    fn open(&mut self) -> rusqlite::Result<GitCommitCursor> {
+        Ok(GitCommitCursor {
+            base: sqlite3_vtab_cursor::default(),
+            config: self.config.clone(),
+            rev_param: None,
+            repo_param: None,
+            sample: None,
+            walk: Prefetch::ready(vec![]),
+            current: None,
+            scan: None,
+            interner: Interner::default(),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CommitShadow {
+    hash: String,
+    message: Option<String>,
+    author_name: Option<Arc<str>>,
+    author_email: Option<Arc<str>>,
+    author_when: DateTime<Utc>,
+    committer_name: Option<Arc<str>>,
+    committer_email: Option<Arc<str>>,
+    committer_when: DateTime<Utc>,
+    is_merge: bool,
+    parent_1: Option<String>,
+    parent_2: Option<String>,
+}
+
+impl CommitShadow {
+    /// Names and emails are interned, they repeat across the commits of a walk.
+    fn new(c: &Commit, interner: &mut Interner) -> Self {
+        CommitShadow::with_columns(c, interner, CommitColumns::ALL)
+    }
+
+    /// Only decodes the message and the signatures when `columns` reads them, the others are
+    /// left empty.
+    fn with_columns(c: &Commit, interner: &mut Interner, columns: CommitColumns) -> Self {
+        let mut commit = CommitShadow {
+            hash: c.id().to_string(),
+            message: None,
+            author_name: None,
+            author_email: None,
+            author_when: DateTime::default(),
+            committer_name: None,
+            committer_email: None,
+            committer_when: DateTime::default(),
+            is_merge: c.parent_count() == 2,
+            parent_1: c.parent_id(0).ok().map(|parent| parent.to_string()),
+            parent_2: c.parent_id(1).ok().map(|parent| parent.to_string()),
+        };
+        if columns.message {
+            commit.message = c.message().map(|msg| msg.to_string());
+        }
+        if columns.signatures {
+            let (author, committer) = (c.author(), c.committer());
+            commit.author_name = author.name().map(|name| interner.intern(name));
+            commit.author_email = author.email().map(|email| interner.intern(email));
+            commit.author_when = to_utc(author.when());
+            commit.committer_name = committer.name().map(|name| interner.intern(name));
+            commit.committer_email = committer.email().map(|email| interner.intern(email));
+            commit.committer_when = to_utc(committer.when());
+        }
+        commit
+    }
+}
+
+/// Which of the costly columns of `commits` a statement reads, passed from `best_index` to
+/// `filter` in the bits of `idx_num` above the [`RepoRevParam`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CommitColumns {
+    message: bool,
+    /// The names, emails and times of the author and the committer
+    signatures: bool,
+}
+
+impl CommitColumns {
+    const ALL: CommitColumns = CommitColumns {
+        message: true,
+        signatures: true,
+    };
+    const MESSAGE: c_int = 1 << 2;
+    const SIGNATURES: c_int = 1 << 3;
+
+    fn from_columns_used(used: u64) -> Self {
+        CommitColumns {
+            message: used & 1 << 1 != 0,
+            signatures: used & 0b1111_1100 != 0,
+        }
+    }
+
+    fn to_idx_num(self) -> c_int {
+        (if self.message { Self::MESSAGE } else { 0 })
+            | (if self.signatures { Self::SIGNATURES } else { 0 })
+    }
+
+    fn from_idx_num(idx_num: c_int) -> Self {
+        CommitColumns {
+            message: idx_num & Self::MESSAGE != 0,
+            signatures: idx_num & Self::SIGNATURES != 0,
+        }
+    }
+}
+
+/// The commits a walk of `commits` keeps when the hidden `sample` column is constrained, to
+/// estimate the churn of histories too long to diff in full. Sums over a sample scale up by
+/// `sample` for every Nth commit and by `1 / sample` for a fraction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Sample {
+    /// `sample = N`, every Nth commit of the walk starting with the first
+    Every(u64),
+    /// `sample = 0.01`, the commits whose hash falls in that fraction of all hashes, the same
+    /// ones on every run
+    Fraction(f64),
+}
+
+impl Sample {
+    /// The bit of an `idx_num` set when `filter` receives the sample after the repository and
+    /// revision.
+    const PLANNED: c_int = 1 << 4;
+
+    /// Passes a usable `=` constraint on the `sample` column to `filter` after the `args`
+    /// repository and revision arguments, returns whether there is one.
+    fn plan(info: &mut IndexInfo, column: c_int, args: usize) -> bool {
+        let constraint = info.constraints().position(|constraint| {
+            constraint.is_usable()
+                && constraint.column() == column
+                && constraint.operator() == IndexConstraintOp::SQLITE_INDEX_CONSTRAINT_EQ
+        });
+        if let Some(i) = constraint {
+            let mut usage = info.constraint_usage(i);
+            usage.set_argv_index(args as c_int + 1);
+            usage.set_omit(true);
+        }
+        constraint.is_some()
+    }
+
+    fn from_arg(value: &ValueRef) -> Result<Sample, CustomError> {
+        match value {
+            ValueRef::Integer(n) if *n >= 1 => Ok(Sample::Every(*n as u64)),
+            ValueRef::Real(fraction) if *fraction > 0.0 && *fraction <= 1.0 => {
+                Ok(Sample::Fraction(*fraction))
+            }
+            other => Err(CustomError::InvalidArgument(format!(
+                "the sample must be a positive INTEGER or a REAL in (0, 1], got {:?}",
+                other
+            ))),
+        }
+    }
+
+    /// Whether the commit `oid`, the `walked`th of the walk counting from 0, is in the sample.
+    fn keeps(self, walked: u64, oid: Oid) -> bool {
+        match self {
+            Sample::Every(n) => walked.is_multiple_of(n),
+            Sample::Fraction(fraction) => {
+                let mut prefix = [0; 8];
+                prefix.copy_from_slice(&oid.as_bytes()[..8]);
+                (u64::from_be_bytes(prefix) as f64) < fraction * u64::MAX as f64
+            }
+        }
+    }
+}
+
+#[repr(C)]
+struct GitCommitCursor {
+    base: sqlite3_vtab_cursor,
+    config: TableConfig,
+    rev_param: Option<String>,
+    repo_param: Option<String>,
+    sample: Option<Sample>,
+    walk: Prefetch<CommitShadow>,
+    current: Option<CommitShadow>,
+    /// The scan of `--profile` the rows count towards
+    scan: Option<usize>,
+    /// Shared by the point lookups of a join, a walk interns on its own thread
+    interner: Interner,
+}
+
+impl GitCommitCursor {
+    fn init(&mut self, idx_num: c_int, vals: &[ValueRef]) -> Result<(), CustomError> {
+        let Some((repo_param, rev_param)) = repo_rev_args(idx_num, vals)? else {
+            self.walk = Prefetch::ready(vec![]);
+            self.current = None;
+            return Ok(());
+        };
+        let repo = self.config.open_repository(repo_param.as_deref())?;
+        let columns = CommitColumns::from_idx_num(idx_num);
+        let sample = match vals.last() {
+            Some(value) if idx_num & Sample::PLANNED != 0 => Some(Sample::from_arg(value)?),
+            _ => None,
+        };
+        let sampled = move |walked: u64, oid: Oid| sample.is_none_or(|s| s.keeps(walked, oid));
+        let warmed =
+            (self.config.warm_index.as_ref()).and_then(|index| index.get(&repo, &self.config));
+        let mut ahead = self.config.ahead.feed(&repo, &self.config.diff);
+        self.walk = match (&rev_param, warmed) {
+            // The history a range like `v1.0..main` excludes is hidden, never walked
+            (Some(range), _) if range.contains("..") => {
+                let checkpoint = self.config.interrupt.checkpoint();
+                let progress = self.config.progress.clone();
+                let range = range.clone();
+                Prefetch::spawn(move |emit| {
+                    let start = Instant::now();
+                    let Some(walk) = functions::walk_range(&repo, &range)? else {
+                        return Ok(());
+                    };
+                    let mut interner = Interner::default();
+                    let mut commits = 0;
+                    for (walked, oid) in walk.enumerate() {
+                        checkpoint.check()?;
+                        let oid = oid?;
+                        progress.walked();
+                        if !sampled(walked as u64, oid) {
+                            continue;
+                        }
+                        let commit = repo.find_commit(oid)?;
+                        commits += 1;
+                        ahead.push(oid);
+                        if !emit(CommitShadow::with_columns(&commit, &mut interner, columns)) {
+                            break;
+                        }
+                    }
+                    info!(commits, range, elapsed = ?start.elapsed(), "revwalk");
+                    Ok(())
+                })
+            }
+            // Only the commit itself is returned for a revision, there's no need to walk or to
+            // sample
+            (Some(rev), warmed) => {
+                let oid = Oid::from_str(rev)?;
+                let commit = match warmed.as_ref().and_then(|warmed| warmed.commit(oid)) {
+                    Some(commit) => commit.clone(),
+                    None => {
+                        let commit = repo.find_commit(oid)?;
+                        CommitShadow::with_columns(&commit, &mut self.interner, columns)
+                    }
+                };
+                Prefetch::ready(vec![commit])
+            }
+            (None, Some(warmed)) => {
+                let mut scan_limit = ScanLimit::new("commits", self.config.scan_limit);
+                Prefetch::spawn(move |emit| {
+                    for (walked, commit) in warmed.commits.iter().enumerate() {
+                        if !scan_limit.allows_next() {
+                            break;
+                        }
+                        let oid = Oid::from_str(&commit.hash)?;
+                        if !sampled(walked as u64, oid) {
+                            continue;
+                        }
+                        ahead.push(oid);
+                        if !emit(commit.clone()) {
+                            break;
+                        }
+                    }
+                    Ok(())
+                })
+            }
+            (None, None) => {
+                let checkpoint = self.config.interrupt.checkpoint();
+                let progress = self.config.progress.clone();
+                let mut scan_limit = ScanLimit::new("commits", self.config.scan_limit);
+                Prefetch::spawn(move |emit| {
+                    let start = Instant::now();
+                    let mut interner = Interner::default();
+                    let (mut walked, mut commits) = (0, 0);
+                    walk_commits(&repo, None, |commit| {
+                        checkpoint.check()?;
+                        if !scan_limit.allows_next() {
+                            return Ok(false);
+                        }
+                        walked += 1;
+                        progress.walked();
+                        if !sampled(walked - 1, commit.id()) {
+                            return Ok(true);
+                        }
+                        commits += 1;
+                        ahead.push(commit.id());
+                        Ok(emit(CommitShadow::with_columns(
+                            &commit,
+                            &mut interner,
+                            columns,
+                        )))
+                    })?;
+                    info!(commits, elapsed = ?start.elapsed(), "revwalk");
+                    Ok(())
+                })
+            }
+        };
+        self.current = self.walk.next()?;
+        self.repo_param = repo_param;
+        self.rev_param = rev_param;
+        self.sample = sample;
+        Ok(())
+    }
+}
+
+unsafe impl VTabCursor for GitCommitCursor {
+    fn filter(
+        &mut self,
+        idx_num: c_int,
+        _idx_str: Option<&str>,
+        args: &Values<'_>,
+    ) -> rusqlite::Result<()> {
+        let vals = args.iter().collect_vec();
+        let _span = debug_span!("filter", table = "commits", idx_num, ?vals).entered();
+        let start = Instant::now();
+        self.init(idx_num, &vals)?;
+        let row = self.current.is_some();
+        self.scan = (self.config.profiler).filter("commits", &vals, start.elapsed(), row);
+
+        Ok(())
+    }
+
+    fn next(&mut self) -> rusqlite::Result<()> {
+        let start = Instant::now();
+        self.current = self.walk.next()?;
+        let row = self.current.is_some();
+        self.config.profiler.next(self.scan, start.elapsed(), row);
+
+        Ok(())
+    }
+
+    fn eof(&self) -> bool {
+        self.current.is_none()
+    }
+
+    /*
+    create table commits (
+            hash            text,
+            message         text,
+            author_name     text,
+            author_email    text,
+            author_when     DATETIME,
+            committer_name  text,
+            committer_email text,
+            committer_when  DATETIME,
+            is_merge        bool,
+            parent_1        text,
+            parent_2        text,
+            repository      hidden,
+            ref             hidden,
+            sample          hidden
+        ) WITHOUT ROWID
+
+     */
+    fn column(&self, ctx: &mut Context, i: c_int) -> rusqlite::Result<()> {
+        let Some(current_commit) = &self.current else {
+            return Ok(());
+        };
+        match i {
+            0 => ctx.set_result(&current_commit.hash),
+            1 => ctx.set_result(&current_commit.message),
+            2 => ctx.set_result(&current_commit.author_name),
+            3 => ctx.set_result(&current_commit.author_email),
+            4 => set_timestamp(ctx, &current_commit.author_when),
+            5 => ctx.set_result(&current_commit.committer_name),
+            6 => ctx.set_result(&current_commit.committer_email),
+            7 => set_timestamp(ctx, &current_commit.committer_when),
+            8 => ctx.set_result(&current_commit.is_merge),
+            9 => ctx.set_result(&current_commit.parent_1),
+            10 => ctx.set_result(&current_commit.parent_2),
+            11 => ctx.set_result(&self.repo_param),
+            12 => ctx.set_result(&self.rev_param),
+            13 => match self.sample {
+                Some(Sample::Every(n)) => ctx.set_result(&(n as i64)),
+                Some(Sample::Fraction(fraction)) => ctx.set_result(&fraction),
+                None => ctx.set_result(&Option::<i64>::None),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    fn rowid(&self) -> rusqlite::Result<i64> {
+        Ok(1)
+    }
+}
+//  Merges -----------------------------------------------------------------------------------------------
+
+#[repr(C)]
+struct GitCommitMerge {
+    base: sqlite3_vtab,
+    config: TableConfig,
+}
+
+unsafe impl<'a> VTab<'a> for GitCommitMerge {
+    type Aux = TableConfig;
+    type Cursor = GitCommitMergeCursor;
+
+    fn connect(
+        _db: &mut VTabConnection,
+        aux: Option<&Self::Aux>,
+        _args: &[&[u8]],
+    ) -> rusqlite::Result<(String, Self)> {
+        let sql = r#"
+        create table merges (
+            hash            text primary key,
+            message         text,
+            author_name     text,
+            author_email    text,
+            author_when     DATETIME,
+            committer_name  text,
+            committer_email text,
+            committer_when  DATETIME,
+            parent_1        text,
+            parent_2        text,
+            time_to_merge   INTEGER,
+            time_of_first_commit DATETIME,
+            repository      hidden,
+            ref             hidden
+        ) WITHOUT ROWID
+        "#;
+        Ok((
+            sql.to_owned(),
+            GitCommitMerge {
+                base: sqlite3_vtab::default(),
+                config: aux.cloned().unwrap_or_default(),
+            },
+        ))
+    }
+
+    fn best_index(&self, info: &mut IndexInfo) -> rusqlite::Result<()> {
+        let plan = plan_repo_rev("merges", info, 12, 13);
+        // A revision returns only the first merge from it, the walk stops there
+        let (cost, rows) = match plan {
+            RepoRevParam::Rev | RepoRevParam::Both => (MERGE_DISTANCE, 1),
+            RepoRevParam::Repo | RepoRevParam::None => (WALK_ROWS, (WALK_ROWS / 10.0) as i64),
+        };
+        info.set_estimated_cost(cost);
+        info.set_estimated_rows(rows);
+
+        Ok(())
+    }
+
+    fn open(&mut self) -> rusqlite::Result<GitCommitMergeCursor> {
+        Ok(GitCommitMergeCursor {
+            base: sqlite3_vtab_cursor::default(),
+            config: self.config.clone(),
+            rev_param: None,
+            repo_param: None,
+            walk: Prefetch::ready(vec![]),
+            current: None,
+            scan: None,
+            interner: Interner::default(),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct CommitMergeShadow {
+    hash: String,
+    message: Option<String>,
+    author_name: Option<Arc<str>>,
+    author_email: Option<Arc<str>>,
+    author_when: DateTime<Utc>,
+    committer_name: Option<Arc<str>>,
+    committer_email: Option<Arc<str>>,
+    committer_when: DateTime<Utc>,
+    time_to_merge: i64,
+    parent_1: Option<String>,
+    parent_2: Option<String>,
+    time_of_first_commit: DateTime<Utc>,
+}
+
+#[repr(C)]
+struct GitCommitMergeCursor {
+    base: sqlite3_vtab_cursor,
+    config: TableConfig,
+    rev_param: Option<String>,
+    repo_param: Option<String>,
+    walk: Prefetch<CommitMergeShadow>,
+    current: Option<CommitMergeShadow>,
+    /// The scan of `--profile` the rows count towards
+    scan: Option<usize>,
+    interner: Interner,
+}
+
+impl GitCommitMergeCursor {
+    fn init(&mut self, idx_num: c_int, vals: &[ValueRef]) -> Result<(), CustomError> {
+        let Some((repo_param, rev_param)) = repo_rev_args(idx_num, vals)? else {
+            self.walk = Prefetch::ready(vec![]);
+            self.current = None;
+            return Ok(());
+        };
+        let repo = self.config.open_repository(repo_param.as_deref())?;
+        let checkpoint = self.config.interrupt.checkpoint();
+        let progress = self.config.progress.clone();
+        self.walk = match &rev_param {
+            // A revision returns only the first merge reachable from it
+            Some(rev) => {
+                let mut first = vec![];
+                let mut emit = |merge| {
+                    first.push(merge);
+                    false
+                };
+                let interner = &mut self.interner;
+                let unlimited = &mut ScanLimit::new("merges", None);
+                walk_merges(
+                    &repo,
+                    Some(rev),
+                    interner,
+                    &checkpoint,
+                    &progress,
+                    unlimited,
+                    &mut emit,
+                )?;
+                Prefetch::ready(first)
+            }
+            None => {
+                let mut scan_limit = ScanLimit::new("merges", self.config.scan_limit);
+                Prefetch::spawn(move |emit| {
+                    let interner = &mut Interner::default();
+                    let scan_limit = &mut scan_limit;
+                    walk_merges(
+                        &repo,
+                        None,
+                        interner,
+                        &checkpoint,
+                        &progress,
+                        scan_limit,
+                        emit,
+                    )
+                })
+            }
+        };
+        self.current = self.walk.next()?;
+        self.repo_param = repo_param;
+        self.rev_param = rev_param;
+        Ok(())
+    }
+}
+
+/// Calls `emit` with the merges reachable from `rev`, or from HEAD when no revision is given,
+/// until it returns false or the walk reaches `scan_limit`.
+fn walk_merges(
+    repo: &Repository,
+    rev: Option<&str>,
+    interner: &mut Interner,
+    checkpoint: &Checkpoint,
+    progress: &Progress,
+    scan_limit: &mut ScanLimit,
+    emit: &mut dyn FnMut(CommitMergeShadow) -> bool,
+) -> Result<(), CustomError> {
+    let start = Instant::now();
+    let mut merges = 0;
+    walk_commits(repo, rev, |c| {
+        checkpoint.check()?;
+        if !scan_limit.allows_next() {
+            return Ok(false);
+        }
+        progress.walked();
+        if c.parent_count() < 2 {
+            return Ok(true);
+        }
+        merges += 1;
+        let time_of_first_commit =
+            get_time_of_first_commit(&c.parent_id(0)?, &c.parent_id(1)?, repo, checkpoint)?;
+        let time_to_merge = c.committer().when().seconds() - time_of_first_commit.seconds();
+        Ok(emit(CommitMergeShadow {
+            hash: c.id().to_string(),
+            message: c.message().map(|msg| msg.to_string()),
+            author_name: c.author().name().map(|name| interner.intern(name)),
+            author_email: c.author().email().map(|email| interner.intern(email)),
+            author_when: to_utc(c.author().when()),
+            committer_name: c.committer().name().map(|name| interner.intern(name)),
+            committer_email: c.committer().email().map(|email| interner.intern(email)),
+            committer_when: to_utc(c.committer().when()),
+            time_to_merge,
+            parent_1: c.parent_id(0).ok().map(|id| id.to_string()),
+            parent_2: c.parent_id(1).ok().map(|id| id.to_string()),
+            time_of_first_commit: to_utc(time_of_first_commit),
+        }))
+    })?;
+    info!(merges, elapsed = ?start.elapsed(), "revwalk");
+    Ok(())
+}
+
+fn get_time_of_first_commit(
+    parent1: &Oid,
+    parent2: &Oid,
+    repo: &Repository,
+    checkpoint: &Checkpoint,
+) -> Result<Time, CustomError> {
+    let parent1_time = repo.find_commit(*parent1)?.committer().when().seconds();
+    let mut earliest_commit = parent2.to_owned();
+    loop {
+        checkpoint.check()?;
+        let commit = repo.find_commit(earliest_commit)?;
+        match commit.parent(0) {
+            Ok(parent) => {
+                if parent.id() == *parent1 || parent.committer().when().seconds() < parent1_time {
+                    return Ok(commit.author().when());
+                }
+                earliest_commit = parent.id().to_owned();
+            }
+            Err(_) => return Ok(commit.committer().when()),
+        };
+    }
+}
+
+unsafe impl VTabCursor for GitCommitMergeCursor {
+    fn filter(
+        &mut self,
+        idx_num: c_int,
+        _idx_str: Option<&str>,
+        args: &Values<'_>,
+    ) -> rusqlite::Result<()> {
+        let vals = args.iter().collect_vec();
+        let _span = debug_span!("filter", table = "merges", idx_num, ?vals).entered();
+        let start = Instant::now();
+        self.init(idx_num, &vals)?;
+        let row = self.current.is_some();
+        self.scan = (self.config.profiler).filter("merges", &vals, start.elapsed(), row);
+
+        Ok(())
+    }
+
+    fn next(&mut self) -> rusqlite::Result<()> {
+        let start = Instant::now();
+        self.current = self.walk.next()?;
+        let row = self.current.is_some();
+        self.config.profiler.next(self.scan, start.elapsed(), row);
+
+        Ok(())
+    }
+
+    fn eof(&self) -> bool {
+        self.current.is_none()
+    }
+
+    /*
+    create table commits (
+            hash            text,
+            message         text,
+            author_name     text,
+            author_email    text,
+            author_when     DATETIME,
+            committer_name  text,
+            committer_email text,
+            committer_when  DATETIME,
+            is_merge        bool,
+            parent_1        text,
+            parent_2        text,
+            repository      hidden,
+            ref             hidden
+        ) WITHOUT ROWID
+
+     */
+    fn column(&self, ctx: &mut Context, i: c_int) -> rusqlite::Result<()> {
+        let Some(current_commit) = &self.current else {
+            return Ok(());
+        };
+        match i {
+            0 => ctx.set_result(&current_commit.hash),
+            1 => ctx.set_result(&current_commit.message),
+            2 => ctx.set_result(&current_commit.author_name),
+            3 => ctx.set_result(&current_commit.author_email),
+            4 => set_timestamp(ctx, &current_commit.author_when),
+            5 => ctx.set_result(&current_commit.committer_name),
+            6 => ctx.set_result(&current_commit.committer_email),
+            7 => set_timestamp(ctx, &current_commit.committer_when),
+            8 => ctx.set_result(&current_commit.parent_1),
+            9 => ctx.set_result(&current_commit.parent_2),
+            10 => ctx.set_result(&current_commit.time_to_merge),
+            11 => set_timestamp(ctx, &current_commit.time_of_first_commit),
+            12 => ctx.set_result(&self.repo_param),
+            13 => ctx.set_result(&self.rev_param),
+            _ => Ok(()),
+        }
+    }
+
+    fn rowid(&self) -> rusqlite::Result<i64> {
+        Ok(1)
+    }
+}
+
+//  STATS ------------------------------------------------------------------------------------------------
+
+/// The columns of `stats` the cursor can sort by: file_name, additions and deletions.
+const SORTABLE_STATS_COLUMNS: c_int = 3;
+
+/// The hidden `repo` column of `stats`, the hidden `hash` column follows it.
+const STATS_REPO_COLUMN: c_int = 3;
+
+/// How the `stats` cursor orders and trims the rows of a commit, packed into `idx_num` above the
+/// bits of the repository and revision plan.
+///
+/// SQLite only passes LIMIT and OFFSET when `stats` is the single table of the query. They are
+/// used when the rows come out in the order the query asks for and every constraint is passed to
+/// `filter`, without an ORDER BY a diff stops once it has enough files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct StatsPlan {
+    /// The filter arguments after the repository and revision are the LIMIT
+    limit: bool,
+    /// And then the OFFSET
+    offset: bool,
+    /// Column and whether it's descending per ORDER BY term
+    order: [Option<(c_int, bool)>; SORTABLE_STATS_COLUMNS as usize],
+}
+
+impl StatsPlan {
+    const LIMIT: c_int = 1 << 2;
+    const OFFSET: c_int = 1 << 3;
+    const ORDER_SHIFT: u32 = 4;
+
+    fn new(info: &mut IndexInfo, args: usize) -> StatsPlan {
+        let mut plan = StatsPlan::default();
+        let mut order = info.order_bys().map(|o| (o.column(), o.is_order_by_desc()));
+        let sortable = info.num_of_order_by() <= plan.order.len()
+            && (&mut order).zip(&mut plan.order).all(|(term, slot)| {
+                *slot = Some(term);
+                (0..SORTABLE_STATS_COLUMNS).contains(&term.0)
+            });
+        if !sortable {
+            return StatsPlan::default();
+        }
+        info.set_order_by_consumed(info.num_of_order_by() > 0);
+
+        // The repository and hash constraints `plan_repo_rev` passes to `filter`, the last usable
+        // equality of each column
+        let mut passed = [None; 2];
+        for (i, constraint) in info.constraints().enumerate() {
+            let column = constraint.column() - STATS_REPO_COLUMN;
+            if constraint.is_usable()
+                && constraint.operator() == IndexConstraintOp::SQLITE_INDEX_CONSTRAINT_EQ
+                && (0..2).contains(&column)
+            {
+                passed[column as usize] = Some(i);
+            }
+        }
+        let (mut limit, mut offset) = (None, None);
+        for (i, constraint) in info.constraints().enumerate() {
+            match constraint.operator() {
+                IndexConstraintOp::SQLITE_INDEX_CONSTRAINT_LIMIT => limit = Some(i),
+                IndexConstraintOp::SQLITE_INDEX_CONSTRAINT_OFFSET => offset = Some(i),
+                // SQLite filters the rows by the other constraints after the cursor returns
+                // them, cutting them to the LIMIT first would drop rows that match
+                _ if !passed.contains(&Some(i)) => return plan,
+                _ => {}
+            }
+        }
+        plan.limit = limit.is_some();
+        plan.offset = offset.is_some();
+        for (argv_index, i) in [limit, offset].into_iter().flatten().enumerate() {
+            let mut usage = info.constraint_usage(i);
+            usage.set_argv_index((args + argv_index) as c_int + 1);
+            // SQLite still counts the LIMIT, but skips no rows for an OFFSET it passed
+            usage.set_omit(true);
+        }
+        plan
+    }
+
+    fn to_idx_num(self) -> c_int {
+        let mut idx_num = 0;
+        if self.limit {
+            idx_num |= StatsPlan::LIMIT;
+        }
+        if self.offset {
+            idx_num |= StatsPlan::OFFSET;
+        }
+        for (i, (column, desc)) in self.order.into_iter().flatten().enumerate() {
+            let term = (column + 1) | (desc as c_int) << 2;
+            idx_num |= term << (StatsPlan::ORDER_SHIFT + 3 * i as u32);
+        }
+        idx_num
+    }
+
+    fn from_idx_num(idx_num: c_int) -> StatsPlan {
+        let mut plan = StatsPlan {
+            limit: idx_num & StatsPlan::LIMIT != 0,
+            offset: idx_num & StatsPlan::OFFSET != 0,
+            ..StatsPlan::default()
+        };
+        for (i, slot) in plan.order.iter_mut().enumerate() {
+            let term = idx_num >> (StatsPlan::ORDER_SHIFT + 3 * i as u32) & 0b111;
+            if term & 0b11 != 0 {
+                *slot = Some(((term & 0b11) - 1, term & 0b100 != 0));
+            }
+        }
+        plan
+    }
+
+    fn is_ordered(&self) -> bool {
+        self.order[0].is_some()
+    }
+
+    /// Sorts `diffs` by the ORDER BY and keeps the rows of the LIMIT and OFFSET in `args`.
+    fn apply(&self, diffs: &mut FileStats, args: &[ValueRef]) {
+        diffs.sort_by(|a, b| {
+            let terms = self.order.iter().flatten();
+            terms.fold(std::cmp::Ordering::Equal, |ordering, &(column, desc)| {
+                let term = match column {
+                    0 => a.0.cmp(&b.0),
+                    1 => a.1.cmp(&b.1),
+                    _ => a.2.cmp(&b.2),
+                };
+                ordering.then(if desc { term.reverse() } else { term })
+            })
+        });
+        let (limit, offset) = self.limit_offset(args);
+        diffs.drain(..offset.min(diffs.len()));
+        if let Some(limit) = limit {
+            diffs.truncate(limit);
+        }
+    }
+
+    /// The LIMIT, None when there is none or it's negative, and the OFFSET.
+    fn limit_offset(&self, args: &[ValueRef]) -> (Option<usize>, usize) {
+        let count = |value: Option<&ValueRef>| match value {
+            Some(ValueRef::Integer(n)) => usize::try_from(*n).ok(),
+            _ => None,
+        };
+        let limit = count(args.first().filter(|_| self.limit));
+        let offset = count(args.get(1).filter(|_| self.offset)).unwrap_or_default();
+        (limit, offset)
+    }
+
+    /// How many files a diff needs before the rows are complete, None for every file.
+    fn files_needed(&self, args: &[ValueRef]) -> Option<usize> {
+        match (self.is_ordered(), self.limit_offset(args)) {
+            (false, (Some(limit), offset)) => Some(limit.saturating_add(offset)),
+            _ => None,
+        }
+    }
+}
+
+#[repr(C)]
+struct GitStats {
+    base: sqlite3_vtab,
+    config: TableConfig,
+}
+
+unsafe impl<'a> VTab<'a> for GitStats {
+    type Aux = TableConfig;
+    type Cursor = GitStatsCursor;
+
+    fn connect(
+        _db: &mut VTabConnection,
+        aux: Option<&Self::Aux>,
+        _args: &[&[u8]],
+    ) -> rusqlite::Result<(String, Self)> {
+        Ok((
+            "create table stats(file_name text, additions integer, deletions integer, repo hidden, hash hidden primary key) WITHOUT ROWID"
+                .to_string(),
+            GitStats {
+                base: sqlite3_vtab::default(),
+                config: aux.cloned().unwrap_or_default(),
+            },
+        ))
+    }
+
+    fn best_index(&self, info: &mut IndexInfo) -> rusqlite::Result<()> {
+        let plan = plan_repo_rev("stats", info, STATS_REPO_COLUMN, STATS_REPO_COLUMN + 1);
+        let stats_plan = StatsPlan::new(info, plan.args());
+        debug!(?stats_plan, "best_index");
+        info.set_idx_num(c_int::from(plan) | stats_plan.to_idx_num());
+        // Either way a single commit is diffed, HEAD when there's no hash. Scanning HEAD's diff
+        // as the outer loop of a join is never what a query means, so that plan gets a cost
+        // no join order can beat.
+        let cost = match plan {
+            RepoRevParam::Rev | RepoRevParam::Both => 100.0,
+            RepoRevParam::Repo | RepoRevParam::None => 1e12,
+        };
+        info.set_estimated_cost(cost);
+        info.set_estimated_rows(DIFF_ROWS as i64);
+
+        Ok(())
+    }
+
+    fn open(&mut self) -> rusqlite::Result<GitStatsCursor> {
+        Ok(GitStatsCursor {
+            base: Default::default(),
+            config: self.config.clone(),
+            diffs: vec![],
+            i: 0,
+            hash: "".to_string(),
+            repo_param: None,
+            rev_param: None,
+            scan: None,
+            interner: Interner::default(),
+            _ahead: self.config.ahead.reader(),
+        })
+    }
+}
+
+#[repr(C)]
+struct GitStatsCursor {
+    base: sqlite3_vtab_cursor,
+    config: TableConfig,
+    diffs: FileStats,
+    i: usize,
+    hash: String,
+    repo_param: Option<String>,
+    rev_param: Option<String>,
+    /// The scan of `--profile` the rows count towards
+    scan: Option<usize>,
+    /// File names repeat across the commits a join diffs
+    interner: Interner,
+    /// Has the walks of `commits` diff their commits ahead while the cursor is open
+    _ahead: AheadReader,
+}
+
+impl Debug for GitStatsCursor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let str = format!(
+            "GitStatsCursor {{ \n  diffs: {:#?},\n  i: {:#?},\n  hash: {:#?}\n}}",
+            self.diffs, self.i, self.hash
+        );
+        f.write_str(&str)
+    }
+}
+
+impl GitStatsCursor {
+    fn init(&mut self, idx_num: c_int, vals: &[ValueRef]) -> Result<(), CustomError> {
+        self.i = 0;
+        let plan = StatsPlan::from_idx_num(idx_num);
+        let (vals, limit_args) =
+            vals.split_at(vals.len() - plan.limit as usize - plan.offset as usize);
+        let Some((repo_param, rev_param)) = repo_rev_args(idx_num, vals)? else {
+            self.diffs.clear();
+            return Ok(());
+        };
+        let repo = self.config.open_repository(repo_param.as_deref())?;
+        self.hash = match &rev_param {
+            Some(rev) => rev.to_string(),
+            None => repo.head()?.peel_to_commit()?.id().to_string(),
+        };
+        let checkpoint = self.config.interrupt.checkpoint();
+        let files = plan.files_needed(limit_args);
+        let mut compute = || {
+            let (hash, settings, interner) = (&self.hash, &self.config.diff, &mut self.interner);
+            // Diffs the workers computed count as well
+            self.config.progress.diffed();
+            let commit = Oid::from_str(hash)?;
+            if let Some(stats) = self
+                .config
+                .ahead
+                .take(&repo, commit, settings, &checkpoint)?
+            {
+                return Ok(stats);
+            }
+            GitStatsCursor::compute_diff(&repo, hash, settings, interner, &checkpoint, files)
+        };
+        self.diffs = match (&self.config.stats_cache, files) {
+            // Only some of the files are diffed, those stats can't be cached
+            (_, Some(_)) => compute()?,
+            (stats_cache, None) => {
+                let (commit, options) = (Oid::from_str(&self.hash)?, self.config.diff.cache_key());
+                let diffs = &self.config.diffs;
+                let recent =
+                    diffs.get_or_compute(&repo, commit, &options, || match stats_cache {
+                        Some(cache) => cache.get_or_compute(&repo, commit, &options, compute),
+                        None => compute(),
+                    })?;
+                // Sorting and LIMIT rearrange the cursor's copy
+                recent.as_ref().clone()
+            }
+        };
+        plan.apply(&mut self.diffs, limit_args);
+        self.repo_param = repo_param;
+        self.rev_param = rev_param;
+        Ok(())
+    }
+
+    /// The lines added and deleted per file by commit `hash`. With `max_files` the diff stops once
+    /// that many files have changed lines.
+    fn compute_diff(
+        repo: &Repository,
+        hash: &str,
+        settings: &DiffSettings,
+        interner: &mut Interner,
+        checkpoint: &Checkpoint,
+        max_files: Option<usize>,
+    ) -> Result<FileStats, CustomError> {
+        let commit = repo.find_commit(Oid::from_str(hash)?)?;
+        trace!(?commit, "diffing");
+        let (tree, parent_tree) = match commit.parent_count() {
+            1 => {
+                let tree = repo.find_tree(commit.tree_id())?;
+                let parent_tree = repo.find_tree(commit.parent(0)?.tree_id())?;
+                (tree, parent_tree)
+            }
+            2 => {
+                let tree = repo.find_tree(commit.parent(1)?.tree_id())?;
+                let parent_tree = repo.find_tree(commit.parent(0)?.tree_id())?;
+                (tree, parent_tree)
+            }
+            0 => {
+                let tree = repo.find_tree(commit.tree_id())?;
+                let tree2 = repo.find_tree(commit.tree_id())?;
+                (tree, tree2)
+            }
+            _ => {
+                return Err(git2::Error::from_str(&format!(
+                    "{} has more than 2 parents, octopus merges are not supported",
+                    hash
+                ))
+                .into())
+            }
+        };
+        let mut diff_options = settings.to_diff_options();
+        let diff =
+            repo.diff_tree_to_tree(Some(&parent_tree), Some(&tree), Some(&mut diff_options))?;
+        let mut counts: HashMap<Arc<str>, (u64, u64)> = HashMap::new();
+        // Files with changed lines so far, the line callback has `counts` borrowed
+        let files = std::cell::Cell::new(0);
+        let mut line_cb =
+            |diff_delta: DiffDelta, _: Option<DiffHunk>, line_dif: DiffLine| -> bool {
+                if checkpoint.check().is_err() {
+                    return false;
+                }
+                let (additions, deletions) = match line_dif.origin_value() {
+                    DiffLineType::Addition => (1, 0),
+                    DiffLineType::Deletion => (0, 1),
+                    _ => return true,
+                };
+                let file_name = diff_delta
+                    .new_file()
+                    .path()
+                    .map(|path| path.to_string_lossy())
+                    .unwrap_or_default();
+                match counts.get_mut(&*file_name) {
+                    Some(count) => {
+                        count.0 += additions;
+                        count.1 += deletions;
+                    }
+                    None => {
+                        counts.insert(interner.intern(&file_name), (additions, deletions));
+                        files.set(counts.len());
+                    }
+                }
+                true
+            };
+        let mut enough = false;
+        let diffed = diff.foreach(
+            &mut |_, _| {
+                enough = max_files.is_some_and(|max| files.get() >= max);
+                !enough && checkpoint.check().is_ok()
+            },
+            None,
+            Some(&mut |_, _| true),
+            Some(&mut line_cb),
+        );
+        // Returning false from a callback makes the diff fail with a generic error
+        checkpoint.check()?;
+        if !enough {
+            diffed?;
+        }
+        Ok(counts
+            .into_iter()
+            .map(|(file_name, (additions, deletions))| (file_name, additions, deletions))
+            .collect())
+    }
+}
+
+unsafe impl VTabCursor for GitStatsCursor {
+    fn filter(
+        &mut self,
+        idx_num: c_int,
+        _idx_str: Option<&str>,
+        args: &Values<'_>,
+    ) -> rusqlite::Result<()> {
+        let vals = args.iter().collect_vec();
+        let _span = debug_span!("filter", table = "stats", idx_num, ?vals).entered();
+        let start = Instant::now();
+        self.init(idx_num, &vals)?;
+        info!(
+            hash = %self.hash,
+            files = self.diffs.len(),
+            elapsed = ?start.elapsed(),
+            "diff"
+        );
+        trace!(diffs = ?self.diffs);
+        let row = !self.diffs.is_empty();
+        self.scan = (self.config.profiler).filter("stats", &vals, start.elapsed(), row);
+        Ok(())
+    }
+
+    fn next(&mut self) -> rusqlite::Result<()> {
+        self.i += 1;
+        let row = self.i < self.diffs.len();
+        self.config.profiler.next(self.scan, Duration::ZERO, row);
+        Ok(())
+    }
+
+    fn eof(&self) -> bool {
+        self.i >= self.diffs.len()
+    }
+
+    fn column(&self, ctx: &mut Context, i: c_int) -> rusqlite::Result<()> {
+        let (filename, additions, deletions) = &self.diffs[self.i];
+        match i {
+            0 => ctx.set_result(filename),
+            1 => ctx.set_result(additions),
+            2 => ctx.set_result(deletions),
+            3 => ctx.set_result(&self.repo_param),
+            4 => ctx.set_result(&self.rev_param),
+            _ => Ok(()),
+        }
+    }
+
+    fn rowid(&self) -> rusqlite::Result<i64> {
+        Ok(1)
+    }
+}
+
+// MAIN ----------------------------------------------------------------------------------------------------------------
+
+#[cfg(feature = "cli")]
+/// Table-valued functions registered by `register_modules`.
+const TABLES: &[&str] = &[
+    "commits",
+    "merges",
+    "stats",
+    "fetch",
+    "tags",
+    "branches",
+    "notes",
+    "config",
+    #[cfg(feature = "github")]
+    "gh_pull_requests",
+    #[cfg(feature = "github")]
+    "gh_issues",
+];
+
+#[cfg(feature = "cli")]
+fn register_modules(
+    db: &Connection,
+    repository: &Path,
+    warm_index: bool,
+    scan_limit: Option<usize>,
+    mirrors: Option<PathBuf>,
+    serve: bool,
+    views: bool,
+) -> rusqlite::Result<SqliteGit> {
+    let mut git = (SqliteGit::new().with_all().with_stats_cache()).repository(repository);
+    if views {
+        git = git.with_views();
+    }
+    // The clients of serve read the repository it was started in and nothing else
+    if serve {
+        git = git.pin_repository();
+    }
+    // Every connection but the ones of serve may write to the repositories and reach remotes:
+    // fetch from them and read GitHub, whether or not there's a directory to mirror the
+    // repositories read by URL in
+    if !serve {
+        git = (git.with_fetch().with_tags().with_branches())
+            .with_notes()
+            .with_config();
+        #[cfg(feature = "github")]
+        {
+            git = git.with_github();
+        }
+    }
+    if let Some(dir) = mirrors {
+        git = git.mirrors(dir);
+    }
+    if warm_index {
+        git = git.with_warm_index();
+    }
+    if let Some(commits) = scan_limit {
+        git = git.scan_limit(commits);
+    }
+    git.register(db)?;
+    Ok(git)
+}
+
+/// Picks the git tables registered on a connection and the names they are registered under.
+#[derive(Debug, Clone, Default)]
+pub struct SqliteGit {
+    commits: bool,
+    merges: bool,
+    stats: bool,
+    fetch: bool,
+    /// The tables statements can write to, like `tags`
+    writable: Vec<&'static writable::Table>,
+    functions: bool,
+    views: bool,
+    prefix: String,
+    config: TableConfig,
+}
+
+impl SqliteGit {
+    /// A builder that registers nothing until tables are added with the `with_*` methods.
+    pub fn new() -> Self {
+        SqliteGit::default()
+    }
+
+    /// Adds `commits`, `merges`, `stats` and the `git_*` functions.
+    pub fn with_all(self) -> Self {
+        self.with_commits()
+            .with_merges()
+            .with_stats()
+            .with_functions()
+    }
+
+    /// Creates views over `commits` for common questions: `recent_commits`, `author_summary`,
+    /// `merge_commits` and `weekly_activity`, named with the table prefix. They read the default
+    /// repository and are created in the connection's main schema unless they exist already.
+    pub fn with_views(mut self) -> Self {
+        self.views = true;
+        self
+    }
+
+    pub fn with_commits(mut self) -> Self {
+        self.commits = true;
+        self
+    }
+
+    pub fn with_merges(mut self) -> Self {
+        self.merges = true;
+        self
+    }
+
+    pub fn with_stats(mut self) -> Self {
+        self.stats = true;
+        self
+    }
+
+    /// Adds `fetch`, which fetches from the remotes of the repositories and returns the refs it
+    /// updated. Not part of [`SqliteGit::with_all`], a query shouldn't write to the repositories
+    /// unless the tables were picked for that.
+    pub fn with_fetch(mut self) -> Self {
+        self.fetch = true;
+        self
+    }
+
+    /// Adds `tags`, the tags of the repositories. INSERT creates a lightweight, annotated or
+    /// signed tag and DELETE removes one. Not part of [`SqliteGit::with_all`] either.
+    pub fn with_tags(mut self) -> Self {
+        self.writable.push(&tags::TAGS);
+        self
+    }
+
+    /// Adds `branches`, the local branches of the repositories with how far they are ahead of
+    /// and behind their upstream. INSERT, UPDATE and DELETE create, rename, move and delete
+    /// branches, except the ones that are checked out. Not part of [`SqliteGit::with_all`].
+    pub fn with_branches(mut self) -> Self {
+        self.writable.push(&branches::BRANCHES);
+        self
+    }
+
+    /// Adds `notes`, the git notes of the repositories. INSERT, UPDATE and DELETE attach, change
+    /// and remove notes in any notes ref. Not part of [`SqliteGit::with_all`].
+    pub fn with_notes(mut self) -> Self {
+        self.writable.push(&notes::NOTES);
+        self
+    }
+
+    /// Adds `config`, the git configuration of the repositories at every level. INSERT, UPDATE
+    /// and DELETE add, change and remove values at the level of the row, local unless it names
+    /// another one. Not part of [`SqliteGit::with_all`].
+    pub fn with_config(mut self) -> Self {
+        self.writable.push(&git_config::CONFIG);
+        self
+    }
+
+    /// Adds `gh_pull_requests` and `gh_issues`, the pull requests and issues of the GitHub
+    /// repository a repository's `origin` points to, or of a GitHub URL. The token is read from
+    /// `GH_TOKEN` or `GITHUB_TOKEN`.
+    #[cfg(feature = "github")]
+    pub fn with_github(mut self) -> Self {
+        self.config.github = Some(github::GitHub::from_env());
+        self
+    }
+
+    /// Adds the scalar `git_*` functions, like `git_rev_parse`, and the `SEMVER` and `MAILMAP`
+    /// collations.
+    pub fn with_functions(mut self) -> Self {
+        self.functions = true;
+        self
+    }
+
+    /// Registers every table as `{prefix}{name}`, e.g. `git_commits` for the prefix `git_`.
+    pub fn table_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    /// Reads `path` instead of the current directory when a query doesn't pass a repository.
+    pub fn repository(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.repository = Some(path.into());
+        self
+    }
+
+    /// Replaces the diff options `stats` counts lines with.
+    pub fn diff_settings(mut self, settings: DiffSettings) -> Self {
+        self.config.diff = settings;
+        self
+    }
+
+    /// Makes `git_blob_content` fail for files larger than `bytes` instead of reading them into
+    /// memory.
+    pub fn max_blob_size(mut self, bytes: u64) -> Self {
+        self.config.max_blob_size = Some(bytes);
+        self
+    }
+
+    /// Makes `git_diff_text` fail for diffs larger than `bytes` instead of building them in
+    /// memory.
+    pub fn max_diff_size(mut self, bytes: u64) -> Self {
+        self.config.max_diff_size = Some(bytes);
+        self
+    }
+
+    /// Keeps the stats `stats` computes in `.git/sqlitegit/stats.db` of each repository and
+    /// reads them from there instead of diffing the same commit again.
+    pub fn with_stats_cache(mut self) -> Self {
+        self.config.stats_cache = Some(StatsCache::default());
+        self
+    }
+
+    /// Reads the commits reachable from HEAD of the default repository on a background thread
+    /// when the tables are registered. `commits` serves its rows from memory while HEAD stays
+    /// where it was, which pays off for connections that run many queries. Histories of more
+    /// than 250,000 commits aren't kept in memory.
+    pub fn with_warm_index(mut self) -> Self {
+        self.config.warm_index = Some(WarmIndex::default());
+        self
+    }
+
+    /// Reads the repository arguments that are URLs, like `https://github.com/rust-lang/log` or
+    /// `git@github.com:rust-lang/log.git`, from bare mirrors below `dir`. A mirror is cloned when
+    /// its URL is first read and fetched once per connection after that. Private repositories
+    /// authenticate like `git` does, with the ssh agent or the keys in `~/.ssh`, and with
+    /// the configured credential helper over https. `SQLITEGIT_TOKEN` is sent too, but only to the
+    /// host named by `SQLITEGIT_TOKEN_HOST`.
+    pub fn mirrors(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.config.mirrors = Some(Mirrors::new(dir.into()));
+        self
+    }
+
+    /// Rejects the repository arguments of the tables and functions, and leaves out
+    /// `git_config_get` and `git_repo_root`, which read outside of the repository. For connections
+    /// answering the queries of clients that may only read the default repository, like the ones
+    /// of `sqlitegit serve`.
+    pub fn pin_repository(mut self) -> Self {
+        self.config.pinned = true;
+        self
+    }
+
+    /// Walks at most `commits` commits from HEAD when a query of `commits` or `merges` passes no
+    /// revision, and logs a warning when older commits are left out. A scan over the whole
+    /// history of a monorepo takes minutes, this keeps an accidental one quick.
+    pub fn scan_limit(mut self, commits: usize) -> Self {
+        self.config.scan_limit = Some(commits);
+        self
+    }
+
+    /// A handle that stops the statements running on the connections this builder registers
+    /// the tables on, e.g. when Ctrl-C is pressed.
+    pub fn interrupt(&self) -> Interrupt {
+        self.config.interrupt.clone()
+    }
+
+    /// Records what the tables do on the connections this builder registers them on, between
+    /// [`Profiler::start`] and [`Profiler::finish`].
+    pub fn profiler(&self) -> Profiler {
+        self.config.profiler.clone()
+    }
+
+    /// Counts the commits the tables walk and the diffs they compute on the connections this
+    /// builder registers them on, e.g. to show the progress of a long scan.
+    pub fn progress(&self) -> Progress {
+        self.config.progress.clone()
+    }
+
+    /// The names the selected tables are registered under.
+    pub fn table_names(&self) -> Vec<String> {
+        [
+            (self.commits, "commits"),
+            (self.merges, "merges"),
+            (self.stats, "stats"),
+            (self.fetch, "fetch"),
+            (self.github(), "gh_pull_requests"),
+            (self.github(), "gh_issues"),
+        ]
+        .into_iter()
+        .chain(self.writable.iter().map(|table| (true, table.name)))
+        .filter(|(selected, _)| *selected)
+        .map(|(_, name)| format!("{}{}", self.prefix, name))
+        .collect()
+    }
+
+    fn github(&self) -> bool {
+        #[cfg(feature = "github")]
+        return self.config.github.is_some();
+        #[cfg(not(feature = "github"))]
+        false
+    }
+
+    pub fn register(&self, db: &Connection) -> rusqlite::Result<()> {
+        let config = TableConfig {
+            _registration: Some(self.config.interrupt.add_connection(db)),
+            ..self.config.clone()
+        };
+        if self.commits {
+            let name = format!("{}commits", self.prefix);
+            db.create_module(
+                &name,
+                eponymous_only_module::<GitCommit>(),
+                Some(config.clone()),
+            )?;
+        }
+        if self.merges {
+            let name = format!("{}merges", self.prefix);
+            db.create_module(
+                &name,
+                eponymous_only_module::<crate::GitCommitMerge>(),
+                Some(config.clone()),
+            )?;
+        }
+        if self.stats {
+            let name = format!("{}stats", self.prefix);
+            db.create_module(
+                &name,
+                eponymous_only_module::<GitStats>(),
+                Some(config.clone()),
+            )?;
+        }
+        if self.fetch {
+            let name = format!("{}fetch", self.prefix);
+            db.create_module(
+                &name,
+                eponymous_only_module::<fetch::GitFetch>(),
+                Some(config.clone()),
+            )?;
+        }
+        for table in &self.writable {
+            let name = format!("{}{}", self.prefix, table.name);
+            db.create_module(
+                &name,
+                update_module::<WritableTable>(),
+                Some((config.clone(), *table)),
+            )?;
+        }
+        #[cfg(feature = "github")]
+        if config.github.is_some() {
+            for resource in [&github::PULL_REQUESTS, &github::ISSUES] {
+                let name = format!("{}{}", self.prefix, resource.name);
+                db.create_module(
+                    &name,
+                    eponymous_only_module::<github::GitHubTable>(),
+                    Some((config.clone(), resource)),
+                )?;
+            }
+        }
+        if self.functions {
+            functions::register(db, &config)?;
+        }
+        if self.views {
+            for (name, sql) in VIEWS {
+                db.execute_batch(&format!(
+                    "CREATE VIEW IF NOT EXISTS {}{} AS {}",
+                    self.prefix,
+                    name,
+                    sql.replace("{prefix}", &self.prefix)
+                ))?;
+            }
+        }
+        if let Some(index) = &self.config.warm_index {
+            index.warm(&self.config);
+        }
+        Ok(())
+    }
+}
+
+/// The views of [`SqliteGit::with_views`], by name. `{prefix}` stands for the prefix of the
+/// tables.
+const VIEWS: [(&str, &str); 4] = [
+    (
+        "recent_commits",
+        "SELECT * FROM {prefix}commits WHERE committer_when >= datetime('now', '-30 days')",
+    ),
+    (
+        "author_summary",
+        r#"
+        SELECT max(author_name) AS author_name, author_email, count(*) AS commits,
+               min(author_when) AS first_commit, max(author_when) AS last_commit
+        FROM {prefix}commits
+        GROUP BY author_email
+        ORDER BY commits DESC
+        "#,
+    ),
+    (
+        "merge_commits",
+        "SELECT * FROM {prefix}commits WHERE is_merge",
+    ),
+    (
+        "weekly_activity",
+        r#"
+        SELECT strftime('%Y-%W', author_when) AS week, count(*) AS commits,
+               count(DISTINCT author_email) AS authors
+        FROM {prefix}commits
+        GROUP BY week
+        ORDER BY week
+        "#,
+    ),
+];
+
+#[cfg(test)]
+mod test {
+    #[cfg(feature = "cli")]
+    use crate::output::OutputOptions;
+    #[cfg(feature = "cli")]
+    use crate::utils::execute_and_write;
+    use crate::{GitCommit, GitStats};
+    use chrono::{DateTime, TimeZone, Utc};
+    use rusqlite::vtab::eponymous_only_module;
+    use rusqlite::Connection;
+
+    #[test]
+    fn commits() -> Result<(), rusqlite::Error> {
+        let db = Connection::open_in_memory().unwrap();
+        let commit_module = eponymous_only_module::<GitCommit>();
+        db.create_module("commits", commit_module, None).unwrap();
+
+        let sql = r#"
+    SELECT hash, message, author_when
+    FROM commits('./tests') ORDER BY author_when ASC;
+    "#;
+        let mut stmt = db.prepare(sql)?;
+        let mut query_res = stmt.query([])?;
+        let row = query_res.next()?.unwrap();
+
+        let hash: String = row.get(0).unwrap();
+        let msg: String = row.get(1).unwrap();
+        let when: DateTime<Utc> = row.get(2).unwrap();
+
+        assert_eq!(
+            hash,
+            String::from("6bf8ee6cd03eac57b7039756edc58c4aed6f6882")
+        );
+        assert_eq!(msg, "First commit\n");
+        assert_eq!(when, Utc.with_ymd_and_hms(2022, 7, 1, 17, 55, 57).unwrap());
+
+        Ok(())
+    }
+
+    #[test]
+    fn stats() -> Result<(), rusqlite::Error> {
+        let db = Connection::open_in_memory().unwrap();
+        let stat_module = eponymous_only_module::<GitStats>();
+        db.create_module("stats", stat_module, None).unwrap();
+
+        let sql = r#"SELECT file_name, additions, deletions FROM stats('./tests', '9096bf0343aecaa4a592da68c10874fd9fe35918')"#;
+        let mut stmt = db.prepare(sql)?;
+        let mut query_res = stmt.query([])?;
+        let row = query_res.next()?.unwrap();
+
+        let filename: String = row.get(0).unwrap();
+        let additions: i64 = row.get(1).unwrap();
+        let deletions: i64 = row.get(2).unwrap();
+
+        assert_eq!(filename, String::from("hello.txt"));
+        assert_eq!(additions, 1);
+        assert_eq!(deletions, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn combined() -> Result<(), rusqlite::Error> {
+        let db = Connection::open_in_memory().unwrap();
+        let commit_module = eponymous_only_module::<GitCommit>();
+        let stat_module = eponymous_only_module::<GitStats>();
+        db.create_module("commits", commit_module, None).unwrap();
+        db.create_module("stats", stat_module, None).unwrap();
+
+        let sql = r#"
+        SELECT c.hash, message, author_when, file_name, additions, deletions
+        FROM commits('./tests') c 
+            LEFT OUTER JOIN stats('./tests') s ON c.hash = s.hash 
+        ORDER BY author_when DESC"#;
+
+        let mut stmt = db.prepare(sql)?;
+
+        let mut query_res = stmt.query([])?;
+        let row = query_res.next()?.unwrap();
+
+        let hash: String = row.get(0).unwrap();
+        let msg: String = row.get(1).unwrap();
+        let when: DateTime<Utc> = row.get(2).unwrap();
+        let filename: String = row.get(3).unwrap();
+        let additions: i64 = row.get(4).unwrap();
+        let deletions: i64 = row.get(5).unwrap();
+
+        assert_eq!(
+            hash,
+            String::from("9096bf0343aecaa4a592da68c10874fd9fe35918")
+        );
+        assert_eq!(msg, "More lines\n");
+        assert_eq!(when, Utc.with_ymd_and_hms(2022, 7, 1, 18, 34, 30).unwrap());
+        assert_eq!(filename, String::from("hello.txt"));
+        assert_eq!(additions, 1);
+        assert_eq!(deletions, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn filter_non_arg() -> Result<(), rusqlite::Error> {
+        let db = Connection::open_in_memory().unwrap();
+        let commit_module = eponymous_only_module::<GitCommit>();
+        let stat_module = eponymous_only_module::<GitStats>();
+        db.create_module("commits", commit_module, None).unwrap();
+        db.create_module("stats", stat_module, None).unwrap();
+
+        let sql = r#"
+        SELECT c.hash, message, author_when, file_name, additions, deletions
+        FROM commits('./tests') c 
+            LEFT OUTER JOIN stats('./tests') s ON c.hash = s.hash
+        WHERE file_name = "hello.txt" 
+        ORDER BY author_when DESC"#;
+
+        let mut stmt = db.prepare(sql)?;
+
+        let mut query_res = stmt.query([])?;
+        let row = query_res.next()?.unwrap();
+
+        let hash: String = row.get(0).unwrap();
+        let msg: String = row.get(1).unwrap();
+        let when: DateTime<Utc> = row.get(2).unwrap();
+        let filename: String = row.get(3).unwrap();
+        let additions: i64 = row.get(4).unwrap();
+        let deletions: i64 = row.get(5).unwrap();
+
+        assert_eq!(
+            hash,
+            String::from("9096bf0343aecaa4a592da68c10874fd9fe35918")
+        );
+        assert_eq!(msg, "More lines\n");
+        assert_eq!(when, Utc.with_ymd_and_hms(2022, 7, 1, 18, 34, 30).unwrap());
+        assert_eq!(filename, String::from("hello.txt"));
+        assert_eq!(additions, 1);
+        assert_eq!(deletions, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn repo_as_where() -> Result<(), rusqlite::Error> {
+        let db = Connection::open_in_memory().unwrap();
+        let commit_module = eponymous_only_module::<GitCommit>();
+        let stat_module = eponymous_only_module::<GitStats>();
+        db.create_module("commits", commit_module, None).unwrap();
+        db.create_module("stats", stat_module, None).unwrap();
+
+        let sql = r#"
+        SELECT c.hash, c.message, c.author_when
+        FROM commits() c
+        WHERE c.hash = "9096bf0343aecaa4a592da68c10874fd9fe35918" and c.repo = "./tests" 
+        ORDER BY author_when DESC"#;
+
+        let mut stmt = db.prepare(sql)?;
+
+        let mut query_res = stmt.query([])?;
+        let row = query_res.next()?.unwrap();
+
+        let hash: String = row.get(0).unwrap();
+        let msg: String = row.get(1).unwrap();
+        let when: DateTime<Utc> = row.get(2).unwrap();
+
+        assert_eq!(
+            hash,
+            String::from("9096bf0343aecaa4a592da68c10874fd9fe35918")
+        );
+        assert_eq!(msg, "More lines\n");
+        assert_eq!(when, Utc.with_ymd_and_hms(2022, 7, 1, 18, 34, 30).unwrap());
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "cli")]
+    fn merges() -> Result<(), rusqlite::Error> {
+        let db = Connection::open_in_memory().unwrap();
+        let commit_module = eponymous_only_module::<crate::GitCommitMerge>();
+        let stat_module = eponymous_only_module::<GitStats>();
+        db.create_module("merges", commit_module, None).unwrap();
+        db.create_module("stats", stat_module, None).unwrap();
+
+        let sql = r#"
+    SELECT author_email, count(m.hash) as merges, AVG(time_to_merge/3600) as ttm, SUM(coalesce(additions, 0)) as additions, SUM(coalesce(deletions, 0)) as deletions
+    FROM merges('/home/rdp/dixa/conversation-service') m left join stats('/home/rdp/dixa/conversation-service') s on m.hash = s.hash
+    GROUP BY author_email
+    ORDER BY ttm ASC
+    "#;
+        let mut stmt = db.prepare(sql)?;
+        // let mut query_res = stmt.query([])?;
+
+        execute_and_write(&mut stmt, &mut std::io::stdout(), &OutputOptions::default())?;
+        // let row = query_res.next()?.unwrap();
+        //
+        // let hash: String = row.get(0).unwrap();
+        // let msg: String = row.get(1).unwrap();
+        // let when: DateTime<Utc> = row.get(2).unwrap();
+        //
+        // assert_eq!(
+        //     hash,
+        //     String::from("6bf8ee6cd03eac57b7039756edc58c4aed6f6882")
+        // );
+        // assert_eq!(msg, "First commit\n");
+        // assert_eq!(when, Utc.with_ymd_and_hms(2022, 7, 1, 17, 55, 57).unwrap());
+
+        Ok(())
+    }
+
+    #[test]
+    fn errors_become_sql_errors() -> Result<(), rusqlite::Error> {
+        let db = Connection::open_in_memory()?;
+        crate::SqliteGit::new().with_all().register(&db)?;
+
+        let code = |sql: &str| match db.query_row(sql, [], |row| row.get::<_, i64>(0)) {
+            Err(rusqlite::Error::SqliteFailure(e, _)) => Some(e.code),
+            _ => None,
+        };
+        assert_eq!(
+            code("SELECT count(*) FROM commits('/does/not/exist')"),
+            Some(rusqlite::ErrorCode::CannotOpen)
+        );
+        assert_eq!(
+            code("SELECT count(*) FROM stats(42)"),
+            Some(rusqlite::ErrorCode::TypeMismatch)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn builder_prefix_and_selection() -> Result<(), rusqlite::Error> {
+        let db = Connection::open_in_memory()?;
+        let builder = crate::SqliteGit::new()
+            .with_commits()
+            .with_stats()
+            .table_prefix("git_");
+        builder.register(&db)?;
+
+        assert_eq!(builder.table_names(), vec!["git_commits", "git_stats"]);
+        assert!(db.prepare("SELECT hash FROM git_commits").is_ok());
+        assert!(db.prepare("SELECT file_name FROM git_stats").is_ok());
+        assert!(db.prepare("SELECT hash FROM git_merges").is_err());
+        assert!(db.prepare("SELECT hash FROM commits").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn builder_views() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("builder_views")?;
+        commit_file(&repo, "file.txt", "one\n", "first")?;
+        let second = commit_file(&repo, "file.txt", "two\n", "second")?;
+        // The same author under another spelling of the name
+        let second = repo.find_commit(second)?;
+        let renamed = git2::Signature::new("someone", "someone@example.com", &second.time())?;
+        let tree = second.tree()?;
+        repo.commit(Some("HEAD"), &renamed, &renamed, "third", &tree, &[&second])?;
+
+        let db = Connection::open_in_memory()?;
+        let builder = crate::SqliteGit::new()
+            .with_commits()
+            .with_views()
+            .table_prefix("git_")
+            .repository(&path);
+        builder.register(&db)?;
+        // The views exist already
+        builder.register(&db)?;
+        let (authors, commits, name) = db.query_row(
+            "SELECT count(*), sum(commits), max(author_name) FROM git_author_summary",
+            [],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            },
+        )?;
+        let merges = db.query_row("SELECT count(*) FROM git_merge_commits", [], |row| {
+            row.get::<_, i64>(0)
+        })?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!((authors, commits, merges), (1, 3, 0));
+        assert_eq!(name, "someone");
+
+        Ok(())
+    }
+
+    #[test]
+    fn builder_default_repository() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("builder_default_repository")?;
+        commit_file(&repo, "file.txt", "one\ntwo\n", "first")?;
+
+        let db = Connection::open_in_memory()?;
+        crate::SqliteGit::new()
+            .with_all()
+            .repository(&path)
+            .register(&db)?;
+        let (count, message): (i64, String) =
+            db.query_row("SELECT count(*), message FROM commits", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!(count, 1);
+        assert_eq!(message, "first");
+
+        Ok(())
+    }
+
+    #[test]
+    fn joins_look_up_by_hash() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("joins_look_up_by_hash")?;
+        commit_file(&repo, "a.txt", "one\n", "first")?;
+        commit_file(&repo, "b.txt", "two\n", "second")?;
+        commit_file(&repo, "a.txt", "three\n", "third")?;
+
+        let db = Connection::open_in_memory()?;
+        crate::SqliteGit::new()
+            .with_all()
+            .repository(&path)
+            .register(&db)?;
+        let stats: i64 = db.query_row(
+            "SELECT count(DISTINCT c.hash) FROM stats s JOIN commits c ON s.hash = c.hash",
+            [],
+            |row| row.get(0),
+        )?;
+        let parents: i64 = db.query_row(
+            "SELECT count(*) FROM commits c JOIN commits p ON p.ref = c.parent_1",
+            [],
+            |row| row.get(0),
+        )?;
+        std::fs::remove_dir_all(&path)?;
+
+        // The root commit has no parent to diff against
+        assert_eq!(stats, 2);
+        assert_eq!(parents, 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn stats_limit_offset_and_order() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("stats_limit_offset_and_order")?;
+        commit_file(&repo, "a.txt", "one\n", "first")?;
+        let workdir = repo.workdir().ok_or("the repository is bare")?;
+        let mut index = repo.index()?;
+        for (file, lines) in [("b.txt", 3), ("c.txt", 1), ("d.txt", 5), ("e.txt", 2)] {
+            std::fs::write(workdir.join(file), "line\n".repeat(lines))?;
+            index.add_path(std::path::Path::new(file))?;
+        }
+        index.write()?;
+        let hash = commit_file(&repo, "f.txt", &"line\n".repeat(4), "second")?.to_string();
+
+        let db = Connection::open_in_memory()?;
+        crate::SqliteGit::new()
+            .with_stats()
+            .repository(&path)
+            .register(&db)?;
+        let files = |sql: &str| -> rusqlite::Result<Vec<String>> {
+            let sql = format!("SELECT file_name FROM stats WHERE hash = ?1 {}", sql);
+            db.prepare(&sql)?
+                .query_map([&hash], |row| row.get(0))?
+                .collect()
+        };
+        let top = files("ORDER BY additions DESC LIMIT 2")?;
+        let offset = files("ORDER BY additions DESC LIMIT 2 OFFSET 1")?;
+        let by_name = files("ORDER BY file_name DESC LIMIT 1")?;
+        let unordered = files("LIMIT 3 OFFSET 1")?;
+        // SQLite sorts by the expression itself, the cursor must not cut the rows short
+        let by_expression = files("ORDER BY length(file_name), additions LIMIT 1")?;
+        let partial = super::GitStatsCursor::compute_diff(
+            &repo,
+            &hash,
+            &crate::DiffSettings::default(),
+            &mut crate::intern::Interner::default(),
+            &Default::default(),
+            Some(2),
+        )?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!(top, ["d.txt", "f.txt"]);
+        assert_eq!(offset, ["f.txt", "b.txt"]);
+        assert_eq!(by_name, ["f.txt"]);
+        assert_eq!(unordered.len(), 3);
+        assert_eq!(itertools::Itertools::unique(unordered.iter()).count(), 3);
+        assert_eq!(by_expression, ["c.txt"]);
+        assert_eq!(partial.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn stats_limit_after_unhandled_constraint() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("stats_limit_after_unhandled_constraint")?;
+        commit_file(&repo, "a.txt", "one\n", "first")?;
+        let workdir = repo.workdir().ok_or("the repository is bare")?;
+        let mut index = repo.index()?;
+        for (file, lines) in [("b.txt", 4), ("c.txt", 2)] {
+            std::fs::write(workdir.join(file), "line\n".repeat(lines))?;
+            index.add_path(std::path::Path::new(file))?;
+        }
+        index.write()?;
+        let hash = commit_file(&repo, "a.txt", "one\ntwo\n", "second")?.to_string();
+
+        let db = Connection::open_in_memory()?;
+        crate::SqliteGit::new()
+            .with_stats()
+            .repository(&path)
+            .register(&db)?;
+        let sql = format!(
+            "SELECT file_name, additions FROM stats WHERE hash='{}' AND additions > 1 LIMIT 1",
+            hash
+        );
+        let rows = db
+            .prepare(&sql)?
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!(rows.len(), 1);
+        assert!(rows[0].1 > 1);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "cli")]
+    #[test]
+    fn remote_tables_without_mirrors_but_not_under_serve() -> Result<(), Box<dyn std::error::Error>>
+    {
+        let modules = |serve| -> Result<Vec<String>, Box<dyn std::error::Error>> {
+            let db = Connection::open_in_memory()?;
+            crate::register_modules(
+                &db,
+                std::path::Path::new("."),
+                false,
+                None,
+                None,
+                serve,
+                false,
+            )?;
+            let mut stmt = db.prepare(
+                "SELECT name FROM pragma_module_list
+                 WHERE name IN ('fetch', 'gh_issues', 'gh_pull_requests') ORDER BY name",
+            )?;
+            let names = stmt.query_map([], |row| row.get(0))?;
+            Ok(names.collect::<rusqlite::Result<_>>()?)
+        };
+
+        let remote = if cfg!(feature = "github") {
+            vec!["fetch", "gh_issues", "gh_pull_requests"]
+        } else {
+            vec!["fetch"]
+        };
+        assert_eq!(modules(false)?, remote);
+        assert_eq!(modules(true)?, Vec::<String>::new());
+
+        Ok(())
+    }
+
+    #[test]
+    fn scan_limit_bounds_walks_without_revision() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("scan_limit")?;
+        let first = commit_file(&repo, "file.txt", "one\n", "first")?;
+        commit_file(&repo, "file.txt", "two\n", "second")?;
+        let head = commit_file(&repo, "file.txt", "three\n", "third")?;
+
+        let db = Connection::open_in_memory()?;
+        crate::SqliteGit::new()
+            .with_all()
+            .repository(&path)
+            .scan_limit(2)
+            .register(&db)?;
+        let hashes = db
+            .prepare("SELECT hash FROM commits")?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        let by_revision: i64 = db.query_row(
+            "SELECT count(*) FROM commits WHERE ref = ?",
+            [first.to_string()],
+            |row| row.get(0),
+        )?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!(hashes.len(), 2);
+        assert_eq!(hashes[0], head.to_string());
+        assert_eq!(by_revision, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn timestamps_format_like_rusqlite() -> Result<(), Box<dyn std::error::Error>> {
+        let db = Connection::open_in_memory()?;
+        let mut buf = [0; crate::TIMESTAMP_LEN];
+        for when in [Utc.timestamp_opt(0, 0), Utc.timestamp_opt(1_600_000_000, 0)] {
+            let when = when.single().ok_or("ambiguous")?;
+            let expected: String = db.query_row("SELECT ?", [when], |row| row.get(0))?;
+            assert_eq!(crate::format_timestamp(&when, &mut buf), Some(&*expected));
+        }
+        let fractional = Utc.timestamp_opt(1_600_000_000, 5).single();
+        let distant = Utc.with_ymd_and_hms(10_000, 1, 1, 0, 0, 0).single();
+        assert_eq!(
+            fractional.and_then(|when| crate::format_timestamp(&when, &mut buf)),
+            None
+        );
+        assert_eq!(
+            distant.and_then(|when| crate::format_timestamp(&when, &mut buf)),
+            None
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn commits_of_a_range() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("commits_of_a_range")?;
+        commit_file(&repo, "file.txt", "one\n", "first")?;
+        let tagged = commit_file(&repo, "file.txt", "two\n", "tagged")?;
+        repo.tag_lightweight("v1.0", &repo.find_object(tagged, None)?, false)?;
+        commit_file(&repo, "file.txt", "three\n", "third")?;
+        commit_file(&repo, "file.txt", "four\n", "fourth")?;
+
+        let db = Connection::open_in_memory()?;
+        crate::SqliteGit::new()
+            .with_all()
+            .repository(&path)
+            .register(&db)?;
+        let messages = |range: &str| {
+            db.prepare("SELECT message FROM commits WHERE ref = ?")?
+                .query_map([range], |row| row.get::<_, String>(0))?
+                .collect::<rusqlite::Result<Vec<_>>>()
+        };
+        let since_tag = messages("v1.0..HEAD")?;
+        let open_ended = messages("v1.0..")?;
+        let unknown = messages("v2.0..HEAD")?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!(since_tag, ["fourth", "third"]);
+        assert_eq!(open_ended, since_tag);
+        assert!(unknown.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn commits_decode_the_columns_a_statement_reads() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("commits_columns")?;
+        let first = commit_file(&repo, "file.txt", "one\n", "first")?;
+        commit_file(&repo, "file.txt", "two\n", "second")?;
+
+        let db = Connection::open_in_memory()?;
+        crate::SqliteGit::new()
+            .with_all()
+            .repository(&path)
+            .register(&db)?;
+        let parents: Vec<Option<String>> = db
+            .prepare("SELECT parent_1 FROM commits")?
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        // Columns only read by the WHERE clause are decoded too
+        let filtered: String = db.query_row(
+            "SELECT hash FROM commits WHERE message = 'first' AND author_name IS NOT NULL",
+            [],
+            |row| row.get(0),
+        )?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!(parents, [Some(first.to_string()), None]);
+        assert_eq!(filtered, first.to_string());
+        let used = |columns: &[u32]| columns.iter().map(|i| 1 << i).sum::<u64>();
+        let decoded = |message, signatures| crate::CommitColumns {
+            message,
+            signatures,
+        };
+        let from_used = crate::CommitColumns::from_columns_used;
+        assert_eq!(from_used(used(&[0, 9, 10, 11])), decoded(false, false));
+        assert_eq!(from_used(used(&[0, 1])), decoded(true, false));
+        assert_eq!(from_used(used(&[4])), decoded(false, true));
+        assert_eq!(from_used(u64::MAX), crate::CommitColumns::ALL);
+
+        Ok(())
+    }
+
+    #[test]
+    fn commits_sample() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("commits_sample")?;
+        let mut commits = vec![];
+        for i in 0..10 {
+            commits.push(commit_file(
+                &repo,
+                "file.txt",
+                &format!("{}\n", i),
+                "change",
+            )?);
+        }
+
+        let db = Connection::open_in_memory()?;
+        crate::SqliteGit::new()
+            .with_all()
+            .repository(&path)
+            .register(&db)?;
+        let hashes = |sample: &dyn rusqlite::ToSql| {
+            db.prepare("SELECT hash FROM commits WHERE sample = ?")?
+                .query_map([sample], |row| row.get::<_, String>(0))?
+                .collect::<rusqlite::Result<Vec<_>>>()
+        };
+        let every_third = hashes(&3)?;
+        let all = hashes(&1.0)?;
+        let fraction = hashes(&0.5)?;
+        let again = hashes(&0.5)?;
+        let invalid = hashes(&0);
+        std::fs::remove_dir_all(&path)?;
+
+        let newest_first = commits
+            .iter()
+            .rev()
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(every_third, [0, 3, 6, 9].map(|i| newest_first[i].clone()));
+        assert_eq!(all, newest_first);
+        assert!(fraction.len() < 10);
+        assert_eq!(fraction, again);
+        assert!(invalid.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn connections_on_many_threads() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("connections_on_many_threads")?;
+        for i in 0..5 {
+            commit_file(&repo, "file.txt", &format!("{}\n", i), "change")?;
+        }
+
+        let git = crate::SqliteGit::new()
+            .with_all()
+            .with_stats_cache()
+            .repository(&path);
+        let sql =
+            "SELECT count(*), sum(s.additions) FROM commits c JOIN stats s ON s.hash = c.hash";
+        let query = |db: &Connection| db.query_row(sql, [], |row| Ok((row.get(0)?, row.get(1)?)));
+        let threads = (0..4)
+            .map(|_| {
+                let git = git.clone();
+                std::thread::spawn(move || -> rusqlite::Result<Vec<(i64, i64)>> {
+                    let db = Connection::open_in_memory()?;
+                    git.register(&db)?;
+                    (0..5).map(|_| query(&db)).collect()
+                })
+            })
+            .collect::<Vec<_>>();
+        // A connection moved to another thread keeps working
+        let db = Connection::open_in_memory()?;
+        git.register(&db)?;
+        let moved = std::thread::spawn(move || query(&db))
+            .join()
+            .map_err(|_| "the thread panicked")??;
+        let mut counts = vec![moved];
+        for thread in threads {
+            counts.extend(thread.join().map_err(|_| "the thread panicked")??);
+        }
+        std::fs::remove_dir_all(&path)?;
+
+        // The root commit has no parent to diff against
+        assert!(counts.iter().all(|&count| count == (4, 4)));
+
+        Ok(())
+    }
+
+    /// Memory the process allocated, in bytes. Unlike the resident size it leaves out the mapped
+    /// pack files, the kernel drops those pages whenever it needs the memory.
+    fn allocated_memory() -> Option<u64> {
+        let status = std::fs::read_to_string("/proc/self/status").ok()?;
+        let kb = status
+            .lines()
+            .find_map(|line| line.strip_prefix("RssAnon:"))?
+            .trim()
+            .trim_end_matches("kB")
+            .trim()
+            .parse::<u64>()
+            .ok()?;
+        Some(kb * 1024)
+    }
+
+    #[test]
+    #[ignore = "builds a 300,000 commit repository, run with --ignored"]
+    fn huge_history_in_bounded_memory() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Write;
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+        const COMMITS: usize = 300_000;
+        let (path, repo) = temp_repository("huge_history")?;
+        let mut import = std::process::Command::new("git")
+            .args(["fast-import", "--quiet"])
+            .current_dir(&path)
+            .stdin(std::process::Stdio::piped())
+            .spawn()?;
+        {
+            let mut stream = std::io::BufWriter::new(import.stdin.take().ok_or("no stdin")?);
+            for i in 1..=COMMITS {
+                let message = format!("change {}\n", i);
+                let content = format!("{}\n", i);
+                writeln!(stream, "commit refs/heads/main\nmark :{}", i)?;
+                writeln!(
+                    stream,
+                    "committer Someone <someone@example.com> {} +0000",
+                    i * 60
+                )?;
+                write!(stream, "data {}\n{}", message.len(), message)?;
+                if i > 1 {
+                    writeln!(stream, "from :{}", i - 1)?;
+                }
+                // Every 100th commit merges an older one, so `merges` has rows to find
+                if i > 2 && i % 100 == 0 {
+                    writeln!(stream, "merge :{}", i - 2)?;
+                }
+                write!(
+                    stream,
+                    "M 644 inline file.txt\ndata {}\n{}",
+                    content.len(),
+                    content
+                )?;
+            }
+        }
+        if !import.wait()?.success() {
+            return Err("git fast-import failed".into());
+        }
+        repo.set_head("refs/heads/main")?;
+
+        let db = Connection::open_in_memory()?;
+        crate::SqliteGit::new()
+            .with_all()
+            .repository(&path)
+            .register(&db)?;
+        // libgit2's object cache is bounded on its own, without it what's left is the cursors
+        git2::opts::enable_caching(false);
+        let before = allocated_memory().ok_or("no RssAnon in /proc/self/status")?;
+        let done = Arc::new(AtomicBool::new(false));
+        let sampler = {
+            let done = done.clone();
+            std::thread::spawn(move || {
+                let mut peak = 0;
+                while !done.load(Ordering::SeqCst) {
+                    peak = peak.max(allocated_memory().unwrap_or_default());
+                    std::thread::sleep(std::time::Duration::from_millis(5));
+                }
+                peak
+            })
+        };
+        let count = |sql: &str| db.query_row(sql, [], |row| row.get::<_, i64>(0));
+        let commits = count("SELECT count(*) FROM commits")?;
+        let merges = count("SELECT count(*) FROM merges")?;
+        let messages = count("SELECT count(DISTINCT message) FROM commits")?;
+        let rev_count = count("SELECT git_rev_count('HEAD')")?;
+        done.store(true, Ordering::SeqCst);
+        git2::opts::enable_caching(true);
+        let grown = sampler
+            .join()
+            .map_err(|_| "the sampler panicked")?
+            .saturating_sub(before);
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!(commits, COMMITS as i64);
+        assert_eq!(merges, (COMMITS / 100) as i64);
+        assert_eq!(messages, COMMITS as i64);
+        assert_eq!(rev_count, COMMITS as i64);
+        // libgit2 keeps a node of about 100 bytes per walked commit, the rows must not pile up
+        assert!(grown < 200 * COMMITS as u64, "grew by {} bytes", grown);
+
+        Ok(())
+    }
+
+    /// An empty repository in the temp directory, `name` keeps tests running in parallel apart.
+    pub(crate) fn temp_repository(
+        name: &str,
+    ) -> Result<(std::path::PathBuf, git2::Repository), Box<dyn std::error::Error>> {
+        let path = std::env::temp_dir().join(format!("sqlitegit_{}", name));
+        let _ = std::fs::remove_dir_all(&path);
+        let repo = git2::Repository::init(&path)?;
+        Ok((path, repo))
+    }
+
+    /// Writes `content` to `path` and commits it on top of HEAD, a minute after the previous
+    /// commit so the history has a stable order.
+    pub(crate) fn commit_file(
+        repo: &git2::Repository,
+        path: &str,
+        content: &str,
+        message: &str,
+    ) -> Result<git2::Oid, Box<dyn std::error::Error>> {
+        let workdir = repo.workdir().ok_or("the repository is bare")?;
+        if let Some(dir) = workdir.join(path).parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(workdir.join(path), content)?;
+        let mut index = repo.index()?;
+        index.add_path(std::path::Path::new(path))?;
+        index.write()?;
+        let tree = repo.find_tree(index.write_tree()?)?;
+
+        let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+        let seconds = parent
+            .as_ref()
+            .map_or(1_656_000_000, |parent| parent.time().seconds() + 60);
+        let signature = git2::Signature::new(
+            "Someone",
+            "someone@example.com",
+            &git2::Time::new(seconds, 0),
+        )?;
+        let parents = parent.iter().collect::<Vec<_>>();
+        Ok(repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parents,
+        )?)
+    }
+}
@@ -0,0 +1,174 @@
+use crate::CustomError;
+use itertools::Itertools;
+use rusqlite::types::{Value, ValueRef};
+use rusqlite::{Connection, Statement};
+use std::path::Path;
+
+/// Columns worth indexing when they show up in a materialized result, these are the columns the
+/// git tables are usually joined and filtered on.
+const INDEXED_COLUMNS: [&str; 8] = [
+    "hash",
+    "parent_1",
+    "parent_2",
+    "author_email",
+    "committer_email",
+    "author_when",
+    "committer_when",
+    "file_name",
+];
+
+/// Copies the result of `stmt` into `table` of the SQLite database at `path`, creating the file
+/// if needed. Returns the number of rows written.
+///
+/// Columns keep the type declared by the git tables, expression columns get the type of their
+/// first value. Well known join columns like `hash` are indexed.
+pub fn execute_and_materialize(
+    stmt: &mut Statement,
+    path: &Path,
+    table: &str,
+    replace: bool,
+) -> Result<usize, CustomError> {
+    let names = unique_names(stmt.column_names());
+    let columns = (names.into_iter())
+        .zip(
+            stmt.columns()
+                .iter()
+                .map(|c| c.decl_type().map(String::from)),
+        )
+        .collect_vec();
+
+    let mut out = Connection::open(path)?;
+    let tx = out.transaction()?;
+    if replace {
+        tx.execute(&format!("DROP TABLE IF EXISTS {}", quote(table)), [])?;
+    }
+
+    let mut rows = stmt.raw_query();
+    let first = rows.next()?.map(to_values).transpose()?;
+
+    let definitions = columns
+        .iter()
+        .enumerate()
+        .map(|(i, (name, declared))| {
+            let declared = declared.clone().filter(|t| !t.is_empty());
+            let kind = declared.unwrap_or_else(|| match first.as_ref().map(|row| &row[i]) {
+                Some(Value::Integer(_)) => "INTEGER".to_string(),
+                Some(Value::Real(_)) => "REAL".to_string(),
+                Some(Value::Text(_)) => "TEXT".to_string(),
+                Some(Value::Blob(_)) => "BLOB".to_string(),
+                _ => String::new(),
+            });
+            format!("{} {}", quote(name), kind).trim_end().to_string()
+        })
+        .join(", ");
+    tx.execute(
+        &format!("CREATE TABLE {} ({})", quote(table), definitions),
+        [],
+    )?;
+
+    let mut count = 0;
+    {
+        let placeholders = (0..columns.len()).map(|_| "?").join(", ");
+        let mut insert = tx.prepare(&format!(
+            "INSERT INTO {} VALUES ({})",
+            quote(table),
+            placeholders
+        ))?;
+        if let Some(row) = first {
+            insert.execute(rusqlite::params_from_iter(row))?;
+            count += 1;
+        }
+        while let Some(row) = rows.next()? {
+            insert.execute(rusqlite::params_from_iter(to_values(row)?))?;
+            count += 1;
+        }
+    }
+
+    for (name, _) in columns
+        .iter()
+        .filter(|(name, _)| INDEXED_COLUMNS.contains(&name.as_str()))
+    {
+        tx.execute(
+            &format!(
+                "CREATE INDEX {} ON {} ({})",
+                quote(&format!("{}_{}_idx", table, name)),
+                quote(table),
+                quote(name)
+            ),
+            [],
+        )?;
+    }
+    tx.commit()?;
+
+    Ok(count)
+}
+
+/// The names of the result columns as columns of a table, a name that's taken already, like the
+/// second `hash` of `SELECT c.hash, s.hash`, gets a number: `hash_2`. SQLite compares them
+/// ignoring case.
+fn unique_names(names: Vec<&str>) -> Vec<String> {
+    let mut taken = std::collections::HashSet::new();
+    names
+        .into_iter()
+        .map(|name| {
+            let mut unique = name.to_string();
+            let mut n = 1;
+            while !taken.insert(unique.to_lowercase()) {
+                n += 1;
+                unique = format!("{}_{}", name, n);
+            }
+            unique
+        })
+        .collect()
+}
+
+fn to_values(row: &rusqlite::Row) -> rusqlite::Result<Vec<Value>> {
+    (0..row.as_ref().column_count())
+        .map(|i| row.get_ref(i).map(|v: ValueRef| Value::from(v)))
+        .try_collect()
+}
+
+fn quote(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod test {
+    use super::execute_and_materialize;
+    use rusqlite::Connection;
+
+    #[test]
+    fn numbers_repeated_column_names() -> Result<(), Box<dyn std::error::Error>> {
+        let path = std::env::temp_dir().join("sqlitegit_materialize_names.db");
+        let _ = std::fs::remove_file(&path);
+
+        let db = Connection::open_in_memory()?;
+        let mut stmt = db.prepare("SELECT 'a' AS hash, 'b' AS hash, 'c' AS HASH, 'd' AS hash_2")?;
+        let count = execute_and_materialize(&mut stmt, &path, "pairs", false)?;
+        let out = Connection::open(&path)?;
+        let columns = out
+            .prepare("SELECT name FROM pragma_table_info('pairs')")?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        let row = out.query_row(
+            "SELECT hash, hash_2, HASH_3, hash_2_2 FROM pairs",
+            [],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                ))
+            },
+        )?;
+        drop(out);
+        std::fs::remove_file(&path)?;
+
+        assert_eq!(count, 1);
+        assert_eq!(columns, vec!["hash", "hash_2", "HASH_3", "hash_2_2"]);
+        assert_eq!(row, ("a".into(), "b".into(), "c".into(), "d".into()));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,104 @@
+use rusqlite::Connection;
+
+/// Adds the commits of the `commits` table that aren't indexed yet to the full-text index over
+/// commit messages in `schema`, creating the index first if needed. Returns the number of
+/// commits added.
+///
+/// The messages are kept in `commit_messages`, keyed by hash, and `commit_messages_fts` is an
+/// FTS5 index with `commit_messages` as its external content:
+///
+/// ```sql
+/// SELECT m.hash, m.message FROM commit_messages_fts f
+/// JOIN commit_messages m ON m.rowid = f.rowid
+/// WHERE commit_messages_fts MATCH 'crash NEAR fix'
+/// ```
+///
+/// Commits that are no longer reachable, e.g. after a rebase, stay in the index.
+pub fn refresh_message_index(db: &Connection, schema: &str) -> rusqlite::Result<usize> {
+    let tx = db.unchecked_transaction()?;
+    tx.execute_batch(&format!(
+        r#"
+        CREATE TABLE IF NOT EXISTS "{schema}".commit_messages (
+            hash TEXT NOT NULL UNIQUE,
+            message TEXT,
+            author_name TEXT,
+            author_email TEXT,
+            author_when DATETIME
+        );
+        CREATE VIRTUAL TABLE IF NOT EXISTS "{schema}".commit_messages_fts USING fts5(
+            message,
+            content = 'commit_messages',
+            content_rowid = 'rowid'
+        );
+        "#
+    ))?;
+
+    let last_rowid: i64 = tx.query_row(
+        &format!(r#"SELECT coalesce(max(rowid), 0) FROM "{schema}".commit_messages"#),
+        [],
+        |row| row.get(0),
+    )?;
+    let added = tx.execute(
+        &format!(
+            r#"
+            INSERT INTO "{schema}".commit_messages (hash, message, author_name, author_email, author_when)
+            SELECT hash, message, author_name, author_email, author_when FROM commits c
+            WHERE NOT EXISTS (SELECT 1 FROM "{schema}".commit_messages m WHERE m.hash = c.hash)
+            ORDER BY author_when
+            "#
+        ),
+        [],
+    )?;
+    tx.execute(
+        &format!(
+            r#"
+            INSERT INTO "{schema}".commit_messages_fts (rowid, message)
+            SELECT rowid, message FROM "{schema}".commit_messages WHERE rowid > ?
+            "#
+        ),
+        [last_rowid],
+    )?;
+    tx.commit()?;
+
+    Ok(added)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::message_index::refresh_message_index;
+    use crate::test::{commit_file, temp_repository};
+    use crate::SqliteGit;
+    use rusqlite::Connection;
+
+    #[test]
+    fn refreshes_incrementally() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("message_index")?;
+        commit_file(&repo, "file.txt", "one\n", "Add the first file")?;
+        commit_file(&repo, "file.txt", "two\n", "Fix a crash in the parser")?;
+
+        let db = Connection::open_in_memory()?;
+        SqliteGit::new()
+            .with_commits()
+            .repository(&path)
+            .register(&db)?;
+        let first = refresh_message_index(&db, "main")?;
+        let unchanged = refresh_message_index(&db, "main")?;
+        let fixed = commit_file(&repo, "file.txt", "three\n", "Fix the parser again")?;
+        let added = refresh_message_index(&db, "main")?;
+
+        let matches: Vec<String> = db
+            .prepare(
+                "SELECT m.hash FROM commit_messages_fts f
+                 JOIN commit_messages m ON m.rowid = f.rowid
+                 WHERE commit_messages_fts MATCH 'fix AND again'",
+            )?
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!((first, unchanged, added), (2, 0, 1));
+        assert_eq!(matches, vec![fixed.to_string()]);
+
+        Ok(())
+    }
+}
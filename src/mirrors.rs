@@ -0,0 +1,235 @@
+use crate::credentials::Credentials;
+use crate::interrupt::Checkpoint;
+use crate::CustomError;
+use git2::build::RepoBuilder;
+use git2::{
+    AutotagOption, Config, FetchOptions, FetchPrune, ObjectType, Oid, RemoteCallbacks, Repository,
+};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use tracing::info;
+
+/// Fetches every ref of the remote to the same name, the mirror has the branches and tags of the
+/// remote as its own.
+const MIRROR_REFSPEC: &str = "+refs/*:refs/*";
+
+/// Bare mirrors of remote repositories, kept below a cache directory so the tables can read a
+/// repository by its URL, e.g. `commits('https://github.com/rust-lang/log')`.
+///
+/// A mirror is cloned the first time its URL is read. Afterwards it's fetched the first time the
+/// connections read it, and not again while they're open.
+#[derive(Debug, Clone)]
+pub(crate) struct Mirrors {
+    dir: PathBuf,
+    /// Whether a mirror is up to date by its path, shared by every clone. The map is only locked
+    /// to look up a mirror, the mirror's own lock is held while it's cloned or fetched
+    updated: Arc<Mutex<HashMap<PathBuf, Arc<Mutex<bool>>>>>,
+}
+
+impl Mirrors {
+    pub(crate) fn new(dir: PathBuf) -> Mirrors {
+        Mirrors {
+            dir,
+            updated: Arc::default(),
+        }
+    }
+
+    /// The path of the mirror of `url`, cloned or fetched first unless it already was.
+    pub(crate) fn open(&self, url: &str, checkpoint: &Checkpoint) -> Result<PathBuf, CustomError> {
+        let path = self.dir.join(mirror_name(url)?);
+        // Held while fetching, so connections reading the same mirror wait for the one fetch
+        let mirror = self.mirror(&path);
+        let mut updated = mirror.lock().unwrap_or_else(PoisonError::into_inner);
+        if *updated {
+            return Ok(path);
+        }
+        match path.exists() {
+            true => fetch(&Repository::open_bare(&path)?, checkpoint)?,
+            false => clone(url, &path, checkpoint)?,
+        }
+        *updated = true;
+        Ok(path)
+    }
+
+    /// The path of the mirror of `url`, cloned first unless it exists, for a caller fetching it
+    /// itself. The mirror counts as up to date afterwards.
+    pub(crate) fn cloned(
+        &self,
+        url: &str,
+        checkpoint: &Checkpoint,
+    ) -> Result<PathBuf, CustomError> {
+        let path = self.dir.join(mirror_name(url)?);
+        let mirror = self.mirror(&path);
+        let mut updated = mirror.lock().unwrap_or_else(PoisonError::into_inner);
+        if !path.exists() {
+            clone(url, &path, checkpoint)?;
+        }
+        *updated = true;
+        Ok(path)
+    }
+
+    /// The lock of the mirror at `path`, holding whether it's up to date.
+    fn mirror(&self, path: &Path) -> Arc<Mutex<bool>> {
+        let mut updated = self.updated.lock().unwrap_or_else(PoisonError::into_inner);
+        updated.entry(path.to_path_buf()).or_default().clone()
+    }
+}
+
+/// Whether the repository argument of a table is the URL of a remote instead of a path, like
+/// `https://host/repo`, `ssh://git@host/repo` or `git@host:repo`.
+pub(crate) fn is_url(repo: &str) -> bool {
+    let schemes = ["https://", "http://", "ssh://", "git://", "file://"];
+    if schemes.iter().any(|scheme| repo.starts_with(scheme)) {
+        return true;
+    }
+    // The scp-like syntax of ssh, a user and a host before a colon and no slash before it
+    match repo.split_once(':') {
+        Some((host, _)) => host.contains('@') && !host.contains('/'),
+        None => false,
+    }
+}
+
+/// The directory of the mirror of `url` below the cache directory, the host and the path of
+/// the URL and a hash of them, e.g. `github.com/rust-lang/log-0123abcd.git`. The URLs of a
+/// repository over https and over ssh share a mirror, the hash keeps apart the ones whose names
+/// only differ in the characters that are replaced, like `a b` and `a_b`.
+fn mirror_name(url: &str) -> Result<PathBuf, CustomError> {
+    let (rest, scp) = match url.split_once("://") {
+        Some((_, rest)) => (rest, false),
+        None => (url, true),
+    };
+    // The user isn't part of the name, an `@` after the host is part of the path
+    let rest = match rest.find('@') {
+        Some(at) if !rest[..at].contains('/') => &rest[at + 1..],
+        _ => rest,
+    };
+    // `git@host:org/repo` is `host/org/repo`, a port stays part of the host
+    let rest = match scp {
+        true => rest.replacen(':', "/", 1),
+        false => rest.to_string(),
+    };
+    let components = (rest.split('/'))
+        .filter(|component| !matches!(*component, "" | "." | ".."))
+        .map(|component| {
+            let safe = |c: char| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_');
+            component.replace(|c| !safe(c), "_")
+        });
+    let rest = rest.strip_suffix(".git").unwrap_or(&rest);
+    let hash = Oid::hash_object(ObjectType::Blob, rest.as_bytes())?.to_string();
+    let mut name = components.collect::<PathBuf>();
+    let repo = name.file_name().unwrap_or_default().to_string_lossy();
+    let repo = format!(
+        "{}-{}.git",
+        repo.strip_suffix(".git").unwrap_or(&repo),
+        &hash[..8]
+    );
+    name.set_file_name(repo);
+    Ok(name)
+}
+
+/// Callbacks stopping a transfer once the statement is interrupted, which authenticate with the
+/// credential helpers of `config`, or of the global git config without one.
+pub(crate) fn callbacks(checkpoint: &Checkpoint, config: Option<Config>) -> RemoteCallbacks<'_> {
+    let mut callbacks = RemoteCallbacks::new();
+    let mut credentials = Credentials::new(config);
+    callbacks.credentials(move |url, username, allowed| credentials.next(url, username, allowed));
+    callbacks.transfer_progress(|_| checkpoint.check().is_ok());
+    callbacks
+}
+
+/// Clones a bare mirror of `url` to `path`. A clone that fails halfway leaves nothing behind, it
+/// is made next to `path` and moved there when it's complete.
+fn clone(url: &str, path: &Path, checkpoint: &Checkpoint) -> Result<(), CustomError> {
+    let partial = path.with_extension("partial");
+    if partial.exists() {
+        std::fs::remove_dir_all(&partial)?;
+    }
+    let mut options = FetchOptions::new();
+    options.remote_callbacks(callbacks(checkpoint, None));
+    info!(url, path = %path.display(), "cloning mirror");
+    let cloned = RepoBuilder::new()
+        .bare(true)
+        .fetch_options(options)
+        .remote_create(|repo, name, url| repo.remote_with_fetch(name, url, MIRROR_REFSPEC))
+        .clone(url, &partial);
+    // An interrupted transfer fails with a generic error
+    checkpoint.check()?;
+    cloned?;
+    std::fs::rename(&partial, path)?;
+    Ok(())
+}
+
+/// Fetches the refs of the mirror's remote, the branches and tags that were deleted there are
+/// deleted too.
+fn fetch(repo: &Repository, checkpoint: &Checkpoint) -> Result<(), CustomError> {
+    let mut remote = repo.find_remote("origin")?;
+    let mut options = FetchOptions::new();
+    options
+        .remote_callbacks(callbacks(checkpoint, repo.config().ok()))
+        .prune(FetchPrune::On)
+        .download_tags(AutotagOption::All);
+    info!(url = remote.url().unwrap_or_default(), path = %repo.path().display(), "fetching mirror");
+    let fetched = remote.fetch(&[MIRROR_REFSPEC], Some(&mut options), None);
+    checkpoint.check()?;
+    fetched?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::mirrors::{is_url, mirror_name};
+    use crate::test::{commit_file, temp_repository};
+    use crate::SqliteGit;
+    use rusqlite::Connection;
+
+    #[test]
+    fn queries_a_mirror_of_a_url() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("mirrors_remote")?;
+        commit_file(&repo, "file.txt", "one\n", "first")?;
+        let mirrors = std::env::temp_dir().join("sqlitegit_mirrors");
+        let _ = std::fs::remove_dir_all(&mirrors);
+        let url = format!("file://{}", path.display());
+        let sql = "SELECT group_concat(message, ',') FROM commits(?)";
+        let messages = || -> Result<String, Box<dyn std::error::Error>> {
+            let db = Connection::open_in_memory()?;
+            SqliteGit::new()
+                .with_all()
+                .mirrors(&mirrors)
+                .register(&db)?;
+            // The second read of a connection doesn't fetch again
+            db.query_row(sql, [&url], |row| row.get::<_, String>(0))?;
+            Ok(db.query_row(sql, [&url], |row| row.get(0))?)
+        };
+
+        let cloned = messages()?;
+        commit_file(&repo, "file.txt", "two\n", "second")?;
+        let fetched = messages()?;
+        let mirror = mirrors.join(mirror_name(&url)?);
+        let bare = mirror.join("HEAD").exists();
+        std::fs::remove_dir_all(&path)?;
+        std::fs::remove_dir_all(&mirrors)?;
+
+        assert_eq!(cloned, "first");
+        assert_eq!(fetched, "second,first");
+        assert!(bare);
+        assert!(is_url("git@github.com:rust-lang/log.git"));
+        assert!(!is_url("../log") && !is_url("C:\\log"));
+        assert_eq!(
+            mirror_name("git@github.com:rust-lang/log.git")?,
+            mirror_name("https://github.com/rust-lang/log")?
+        );
+        let name = mirror_name("ssh://git@host:22/../repo")?;
+        assert!(
+            name.to_string_lossy().starts_with("host_22/repo-"),
+            "{:?}",
+            name
+        );
+        assert_ne!(
+            mirror_name("https://host/a b")?,
+            mirror_name("https://host/a_b")?
+        );
+
+        Ok(())
+    }
+}
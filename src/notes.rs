@@ -0,0 +1,178 @@
+use crate::writable::{required, text, Row, Table};
+use crate::CustomError;
+use git2::{Oid, Repository};
+use rusqlite::types::Value;
+
+/// `notes([repo])`, the git notes of a repository, of every notes ref. INSERT attaches a note to
+/// the object `hash` resolves to, in `notes_ref`, the default notes ref (`core.notesRef`,
+/// `refs/notes/commits` unless configured) when it's NULL. UPDATE changes a note or moves it to
+/// another object or notes ref, DELETE removes it. The notes are committed with the configured
+/// identity.
+pub(crate) static NOTES: Table = Table {
+    name: "notes",
+    columns: &[("hash", "text"), ("note", "text"), ("notes_ref", "text")],
+    rows,
+    insert,
+    update: Some(update),
+    delete,
+};
+
+const HASH: usize = 0;
+const NOTE: usize = 1;
+const NOTES_REF: usize = 2;
+
+fn rows(repo: &Repository) -> Result<Vec<Row>, CustomError> {
+    let mut rows = vec![];
+    for reference in repo.references_glob("refs/notes/*")? {
+        let reference = reference?;
+        let Some(notes_ref) = reference.name() else {
+            continue;
+        };
+        for note in repo.notes(Some(notes_ref))? {
+            let (_, annotated) = note?;
+            let note = repo.find_note(Some(notes_ref), annotated)?;
+            rows.push(vec![
+                Value::Text(annotated.to_string()),
+                Value::Text(String::from_utf8_lossy(note.message_bytes()).to_string()),
+                Value::Text(notes_ref.to_string()),
+            ]);
+        }
+    }
+    Ok(rows)
+}
+
+fn insert(repo: &Repository, row: &Row) -> Result<(), CustomError> {
+    let (annotated, notes_ref) = target(repo, row)?;
+    add(repo, row, annotated, &notes_ref, false)
+}
+
+fn update(repo: &Repository, old: &Row, new: &Row) -> Result<(), CustomError> {
+    let (old_annotated, old_ref) = target(repo, old)?;
+    let (annotated, notes_ref) = target(repo, new)?;
+    if (old_annotated, &old_ref) == (annotated, &notes_ref) {
+        return add(repo, new, annotated, &notes_ref, true);
+    }
+    add(repo, new, annotated, &notes_ref, false)?;
+    remove(repo, old_annotated, &old_ref)
+}
+
+fn delete(repo: &Repository, row: &Row) -> Result<(), CustomError> {
+    let (annotated, notes_ref) = target(repo, row)?;
+    remove(repo, annotated, &notes_ref)
+}
+
+/// The object a row's note is attached to and its notes ref.
+fn target(repo: &Repository, row: &Row) -> Result<(Oid, String), CustomError> {
+    let annotated = repo.revparse_single(required(&NOTES, row, HASH)?)?.id();
+    let notes_ref = match text(&NOTES, row, NOTES_REF)? {
+        Some(name) if name.starts_with("refs/") => name.to_string(),
+        // A short name like `git notes --ref` takes
+        Some(name) => format!("refs/notes/{}", name),
+        None => repo.note_default_ref()?,
+    };
+    Ok((annotated, notes_ref))
+}
+
+fn add(
+    repo: &Repository,
+    row: &Row,
+    annotated: Oid,
+    notes_ref: &str,
+    force: bool,
+) -> Result<(), CustomError> {
+    let signature = repo.signature()?;
+    let note = required(&NOTES, row, NOTE)?;
+    repo.note(
+        &signature,
+        &signature,
+        Some(notes_ref),
+        annotated,
+        note,
+        force,
+    )?;
+    Ok(())
+}
+
+fn remove(repo: &Repository, annotated: Oid, notes_ref: &str) -> Result<(), CustomError> {
+    let signature = repo.signature()?;
+    Ok(repo.note_delete(annotated, Some(notes_ref), &signature, &signature)?)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test::{commit_file, temp_repository};
+    use crate::SqliteGit;
+    use rusqlite::Connection;
+
+    type Note = (String, String, String);
+
+    fn notes(db: &Connection) -> rusqlite::Result<Vec<Note>> {
+        let mut stmt = db.prepare("SELECT hash, note, notes_ref FROM notes ORDER BY notes_ref")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+        rows.collect()
+    }
+
+    #[test]
+    fn attaches_changes_and_removes_notes() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("notes")?;
+        let first = commit_file(&repo, "file.txt", "one\n", "first")?;
+        let second = commit_file(&repo, "file.txt", "two\n", "second")?;
+        let mut config = repo.config()?;
+        config.set_str("user.name", "Pipeline")?;
+        config.set_str("user.email", "pipeline@example.com")?;
+
+        let db = Connection::open_in_memory()?;
+        SqliteGit::new()
+            .with_notes()
+            .repository(&path)
+            .register(&db)?;
+        db.execute_batch(
+            "BEGIN;
+             INSERT INTO notes(hash, note) VALUES ('HEAD', 'coverage: 81%');
+             INSERT INTO notes(hash, note, notes_ref) VALUES ('HEAD~1', 'slow', 'perf');
+             COMMIT;",
+        )?;
+        let attached = notes(&db)?;
+        let existing = db.execute("INSERT INTO notes(hash, note) VALUES ('HEAD', 'again')", []);
+        db.execute_batch(
+            "BEGIN;
+             UPDATE notes SET note = 'coverage: 83%' WHERE notes_ref = 'refs/notes/commits';
+             UPDATE notes SET hash = 'HEAD', notes_ref = 'refs/notes/review' WHERE note = 'slow';
+             COMMIT;",
+        )?;
+        let changed = notes(&db)?;
+        let note = repo.find_note(Some("refs/notes/review"), second)?;
+        let note = note.message().map(String::from);
+        db.execute(
+            "DELETE FROM notes WHERE notes_ref = 'refs/notes/commits'",
+            [],
+        )?;
+        let removed = notes(&db)?;
+        let gone = repo.find_note(None, second).is_err();
+        std::fs::remove_dir_all(&path)?;
+
+        let row = |hash: git2::Oid, note: &str, notes_ref: &str| {
+            (hash.to_string(), note.to_string(), notes_ref.to_string())
+        };
+        assert_eq!(
+            attached,
+            vec![
+                row(second, "coverage: 81%", "refs/notes/commits"),
+                row(first, "slow", "refs/notes/perf"),
+            ]
+        );
+        assert!(existing.is_err());
+        assert_eq!(
+            changed,
+            vec![
+                row(second, "coverage: 83%", "refs/notes/commits"),
+                row(second, "slow", "refs/notes/review"),
+            ]
+        );
+        assert_eq!(note.as_deref(), Some("slow"));
+        assert_eq!(removed, vec![row(second, "slow", "refs/notes/review")]);
+        assert!(gone);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,864 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, FixedOffset, Local, Utc};
+use itertools::Itertools;
+use rusqlite::types::{Type, ValueRef};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::time::{Duration, Instant};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Widest a column of a table is printed by default, longer values are cut off.
+pub const DEFAULT_MAX_WIDTH: usize = 50;
+
+/// Rows a table is sized by before it's written, the rows after them stream out.
+const TABLE_SAMPLE_ROWS: usize = 100;
+/// How long a table waits for its first rows before it's written.
+const TABLE_SAMPLE_TIME: Duration = Duration::from_millis(200);
+
+/// How the tables return DATETIME columns, `2022-06-23 16:00:00+00:00`.
+const TIMESTAMP_FORMAT: &str = "%F %T%.f%:z";
+
+/// Rows of a result set printed to a terminal by default, the rest are only counted.
+pub const DEFAULT_ROW_LIMIT: usize = 1000;
+
+// The ANSI styles of colored tables, each with the code that ends it without ending the others
+const BOLD: (&str, &str) = ("\x1b[1m", "\x1b[22m");
+const DIM: (&str, &str) = ("\x1b[2m", "\x1b[22m");
+/// The background of every other row
+const STRIPE: (&str, &str) = ("\x1b[48;5;236m", "\x1b[49m");
+
+/// The start of an html page up to its table's header row, the page has everything it needs to
+/// be attached or mailed: clicking a header sorts the rows by the column, again reverses them.
+const HTML_START: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>sqlitegit</title>
+<style>
+body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; font-size: 14px; }
+th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: left; vertical-align: top; white-space: pre-wrap; }
+th { background: #eee; cursor: pointer; user-select: none; }
+th[data-sort="asc"]::after { content: " \25B2"; }
+th[data-sort="desc"]::after { content: " \25BC"; }
+tr:nth-child(even) td { background: #f8f8f8; }
+td.number { text-align: right; }
+td.null { color: #999; }
+</style>
+</head>
+<body>
+<table>
+<thead>
+<tr>"#;
+
+/// The end of an html page after its table's rows.
+const HTML_END: &str = r#"</tbody>
+</table>
+<script>
+document.querySelectorAll("th").forEach((th, column) => th.addEventListener("click", () => {
+  const body = document.querySelector("tbody");
+  const descending = th.dataset.sort === "asc";
+  const key = row => row.children[column];
+  const compare = (a, b) => {
+    const [x, y] = [key(a), key(b)];
+    // NULLs come first, numbers are compared as numbers
+    if (x.classList.contains("null") || y.classList.contains("null")) {
+      return y.classList.contains("null") - x.classList.contains("null");
+    }
+    if (x.classList.contains("number") && y.classList.contains("number")) {
+      return x.textContent - y.textContent;
+    }
+    return x.textContent.localeCompare(y.textContent);
+  };
+  const rows = Array.from(body.rows).sort((a, b) => descending ? compare(b, a) : compare(a, b));
+  document.querySelectorAll("th").forEach(other => delete other.dataset.sort);
+  th.dataset.sort = descending ? "desc" : "asc";
+  body.append(...rows);
+}));
+</script>
+</body>
+</html>
+"#;
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputMode {
+    Table,
+    Json,
+    Ndjson,
+    Csv,
+    Tsv,
+    Markdown,
+    /// Every column of a row on a line of its own, like psql's `\x`
+    Expanded,
+    /// Tab-separated values without a header row, for piping into awk, cut or xargs
+    Plain,
+    /// A standalone page with a table sorted by clicking its header
+    Html,
+    /// A Graphviz graph of edges from the first column to the second, e.g. a commit to its
+    /// parent, the third column labels the first
+    Dot,
+}
+
+/// The time zone timestamps are written in, the git tables return them in UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeZoneChoice {
+    /// The time zone of this machine
+    Local,
+    #[default]
+    Utc,
+    /// The offset the timestamp was returned with, e.g. the one of its author
+    Original,
+}
+
+impl TimeZoneChoice {
+    fn convert(self, time: DateTime<FixedOffset>) -> DateTime<FixedOffset> {
+        match self {
+            TimeZoneChoice::Local => time.with_timezone(&Local).fixed_offset(),
+            TimeZoneChoice::Utc => time.with_timezone(&Utc).fixed_offset(),
+            TimeZoneChoice::Original => time,
+        }
+    }
+}
+
+/// A strftime-style format DATETIME columns are written in, e.g. `%d %b %Y %H:%M`.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+#[serde(try_from = "String")]
+pub struct DateFormat(String);
+
+impl std::str::FromStr for DateFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // chrono panics writing a format it can't parse
+        match StrftimeItems::new(s).any(|item| item == Item::Error) {
+            true => Err(format!("{} isn't a strftime date format", s)),
+            false => Ok(DateFormat(s.to_string())),
+        }
+    }
+}
+
+impl TryFrom<String> for DateFormat {
+    type Error = String;
+
+    fn try_from(format: String) -> Result<Self, Self::Error> {
+        format.parse()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OutputOptions {
+    pub mode: OutputMode,
+    /// Whether csv and tsv output starts with a header row
+    pub headers: bool,
+    /// Whether tables are printed with colors
+    pub color: bool,
+    /// Widest a column of a table is printed, 0 doesn't cut values off
+    pub max_width: usize,
+    /// Columns with a width of their own instead of `max_width`
+    pub widths: Vec<ColumnWidth>,
+    /// Rows written before the rest are only counted, None writes them all
+    pub limit: Option<RowLimit>,
+    /// The time zone the values of DATETIME columns are written in
+    pub timezone: TimeZoneChoice,
+    /// The format DATETIME columns are written in whatever the mode, instead of `date_formats`
+    pub date_format: Option<DateFormat>,
+    /// The formats DATETIME columns are written in by mode, the others write them like the
+    /// tables return them
+    pub date_formats: BTreeMap<OutputMode, DateFormat>,
+}
+
+impl Default for OutputOptions {
+    fn default() -> Self {
+        OutputOptions {
+            mode: OutputMode::Table,
+            headers: true,
+            color: false,
+            max_width: DEFAULT_MAX_WIDTH,
+            widths: vec![],
+            limit: None,
+            timezone: TimeZoneChoice::Utc,
+            date_format: None,
+            date_formats: BTreeMap::new(),
+        }
+    }
+}
+
+impl OutputOptions {
+    /// The writer of the mode for a result set with `columns`.
+    pub(crate) fn writer(&self, columns: &[String]) -> Box<dyn OutputWriter> {
+        let columns = columns.to_vec();
+        match self.mode {
+            OutputMode::Table => Box::new(Table::new(&columns, self)),
+            OutputMode::Json => Box::new(Json { columns, rows: 0 }),
+            OutputMode::Ndjson => Box::new(Ndjson { columns }),
+            OutputMode::Csv => Box::new(Delimited::csv(columns, self.headers)),
+            OutputMode::Tsv => Box::new(Delimited::tsv(columns, self.headers)),
+            OutputMode::Plain => Box::new(Delimited::tsv(columns, false)),
+            OutputMode::Markdown => Box::new(Markdown { columns }),
+            OutputMode::Expanded => Box::new(Expanded {
+                columns,
+                color: self.color,
+                rows: 0,
+            }),
+            OutputMode::Html => Box::new(Html { columns }),
+            OutputMode::Dot => Box::new(Dot),
+        }
+    }
+
+    /// Whether DATETIME columns are written other than the tables return them.
+    pub(crate) fn formats_datetimes(&self) -> bool {
+        self.timezone != TimeZoneChoice::Original || self.date_format().is_some()
+    }
+
+    /// The DATETIME `text` in the time zone and the date format of the mode, None when it's
+    /// written as it is or isn't a timestamp.
+    pub(crate) fn datetime(&self, text: &str) -> Option<String> {
+        let format = self.date_format();
+        // Saves parsing the timestamps of the tables in the default time zone
+        if format.is_none() && self.timezone == TimeZoneChoice::Utc && text.ends_with("+00:00") {
+            return None;
+        }
+        let time = DateTime::parse_from_str(text, TIMESTAMP_FORMAT).ok()?;
+        let converted = self.timezone.convert(time);
+        match format {
+            Some(DateFormat(format)) => Some(converted.format(format).to_string()),
+            None if converted.offset() == time.offset() => None,
+            None => Some(converted.format(TIMESTAMP_FORMAT).to_string()),
+        }
+    }
+
+    fn date_format(&self) -> Option<&DateFormat> {
+        (self.date_format.as_ref()).or_else(|| self.date_formats.get(&self.mode))
+    }
+
+    /// Widest the column `name` of a table is printed, 0 when its values aren't cut off.
+    fn max_width(&self, name: &str) -> usize {
+        let width = self.widths.iter().find(|width| width.column == name);
+        width.map_or(self.max_width, |width| width.width)
+    }
+}
+
+/// How many rows of a result set are written, so selecting the whole history by accident
+/// doesn't flood the terminal.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RowLimit {
+    pub rows: usize,
+    /// How to write every row instead, e.g. `--no-limit`
+    pub hint: &'static str,
+}
+
+/// The widest a column of a table is printed, `NAME=WIDTH`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnWidth {
+    pub column: String,
+    /// 0 doesn't cut the values of the column off
+    pub width: usize,
+}
+
+impl std::str::FromStr for ColumnWidth {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (column, width) = (s.rsplit_once('='))
+            .ok_or_else(|| format!("{} isn't a column and its width, NAME=WIDTH", s))?;
+        let width = (width.parse())
+            .map_err(|_| format!("the width of {} isn't a number: {}", column, width))?;
+        Ok(ColumnWidth {
+            column: column.to_string(),
+            width,
+        })
+    }
+}
+
+/// Writes a result set in an output mode: what comes before the rows, the rows a row at a time
+/// as the statement produces them, and what comes after the rows.
+pub(crate) trait OutputWriter {
+    /// Writes what comes before the rows, the header row or the opening bracket.
+    fn start(&mut self, _out: &mut dyn Write) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    fn row(&mut self, out: &mut dyn Write, values: &[ValueRef]) -> std::io::Result<()>;
+
+    /// Writes what comes after the rows.
+    fn finish(&mut self, _out: &mut dyn Write) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes rows that were already read, e.g. the result the tui shows, like
+/// [`execute_and_write`](crate::utils::execute_and_write) writes the rows of a statement.
+#[cfg(feature = "tui")]
+pub(crate) fn write_rows<'v>(
+    columns: &[String],
+    rows: impl Iterator<Item = Vec<ValueRef<'v>>>,
+    out: &mut dyn Write,
+    options: &OutputOptions,
+) -> std::io::Result<()> {
+    let mut writer = options.writer(columns);
+    writer.start(out)?;
+    for row in rows {
+        writer.row(out, &row)?;
+    }
+    writer.finish(out)
+}
+
+/// A table under a header row, numbers are aligned to the right. Values are cut off at the widths
+/// of the options, measured in columns of the terminal. With color the header is bold, NULLs are
+/// dim and every other row is striped.
+///
+/// The columns are sized by the first rows, written as soon as [`TABLE_SAMPLE_ROWS`] were read or
+/// [`TABLE_SAMPLE_TIME`] passed. The rows after them stream out at those widths, longer values are
+/// cut off.
+pub(crate) struct Table {
+    /// The names of the columns, cut off, and how wide their values may be
+    names: Vec<(String, usize)>,
+    /// The rows the widths are taken from
+    rows: Vec<Vec<(String, Type)>>,
+    color: bool,
+    /// The widths of the columns once the header was written
+    widths: Option<Vec<usize>>,
+    written: usize,
+    started: Instant,
+}
+
+impl Table {
+    pub(crate) fn new(columns: &[String], options: &OutputOptions) -> Table {
+        let names = columns.iter().map(|name| {
+            let max_width = options.max_width(name);
+            (cut(name, max_width), max_width)
+        });
+        Table {
+            names: names.collect(),
+            rows: vec![],
+            color: options.color,
+            widths: None,
+            written: 0,
+            started: Instant::now(),
+        }
+    }
+
+    pub(crate) fn columns(&self) -> usize {
+        self.names.len()
+    }
+
+    pub(crate) fn push(&mut self, values: &[ValueRef]) {
+        let row = self.cells(values);
+        self.rows.push(row);
+    }
+
+    /// The lines of the table of every row pushed, sized by all of them.
+    pub(crate) fn lines(&self) -> Vec<String> {
+        let widths = self.sampled_widths();
+        let rows = (self.rows.iter().enumerate()).map(|(i, row)| self.line(i, row, &widths));
+        self.header(&widths).into_iter().chain(rows).collect()
+    }
+
+    /// The cells of a row, text wider than its column may be is cut off. Numbers never are.
+    fn cells(&self, values: &[ValueRef]) -> Vec<(String, Type)> {
+        let cells = (self.names.iter().zip(values)).map(|((_, max_width), value)| {
+            let text = table_cell(*value);
+            match value.data_type() {
+                kind @ (Type::Integer | Type::Real) => (text, kind),
+                kind => (cut(&text, *max_width), kind),
+            }
+        });
+        cells.collect()
+    }
+
+    fn sampled_widths(&self) -> Vec<usize> {
+        (0..self.names.len())
+            .map(|i| {
+                let values = self.rows.iter().map(|row| row[i].0.width());
+                let widest = values.chain([self.names[i].0.width()]).max();
+                widest.unwrap_or_default()
+            })
+            .collect()
+    }
+
+    fn style(&self, text: String, (start, end): (&str, &str), styled: bool) -> String {
+        match self.color && styled {
+            true => format!("{}{}{}", start, text, end),
+            false => text,
+        }
+    }
+
+    /// The header row and the line under it.
+    fn header(&self, widths: &[usize]) -> [String; 2] {
+        let header = (self.names.iter().zip(widths))
+            .map(|((name, _), &width)| self.style(pad(name, width, false), BOLD, true))
+            .join(" | ");
+        let line = "-".repeat(widths.iter().sum::<usize>() + self.names.len() * 3 + 1);
+        [format!("| {} |", header), line]
+    }
+
+    /// The `i`th row, text wider than its column is cut off. Numbers are never cut, one wider
+    /// than its column pushes the rest of the row to the right.
+    fn line(&self, i: usize, row: &[(String, Type)], widths: &[usize]) -> String {
+        let cells = row.iter().zip(widths).map(|((text, kind), &width)| {
+            let number = matches!(kind, Type::Integer | Type::Real);
+            let text = match number {
+                true => text.clone(),
+                false => cut(text, width.max(1)),
+            };
+            self.style(pad(&text, width, number), DIM, *kind == Type::Null)
+        });
+        let line = format!("| {} |", cells.collect_vec().join(" | "));
+        self.style(line, STRIPE, i % 2 == 1)
+    }
+
+    /// Writes the header and the sampled rows, the widths are fixed from then on.
+    fn write_sample(&mut self, out: &mut dyn Write) -> std::io::Result<()> {
+        let widths = self.sampled_widths();
+        for line in self.header(&widths) {
+            writeln!(out, "{}", line)?;
+        }
+        for (i, row) in self.rows.iter().enumerate() {
+            writeln!(out, "{}", self.line(i, row, &widths))?;
+        }
+        self.written = std::mem::take(&mut self.rows).len();
+        self.widths = Some(widths);
+        out.flush()
+    }
+}
+
+impl OutputWriter for Table {
+    fn row(&mut self, out: &mut dyn Write, values: &[ValueRef]) -> std::io::Result<()> {
+        let row = self.cells(values);
+        match &self.widths {
+            Some(widths) => {
+                writeln!(out, "{}", self.line(self.written, &row, widths))?;
+                self.written += 1;
+                out.flush()
+            }
+            None => {
+                self.rows.push(row);
+                let sampled = self.rows.len() >= TABLE_SAMPLE_ROWS
+                    || self.started.elapsed() >= TABLE_SAMPLE_TIME;
+                match sampled {
+                    true => self.write_sample(out),
+                    false => Ok(()),
+                }
+            }
+        }
+    }
+
+    fn finish(&mut self, out: &mut dyn Write) -> std::io::Result<()> {
+        match self.widths {
+            Some(_) => Ok(()),
+            None => self.write_sample(out),
+        }
+    }
+}
+
+/// An array of objects keyed by the column names.
+struct Json {
+    columns: Vec<String>,
+    /// How many rows were written
+    rows: usize,
+}
+
+impl OutputWriter for Json {
+    fn start(&mut self, out: &mut dyn Write) -> std::io::Result<()> {
+        write!(out, "[")
+    }
+
+    fn row(&mut self, out: &mut dyn Write, values: &[ValueRef]) -> std::io::Result<()> {
+        if self.rows > 0 {
+            writeln!(out, ",")?;
+        }
+        self.rows += 1;
+        write!(out, "{}", values_to_json(values, &self.columns))
+    }
+
+    fn finish(&mut self, out: &mut dyn Write) -> std::io::Result<()> {
+        writeln!(out, "]")
+    }
+}
+
+/// An object per line, consumers see rows as soon as the cursor produces them.
+struct Ndjson {
+    columns: Vec<String>,
+}
+
+impl OutputWriter for Ndjson {
+    fn row(&mut self, out: &mut dyn Write, values: &[ValueRef]) -> std::io::Result<()> {
+        writeln!(out, "{}", values_to_json(values, &self.columns))?;
+        out.flush()
+    }
+}
+
+/// Comma- or tab-separated values, with a header row of the column names.
+struct Delimited {
+    /// None without a header row
+    columns: Option<Vec<String>>,
+    delimiter: &'static str,
+    terminator: &'static str,
+    field: fn(ValueRef) -> String,
+}
+
+impl Delimited {
+    fn csv(columns: Vec<String>, headers: bool) -> Delimited {
+        Delimited {
+            columns: headers.then_some(columns),
+            delimiter: ",",
+            terminator: "\r\n",
+            field: csv_field,
+        }
+    }
+
+    fn tsv(columns: Vec<String>, headers: bool) -> Delimited {
+        Delimited {
+            columns: headers.then_some(columns),
+            delimiter: "\t",
+            terminator: "\n",
+            field: tsv_field,
+        }
+    }
+
+    fn write_line<'v>(
+        &self,
+        out: &mut dyn Write,
+        values: impl Iterator<Item = ValueRef<'v>>,
+    ) -> std::io::Result<()> {
+        let line = values.map(self.field).join(self.delimiter);
+        write!(out, "{}{}", line, self.terminator)
+    }
+}
+
+impl OutputWriter for Delimited {
+    fn start(&mut self, out: &mut dyn Write) -> std::io::Result<()> {
+        match &self.columns {
+            Some(columns) => self.write_line(out, names(columns)),
+            None => Ok(()),
+        }
+    }
+
+    fn row(&mut self, out: &mut dyn Write, values: &[ValueRef]) -> std::io::Result<()> {
+        self.write_line(out, values.iter().copied())
+    }
+}
+
+struct Markdown {
+    columns: Vec<String>,
+}
+
+impl OutputWriter for Markdown {
+    fn start(&mut self, out: &mut dyn Write) -> std::io::Result<()> {
+        write_markdown_line(out, names(&self.columns))?;
+        writeln!(out, "|{}", " --- |".repeat(self.columns.len()))
+    }
+
+    fn row(&mut self, out: &mut dyn Write, values: &[ValueRef]) -> std::io::Result<()> {
+        write_markdown_line(out, values.iter().copied())
+    }
+}
+
+/// Every row as a record with the name of each column next to its value, like psql's `\x`.
+/// Values aren't cut off, the lines after the first of a value are indented under it.
+struct Expanded {
+    columns: Vec<String>,
+    color: bool,
+    /// How many rows were written
+    rows: usize,
+}
+
+impl OutputWriter for Expanded {
+    fn row(&mut self, out: &mut dyn Write, values: &[ValueRef]) -> std::io::Result<()> {
+        self.rows += 1;
+        let width = (self.columns.iter())
+            .map(|name| name.width())
+            .max()
+            .unwrap_or_default();
+        let header = format!("-[ RECORD {} ]", self.rows);
+        let line = "-".repeat((width + 3).saturating_sub(header.len()));
+        writeln!(out, "{}{}", header, line)?;
+        let indent = " ".repeat(width);
+        for (name, value) in self.columns.iter().zip(values) {
+            let name = match self.color {
+                true => format!("{}{}{}", BOLD.0, pad(name, width, false), BOLD.1),
+                false => pad(name, width, false),
+            };
+            let text = match value {
+                ValueRef::Null if self.color => format!("{}NULL{}", DIM.0, DIM.1),
+                ValueRef::Null => "NULL".to_string(),
+                _ => value_to_string(*value),
+            };
+            let mut lines = text.lines();
+            writeln!(out, "{} | {}", name, lines.next().unwrap_or_default())?;
+            for line in lines {
+                writeln!(out, "{} | {}", indent, line)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A standalone html page, see [`HTML_START`].
+struct Html {
+    columns: Vec<String>,
+}
+
+impl OutputWriter for Html {
+    fn start(&mut self, out: &mut dyn Write) -> std::io::Result<()> {
+        writeln!(out, "{}", HTML_START)?;
+        for name in &self.columns {
+            writeln!(out, "<th>{}</th>", html_escape(name))?;
+        }
+        writeln!(out, "</tr>\n</thead>\n<tbody>")
+    }
+
+    /// The cells are classed by the type of their value.
+    fn row(&mut self, out: &mut dyn Write, values: &[ValueRef]) -> std::io::Result<()> {
+        writeln!(out, "<tr>")?;
+        for value in values {
+            match value.data_type() {
+                Type::Null => writeln!(out, "<td class=\"null\">NULL</td>")?,
+                Type::Integer | Type::Real => {
+                    writeln!(out, "<td class=\"number\">{}</td>", value_to_string(*value))?
+                }
+                Type::Text | Type::Blob => {
+                    writeln!(out, "<td>{}</td>", html_escape(&value_to_string(*value)))?
+                }
+            }
+        }
+        writeln!(out, "</tr>")
+    }
+
+    fn finish(&mut self, out: &mut dyn Write) -> std::io::Result<()> {
+        write!(out, "{}", HTML_END)
+    }
+}
+
+/// A Graphviz graph, every row an edge from the first value to the second, only the first when
+/// the second is NULL, labeled by the third.
+struct Dot;
+
+impl OutputWriter for Dot {
+    fn start(&mut self, out: &mut dyn Write) -> std::io::Result<()> {
+        writeln!(out, "digraph {{\n  node [shape=box];")
+    }
+
+    fn row(&mut self, out: &mut dyn Write, values: &[ValueRef]) -> std::io::Result<()> {
+        let Some(&node) = values.first() else {
+            return Ok(());
+        };
+        let id = dot_string(node);
+        match values.get(1) {
+            Some(ValueRef::Null) | None => writeln!(out, "  {};", id)?,
+            Some(&to) => writeln!(out, "  {} -> {};", id, dot_string(to))?,
+        }
+        if let Some(&label) = values.get(2) {
+            writeln!(out, "  {} [label={}];", id, dot_string(label))?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, out: &mut dyn Write) -> std::io::Result<()> {
+        writeln!(out, "}}")
+    }
+}
+
+/// The column names as the text values of a header row.
+fn names(columns: &[String]) -> impl Iterator<Item = ValueRef<'_>> {
+    columns.iter().map(|name| ValueRef::Text(name.as_bytes()))
+}
+
+/// A value as a table shows it, on a single line.
+fn table_cell(value: ValueRef) -> String {
+    match value {
+        ValueRef::Null => "NULL".to_string(),
+        ValueRef::Text(_) => value_to_string(value).lines().join(""),
+        _ => value_to_string(value),
+    }
+}
+
+/// `text` cut off between graphemes to at most `max_width` columns of the terminal, with an
+/// ellipsis at the end, 0 doesn't cut it off.
+fn cut(text: &str, max_width: usize) -> String {
+    if max_width == 0 || text.width() <= max_width {
+        return text.to_string();
+    }
+    let mut cut = String::new();
+    let mut width = 0;
+    for grapheme in text.graphemes(true) {
+        // Room for the ellipsis
+        width += grapheme.width();
+        if width + 1 > max_width {
+            break;
+        }
+        cut.push_str(grapheme);
+    }
+    cut.push('…');
+    cut
+}
+
+/// `text` padded with spaces to `width` columns of the terminal, on the left when `right`
+/// aligned.
+fn pad(text: &str, width: usize, right: bool) -> String {
+    let padding = " ".repeat(width.saturating_sub(text.width()));
+    match right {
+        true => format!("{}{}", padding, text),
+        false => format!("{}{}", text, padding),
+    }
+}
+
+/// The values of a row as an object keyed by the column names.
+pub(crate) fn values_to_json(values: &[ValueRef], col_names: &[String]) -> serde_json::Value {
+    let mut object = serde_json::Map::new();
+    col_names.iter().zip(values).for_each(|(name, value)| {
+        let value = match *value {
+            ValueRef::Null => serde_json::Value::Null,
+            ValueRef::Integer(i) => i.into(),
+            ValueRef::Real(f) => f.into(),
+            ValueRef::Text(t) | ValueRef::Blob(t) => String::from_utf8_lossy(t).into(),
+        };
+        object.insert(name.to_owned(), value);
+    });
+    serde_json::Value::Object(object)
+}
+
+/// A quoted DOT id, line breaks become line breaks of the label.
+fn dot_string(value: ValueRef) -> String {
+    let escaped = value_to_string(value)
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace("\r\n", "\\n")
+        .replace(['\r', '\n'], "\\n");
+    format!("\"{}\"", escaped)
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// A row of a markdown table.
+fn write_markdown_line<'v>(
+    out: &mut dyn Write,
+    values: impl Iterator<Item = ValueRef<'v>>,
+) -> std::io::Result<()> {
+    let cells = values.map(|value| format!(" {} |", markdown_field(value)));
+    writeln!(out, "|{}", cells.collect::<String>())
+}
+
+pub(crate) fn value_to_string(value: ValueRef) -> String {
+    match value {
+        ValueRef::Null => String::new(),
+        ValueRef::Integer(i) => i.to_string(),
+        ValueRef::Real(f) => f.to_string(),
+        ValueRef::Text(t) | ValueRef::Blob(t) => String::from_utf8_lossy(t).to_string(),
+    }
+}
+
+/// RFC 4180: fields containing a comma, quote or line break are quoted, quotes are doubled.
+fn csv_field(value: ValueRef) -> String {
+    let str = value_to_string(value);
+    if str.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", str.replace('"', "\"\""))
+    } else {
+        str
+    }
+}
+
+/// Tabs, line breaks and backslashes are escaped so every row stays on one line.
+fn tsv_field(value: ValueRef) -> String {
+    value_to_string(value)
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+/// Pipes are escaped and line breaks become `<br>` so every row stays a row of the table.
+fn markdown_field(value: ValueRef) -> String {
+    value_to_string(value)
+        .replace('|', "\\|")
+        .replace("\r\n", "<br>")
+        .replace(['\r', '\n'], "<br>")
+}
+
+#[cfg(test)]
+mod test {
+    use crate::output::{OutputMode, OutputOptions, TABLE_SAMPLE_ROWS};
+    use rusqlite::types::ValueRef;
+
+    #[test]
+    fn writers_take_typed_values() -> std::io::Result<()> {
+        let columns = ["hash".to_string(), "additions".to_string()];
+        let rows = [
+            [ValueRef::Text(b"a1b2"), ValueRef::Integer(12)],
+            [ValueRef::Text(b"c3d4"), ValueRef::Null],
+        ];
+        let write = |mode| -> std::io::Result<String> {
+            let options = OutputOptions {
+                mode,
+                ..OutputOptions::default()
+            };
+            let mut writer = options.writer(&columns);
+            let mut out = vec![];
+            writer.start(&mut out)?;
+            for row in &rows {
+                writer.row(&mut out, row)?;
+            }
+            writer.finish(&mut out)?;
+            Ok(String::from_utf8(out).unwrap())
+        };
+
+        assert_eq!(
+            write(OutputMode::Table)?,
+            "| hash | additions |\n\
+             --------------------\n\
+             | a1b2 |        12 |\n\
+             | c3d4 | NULL      |\n"
+        );
+        assert_eq!(
+            write(OutputMode::Json)?,
+            "[{\"hash\":\"a1b2\",\"additions\":12},\n{\"hash\":\"c3d4\",\"additions\":null}]\n"
+        );
+        assert_eq!(
+            write(OutputMode::Csv)?,
+            "hash,additions\r\na1b2,12\r\nc3d4,\r\n"
+        );
+        assert_eq!(write(OutputMode::Plain)?, "a1b2\t12\nc3d4\t\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn tables_stream_after_the_sample() -> std::io::Result<()> {
+        let columns = ["id".to_string(), "name".to_string()];
+        let mut writer = OutputOptions::default().writer(&columns);
+        let mut out = vec![];
+        for i in 0..TABLE_SAMPLE_ROWS as i64 - 1 {
+            writer.row(&mut out, &[ValueRef::Integer(i), ValueRef::Text(b"ab")])?;
+        }
+        assert!(out.is_empty());
+        writer.row(&mut out, &[ValueRef::Integer(99), ValueRef::Text(b"ab")])?;
+        assert_eq!(
+            String::from_utf8_lossy(&out).lines().count(),
+            TABLE_SAMPLE_ROWS + 2
+        );
+        // The widths are fixed by now, text is cut to them and numbers overflow them
+        writer.row(
+            &mut out,
+            &[ValueRef::Integer(101000), ValueRef::Text(b"abcde")],
+        )?;
+        writer.row(&mut out, &[ValueRef::Real(2.5), ValueRef::Null])?;
+        writer.finish(&mut out)?;
+        let table = String::from_utf8(out).unwrap();
+        let last = table.lines().rev().take(2).collect::<Vec<_>>();
+
+        assert_eq!(table.lines().next(), Some("| id | name |"));
+        assert_eq!(last, ["| 2.5 | NULL |", "| 101000 | abc… |"]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+use std::io::{BufWriter, IsTerminal, Stdout, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use tracing::debug;
+
+/// The pager when neither `SQLITEGIT_PAGER` nor `PAGER` is set.
+const DEFAULT_PAGER: &str = "less";
+
+/// The options of less when `LESS` isn't set: quit when the output fits on the screen, pass
+/// colors through, chop long lines so wide tables scroll sideways with the arrow keys, and
+/// leave the output on the screen after quitting.
+const LESS: &str = "FRSX";
+
+/// The output of `query` and `run`, piped through a pager when stdout is a terminal, like the
+/// output of `git log`.
+pub(crate) enum Output {
+    Stdout(Stdout),
+    Pager {
+        pager: Child,
+        stdin: BufWriter<ChildStdin>,
+    },
+}
+
+impl Output {
+    /// Starts the pager of `SQLITEGIT_PAGER` or `PAGER`, less by default, when `page` is set and
+    /// stdout is a terminal. An empty pager or `cat` prints to stdout, so does a pager that
+    /// can't be started.
+    pub(crate) fn start(page: bool) -> Output {
+        let stdout = std::io::stdout();
+        if !page || !stdout.is_terminal() {
+            return Output::Stdout(stdout);
+        }
+        let pager = (std::env::var("SQLITEGIT_PAGER"))
+            .or_else(|_| std::env::var("PAGER"))
+            .unwrap_or_else(|_| DEFAULT_PAGER.to_string());
+        if matches!(pager.trim(), "" | "cat") {
+            return Output::Stdout(stdout);
+        }
+
+        Output::pager(&pager).unwrap_or_else(|e| {
+            debug!(pager, error = %e, "can't start the pager");
+            Output::Stdout(stdout)
+        })
+    }
+
+    /// Starts `pager` with the shell, like git does, `PAGER` may have arguments.
+    fn pager(pager: &str) -> std::io::Result<Output> {
+        let (shell, flag) = match cfg!(windows) {
+            true => ("cmd", "/C"),
+            false => ("sh", "-c"),
+        };
+        let mut command = Command::new(shell);
+        command.args([flag, pager]).stdin(Stdio::piped());
+        if std::env::var_os("LESS").is_none() {
+            command.env("LESS", LESS);
+        }
+        let mut child = command.spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        Ok(Output::Pager {
+            pager: child,
+            stdin: BufWriter::new(stdin),
+        })
+    }
+
+    /// Flushes the output and waits until the pager is quit.
+    pub(crate) fn finish(self) -> std::io::Result<()> {
+        match self {
+            Output::Stdout(mut stdout) => stdout.flush(),
+            Output::Pager { mut pager, stdin } => {
+                // Closes the pipe, the pager may have been quit before it read everything
+                let flushed = stdin.into_inner().map(drop).map_err(|e| e.into_error());
+                pager.wait()?;
+                flushed
+            }
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Output::Stdout(stdout) => stdout.write(buf),
+            Output::Pager { stdin, .. } => stdin.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Output::Stdout(stdout) => stdout.flush(),
+            Output::Pager { stdin, .. } => stdin.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::pager::Output;
+    use std::io::Write;
+
+    #[test]
+    #[cfg(unix)]
+    fn pipes_the_output_through_the_pager() -> Result<(), Box<dyn std::error::Error>> {
+        let path = std::env::temp_dir().join(format!("sqlitegit-pager-{}", std::process::id()));
+        let pager = format!("cat > '{}'", path.display());
+        let mut out = Output::pager(&pager)?;
+        writeln!(out, "| hash |")?;
+        out.finish()?;
+        let paged = std::fs::read_to_string(&path)?;
+        std::fs::remove_file(&path)?;
+
+        assert_eq!(paged, "| hash |\n");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,139 @@
+use rusqlite::Statement;
+use std::collections::HashMap;
+
+/// A value passed on the command line with `--param`.
+///
+/// `NAME=VALUE` binds `:NAME`, `@NAME` and `$NAME`, `N=VALUE` binds `?N` and anything else is
+/// bound to the anonymous `?` parameters in the order given. Statements mixing `?` and `?NNN`
+/// only accept `N=VALUE`. Values are always bound as TEXT, SQLite's column affinity takes care
+/// of comparisons against numeric columns.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Param {
+    Named(String, String),
+    Indexed(usize, String),
+    Positional(String),
+}
+
+impl std::str::FromStr for Param {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let param = match s.split_once('=') {
+            Some((name, value)) if name.parse::<usize>().is_ok() => {
+                let index = name.parse::<usize>().unwrap();
+                if index == 0 {
+                    return Err("parameter indexes start at 1".to_string());
+                }
+                Param::Indexed(index, value.to_string())
+            }
+            Some((name, value)) if is_identifier(name) => {
+                Param::Named(name.to_string(), value.to_string())
+            }
+            _ => Param::Positional(s.to_string()),
+        };
+        Ok(param)
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// The parameters of a single invocation, bound to every statement that is executed.
+#[derive(Debug, Default, Clone)]
+pub struct Params {
+    named: HashMap<String, String>,
+    indexed: HashMap<usize, String>,
+    positional: Vec<String>,
+}
+
+impl From<Vec<Param>> for Params {
+    fn from(params: Vec<Param>) -> Self {
+        let mut result = Params::default();
+        for param in params {
+            match param {
+                Param::Named(name, value) => {
+                    result.named.insert(name, value);
+                }
+                Param::Indexed(index, value) => {
+                    result.indexed.insert(index, value);
+                }
+                Param::Positional(value) => result.positional.push(value),
+            }
+        }
+        result
+    }
+}
+
+impl Params {
+    /// Binds every parameter of `stmt`, failing on parameters that have no value.
+    pub fn bind(&self, stmt: &mut Statement) -> rusqlite::Result<()> {
+        let mut positional = self.positional.iter();
+        // `?NNN` leaves gaps in the parameter indexes which look exactly like anonymous `?`
+        let has_gaps = (1..=stmt.parameter_count()).any(|index| {
+            stmt.parameter_name(index)
+                .is_some_and(|n| n.starts_with('?'))
+        });
+        for index in 1..=stmt.parameter_count() {
+            let value = match stmt.parameter_name(index) {
+                Some(name) if name.starts_with('?') => self.indexed.get(&index),
+                Some(name) => self.named.get(&name[1..]),
+                None if has_gaps => self.indexed.get(&index),
+                None => self.indexed.get(&index).or_else(|| positional.next()),
+            };
+            match value {
+                Some(value) => stmt.raw_bind_parameter(index, value)?,
+                None if has_gaps && stmt.parameter_name(index).is_none() => {}
+                None => {
+                    let name = stmt
+                        .parameter_name(index)
+                        .map_or_else(|| format!("?{}", index), |name| name.to_string());
+                    return Err(rusqlite::Error::InvalidParameterName(format!(
+                        "{} has no value, pass it with --param",
+                        name
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::params::{Param, Params};
+    use rusqlite::Connection;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            "since=2024-01-01".parse(),
+            Ok(Param::Named("since".to_string(), "2024-01-01".to_string()))
+        );
+        assert_eq!("2=x".parse(), Ok(Param::Indexed(2, "x".to_string())));
+        assert_eq!("a b=c".parse(), Ok(Param::Positional("a b=c".to_string())));
+        assert!("0=x".parse::<Param>().is_err());
+    }
+
+    #[test]
+    fn bind() -> Result<(), rusqlite::Error> {
+        let db = Connection::open_in_memory()?;
+        let params = Params::from(vec![
+            Param::Positional("first".to_string()),
+            Param::Named("name".to_string(), "it's".to_string()),
+        ]);
+        let mut stmt = db.prepare("SELECT ?, :name")?;
+        params.bind(&mut stmt)?;
+        let mut rows = stmt.raw_query();
+        let row = rows.next()?.unwrap();
+
+        assert_eq!(row.get::<_, String>(0)?, "first");
+        assert_eq!(row.get::<_, String>(1)?, "it's");
+
+        Ok(())
+    }
+}
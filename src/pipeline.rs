@@ -0,0 +1,196 @@
+use crate::diff_ahead::diff;
+use crate::intern::Interner;
+use crate::stats_cache::FileStats;
+use crate::{CustomError, DiffSettings};
+use git2::{Oid, Repository};
+use std::collections::{BTreeMap, HashSet};
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+
+/// A walked commit with its stats, `None` for the commits the walk was told not to diff.
+pub(crate) type Diffed = (Oid, Option<FileStats>);
+
+/// How many walked commits may wait for a worker, per worker.
+const QUEUE_PER_WORKER: usize = 16;
+
+/// How many walked commits may be waiting for a worker or for the consumer, per worker.
+const WINDOW_PER_WORKER: usize = 4 * QUEUE_PER_WORKER;
+
+/// A revwalk whose commits are diffed on a pool of threads.
+///
+/// One thread walks the history and hands the commits to the workers, each worker diffs with its
+/// own handle on the repository. The results come out in walk order, the walk runs ahead of the
+/// consumer by a bounded number of commits. Dropping the pipeline stops the threads.
+pub(crate) struct DiffPipeline {
+    results: Receiver<(usize, Result<Diffed, CustomError>)>,
+    /// Results that arrived before the ones in front of them
+    pending: BTreeMap<usize, Result<Diffed, CustomError>>,
+    next: usize,
+    /// Lets the walk send one more commit, for each result returned. Commits the walker passes
+    /// through without diffing skip the workers, this keeps them from piling up in `pending`.
+    credits: SyncSender<()>,
+}
+
+impl DiffPipeline {
+    /// Walks the repository at `path` from `tips`, leaving out `hidden` and the commits they
+    /// reach. The commits in `known` are passed through without diffing.
+    pub(crate) fn walk(
+        path: &Path,
+        tips: Vec<Oid>,
+        hidden: Vec<Oid>,
+        known: HashSet<Oid>,
+        settings: DiffSettings,
+    ) -> DiffPipeline {
+        let workers = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+        let (work_tx, work_rx) = sync_channel::<(usize, Oid)>(workers * QUEUE_PER_WORKER);
+        let (results_tx, results) = sync_channel(workers * QUEUE_PER_WORKER);
+        let work_rx = Arc::new(Mutex::new(work_rx));
+        let window = workers * WINDOW_PER_WORKER;
+        let (credits, credits_rx) = sync_channel(window);
+        for _ in 0..window {
+            let _ = credits.send(());
+        }
+
+        for _ in 0..workers {
+            let work_rx = work_rx.clone();
+            let results_tx = results_tx.clone();
+            let path = path.to_path_buf();
+            let settings = settings.clone();
+            thread::spawn(move || diff_worker(&path, &settings, &work_rx, &results_tx));
+        }
+        let path = path.to_path_buf();
+        thread::spawn(move || {
+            let mut seq = 0;
+            let walked = walk_history(&path, tips, hidden, |oid| {
+                // The pipeline was dropped
+                if credits_rx.recv().is_err() {
+                    return false;
+                }
+                let sent = if known.contains(&oid) {
+                    results_tx.send((seq, Ok((oid, None)))).is_ok()
+                } else {
+                    work_tx.send((seq, oid)).is_ok()
+                };
+                seq += 1;
+                sent
+            });
+            if let Err(e) = walked {
+                let _ = results_tx.send((seq, Err(e)));
+            }
+        });
+
+        DiffPipeline {
+            results,
+            pending: BTreeMap::new(),
+            next: 0,
+            credits,
+        }
+    }
+}
+
+impl Iterator for DiffPipeline {
+    type Item = Result<Diffed, CustomError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(result) = self.pending.remove(&self.next) {
+                self.next += 1;
+                let _ = self.credits.try_send(());
+                return Some(result);
+            }
+            let (seq, result) = self.results.recv().ok()?;
+            self.pending.insert(seq, result);
+        }
+    }
+}
+
+/// Calls `send` with every commit of the walk until it returns false.
+fn walk_history(
+    path: &Path,
+    tips: Vec<Oid>,
+    hidden: Vec<Oid>,
+    mut send: impl FnMut(Oid) -> bool,
+) -> Result<(), CustomError> {
+    let repo = Repository::open(path)?;
+    let mut walk = repo.revwalk()?;
+    for tip in tips {
+        walk.push(tip)?;
+    }
+    for oid in hidden {
+        walk.hide(oid)?;
+    }
+    for oid in walk {
+        if !send(oid?) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn diff_worker(
+    path: &Path,
+    settings: &DiffSettings,
+    work: &Mutex<Receiver<(usize, Oid)>>,
+    results: &SyncSender<(usize, Result<Diffed, CustomError>)>,
+) {
+    let repo = Repository::open(path);
+    let mut interner = Interner::default();
+    loop {
+        let next = work.lock().unwrap_or_else(PoisonError::into_inner).recv();
+        let Ok((seq, oid)) = next else {
+            return;
+        };
+        let diffed = diff(&repo, oid, settings, &mut interner).map(|stats| (oid, Some(stats)));
+        if results.send((seq, diffed)).is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::pipeline::DiffPipeline;
+    use crate::test::{commit_file, temp_repository};
+    use crate::DiffSettings;
+    use std::collections::HashSet;
+
+    #[test]
+    fn diffs_in_walk_order() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("pipeline")?;
+        let mut commits = vec![];
+        for i in 0..20 {
+            commits.push(commit_file(
+                &repo,
+                "file.txt",
+                &format!("{}\n", i),
+                "change",
+            )?);
+        }
+        let known = HashSet::from([commits[5]]);
+
+        let diffed = DiffPipeline::walk(
+            &path,
+            vec![commits[19]],
+            vec![commits[1]],
+            known,
+            DiffSettings::default(),
+        )
+        .collect::<Result<Vec<_>, _>>()?;
+        std::fs::remove_dir_all(&path)?;
+
+        let walked = diffed.iter().map(|(oid, _)| *oid).collect::<Vec<_>>();
+        let expected = commits[2..].iter().rev().copied().collect::<Vec<_>>();
+        assert_eq!(walked, expected);
+        for (oid, stats) in diffed {
+            match stats {
+                None => assert_eq!(oid, commits[5]),
+                Some(stats) => assert_eq!(stats, vec![("file.txt".into(), 1, 1)]),
+            }
+        }
+
+        Ok(())
+    }
+}
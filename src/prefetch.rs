@@ -0,0 +1,117 @@
+use crate::CustomError;
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::thread;
+use tracing::Span;
+
+/// Rows per batch sent by the producing thread.
+const BATCH: usize = 256;
+
+/// How many batches the producing thread may run ahead of the cursor.
+const BATCHES_AHEAD: usize = 4;
+
+/// The rows of a cursor, produced on a background thread while SQLite processes the rows before
+/// them.
+///
+/// The producer runs ahead by a bounded number of batches. Dropping the `Prefetch`, e.g. when a
+/// `LIMIT` is reached, stops the producer at its next batch.
+pub(crate) struct Prefetch<T> {
+    batches: Option<Receiver<Result<Vec<T>, CustomError>>>,
+    batch: std::vec::IntoIter<T>,
+}
+
+impl<T: Send + 'static> Prefetch<T> {
+    /// Rows that don't need a thread to produce them.
+    pub(crate) fn ready(rows: Vec<T>) -> Self {
+        Prefetch {
+            batches: None,
+            batch: rows.into_iter(),
+        }
+    }
+
+    /// Runs `produce` on a thread of its own. It emits rows with the function it's passed until
+    /// that returns false, as nobody reads the rows anymore.
+    pub(crate) fn spawn(
+        produce: impl FnOnce(&mut dyn FnMut(T) -> bool) -> Result<(), CustomError> + Send + 'static,
+    ) -> Self {
+        let (batches, receiver) = sync_channel(BATCHES_AHEAD);
+        let span = Span::current();
+        thread::spawn(move || {
+            let _span = span.enter();
+            let mut batch = Vec::with_capacity(BATCH);
+            let produced = produce(&mut |row| {
+                batch.push(row);
+                batch.len() < BATCH
+                    || batches
+                        .send(Ok(std::mem::replace(&mut batch, Vec::with_capacity(BATCH))))
+                        .is_ok()
+            });
+            let _ = batches.send(produced.map(|()| batch));
+        });
+        Prefetch {
+            batches: Some(receiver),
+            batch: Vec::new().into_iter(),
+        }
+    }
+
+    /// The next row, `None` once the producer is done.
+    pub(crate) fn next(&mut self) -> Result<Option<T>, CustomError> {
+        loop {
+            if let Some(row) = self.batch.next() {
+                return Ok(Some(row));
+            }
+            let Some(batches) = &self.batches else {
+                return Ok(None);
+            };
+            match batches.recv() {
+                Ok(batch) => self.batch = batch?.into_iter(),
+                Err(_) => self.batches = None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::prefetch::{Prefetch, BATCH};
+    use crate::CustomError;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn streams_rows_and_stops_early() -> Result<(), CustomError> {
+        let mut all = Prefetch::spawn(|emit| {
+            for i in 0..1000 {
+                if !emit(i) {
+                    break;
+                }
+            }
+            Ok(())
+        });
+        let mut rows = vec![];
+        while let Some(row) = all.next()? {
+            rows.push(row);
+        }
+
+        let produced = Arc::new(AtomicUsize::new(0));
+        let counter = produced.clone();
+        let mut endless = Prefetch::spawn(move |emit| {
+            while emit(counter.fetch_add(1, Ordering::SeqCst)) {}
+            Ok(())
+        });
+        let first = endless.next()?;
+        drop(endless);
+        std::thread::sleep(std::time::Duration::from_millis(50));
+
+        let mut failing =
+            Prefetch::<i32>::spawn(|_| Err(CustomError::InvalidArgument("broken".to_string())));
+
+        assert_eq!(rows, (0..1000).collect::<Vec<_>>());
+        assert_eq!(first, Some(0));
+        // The batches in the channel, the one being sent and the one being filled
+        assert!(produced.load(Ordering::SeqCst) <= BATCH * 7);
+        assert!(failing.next().is_err());
+        assert!(failing.next()?.is_none());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,202 @@
+use itertools::Itertools;
+use rusqlite::types::ValueRef;
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+/// Number of the slowest `filter` calls a report lists with their arguments.
+const SLOWEST_FILTERS: usize = 5;
+
+/// Records the `filter` calls of the git tables and the rows they produce while profiling, e.g.
+/// to find out why a join of `commits` and `stats` is slow. Shared by every cursor of the
+/// connections the tables are registered on.
+#[derive(Clone, Default)]
+pub struct Profiler {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    enabled: AtomicBool,
+    scans: Mutex<Vec<Scan>>,
+}
+
+/// A `filter` call and the rows the cursor produced for it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scan {
+    pub table: &'static str,
+    /// The arguments SQLite passed, the hidden columns and LIMIT/OFFSET the plan uses
+    pub args: String,
+    pub filter: Duration,
+    /// Spent moving to the next rows
+    pub next: Duration,
+    pub rows: u64,
+}
+
+/// The scans recorded between [`Profiler::start`] and [`Profiler::finish`]. Displays the calls,
+/// rows and time per table, and the slowest calls with their arguments.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Profile {
+    pub scans: Vec<Scan>,
+}
+
+impl Profiler {
+    /// Forgets the scans recorded so far and records the next ones.
+    pub fn start(&self) {
+        self.lock().clear();
+        self.inner.enabled.store(true, Ordering::SeqCst);
+    }
+
+    /// Stops recording and returns the scans since `start`.
+    pub fn finish(&self) -> Profile {
+        self.inner.enabled.store(false, Ordering::SeqCst);
+        Profile {
+            scans: std::mem::take(&mut *self.lock()),
+        }
+    }
+
+    /// Records a `filter` call that took `elapsed` and returns the scan the cursor's next rows
+    /// count towards, None while not profiling.
+    pub(crate) fn filter(
+        &self,
+        table: &'static str,
+        args: &[ValueRef],
+        elapsed: Duration,
+        row: bool,
+    ) -> Option<usize> {
+        if !self.inner.enabled.load(Ordering::Relaxed) {
+            return None;
+        }
+        let mut scans = self.lock();
+        scans.push(Scan {
+            table,
+            args: args.iter().map(format_arg).join(", "),
+            filter: elapsed,
+            next: Duration::ZERO,
+            rows: row as u64,
+        });
+        Some(scans.len() - 1)
+    }
+
+    /// Records a `next` call of the cursor of `scan` that took `elapsed`.
+    pub(crate) fn next(&self, scan: Option<usize>, elapsed: Duration, row: bool) {
+        let Some(scan) = scan else {
+            return;
+        };
+        // Scans of a statement that ran before `start` aren't there anymore
+        if let Some(scan) = self.lock().get_mut(scan) {
+            scan.next += elapsed;
+            scan.rows += row as u64;
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Scan>> {
+        self.inner
+            .scans
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Debug for Profiler {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Profiler")
+            .field("enabled", &self.inner.enabled)
+            .finish_non_exhaustive()
+    }
+}
+
+fn format_arg(value: &ValueRef) -> String {
+    match value {
+        ValueRef::Null => "NULL".to_string(),
+        ValueRef::Integer(i) => i.to_string(),
+        ValueRef::Real(f) => f.to_string(),
+        ValueRef::Text(text) => format!("'{}'", String::from_utf8_lossy(text)),
+        ValueRef::Blob(blob) => format!("<{} bytes>", blob.len()),
+    }
+}
+
+impl Display for Profile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut tables: BTreeMap<&str, (usize, u64, Duration, Duration)> = BTreeMap::new();
+        for scan in &self.scans {
+            let table = tables.entry(scan.table).or_default();
+            table.0 += 1;
+            table.1 += scan.rows;
+            table.2 += scan.filter;
+            table.3 += scan.next;
+        }
+        writeln!(
+            f,
+            "{:<8} {:>8} {:>8} {:>10} {:>10}",
+            "table", "filters", "rows", "filter", "next"
+        )?;
+        for (table, (filters, rows, filter, next)) in &tables {
+            writeln!(
+                f,
+                "{:<8} {:>8} {:>8} {:>10} {:>10}",
+                table,
+                filters,
+                rows,
+                format!("{:.1?}", filter),
+                format!("{:.1?}", next)
+            )?;
+        }
+        let slowest = (self.scans.iter())
+            .sorted_by_key(|scan| std::cmp::Reverse(scan.filter + scan.next))
+            .take(SLOWEST_FILTERS);
+        if !self.scans.is_empty() {
+            writeln!(f, "slowest filters")?;
+        }
+        for scan in slowest {
+            writeln!(
+                f,
+                "  {}({}) {:.1?}, {} rows",
+                scan.table,
+                scan.args,
+                scan.filter + scan.next,
+                scan.rows
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test::{commit_file, temp_repository};
+    use crate::SqliteGit;
+    use rusqlite::Connection;
+
+    #[test]
+    fn records_filters_and_rows_per_table() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("profile")?;
+        commit_file(&repo, "file.txt", "one\n", "first")?;
+        let head = commit_file(&repo, "file.txt", "two\n", "second")?;
+
+        let git = SqliteGit::new().with_all().repository(&path);
+        let db = Connection::open_in_memory()?;
+        git.register(&db)?;
+        let profiler = git.profiler();
+        let sql = "SELECT count(*) FROM commits c JOIN stats s ON s.hash = c.hash";
+        let unprofiled: i64 = db.query_row(sql, [], |row| row.get(0))?;
+        profiler.start();
+        db.query_row(sql, [], |row| row.get::<_, i64>(0))?;
+        let profile = profiler.finish();
+        let (scans, report) = (&profile.scans, profile.to_string());
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!(unprofiled, 1);
+        let tables = scans.iter().map(|scan| scan.table).collect::<Vec<_>>();
+        assert_eq!(tables, ["commits", "stats", "stats"]);
+        assert_eq!(scans[0].rows, 2);
+        assert_eq!(scans[0].args, "");
+        assert_eq!(scans[1].args, format!("'{}'", head));
+        assert_eq!(scans[1].rows + scans[2].rows, 1);
+        assert!(report.contains(&format!("stats('{}')", head)));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,179 @@
+use std::fmt::{Debug, Formatter};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Counts the work the git tables do, the commits their revwalks visit and the diffs `stats`
+/// computes, so a long scan can show that it's getting somewhere. Shared by every cursor of the
+/// connections the tables are registered on, the counts only go up.
+#[derive(Clone, Default)]
+pub struct Progress {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    commits: AtomicU64,
+    diffs: AtomicU64,
+}
+
+impl Progress {
+    /// The commits walked so far.
+    pub fn commits(&self) -> u64 {
+        self.inner.commits.load(Ordering::Relaxed)
+    }
+
+    /// The diffs computed so far, diffs read from a cache don't count.
+    pub fn diffs(&self) -> u64 {
+        self.inner.diffs.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn walked(&self) {
+        self.inner.commits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn diffed(&self) {
+        self.inner.diffs.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Debug for Progress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Progress")
+            .field("commits", &self.commits())
+            .field("diffs", &self.diffs())
+            .finish()
+    }
+}
+
+#[cfg(feature = "cli")]
+pub(crate) use spinner::Spinner;
+
+#[cfg(feature = "cli")]
+mod spinner {
+    use crate::Progress;
+    use std::io::{IsTerminal, Write};
+    use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+    use std::thread::JoinHandle;
+    use std::time::{Duration, Instant};
+
+    const FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+    /// Quick statements finish before the spinner shows up
+    const DELAY: Duration = Duration::from_millis(500);
+    const INTERVAL: Duration = Duration::from_millis(100);
+
+    /// Shows the commits walked, the diffs computed and the time spent on stderr while a
+    /// statement runs, when stderr is a terminal.
+    pub(crate) struct Spinner {
+        running: Option<(Sender<()>, JoinHandle<()>)>,
+    }
+
+    impl Spinner {
+        pub(crate) fn start(progress: &Progress) -> Spinner {
+            if !std::io::stderr().is_terminal() {
+                return Spinner { running: None };
+            }
+            let (stop, stopped) = channel();
+            let progress = progress.clone();
+            let (commits, diffs) = (progress.commits(), progress.diffs());
+            let start = Instant::now();
+            let thread = std::thread::spawn(move || {
+                if stopped.recv_timeout(DELAY) != Err(RecvTimeoutError::Timeout) {
+                    return;
+                }
+                let mut stderr = std::io::stderr();
+                for frame in FRAMES.iter().cycle() {
+                    let line = format!(
+                        "{} {} commits walked, {} diffs computed, {:.1}s",
+                        frame,
+                        progress.commits() - commits,
+                        progress.diffs() - diffs,
+                        start.elapsed().as_secs_f64()
+                    );
+                    // Redraws the line in place
+                    let _ = write!(stderr, "\r\x1b[2K{}", line).and_then(|()| stderr.flush());
+                    if stopped.recv_timeout(INTERVAL) != Err(RecvTimeoutError::Timeout) {
+                        break;
+                    }
+                }
+                let _ = write!(stderr, "\r\x1b[2K").and_then(|()| stderr.flush());
+            });
+            Spinner {
+                running: Some((stop, thread)),
+            }
+        }
+
+        /// A spinner that shows nothing, e.g. with `--no-progress`.
+        pub(crate) fn none() -> Spinner {
+            Spinner { running: None }
+        }
+
+        /// Clears the spinner off the terminal, it isn't shown again.
+        pub(crate) fn stop(&mut self) {
+            if let Some((stop, thread)) = self.running.take() {
+                drop(stop);
+                let _ = thread.join();
+            }
+        }
+
+        /// `out` stopping the spinner before it's first written to, so the output and the
+        /// spinner don't mix on the terminal.
+        pub(crate) fn stopped_by<'w>(&'w mut self, out: &'w mut dyn Write) -> StoppedBy<'w> {
+            StoppedBy { spinner: self, out }
+        }
+    }
+
+    impl Drop for Spinner {
+        fn drop(&mut self) {
+            self.stop();
+        }
+    }
+
+    pub(crate) struct StoppedBy<'w> {
+        spinner: &'w mut Spinner,
+        out: &'w mut dyn Write,
+    }
+
+    impl Write for StoppedBy<'_> {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.spinner.stop();
+            self.out.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.out.flush()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test::{commit_file, temp_repository};
+    use crate::SqliteGit;
+    use rusqlite::Connection;
+
+    #[test]
+    fn counts_commits_walked_and_diffs_computed() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("progress")?;
+        commit_file(&repo, "file.txt", "one\n", "first")?;
+        commit_file(&repo, "file.txt", "two\n", "second")?;
+
+        let git = SqliteGit::new().with_all().repository(&path);
+        let db = Connection::open_in_memory()?;
+        git.register(&db)?;
+        let progress = git.progress();
+        let sql = "SELECT count(*) FROM commits c JOIN stats s ON s.hash = c.hash";
+        db.query_row(sql, [], |row| row.get::<_, i64>(0))?;
+        let (commits, diffs) = (progress.commits(), progress.diffs());
+        // The diffs are cached, the merges walk the commits again
+        db.query_row(sql, [], |row| row.get::<_, i64>(0))?;
+        db.query_row("SELECT count(*) FROM merges", [], |row| {
+            row.get::<_, i64>(0)
+        })?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!((commits, diffs), (2, 2));
+        assert_eq!((progress.commits(), progress.diffs()), (6, 2));
+
+        Ok(())
+    }
+}
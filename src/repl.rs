@@ -0,0 +1,321 @@
+use crate::cancel::CtrlC;
+use crate::complete::Schema;
+use crate::output::{OutputMode, OutputOptions, RowLimit, TimeZoneChoice};
+use crate::params::Params;
+use crate::utils::execute_all_and_write;
+use crate::{CustomError, Interrupt, Profiler, TABLES};
+use clap::ValueEnum;
+use itertools::Itertools;
+use rusqlite::Connection;
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{CompletionType, Editor, Helper};
+use std::path::PathBuf;
+
+const PROMPT: &str = "sqlitegit> ";
+const CONTINUATION_PROMPT: &str = "      ...> ";
+const HISTORY_FILE: &str = ".sqlitegit_history";
+
+const HELP: &str = r#".help              Show this message
+.tables            List the git tables and any user created tables and views
+.schema TABLE      Show the columns of TABLE, including hidden parameter columns
+.mode MODE         Set the output mode: table, json, ndjson, csv, tsv, markdown,
+                   expanded, plain, html or dot
+.expanded on|off   Toggle printing every column of a row on a line of its own
+.headers on|off    Toggle the header row of csv and tsv output
+.limit N|off       Print at most N rows of a result set and count the rest
+.tz ZONE           Print DATETIME columns in ZONE: local, utc or original
+.profile on|off    Toggle writing the query plan, timings and git table scans to stderr
+.quit              Exit the REPL"#;
+
+struct Repl<'a> {
+    db: &'a Connection,
+    interrupt: &'a Interrupt,
+    profiler: &'a Profiler,
+    output: OutputOptions,
+    profile: bool,
+}
+
+/// Completes the table, column and function names of the statement being typed with Tab.
+struct SqlCompleter {
+    schema: Schema,
+    /// The lines of the statement typed before the current line
+    statement: String,
+}
+
+impl Completer for SqlCompleter {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &rustyline::Context,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        if self.statement.is_empty() && line.trim_start().starts_with('.') {
+            return Ok((pos, vec![]));
+        }
+        // The earlier lines end with a line break, a word never starts in them
+        let sql = format!("{}{}", self.statement, line);
+        let (start, completions) = self.schema.complete(&sql, self.statement.len() + pos);
+        let pairs = completions.into_iter().map(|completion| Pair {
+            display: completion.display,
+            replacement: completion.text,
+        });
+        Ok((start - self.statement.len(), pairs.collect()))
+    }
+}
+
+impl Hinter for SqlCompleter {
+    type Hint = String;
+}
+
+impl Highlighter for SqlCompleter {}
+
+impl Validator for SqlCompleter {}
+
+impl Helper for SqlCompleter {}
+
+/// Reads statements until EOF. A statement ends with a `;` and may span multiple lines,
+/// lines starting with `.` outside of a statement are dot-commands. Ctrl-C stops the statement
+/// that is running. With `profile` each statement is profiled until `.profile off`. Tab
+/// completes the names of tables, their columns and functions. Result sets are written like
+/// `output` says until a dot-command changes it, at most `limit` rows of each.
+pub fn run(
+    db: &Connection,
+    interrupt: &Interrupt,
+    profiler: &Profiler,
+    profile: bool,
+    output: OutputOptions,
+    limit: usize,
+) -> Result<(), CustomError> {
+    let config = rustyline::Config::builder()
+        .completion_type(CompletionType::List)
+        .build();
+    let mut editor = Editor::<SqlCompleter, DefaultHistory>::with_config(config)?;
+    editor.set_helper(Some(SqlCompleter {
+        schema: Schema::read(db)?,
+        statement: String::new(),
+    }));
+    let history = history_path();
+    if let Some(path) = &history {
+        // A missing history file just means this is the first session
+        let _ = editor.load_history(path);
+    }
+
+    let mut repl = Repl {
+        db,
+        interrupt,
+        profiler,
+        output: OutputOptions {
+            limit: row_limit(limit),
+            ..output
+        },
+        profile,
+    };
+    let mut buffer = String::new();
+
+    loop {
+        let prompt = if buffer.is_empty() {
+            PROMPT
+        } else {
+            CONTINUATION_PROMPT
+        };
+        if let Some(completer) = editor.helper_mut() {
+            completer.statement.clone_from(&buffer);
+        }
+        let line = match editor.readline(prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => {
+                buffer.clear();
+                continue;
+            }
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+
+        if buffer.is_empty() && line.trim_start().starts_with('.') {
+            editor.add_history_entry(line.trim())?;
+            if !repl.dot_command(line.trim()) {
+                break;
+            }
+            continue;
+        }
+
+        if buffer.is_empty() && line.trim().is_empty() {
+            continue;
+        }
+        buffer.push_str(&line);
+        buffer.push('\n');
+
+        if buffer.trim_end().ends_with(';') {
+            editor.add_history_entry(buffer.trim())?;
+            repl.execute(buffer.trim());
+            buffer.clear();
+            // The statement may have created or dropped tables
+            if let (Some(completer), Ok(schema)) = (editor.helper_mut(), Schema::read(db)) {
+                completer.schema = schema;
+            }
+        }
+    }
+
+    if let Some(path) = &history {
+        editor.save_history(path)?;
+    }
+
+    Ok(())
+}
+
+/// The limit of `rows` rows, 0 prints them all.
+fn row_limit(rows: usize) -> Option<RowLimit> {
+    (rows > 0).then_some(RowLimit {
+        rows,
+        hint: ".limit off",
+    })
+}
+
+fn history_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE))
+}
+
+impl Repl<'_> {
+    fn execute(&self, sql: &str) {
+        let _ctrl_c = CtrlC::interrupting(self.interrupt);
+        let profiler = self.profile.then_some(self.profiler);
+        let params = Params::default();
+        let mut stdout = std::io::stdout();
+        let output = &self.output;
+        if let Err(e) = execute_all_and_write(self.db, sql, &params, output, profiler, &mut stdout)
+        {
+            eprintln!("error: {}", e.diagnose(self.db, sql));
+        }
+    }
+
+    /// Returns false when the REPL should exit.
+    fn dot_command(&mut self, line: &str) -> bool {
+        let mut parts = line.split_whitespace();
+        let command = parts.next().unwrap_or_default();
+        let args = parts.collect_vec();
+
+        let result = match (command, &args[..]) {
+            (".quit" | ".exit", []) => return false,
+            (".help", []) => {
+                println!("{}", HELP);
+                Ok(())
+            }
+            (".tables", []) => self.print_tables(),
+            (".schema", [table]) => self.print_schema(table),
+            (".mode", [mode]) => OutputMode::from_str(mode, true)
+                .map(|mode| self.output.mode = mode)
+                .map_err(|e| {
+                    CustomError::Io(std::io::Error::new(std::io::ErrorKind::InvalidInput, e))
+                }),
+            (".expanded", ["on"]) => {
+                self.output.mode = OutputMode::Expanded;
+                Ok(())
+            }
+            (".expanded", ["off"]) => {
+                self.output.mode = OutputMode::Table;
+                Ok(())
+            }
+            (".headers", ["on"]) => {
+                self.output.headers = true;
+                Ok(())
+            }
+            (".headers", ["off"]) => {
+                self.output.headers = false;
+                Ok(())
+            }
+            (".limit", ["off"]) => {
+                self.output.limit = None;
+                Ok(())
+            }
+            (".limit", [rows]) => rows
+                .parse()
+                .map(|rows| self.output.limit = row_limit(rows))
+                .map_err(|_| {
+                    CustomError::InvalidArgument(format!("{} isn't a number of rows", rows))
+                }),
+            (".tz", [zone]) => TimeZoneChoice::from_str(zone, true)
+                .map(|timezone| self.output.timezone = timezone)
+                .map_err(|e| {
+                    CustomError::Io(std::io::Error::new(std::io::ErrorKind::InvalidInput, e))
+                }),
+            (".profile", ["on"]) => {
+                self.profile = true;
+                Ok(())
+            }
+            (".profile", ["off"]) => {
+                self.profile = false;
+                Ok(())
+            }
+            _ => {
+                eprintln!("unknown command or wrong arguments: {}, see .help", line);
+                Ok(())
+            }
+        };
+        if let Err(e) = result {
+            eprintln!("error: {}", e);
+        }
+        true
+    }
+
+    fn print_tables(&self) -> Result<(), CustomError> {
+        let mut stmt = self.db.prepare(
+            "SELECT name FROM sqlite_schema WHERE type IN ('table', 'view') ORDER BY name",
+        )?;
+        let user_tables: Vec<String> = stmt.query_map([], |row| row.get(0))?.try_collect()?;
+        TABLES
+            .iter()
+            .map(|t| t.to_string())
+            .chain(user_tables)
+            .for_each(|name| println!("{}", name));
+        Ok(())
+    }
+
+    fn print_schema(&self, table: &str) -> Result<(), CustomError> {
+        let sql: Option<String> = self
+            .db
+            .query_row(
+                "SELECT sql FROM sqlite_schema WHERE name = ?",
+                [table],
+                |row| row.get(0),
+            )
+            .ok();
+        if let Some(sql) = sql {
+            println!("{};", sql);
+            return Ok(());
+        }
+
+        let mut stmt = self
+            .db
+            .prepare("SELECT name, type, hidden FROM pragma_table_xinfo(?)")?;
+        let columns: Vec<(String, String, bool)> = stmt
+            .query_map([table], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .try_collect()?;
+        if columns.is_empty() {
+            eprintln!("no such table: {}", table);
+            return Ok(());
+        }
+
+        let width = columns
+            .iter()
+            .map(|(name, _, _)| name.len())
+            .max()
+            .unwrap_or(0);
+        println!("{} (", table);
+        let lines = columns
+            .iter()
+            .map(|(name, kind, hidden)| {
+                let kind = if *hidden { "hidden" } else { kind };
+                format!("    {:width$} {}", name, kind, width = width)
+            })
+            .join(",\n");
+        println!("{}\n)", lines);
+        Ok(())
+    }
+}
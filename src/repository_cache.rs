@@ -0,0 +1,126 @@
+use crate::{commit_graph, CustomError};
+use git2::Repository;
+use std::fmt::{Debug, Formatter};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use tracing::debug;
+
+/// How many repositories stay open while nothing uses them.
+const CAPACITY: usize = 16;
+
+/// The repositories opened by the tables and functions of a connection, keyed by canonical path.
+///
+/// A repository is taken out of the cache while it's in use and put back when its
+/// [`CachedRepository`] is dropped, so a handle is never shared between threads; two users of
+/// the same repository at the same time each get their own. When more than [`CAPACITY`]
+/// repositories are idle the least recently used one is closed.
+#[derive(Clone, Default)]
+pub(crate) struct RepositoryCache {
+    /// The idle repositories, least recently used first
+    idle: Arc<Mutex<Vec<(PathBuf, Repository)>>>,
+}
+
+impl RepositoryCache {
+    pub(crate) fn open(&self, path: &Path) -> Result<CachedRepository, CustomError> {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        let cached = {
+            let mut idle = self.idle();
+            idle.iter()
+                .rposition(|(open, _)| *open == path)
+                .map(|i| idle.remove(i).1)
+        };
+        let repo = match cached {
+            Some(repo) => repo,
+            None => {
+                let repo = Repository::open(&path)?;
+                let commit_graph = commit_graph::loaded(&repo);
+                debug!(path = %path.display(), commit_graph, "opened repository");
+                repo
+            }
+        };
+        Ok(CachedRepository {
+            path,
+            repo: Some(repo),
+            cache: self.clone(),
+        })
+    }
+
+    fn idle(&self) -> MutexGuard<'_, Vec<(PathBuf, Repository)>> {
+        self.idle.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Debug for RepositoryCache {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.idle().iter().map(|(path, _)| path))
+            .finish()
+    }
+}
+
+/// A repository checked out of a [`RepositoryCache`], returned to it when dropped.
+pub(crate) struct CachedRepository {
+    path: PathBuf,
+    repo: Option<Repository>,
+    cache: RepositoryCache,
+}
+
+impl Deref for CachedRepository {
+    type Target = Repository;
+
+    fn deref(&self) -> &Repository {
+        self.repo
+            .as_ref()
+            .expect("the repository is only taken on drop")
+    }
+}
+
+impl Drop for CachedRepository {
+    fn drop(&mut self) {
+        if let Some(repo) = self.repo.take() {
+            let mut idle = self.cache.idle();
+            idle.push((std::mem::take(&mut self.path), repo));
+            if idle.len() > CAPACITY {
+                idle.remove(0);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::repository_cache::{RepositoryCache, CAPACITY};
+    use crate::test::temp_repository;
+
+    #[test]
+    fn reuses_and_evicts() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, _repo) = temp_repository("repository_cache")?;
+        let cache = RepositoryCache::default();
+
+        let first = cache.open(&path)?;
+        let first_ptr = first.path() as *const _;
+        // In use, so a second user gets its own handle
+        let second = cache.open(&path.join("."))?;
+        let second_ptr = second.path() as *const _;
+        drop(second);
+        drop(first);
+        let reused = cache.open(&path)?.path() as *const _;
+
+        for _ in 0..CAPACITY {
+            drop(cache.open(&path)?);
+        }
+        let held: Vec<_> = (0..=CAPACITY)
+            .map(|_| cache.open(&path))
+            .collect::<Result<_, _>>()?;
+        drop(held);
+        let idle = cache.idle().len();
+        std::fs::remove_dir_all(&path)?;
+
+        assert_ne!(first_ptr, second_ptr);
+        assert_eq!(reused, first_ptr);
+        assert_eq!(idle, CAPACITY);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,109 @@
+use std::cmp::Ordering;
+
+/// A version read leniently from a tag name like `v1.10.0-rc.1`, anything in front of the first
+/// digit is skipped and build metadata after `+` is ignored.
+#[derive(Debug)]
+struct Version<'a> {
+    core: Vec<u64>,
+    pre_release: Option<Vec<&'a str>>,
+}
+
+impl<'a> Version<'a> {
+    fn parse(name: &'a str) -> Option<Version<'a>> {
+        let start = name.find(|c: char| c.is_ascii_digit())?;
+        let version = name[start..].split('+').next().unwrap_or_default();
+        let (core, pre_release) = match version.split_once('-') {
+            Some((core, pre_release)) => (core, Some(pre_release.split('.').collect())),
+            None => (version, None),
+        };
+        let core = core
+            .split('.')
+            .map(|part| part.parse().ok())
+            .collect::<Option<Vec<u64>>>()?;
+        Some(Version { core, pre_release })
+    }
+
+    fn cmp(&self, other: &Version) -> Ordering {
+        let len = self.core.len().max(other.core.len());
+        let component = |core: &[u64], i: usize| core.get(i).copied().unwrap_or(0);
+        (0..len)
+            .map(|i| component(&self.core, i).cmp(&component(&other.core, i)))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| match (&self.pre_release, &other.pre_release) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(a), Some(b)) => cmp_pre_release(a, b),
+            })
+    }
+}
+
+/// Numeric identifiers compare as numbers and before alphanumeric ones, a shorter list of
+/// otherwise equal identifiers comes first.
+fn cmp_pre_release(a: &[&str], b: &[&str]) -> Ordering {
+    for (a, b) in a.iter().zip(b) {
+        let ordering = match (a.parse::<u64>(), b.parse::<u64>()) {
+            (Ok(a), Ok(b)) => a.cmp(&b),
+            (Ok(_), Err(_)) => Ordering::Less,
+            (Err(_), Ok(_)) => Ordering::Greater,
+            (Err(_), Err(_)) => a.cmp(b),
+        };
+        if ordering.is_ne() {
+            return ordering;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+/// The `SEMVER` collation, sorts `v1.10.0` after `v1.9.0` and `v1.0.0-rc.1` before `v1.0.0`.
+///
+/// Names without a version sort after the ones with a version, ties are broken by comparing
+/// the names as plain strings so the order is total.
+pub fn compare(a: &str, b: &str) -> Ordering {
+    let by_version = match (Version::parse(a), Version::parse(b)) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    };
+    by_version.then_with(|| a.cmp(b))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::semver::compare;
+
+    #[test]
+    fn sorts_versions() {
+        let mut tags = vec![
+            "v1.10.0",
+            "main",
+            "v1.0.0",
+            "v1.9.0",
+            "v1.0.0-rc.1",
+            "v1.0.0-alpha",
+            "v1.0.0-rc.10",
+            "v1.0.0-rc.2",
+            "release-2.0",
+            "v0.9",
+        ];
+        tags.sort_by(|a, b| compare(a, b));
+
+        assert_eq!(
+            tags,
+            vec![
+                "v0.9",
+                "v1.0.0-alpha",
+                "v1.0.0-rc.1",
+                "v1.0.0-rc.2",
+                "v1.0.0-rc.10",
+                "v1.0.0",
+                "v1.9.0",
+                "v1.10.0",
+                "release-2.0",
+                "main",
+            ]
+        );
+    }
+}
@@ -0,0 +1,227 @@
+use crate::cancel::Deadline;
+use crate::params::{Param, Params};
+use crate::utils::{column_names, row_to_json};
+use crate::{CustomError, Interrupt};
+use rusqlite::{ffi, Batch, Connection};
+use serde_json::{json, Value};
+use std::ffi::CString;
+use std::io::Read;
+use std::ptr;
+use std::time::Duration;
+use tiny_http::{Header, Method, Response, Server};
+
+/// Largest body of a request, 1 MiB, larger ones are answered with 413 without reading the rest.
+const MAX_BODY_SIZE: u64 = 1024 * 1024;
+
+/// Serves `POST /query` on `bind` until the process is killed.
+///
+/// The body is either the SQL statement itself or a JSON object like
+/// `{"sql": "SELECT ... WHERE hash = :hash", "params": {"hash": "..."}}`, `params` may also be
+/// an array for `?` and `?N` parameters. The response is a JSON array with an object per row.
+/// Queries running longer than `timeout` are stopped with `interrupt`.
+pub fn run(
+    db: &Connection,
+    bind: &str,
+    timeout: Option<Duration>,
+    interrupt: &Interrupt,
+) -> Result<(), CustomError> {
+    db.pragma_update(None, "query_only", true)?;
+    let server = Server::http(bind).map_err(std::io::Error::other)?;
+    eprintln!("listening on http://{}", bind);
+
+    for mut request in server.incoming_requests() {
+        let response = match (request.method(), request.url()) {
+            (Method::Post, "/query") => match read_body(request.as_reader()) {
+                Ok(Some(body)) => {
+                    let deadline = timeout.map(|timeout| Deadline::start(interrupt, timeout));
+                    match handle_query(db, &body) {
+                        Ok(rows) => json_response(200, rows),
+                        Err(_) if deadline.as_ref().is_some_and(Deadline::expired) => {
+                            let timeout = timeout.unwrap_or_default().as_secs();
+                            let error = format!("the query ran longer than {}s", timeout);
+                            json_response(503, json!({ "error": error }))
+                        }
+                        Err(e) => json_response(400, json!({ "error": e.to_string() })),
+                    }
+                }
+                Ok(None) => {
+                    let error = format!("the body is larger than {} bytes", MAX_BODY_SIZE);
+                    json_response(413, json!({ "error": error }))
+                }
+                Err(e) => json_response(400, json!({ "error": e.to_string() })),
+            },
+            _ => json_response(404, json!({ "error": "only POST /query is supported" })),
+        };
+        if let Err(e) = request.respond(response) {
+            eprintln!("error: {}", e);
+        }
+    }
+
+    Ok(())
+}
+
+/// The body of a request, None when it's larger than [`MAX_BODY_SIZE`].
+fn read_body(reader: impl Read) -> Result<Option<String>, CustomError> {
+    let mut body = String::new();
+    reader.take(MAX_BODY_SIZE + 1).read_to_string(&mut body)?;
+    Ok((body.len() as u64 <= MAX_BODY_SIZE).then_some(body))
+}
+
+fn handle_query(db: &Connection, body: &str) -> Result<Value, CustomError> {
+    let (sql, params) = parse_body(body)?;
+    execute(db, &sql, params)
+}
+
+/// Runs a single statement that reads, and returns its rows.
+fn execute(db: &Connection, sql: &str, params: Params) -> Result<Value, CustomError> {
+    let mut batch = Batch::new(db, sql);
+    let mut stmt = match batch.next()? {
+        Some(stmt) => stmt,
+        None => return Err(invalid_input("empty statement".to_string())),
+    };
+    if batch.next()?.is_some() {
+        return Err(invalid_input(
+            "only a single statement is allowed".to_string(),
+        ));
+    }
+    // ATTACH and BEGIN count as reading too, but return no rows
+    if stmt.column_count() == 0 || !is_readonly(db, sql)? {
+        return Err(invalid_input(
+            "only statements that read and return rows are allowed".to_string(),
+        ));
+    }
+
+    params.bind(&mut stmt)?;
+    let col_names = column_names(&stmt);
+    let mut rows = stmt.raw_query();
+    let mut result = vec![];
+    while let Some(row) = rows.next()? {
+        result.push(row_to_json(row, &col_names));
+    }
+    Ok(Value::Array(result))
+}
+
+/// Whether the first statement of `sql` doesn't write, as SQLite tells from the prepared
+/// statement. The connection is additionally switched to `query_only`.
+fn is_readonly(db: &Connection, sql: &str) -> Result<bool, CustomError> {
+    let sql =
+        CString::new(sql).map_err(|_| invalid_input("the statement has a NUL".to_string()))?;
+    let mut stmt = ptr::null_mut();
+    // SAFETY: the statement is prepared on the open connection and finalized before returning
+    unsafe {
+        let rc = ffi::sqlite3_prepare_v2(db.handle(), sql.as_ptr(), -1, &mut stmt, ptr::null_mut());
+        if rc != ffi::SQLITE_OK {
+            return Err(rusqlite::Error::SqliteFailure(ffi::Error::new(rc), None).into());
+        }
+        let readonly = ffi::sqlite3_stmt_readonly(stmt) != 0;
+        ffi::sqlite3_finalize(stmt);
+        Ok(readonly)
+    }
+}
+
+fn parse_body(body: &str) -> Result<(String, Params), CustomError> {
+    let json = match serde_json::from_str::<Value>(body) {
+        Ok(Value::Object(json)) => json,
+        _ => return Ok((body.to_string(), Params::default())),
+    };
+    let sql = json
+        .get("sql")
+        .and_then(Value::as_str)
+        .ok_or_else(|| invalid_input("the sql field is missing".to_string()))?;
+    let params = match json.get("params") {
+        Some(Value::Object(named)) => named
+            .iter()
+            .map(|(name, value)| Param::Named(name.to_string(), param_value(value)))
+            .collect(),
+        Some(Value::Array(indexed)) => indexed
+            .iter()
+            .enumerate()
+            .map(|(i, value)| Param::Indexed(i + 1, param_value(value)))
+            .collect(),
+        _ => vec![],
+    };
+    Ok((sql.to_string(), params.into()))
+}
+
+fn param_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.to_string(),
+        other => other.to_string(),
+    }
+}
+
+fn invalid_input(message: String) -> CustomError {
+    CustomError::Io(std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        message,
+    ))
+}
+
+fn json_response(status: u16, body: Value) -> Response<std::io::Cursor<Vec<u8>>> {
+    let content_type = Header::from_bytes("Content-Type", "application/json").unwrap();
+    Response::from_string(body.to_string())
+        .with_status_code(status)
+        .with_header(content_type)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::params::Params;
+    use crate::serve::{execute, read_body, MAX_BODY_SIZE};
+    use crate::test::{commit_file, temp_repository};
+    use crate::SqliteGit;
+    use rusqlite::Connection;
+    use serde_json::json;
+
+    #[test]
+    fn reads_only_its_repository() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("serve")?;
+        commit_file(&repo, "file.txt", "one\n", "first")?;
+        let (other, other_repo) = temp_repository("serve_other")?;
+        commit_file(&other_repo, "file.txt", "secret\n", "secret")?;
+        let db = Connection::open_in_memory()?;
+        SqliteGit::new()
+            .with_all()
+            .repository(&path)
+            .pin_repository()
+            .register(&db)?;
+        db.pragma_update(None, "query_only", true)?;
+        let run = |sql: &str| execute(&db, sql, Params::default()).map_err(|e| e.to_string());
+
+        let parenthesized = run("SELECT(1) AS one");
+        let commented = run("/* latest */ SELECT message FROM commits");
+        let other_repo = run(&format!(
+            "SELECT message FROM commits('{}')",
+            other.display()
+        ));
+        let config = run("SELECT git_config_get('user.name')");
+        let root = run("SELECT git_repo_root('/')");
+        let attach = run("ATTACH 'other.db' AS other");
+        let create = run("CREATE TABLE t(x)");
+        std::fs::remove_dir_all(&path)?;
+        std::fs::remove_dir_all(&other)?;
+
+        assert_eq!(parenthesized, Ok(json!([{"one": 1}])));
+        assert_eq!(commented, Ok(json!([{"message": "first"}])));
+        assert!(other_repo
+            .unwrap_err()
+            .contains("only the default repository"));
+        assert!(config.unwrap_err().contains("no such function"));
+        assert!(root.unwrap_err().contains("no such function"));
+        assert!(attach.unwrap_err().contains("only statements that read"));
+        assert!(create.unwrap_err().contains("only statements that read"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn refuses_large_bodies() -> Result<(), Box<dyn std::error::Error>> {
+        let largest = "x".repeat(MAX_BODY_SIZE as usize);
+        let larger = format!("{}x", largest);
+
+        assert_eq!(read_body(largest.as_bytes())?, Some(largest.clone()));
+        assert_eq!(read_body(larger.as_bytes())?, None);
+
+        Ok(())
+    }
+}
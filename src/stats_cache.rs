@@ -0,0 +1,168 @@
+use crate::CustomError;
+use git2::{Oid, Repository};
+use rusqlite::{Connection, OptionalExtension};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError};
+use tracing::debug;
+
+/// The directory below the git directory the cache is kept in.
+pub(crate) const CACHE_DIR: &str = "sqlitegit";
+
+/// The lines `stats` counts per file of a commit: file name, additions and deletions.
+pub(crate) type FileStats = Vec<(Arc<str>, u64, u64)>;
+
+/// The diff stats of commits kept in `.git/sqlitegit/stats.db`, so whole-history churn queries
+/// only diff the commits they haven't seen before.
+///
+/// Stats are keyed by commit and by the diff options they were counted with. Everything in the
+/// cache can be recomputed, a cache that can't be opened or written is skipped.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct StatsCache {
+    /// The cache database per git directory
+    dbs: Arc<Mutex<HashMap<PathBuf, Connection>>>,
+}
+
+impl StatsCache {
+    /// The stats of `commit` counted with `options`, computed with `compute` and stored when
+    /// they aren't cached yet.
+    ///
+    /// The cache isn't locked while `compute` diffs, connections on other threads read and
+    /// store stats meanwhile. When two of them compute the same commit the first one is kept.
+    pub(crate) fn get_or_compute(
+        &self,
+        repo: &Repository,
+        commit: Oid,
+        options: &str,
+        compute: impl FnOnce() -> Result<FileStats, CustomError>,
+    ) -> Result<FileStats, CustomError> {
+        let hash = commit.to_string();
+        match self.with_db(repo, |db| lookup(db, &hash, options)) {
+            Some(Ok(Some(stats))) => return Ok(stats),
+            Some(Ok(None)) | None => {}
+            Some(Err(e)) => debug!(error = %e, commit = hash, "not reading cached stats"),
+        }
+        let stats = compute()?;
+        if let Some(Err(e)) = self.with_db(repo, |db| store(db, &hash, options, &stats)) {
+            debug!(error = %e, commit = hash, "not caching stats");
+        }
+        Ok(stats)
+    }
+
+    /// Calls `f` with the cache database of `repo` while holding the lock, None when it can't
+    /// be opened.
+    fn with_db<T>(&self, repo: &Repository, f: impl FnOnce(&Connection) -> T) -> Option<T> {
+        let mut dbs = self.dbs.lock().unwrap_or_else(PoisonError::into_inner);
+        let db = match dbs.entry(repo.path().to_path_buf()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => match open(repo) {
+                Ok(db) => entry.insert(db),
+                Err(e) => {
+                    debug!(error = %e, "not caching stats");
+                    return None;
+                }
+            },
+        };
+        Some(f(db))
+    }
+}
+
+fn open(repo: &Repository) -> Result<Connection, CustomError> {
+    let dir = repo.path().join(CACHE_DIR);
+    std::fs::create_dir_all(&dir)?;
+    let db = Connection::open(dir.join("stats.db"))?;
+    // Losing the last writes only means diffing those commits again
+    db.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+    db.pragma_update(None, "synchronous", "OFF")?;
+    db.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS diffed_commits (
+            hash TEXT NOT NULL,
+            options TEXT NOT NULL,
+            PRIMARY KEY (hash, options)
+        ) WITHOUT ROWID;
+        CREATE TABLE IF NOT EXISTS file_stats (
+            hash TEXT NOT NULL,
+            options TEXT NOT NULL,
+            file_name TEXT NOT NULL,
+            additions INTEGER NOT NULL,
+            deletions INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS file_stats_commit ON file_stats (hash, options);
+        "#,
+    )?;
+    Ok(db)
+}
+
+fn lookup(db: &Connection, hash: &str, options: &str) -> rusqlite::Result<Option<FileStats>> {
+    let diffed = db
+        .prepare_cached("SELECT 1 FROM diffed_commits WHERE hash = ? AND options = ?")?
+        .query_row([hash, options], |_| Ok(()))
+        .optional()?;
+    if diffed.is_none() {
+        return Ok(None);
+    }
+    db.prepare_cached(
+        "SELECT file_name, additions, deletions FROM file_stats
+         WHERE hash = ? AND options = ? ORDER BY rowid",
+    )?
+    .query_map([hash, options], |row| {
+        Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+    })?
+    .collect::<rusqlite::Result<_>>()
+    .map(Some)
+}
+
+fn store(db: &Connection, hash: &str, options: &str, stats: &FileStats) -> rusqlite::Result<()> {
+    let tx = db.unchecked_transaction()?;
+    let new = tx.execute(
+        "INSERT OR IGNORE INTO diffed_commits (hash, options) VALUES (?, ?)",
+        [hash, options],
+    )?;
+    // Stored by another thread, or another process, since the lookup
+    if new == 0 {
+        return Ok(());
+    }
+    {
+        let mut insert = tx.prepare_cached(
+            "INSERT INTO file_stats (hash, options, file_name, additions, deletions)
+             VALUES (?, ?, ?, ?, ?)",
+        )?;
+        for (file_name, additions, deletions) in stats {
+            insert.execute(rusqlite::params![
+                hash, options, file_name, additions, deletions
+            ])?;
+        }
+    }
+    tx.commit()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::stats_cache::StatsCache;
+    use crate::test::{commit_file, temp_repository};
+
+    #[test]
+    fn computes_once() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("stats_cache")?;
+        let commit = commit_file(&repo, "file.txt", "one\n", "first")?;
+        let stats = vec![("file.txt".into(), 1, 0)];
+
+        let cache = StatsCache::default();
+        let first = cache.get_or_compute(&repo, commit, "a", || Ok(stats.clone()))?;
+        // A fresh cache reads what the first one stored
+        let cached = StatsCache::default()
+            .get_or_compute(&repo, commit, "a", || panic!("the stats are cached"))?;
+        let other_options = cache.get_or_compute(&repo, commit, "b", || Ok(vec![]))?;
+        let stored = path.join(".git/sqlitegit/stats.db").exists();
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!(first, stats);
+        assert_eq!(cached, stats);
+        assert_eq!(other_options, vec![]);
+        assert!(stored);
+
+        Ok(())
+    }
+}
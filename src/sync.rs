@@ -0,0 +1,241 @@
+use crate::intern::Interner;
+use crate::pipeline::DiffPipeline;
+use crate::{CommitShadow, CustomError, DiffSettings};
+use git2::{Oid, Repository};
+use rusqlite::Connection;
+use std::collections::HashSet;
+
+/// What [`sync_repository`] changed.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Synced {
+    /// Commits ingested, with their stats
+    pub added: usize,
+    /// Commits deleted because no synced ref reaches them anymore
+    pub removed: usize,
+}
+
+/// Brings the `commits`, `stats` and `refs` tables in `schema` up to date with `repo`, creating
+/// them first if needed.
+///
+/// The commits reachable from HEAD, the local branches and the tags are synced. The commits those
+/// refs point to are recorded in `sync_tips`, the next sync only walks and diffs the commits added
+/// since. When a recorded tip isn't reachable anymore, e.g. after a force push, the whole history
+/// is walked again and the commits that are gone are deleted.
+pub fn sync_repository(
+    db: &Connection,
+    schema: &str,
+    repo: &Repository,
+) -> Result<Synced, CustomError> {
+    let tx = db.unchecked_transaction()?;
+    tx.execute_batch(&format!(
+        r#"
+        CREATE TABLE IF NOT EXISTS "{schema}".commits (
+            hash            TEXT PRIMARY KEY,
+            message         TEXT,
+            author_name     TEXT,
+            author_email    TEXT,
+            author_when     DATETIME,
+            committer_name  TEXT,
+            committer_email TEXT,
+            committer_when  DATETIME,
+            is_merge        BOOL,
+            parent_1        TEXT,
+            parent_2        TEXT
+        );
+        CREATE TABLE IF NOT EXISTS "{schema}".stats (
+            hash      TEXT NOT NULL,
+            file_name TEXT,
+            additions INTEGER,
+            deletions INTEGER
+        );
+        CREATE INDEX IF NOT EXISTS "{schema}".stats_hash ON stats (hash);
+        CREATE TABLE IF NOT EXISTS "{schema}".refs (
+            name TEXT PRIMARY KEY,
+            hash TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS "{schema}".sync_tips (
+            hash TEXT PRIMARY KEY
+        ) WITHOUT ROWID;
+        "#
+    ))?;
+
+    let tips = current_tips(repo)?;
+    let recorded = tx
+        .prepare(&format!(r#"SELECT hash FROM "{schema}".sync_tips"#))?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .map(|hash| Ok(Oid::from_str(&hash?)?))
+        .collect::<Result<Vec<_>, CustomError>>()?;
+    let fast_forward = recorded.iter().all(|old| {
+        tips.iter()
+            .any(|(_, new)| new == old || repo.graph_descendant_of(*new, *old).unwrap_or(false))
+    });
+
+    let hidden = if fast_forward { recorded } else { vec![] };
+    // After a rewrite everything is walked again, the commits that are still there aren't diffed
+    let known = if fast_forward {
+        HashSet::new()
+    } else {
+        tx.prepare(&format!(r#"SELECT hash FROM "{schema}".commits"#))?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .map(|hash| Ok(Oid::from_str(&hash?)?))
+            .collect::<Result<_, CustomError>>()?
+    };
+    let walk = DiffPipeline::walk(
+        repo.path(),
+        tips.iter().map(|(_, tip)| *tip).collect(),
+        hidden,
+        known,
+        DiffSettings::default(),
+    );
+
+    let mut interner = Interner::default();
+    let mut reachable = HashSet::new();
+    let mut added = 0;
+    {
+        let mut exists = tx.prepare(&format!(
+            r#"SELECT 1 FROM "{schema}".commits WHERE hash = ?"#
+        ))?;
+        let mut insert_commit = tx.prepare(&format!(
+            r#"INSERT INTO "{schema}".commits VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#
+        ))?;
+        let mut insert_stats = tx.prepare(&format!(
+            r#"INSERT INTO "{schema}".stats (hash, file_name, additions, deletions)
+               VALUES (?, ?, ?, ?)"#
+        ))?;
+        for diffed in walk {
+            let (oid, stats) = diffed?;
+            if !fast_forward {
+                reachable.insert(oid);
+            }
+            let hash = oid.to_string();
+            let Some(stats) = stats else {
+                continue;
+            };
+            if exists.exists([&hash])? {
+                continue;
+            }
+            for (file_name, additions, deletions) in stats {
+                insert_stats.execute(rusqlite::params![hash, file_name, additions, deletions])?;
+            }
+            let c = CommitShadow::new(&repo.find_commit(oid)?, &mut interner);
+            insert_commit.execute(rusqlite::params![
+                c.hash,
+                c.message,
+                c.author_name,
+                c.author_email,
+                c.author_when,
+                c.committer_name,
+                c.committer_email,
+                c.committer_when,
+                c.is_merge,
+                c.parent_1,
+                c.parent_2,
+            ])?;
+            added += 1;
+        }
+    }
+
+    let mut removed = 0;
+    if !fast_forward {
+        // Only the commits that are gone are collected, not the whole history
+        let mut gone = vec![];
+        let mut synced = tx.prepare(&format!(r#"SELECT hash FROM "{schema}".commits"#))?;
+        let mut rows = synced.query([])?;
+        while let Some(row) = rows.next()? {
+            let hash: String = row.get(0)?;
+            if !reachable.contains(&Oid::from_str(&hash)?) {
+                gone.push(hash);
+            }
+        }
+        for hash in &gone {
+            tx.execute(
+                &format!(r#"DELETE FROM "{schema}".stats WHERE hash = ?"#),
+                [hash],
+            )?;
+            removed += tx.execute(
+                &format!(r#"DELETE FROM "{schema}".commits WHERE hash = ?"#),
+                [hash],
+            )?;
+        }
+    }
+
+    tx.execute(&format!(r#"DELETE FROM "{schema}".refs"#), [])?;
+    tx.execute(&format!(r#"DELETE FROM "{schema}".sync_tips"#), [])?;
+    for (name, tip) in &tips {
+        tx.execute(
+            &format!(r#"INSERT INTO "{schema}".refs (name, hash) VALUES (?, ?)"#),
+            [name, &tip.to_string()],
+        )?;
+        tx.execute(
+            &format!(r#"INSERT OR IGNORE INTO "{schema}".sync_tips (hash) VALUES (?)"#),
+            [tip.to_string()],
+        )?;
+    }
+    tx.commit()?;
+
+    Ok(Synced { added, removed })
+}
+
+/// HEAD, the local branches and the tags, with the commits they point to. Tags of other objects
+/// and an unborn HEAD are left out.
+fn current_tips(repo: &Repository) -> Result<Vec<(String, Oid)>, CustomError> {
+    let mut tips = vec![];
+    if let Ok(head) = repo.head().and_then(|head| head.peel_to_commit()) {
+        tips.push(("HEAD".to_string(), head.id()));
+    }
+    for reference in repo.references()? {
+        let reference = reference?;
+        let Some(name) = reference.name() else {
+            continue;
+        };
+        if !name.starts_with("refs/heads/") && !name.starts_with("refs/tags/") {
+            continue;
+        }
+        if let Ok(commit) = reference.peel_to_commit() {
+            tips.push((name.to_string(), commit.id()));
+        }
+    }
+    Ok(tips)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sync::{sync_repository, Synced};
+    use crate::test::{commit_file, temp_repository};
+    use rusqlite::Connection;
+
+    #[test]
+    fn syncs_new_and_rewritten_history() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("sync")?;
+        let first = commit_file(&repo, "file.txt", "one\n", "first")?;
+        commit_file(&repo, "file.txt", "two\n", "second")?;
+
+        let db = Connection::open_in_memory()?;
+        let initial = sync_repository(&db, "main", &repo)?;
+        commit_file(&repo, "file.txt", "three\n", "third")?;
+        let new_commit = sync_repository(&db, "main", &repo)?;
+        let unchanged = sync_repository(&db, "main", &repo)?;
+        // Force the branch back to the first commit
+        let branch = repo.head()?.name().unwrap_or_default().to_string();
+        repo.reference(&branch, first, true, "reset")?;
+        let rewritten = sync_repository(&db, "main", &repo)?;
+
+        let counts: (i64, i64, String) = db.query_row(
+            "SELECT (SELECT count(*) FROM commits), (SELECT count(*) FROM stats),
+                    (SELECT hash FROM refs WHERE name = 'HEAD')",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+        std::fs::remove_dir_all(&path)?;
+
+        let synced = |added, removed| Synced { added, removed };
+        assert_eq!(initial, synced(2, 0));
+        assert_eq!(new_commit, synced(1, 0));
+        assert_eq!(unchanged, synced(0, 0));
+        assert_eq!(rewritten, synced(0, 2));
+        // The root commit has no parent to diff against
+        assert_eq!(counts, (1, 0, first.to_string()));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,331 @@
+use crate::writable::{flag, optional, required, text, timestamp, Row, Table};
+use crate::CustomError;
+use git2::{ObjectType, Reference, Repository, Signature};
+use rusqlite::types::Value;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use tracing::debug;
+
+/// `tags([repo])`, the tags of a repository. INSERT creates a tag: a lightweight one when the
+/// row has no message, an annotated one when it has, and a signed one when `is_signed` is true,
+/// signed with gpg like `git tag -s` does. `target` is a revision, HEAD when it's NULL, the
+/// tagger is the configured identity unless `tagger_name` and `tagger_email` are given. DELETE
+/// removes a tag, tags can't be updated.
+pub(crate) static TAGS: Table = Table {
+    name: "tags",
+    columns: &[
+        ("name", "text"),
+        ("target", "text"),
+        ("message", "text"),
+        ("tagger_name", "text"),
+        ("tagger_email", "text"),
+        ("tagger_when", "DATETIME"),
+        ("is_signed", "integer"),
+    ],
+    rows,
+    insert,
+    update: None,
+    delete,
+};
+
+const NAME: usize = 0;
+const TARGET: usize = 1;
+const MESSAGE: usize = 2;
+const TAGGER_NAME: usize = 3;
+const TAGGER_EMAIL: usize = 4;
+const IS_SIGNED: usize = 6;
+
+/// Where the signature of a signed tag starts in its message.
+const SIGNATURES: [&str; 3] = [
+    "-----BEGIN PGP SIGNATURE-----",
+    "-----BEGIN SSH SIGNATURE-----",
+    "-----BEGIN SIGNED MESSAGE-----",
+];
+
+fn rows(repo: &Repository) -> Result<Vec<Row>, CustomError> {
+    let mut rows = vec![];
+    for reference in repo.references_glob("refs/tags/*")? {
+        let reference = reference?;
+        let Some(name) = reference.shorthand() else {
+            continue;
+        };
+        let name = Value::Text(name.to_string());
+        rows.push(match reference.peel_to_tag() {
+            Ok(tag) => {
+                let message = String::from_utf8_lossy(tag.message_bytes().unwrap_or_default());
+                let signed = SIGNATURES.iter().find_map(|start| message.find(start));
+                let tagger = tag.tagger();
+                vec![
+                    name,
+                    Value::Text(tag.target_id().to_string()),
+                    Value::Text(message[..signed.unwrap_or(message.len())].to_string()),
+                    optional(tagger.as_ref().map(|t| lossy(t.name_bytes()))),
+                    optional(tagger.as_ref().map(|t| lossy(t.email_bytes()))),
+                    tagger.map_or(Value::Null, |tagger| timestamp(tagger.when())),
+                    Value::Integer(signed.is_some().into()),
+                ]
+            }
+            Err(_) => vec![
+                name,
+                optional(reference.target().map(|oid| oid.to_string())),
+                Value::Null,
+                Value::Null,
+                Value::Null,
+                Value::Null,
+                Value::Integer(0),
+            ],
+        });
+    }
+    Ok(rows)
+}
+
+fn insert(repo: &Repository, row: &Row) -> Result<(), CustomError> {
+    let name = required(&TAGS, row, NAME)?;
+    let refname = format!("refs/tags/{}", name);
+    if !Reference::is_valid_name(&refname) {
+        return Err(CustomError::InvalidArgument(format!(
+            "{} isn't a valid tag name",
+            name
+        )));
+    }
+    let target = repo.revparse_single(text(&TAGS, row, TARGET)?.unwrap_or("HEAD"))?;
+    let signed = flag(&TAGS, row, IS_SIGNED)?;
+    let message = text(&TAGS, row, MESSAGE)?;
+    let log = format!("tags: tag {}", name);
+    if message.is_none() && !signed {
+        repo.reference(&refname, target.id(), false, &log)?;
+        return Ok(());
+    }
+
+    let tagger = tagger(repo, row)?;
+    let mut message = message.unwrap_or_default().to_string();
+    // Like `git tag -m`, the signature goes on a line of its own
+    if !message.is_empty() && !message.ends_with('\n') {
+        message.push('\n');
+    }
+    let mut buffer = format!(
+        "object {}\ntype {}\ntag {}\ntagger {}\n\n{}",
+        target.id(),
+        target.kind().unwrap_or(ObjectType::Commit),
+        name,
+        identity(&tagger),
+        message
+    );
+    if signed {
+        buffer.push_str(&sign(repo, &tagger, &buffer)?);
+    }
+    let tag = repo.odb()?.write(ObjectType::Tag, buffer.as_bytes())?;
+    repo.reference(&refname, tag, false, &log)?;
+    Ok(())
+}
+
+fn delete(repo: &Repository, row: &Row) -> Result<(), CustomError> {
+    Ok(repo.tag_delete(required(&TAGS, row, NAME)?)?)
+}
+
+/// The tagger of a new tag, the configured identity fills in what the row leaves out.
+fn tagger(repo: &Repository, row: &Row) -> Result<Signature<'static>, CustomError> {
+    let (name, email) = (
+        text(&TAGS, row, TAGGER_NAME)?,
+        text(&TAGS, row, TAGGER_EMAIL)?,
+    );
+    if let (Some(name), Some(email)) = (name, email) {
+        return Ok(Signature::now(name, email)?);
+    }
+    let configured = repo.signature()?;
+    Ok(Signature::now(
+        name.unwrap_or(&lossy(configured.name_bytes())),
+        email.unwrap_or(&lossy(configured.email_bytes())),
+    )?)
+}
+
+/// `Name <email> seconds offset`, like in the header of a tag.
+fn identity(signature: &Signature) -> String {
+    let when = signature.when();
+    let offset = when.offset_minutes();
+    format!(
+        "{} <{}> {} {}{:02}{:02}",
+        lossy(signature.name_bytes()),
+        lossy(signature.email_bytes()),
+        when.seconds(),
+        if offset < 0 { '-' } else { '+' },
+        offset.abs() / 60,
+        offset.abs() % 60
+    )
+}
+
+/// The detached signature of `buffer`, made with `gpg.program` and the key of `user.signingKey`
+/// or else the tagger, like git makes it.
+fn sign(repo: &Repository, tagger: &Signature, buffer: &str) -> Result<String, CustomError> {
+    let config = repo.config()?;
+    let program = config
+        .get_string("gpg.program")
+        .unwrap_or_else(|_| "gpg".to_string());
+    let key = config.get_string("user.signingKey").unwrap_or_else(|_| {
+        let (name, email) = (tagger.name_bytes(), tagger.email_bytes());
+        format!("{} <{}>", lossy(name), lossy(email))
+    });
+    debug!(program, key, "signing tag");
+    let mut gpg = Command::new(&program)
+        .args(["--status-fd=2", "-bsau", &key])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| CustomError::InvalidArgument(format!("can't run {}: {}", program, e)))?;
+    if let Some(mut stdin) = gpg.stdin.take() {
+        stdin.write_all(buffer.as_bytes())?;
+    }
+    let output = gpg.wait_with_output()?;
+    let status = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() || !status.contains("[GNUPG:] SIG_CREATED ") {
+        return Err(CustomError::InvalidArgument(format!(
+            "{} failed to sign the tag: {}",
+            program,
+            status.trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+fn lossy(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test::{commit_file, temp_repository};
+    use crate::SqliteGit;
+    use rusqlite::Connection;
+    use std::path::Path;
+    use std::process::Command;
+
+    type Tag = (String, String, Option<String>, Option<String>, bool);
+
+    fn tags_db(repository: &Path) -> rusqlite::Result<Connection> {
+        let db = Connection::open_in_memory()?;
+        SqliteGit::new()
+            .with_tags()
+            .repository(repository)
+            .register(&db)?;
+        Ok(db)
+    }
+
+    fn tags(db: &Connection) -> rusqlite::Result<Vec<Tag>> {
+        let mut stmt = db.prepare(
+            "SELECT name, target, message, tagger_name, is_signed FROM tags ORDER BY name",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+            ))
+        })?;
+        rows.collect()
+    }
+
+    #[test]
+    fn tags_and_untags_in_a_transaction() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("tags")?;
+        let first = commit_file(&repo, "file.txt", "one\n", "first")?;
+        let second = commit_file(&repo, "file.txt", "two\n", "second")?;
+        let mut config = repo.config()?;
+        config.set_str("user.name", "Releaser")?;
+        config.set_str("user.email", "releaser@example.com")?;
+
+        let db = tags_db(&path)?;
+        db.execute_batch(
+            "BEGIN;
+             INSERT INTO tags(name) VALUES ('v1');
+             INSERT INTO tags(name, target, message) VALUES ('v0', 'HEAD~1', 'First release');
+             COMMIT;",
+        )?;
+        let tagged = tags(&db)?;
+        let annotated = repo.revparse_single("v0")?.peel_to_tag()?;
+        let existing = db.execute("INSERT INTO tags(name) VALUES ('v1')", []);
+        let update = db.execute("UPDATE tags SET message = 'Moved' WHERE name = 'v1'", []);
+        db.execute_batch("BEGIN; DELETE FROM tags WHERE name = 'v1'; COMMIT;")?;
+        let untagged = tags(&db)?;
+        let deleted = repo.revparse_single("v1").is_err();
+        std::fs::remove_dir_all(&path)?;
+
+        let v0 = (
+            "v0".to_string(),
+            first.to_string(),
+            Some("First release\n".to_string()),
+            Some("Releaser".to_string()),
+            false,
+        );
+        let v1 = ("v1".to_string(), second.to_string(), None, None, false);
+        assert_eq!(tagged, vec![v0.clone(), v1]);
+        assert_eq!(annotated.message(), Some("First release\n"));
+        assert_eq!(annotated.target_id(), first);
+        assert!(existing.is_err());
+        assert!(update.unwrap_err().to_string().contains("can't be updated"));
+        assert_eq!(untagged, vec![v0]);
+        assert!(deleted);
+
+        Ok(())
+    }
+
+    #[test]
+    fn signs_tags_with_gpg() -> Result<(), Box<dyn std::error::Error>> {
+        if Command::new("gpg").arg("--version").output().is_err() {
+            return Ok(());
+        }
+        let (path, repo) = temp_repository("tags_signed")?;
+        commit_file(&repo, "file.txt", "one\n", "first")?;
+        // A key of its own, gpg.program points git and the table at it
+        let home = path.join(".git").join("gnupg");
+        std::fs::create_dir_all(&home)?;
+        let generated = Command::new("gpg")
+            .arg("--homedir")
+            .arg(&home)
+            .args(["--batch", "--passphrase", "", "--quick-gen-key"])
+            .args(["Signer <signer@example.com>", "ed25519", "sign", "never"])
+            .output()?;
+        assert!(generated.status.success(), "{:?}", generated);
+        let program = path.join(".git").join("gpg.sh");
+        let script = format!(
+            "#!/bin/sh\nexec gpg --homedir '{}' \"$@\"\n",
+            home.display()
+        );
+        std::fs::write(&program, script)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o755))?;
+        }
+        let mut config = repo.config()?;
+        config.set_str("gpg.program", &program.to_string_lossy())?;
+        config.set_str("user.signingKey", "signer@example.com")?;
+
+        let db = tags_db(&path)?;
+        db.execute(
+            "INSERT INTO tags(name, message, tagger_name, tagger_email, is_signed)
+             VALUES ('v1', 'Signed release', 'Signer', 'signer@example.com', 1)",
+            [],
+        )?;
+        let signed = tags(&db)?;
+        let verified = Command::new("git")
+            .args(["verify-tag", "v1"])
+            .current_dir(&path)
+            .output()?;
+        let _ = Command::new("gpgconf")
+            .arg("--homedir")
+            .arg(&home)
+            .args(["--kill", "gpg-agent"])
+            .output();
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!(signed.len(), 1);
+        assert_eq!(signed[0].2.as_deref(), Some("Signed release\n"));
+        assert!(signed[0].4);
+        assert!(verified.status.success(), "{:?}", verified);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,2814 @@
+use crate::complete::{Completion, Schema};
+use crate::config::{Config, Panel, QueryTemplate, TuiConfig};
+use crate::highlight::Highlighter;
+use crate::output::{value_to_string, write_rows, OutputMode, OutputOptions};
+use crate::params::{Param, Params};
+use crate::watch::Changes;
+use crate::{CustomError, Interrupt};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Position, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Cell, Clear, Paragraph, Row, Table, TableState, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use rusqlite::types::{Value, ValueRef};
+use rusqlite::{Batch, Connection};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Height of the SQL editor, with its border.
+const EDITOR_HEIGHT: u16 = 10;
+
+/// Rows of a result kept for browsing, the rest of a bigger result isn't read.
+const MAX_ROWS: usize = 10_000;
+
+/// Widest a result column is drawn, longer values are cut off.
+const MAX_COLUMN_WIDTH: usize = 40;
+
+/// Completions listed at once, the list scrolls.
+const MAX_LISTED_COMPLETIONS: u16 = 8;
+
+/// How often a running query looks for Ctrl-C or Esc.
+const POLL: Duration = Duration::from_millis(50);
+
+/// Commits the browser lists, the newest that match the search.
+const MAX_COMMITS: usize = 1_000;
+
+const SEARCH_TITLE: &str =
+    " Search: author:NAME path:FILE rev:RANGE or words of the message, Enter applies ";
+
+/// Runs the tui until Ctrl-Q. It starts with the query workbench, a SQL editor above a table of
+/// the last result, F2 switches to the commit browser and back, F3 to the blame of a file and F4
+/// to the queries of the config, queries are saved to the file at `config_path` or the one
+/// [`Config::save_path`] picks. F6 shows the dashboard of the config's panels, it starts with
+/// it with `dashboard`. Ctrl-C or Esc stops a query that is running. The keys and the colors
+/// are the ones of `[tui]` in the config.
+pub fn run(
+    db: &Connection,
+    interrupt: &Interrupt,
+    config: &Config,
+    config_path: Option<&Path>,
+    repository: &Path,
+    dashboard: bool,
+) -> Result<(), CustomError> {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    let settings = Settings::new(&config.tui, no_color)?;
+    let queries = Queries::new(
+        config.queries.clone(),
+        Config::save_path(config_path, repository),
+        &settings,
+    );
+    let panels = config.tui.panels.clone();
+    let mut app = App::new(db, queries, panels, repository, &settings);
+    if dashboard {
+        app.screen = Screen::Dashboard;
+    }
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal, interrupt);
+    ratatui::restore();
+    result
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Screen {
+    Workbench,
+    Browser,
+    Blame,
+    Queries,
+    Dashboard,
+}
+
+struct App<'a> {
+    workbench: Workbench<'a>,
+    browser: Browser<'a>,
+    blame: Blame<'a>,
+    queries: Queries<'a>,
+    dashboard: Dashboard<'a>,
+    screen: Screen,
+    settings: &'a Settings,
+}
+
+impl<'a> App<'a> {
+    fn new(
+        db: &'a Connection,
+        queries: Queries<'a>,
+        panels: Vec<Panel>,
+        repository: &Path,
+        settings: &'a Settings,
+    ) -> Self {
+        App {
+            workbench: Workbench::new(db, settings),
+            browser: Browser::new(db, settings),
+            blame: Blame::new(db, settings),
+            queries,
+            dashboard: Dashboard::new(db, panels, repository, settings),
+            screen: Screen::Workbench,
+            settings,
+        }
+    }
+
+    fn run(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        interrupt: &Interrupt,
+    ) -> Result<(), CustomError> {
+        // The dashboard starts with its panels filled
+        let mut pending = (self.screen == Screen::Dashboard).then_some(Action::Execute);
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            let action = match pending.take() {
+                Some(action) => action,
+                None => self.next_action()?,
+            };
+            match action {
+                Action::None => {}
+                Action::Execute => {
+                    let stop = self.settings.keys(Binding::Stop);
+                    *self.status() = format!(
+                        "Running, {} stops the query",
+                        stop.iter()
+                            .map(Key::to_string)
+                            .collect::<Vec<_>>()
+                            .join(" or ")
+                    );
+                    terminal.draw(|frame| self.draw(frame))?;
+                    let _stop = StopKeys::interrupting(interrupt, stop.to_vec());
+                    match self.screen {
+                        Screen::Workbench => self.workbench.execute(),
+                        Screen::Browser => self.browser.execute(),
+                        Screen::Blame => self.blame.execute(),
+                        Screen::Dashboard => self.dashboard.execute(),
+                        // Saved queries run in the workbench
+                        Screen::Queries => {}
+                    }
+                }
+                Action::Quit => return Ok(()),
+                _ => unreachable!("handled by the app"),
+            }
+        }
+    }
+
+    /// Waits for a key, the dashboard is refreshed when the repository changes in the meantime.
+    /// Other events only redraw the screen.
+    fn next_action(&mut self) -> Result<Action, CustomError> {
+        loop {
+            if event::poll(POLL)? {
+                return Ok(match event::read()? {
+                    Event::Key(key) => self.handle_key(key),
+                    _ => Action::None,
+                });
+            }
+            if self.screen == Screen::Dashboard && self.dashboard.changed() {
+                return Ok(Action::Execute);
+            }
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Action {
+        let screen = match (self.settings.binding(&key), self.screen) {
+            (Some(Binding::Dashboard), Screen::Dashboard) => Some(Screen::Workbench),
+            (Some(Binding::Dashboard), _) => Some(Screen::Dashboard),
+            (Some(Binding::Commits), Screen::Browser) => Some(Screen::Workbench),
+            (Some(Binding::Commits), _) | (Some(Binding::Blame), Screen::Blame) => {
+                Some(Screen::Browser)
+            }
+            (Some(Binding::Blame), _) => Some(Screen::Blame),
+            (Some(Binding::Queries), Screen::Queries) => Some(Screen::Workbench),
+            (Some(Binding::Queries), _) => Some(Screen::Queries),
+            _ => None,
+        };
+        if let Some(screen) = screen {
+            self.screen = screen;
+            // The commits are listed and the panels filled the first time they're shown
+            let first_visit = match self.screen {
+                Screen::Browser => !self.browser.listed,
+                Screen::Dashboard => !self.dashboard.refreshed,
+                _ => false,
+            };
+            return if first_visit {
+                Action::Execute
+            } else {
+                Action::None
+            };
+        }
+        let action = match self.screen {
+            Screen::Workbench => self.workbench.handle_key(key),
+            Screen::Browser => self.browser.handle_key(key),
+            Screen::Blame => self.blame.handle_key(key),
+            Screen::Queries => self.queries.handle_key(key),
+            Screen::Dashboard => self.dashboard.handle_key(key),
+        };
+        match action {
+            Action::Blame { path, rev } => {
+                self.blame.file = Editor::with_text(&format!("{} {}", path, rev));
+                self.screen = Screen::Blame;
+                Action::Execute
+            }
+            Action::Show(hash) => {
+                self.browser.search = Editor::with_text(&format!("rev:{}", hash));
+                self.screen = Screen::Browser;
+                Action::Execute
+            }
+            Action::Open { name, run } => {
+                let Some(template) = self.queries.queries.get(&name) else {
+                    return Action::None;
+                };
+                self.workbench.open(&name, template);
+                self.screen = Screen::Workbench;
+                match run {
+                    true => Action::Execute,
+                    false => Action::None,
+                }
+            }
+            Action::SaveAs => {
+                let name = self.workbench.saved_as.as_deref().unwrap_or_default();
+                self.queries.name = Editor::with_text(name);
+                self.queries.focus = QueriesFocus::Name;
+                self.screen = Screen::Queries;
+                Action::None
+            }
+            Action::Save(name) => {
+                self.save(name);
+                Action::None
+            }
+            action => action,
+        }
+    }
+
+    /// Saves the query and the parameters of the workbench to the config file as `name`.
+    fn save(&mut self, name: String) {
+        let description = (self.queries.queries.get(&name)).and_then(|q| q.description.clone());
+        let template = match self.workbench.template(description) {
+            Ok(template) => template,
+            Err(e) => {
+                self.queries.status = format!("error: {}", e);
+                return;
+            }
+        };
+        match Config::save_query(&self.queries.path, &name, &template) {
+            Ok(()) => {
+                self.workbench.status =
+                    format!("Saved {} to {}", name, self.queries.path.display());
+                self.workbench.saved_as = Some(name.clone());
+                self.queries.queries.insert(name, template);
+                self.screen = Screen::Workbench;
+            }
+            Err(e) => self.queries.status = format!("error: {}", e),
+        }
+    }
+
+    fn status(&mut self) -> &mut String {
+        match self.screen {
+            Screen::Workbench => &mut self.workbench.status,
+            Screen::Browser => &mut self.browser.status,
+            Screen::Blame => &mut self.blame.status,
+            Screen::Queries => &mut self.queries.status,
+            Screen::Dashboard => &mut self.dashboard.status,
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        match self.screen {
+            Screen::Workbench => self.workbench.draw(frame),
+            Screen::Browser => self.browser.draw(frame),
+            Screen::Blame => self.blame.draw(frame),
+            Screen::Queries => self.queries.draw(frame),
+            Screen::Dashboard => self.dashboard.draw(frame),
+        }
+    }
+}
+
+/// What a key asks the tui to do besides editing or moving around.
+#[derive(Debug, PartialEq)]
+enum Action {
+    None,
+    Execute,
+    Quit,
+    /// Open the blame of the file at `path` in `rev`
+    Blame {
+        path: String,
+        rev: String,
+    },
+    /// Show a commit in the browser
+    Show(String),
+    /// Open a saved query in the workbench, and run it
+    Open {
+        name: String,
+        run: bool,
+    },
+    /// Ask for the name to save the workbench's query as
+    SaveAs,
+    /// Save the workbench's query as the given name
+    Save(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Focus {
+    Editor,
+    Params,
+    Results,
+}
+
+struct Workbench<'a> {
+    db: &'a Connection,
+    settings: &'a Settings,
+    editor: Editor,
+    /// The parameters bound to the statements, a `NAME=VALUE` per line
+    params: Editor,
+    results: Results,
+    focus: Focus,
+    /// The name of the saved query that was opened or saved last
+    saved_as: Option<String>,
+    /// The file the result is exported to, while it's typed
+    export: Option<Editor>,
+    /// The file the result was exported to last
+    exported: String,
+    /// The names the query can use, read when a name is first completed
+    schema: Option<Schema>,
+    /// The completions of the word at the cursor, while they're listed
+    completions: Option<Completions>,
+    /// The outcome of the last run, or an error
+    status: String,
+}
+
+/// The completions listed below the cursor of the SQL editor.
+#[derive(Debug)]
+struct Completions {
+    /// Where the word they replace starts, a byte offset of the editor's text
+    start: usize,
+    completions: Vec<Completion>,
+    state: TableState,
+}
+
+impl Completions {
+    /// Draws the list below the cursor, or above it when there's no room below.
+    fn render(&mut self, frame: &mut Frame, cursor: Position, theme: &Theme) {
+        let area = frame.area();
+        let widest = (self.completions.iter())
+            .map(|completion| completion.display.chars().count())
+            .max()
+            .unwrap_or_default();
+        let width = (widest as u16 + 2).min(area.width);
+        let height = (self.completions.len() as u16).min(MAX_LISTED_COMPLETIONS) + 2;
+        let x = cursor.x.min(area.right().saturating_sub(width));
+        let y = match cursor.y + 1 + height <= area.bottom() {
+            true => cursor.y + 1,
+            false => cursor.y.saturating_sub(height),
+        };
+        let list = Rect::new(x, y, width, height).intersection(area);
+        let rows =
+            (self.completions.iter()).map(|completion| Row::new([completion.display.clone()]));
+        let table = Table::new(rows, [Constraint::Min(1)])
+            .row_highlight_style(theme.selected)
+            .block(Block::bordered().border_style(theme.border(true)));
+        frame.render_widget(Clear, list);
+        frame.render_stateful_widget(table, list, &mut self.state);
+    }
+}
+
+impl<'a> Workbench<'a> {
+    fn new(db: &'a Connection, settings: &'a Settings) -> Self {
+        Workbench {
+            db,
+            settings,
+            editor: Editor::default(),
+            params: Editor::default(),
+            results: Results::default(),
+            focus: Focus::Editor,
+            saved_as: None,
+            export: None,
+            exported: "result.csv".to_string(),
+            schema: None,
+            completions: None,
+            status: format!(
+                "Type a query and press {}, {} completes names",
+                settings.key_name(Binding::Run),
+                settings.key_name(Binding::Complete)
+            ),
+        }
+    }
+
+    /// Replaces the query and the parameters with a saved query and its defaults.
+    fn open(&mut self, name: &str, template: &QueryTemplate) {
+        let params = (template.params.iter()).map(|(name, value)| format!("{}={}", name, value));
+        self.editor = Editor::with_text(&template.sql);
+        self.params = Editor::with_text(&params.collect::<Vec<_>>().join("\n"));
+        self.focus = Focus::Editor;
+        self.saved_as = Some(name.to_string());
+        self.status = match &template.description {
+            Some(description) => format!("{}: {}", name, description),
+            None => name.to_string(),
+        };
+    }
+
+    /// The parameters of the lines that aren't blank, as `--param` takes them.
+    fn params(&self) -> Result<Vec<Param>, String> {
+        (self.params.lines.iter())
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.trim().parse())
+            .collect()
+    }
+
+    /// The query as a saved query, the parameters become the defaults.
+    fn template(&self, description: Option<String>) -> Result<QueryTemplate, String> {
+        let params = self.params()?.into_iter().map(|param| match param {
+            Param::Named(name, value) => Ok((name, value)),
+            _ => Err("only NAME=VALUE parameters can be saved".to_string()),
+        });
+        Ok(QueryTemplate {
+            sql: self.editor.text(),
+            description,
+            params: params.collect::<Result<_, _>>()?,
+        })
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Action {
+        if key.kind == KeyEventKind::Release {
+            return Action::None;
+        }
+        if let Some(export) = &mut self.export {
+            match key.code {
+                KeyCode::Enter => self.export(),
+                KeyCode::Esc => self.export = None,
+                _ => export.handle_key(key),
+            }
+            return Action::None;
+        }
+        if let Some(completions) = &mut self.completions {
+            match key.code {
+                KeyCode::Up | KeyCode::Down | KeyCode::PageUp | KeyCode::PageDown => {
+                    let listed = completions.completions.len();
+                    move_selection(&mut completions.state, key.code, listed);
+                    return Action::None;
+                }
+                KeyCode::Enter | KeyCode::Tab => {
+                    self.accept_completion();
+                    return Action::None;
+                }
+                KeyCode::Esc => {
+                    self.completions = None;
+                    return Action::None;
+                }
+                _ => {}
+            }
+        }
+        match self.settings.binding(&key) {
+            Some(Binding::Quit) => return Action::Quit,
+            Some(Binding::Complete) if self.focus == Focus::Editor => {
+                self.complete(false);
+                return Action::None;
+            }
+            Some(Binding::Run) => return Action::Execute,
+            Some(Binding::Save) => return Action::SaveAs,
+            Some(Binding::Export) if self.results.columns.is_empty() => {
+                self.status = "error: there's no result to export, run a query first".to_string();
+                return Action::None;
+            }
+            Some(Binding::Export) => {
+                self.export = Some(Editor::with_text(&self.exported));
+                return Action::None;
+            }
+            Some(Binding::NextPane) => {
+                self.completions = None;
+                self.focus = match self.focus {
+                    Focus::Editor => Focus::Params,
+                    Focus::Params => Focus::Results,
+                    Focus::Results => Focus::Editor,
+                };
+                return Action::None;
+            }
+            _ => {}
+        }
+        if key.code == KeyCode::BackTab {
+            self.completions = None;
+            self.focus = match self.focus {
+                Focus::Editor => Focus::Results,
+                Focus::Params => Focus::Editor,
+                Focus::Results => Focus::Params,
+            };
+            return Action::None;
+        }
+        match self.focus {
+            Focus::Editor => self.editor.handle_key(key),
+            Focus::Params => self.params.handle_key(key),
+            Focus::Results => self.results.handle_key(key),
+        }
+        // The list follows the word while it's typed
+        if self.completions.is_some() {
+            self.complete(true);
+        }
+        Action::None
+    }
+
+    /// Lists the completions of the word at the cursor of the SQL editor, a single completion
+    /// replaces the word unless it's being `typing`.
+    fn complete(&mut self, typing: bool) {
+        if self.schema.is_none() {
+            match Schema::read(self.db) {
+                Ok(schema) => self.schema = Some(schema),
+                Err(e) => {
+                    self.status = format!("error: {}", e);
+                    return;
+                }
+            }
+        }
+        let Some(schema) = &self.schema else {
+            return;
+        };
+        let (start, completions) = schema.complete(&self.editor.text(), self.editor.position());
+        self.completions = match completions.len() {
+            0 => None,
+            _ => Some(Completions {
+                start,
+                completions,
+                state: TableState::default().with_selected(Some(0)),
+            }),
+        };
+        if !typing
+            && self
+                .completions
+                .as_ref()
+                .is_some_and(|c| c.completions.len() == 1)
+        {
+            self.accept_completion();
+        }
+    }
+
+    /// Replaces the word at the cursor with the selected completion. Tables and functions are
+    /// shown with their arguments in the status line.
+    fn accept_completion(&mut self) {
+        let Some(mut completions) = self.completions.take() else {
+            return;
+        };
+        let selected = completions.state.selected().unwrap_or_default();
+        if selected >= completions.completions.len() {
+            return;
+        }
+        let completion = completions.completions.swap_remove(selected);
+        self.editor
+            .replace_word(completions.start, &completion.text);
+        if completion.display != completion.text {
+            self.status = completion.display;
+        }
+    }
+
+    /// Runs the statements in the editor, the result of the last one that returns columns is
+    /// shown.
+    fn execute(&mut self) {
+        let params = match self.params() {
+            Ok(params) => Params::from(params),
+            Err(e) => {
+                self.status = format!("error: {}", e);
+                return;
+            }
+        };
+        let start = Instant::now();
+        match Results::load(self.db, &self.editor.text(), &params) {
+            Ok(Some(results)) => {
+                let shown = if results.truncated {
+                    format!(", the first {} are shown", MAX_ROWS)
+                } else {
+                    String::new()
+                };
+                self.status = format!(
+                    "{} rows in {:.1?}{}",
+                    results.rows.len(),
+                    start.elapsed(),
+                    shown
+                );
+                self.results = results;
+            }
+            Ok(None) => self.status = format!("Done in {:.1?}", start.elapsed()),
+            Err(e) => self.status = format!("error: {}", e),
+        }
+        // The statements may have created or dropped tables
+        self.schema = None;
+    }
+
+    /// Writes the result as it's shown to the file of the export prompt, in the format of the
+    /// file's extension.
+    fn export(&mut self) {
+        let Some(path) = self
+            .export
+            .take()
+            .map(|export| export.text().trim().to_string())
+        else {
+            return;
+        };
+        self.status = match self.results.export(Path::new(&path)) {
+            Ok(rows) if self.results.truncated => {
+                format!(
+                    "Exported the first {} rows to {}, the result has more",
+                    rows, path
+                )
+            }
+            Ok(rows) => format!("Exported {} rows to {}", rows, path),
+            Err(e) => format!("error: {}", e),
+        };
+        self.exported = path;
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let prompt = if self.export.is_some() { 3 } else { 1 };
+        let [editor, results, status] = Layout::vertical([
+            Constraint::Length(EDITOR_HEIGHT),
+            Constraint::Min(3),
+            Constraint::Length(prompt),
+        ])
+        .areas(frame.area());
+        let [editor, params] =
+            Layout::horizontal([Constraint::Percentage(75), Constraint::Percentage(25)])
+                .areas(editor);
+        let keys = self.settings;
+        let title = format!(
+            " SQL: {} runs, {} saves, {} browses commits, {} saved queries ",
+            keys.key_name(Binding::Run),
+            keys.key_name(Binding::Save),
+            keys.key_name(Binding::Commits),
+            keys.key_name(Binding::Queries)
+        );
+        let theme = &self.settings.theme;
+        let block = Block::bordered()
+            .title(title)
+            .border_style(theme.border(self.focus == Focus::Editor));
+        // The export prompt has the cursor while it's open
+        let focus = self.export.is_none().then_some(self.focus);
+        let cursor = self.editor.cursor(block.inner(editor));
+        self.editor
+            .render(frame, editor, block, focus == Some(Focus::Editor));
+        let block = Block::bordered()
+            .title(" Parameters: NAME=VALUE ")
+            .border_style(theme.border(self.focus == Focus::Params));
+        self.params
+            .render(frame, params, block, focus == Some(Focus::Params));
+        let title = format!(
+            " Results: {} exports, s sorts, h hides, H shows all, < and > resize, p pins ",
+            keys.key_name(Binding::Export)
+        );
+        let block = Block::bordered()
+            .title(title)
+            .border_style(theme.border(self.focus == Focus::Results));
+        self.results.render(frame, results, block, theme);
+        match &self.export {
+            Some(export) => {
+                let block = Block::bordered()
+                    .title(" Export the result to FILE.csv, .tsv, .json, .ndjson or .md, Enter writes ")
+                    .border_style(theme.border(true));
+                export.render(frame, status, block, true);
+            }
+            None => frame.render_widget(theme.status(&self.status), status),
+        }
+        if let Some(completions) = &mut self.completions {
+            completions.render(frame, cursor, theme);
+        }
+    }
+}
+
+/// The text of the SQL editor, with the cursor at a line and a character of it.
+#[derive(Debug)]
+struct Editor {
+    lines: Vec<String>,
+    row: usize,
+    col: usize,
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Editor {
+            lines: vec![String::new()],
+            row: 0,
+            col: 0,
+        }
+    }
+}
+
+impl Editor {
+    /// An editor with `text` and the cursor at its end.
+    fn with_text(text: &str) -> Self {
+        let lines = text.split('\n').map(str::to_string).collect::<Vec<_>>();
+        let row = lines.len() - 1;
+        let col = lines[row].chars().count();
+        Editor { lines, row, col }
+    }
+
+    fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    /// The byte offset of the cursor in its line.
+    fn offset(&self) -> usize {
+        let line = &self.lines[self.row];
+        line.char_indices()
+            .nth(self.col)
+            .map_or(line.len(), |(offset, _)| offset)
+    }
+
+    fn line_len(&self, row: usize) -> usize {
+        self.lines[row].chars().count()
+    }
+
+    /// The byte offset of the cursor in the text.
+    fn position(&self) -> usize {
+        let before = self.lines[..self.row].iter().map(|line| line.len() + 1);
+        before.sum::<usize>() + self.offset()
+    }
+
+    /// Replaces the text from the byte offset `start` of the text, in the cursor's line, up to
+    /// the cursor with `text`.
+    fn replace_word(&mut self, start: usize, text: &str) {
+        let offset = self.offset();
+        let start = offset - (self.position() - start);
+        let line = &mut self.lines[self.row];
+        line.replace_range(start..offset, text);
+        self.col = line[..start + text.len()].chars().count();
+    }
+
+    /// Lines scrolled out of view above the `inner` area of the editor's block.
+    fn scroll(&self, inner: Rect) -> u16 {
+        (self.row as u16).saturating_sub(inner.height.saturating_sub(1))
+    }
+
+    /// Where the cursor is drawn in the `inner` area of the editor's block.
+    fn cursor(&self, inner: Rect) -> Position {
+        // Long lines are cut off at the border, so is the cursor
+        let col = (self.col as u16).min(inner.width.saturating_sub(1));
+        Position::new(
+            inner.x + col,
+            inner.y + self.row as u16 - self.scroll(inner),
+        )
+    }
+
+    fn render(&self, frame: &mut Frame, area: Rect, block: Block, focused: bool) {
+        let inner = block.inner(area);
+        let scroll = self.scroll(inner);
+        frame.render_widget(
+            Paragraph::new(self.text()).block(block).scroll((scroll, 0)),
+            area,
+        );
+        if focused {
+            frame.set_cursor_position(self.cursor(inner));
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                let offset = self.offset();
+                self.lines[self.row].insert(offset, c);
+                self.col += 1;
+            }
+            KeyCode::Enter => {
+                let offset = self.offset();
+                let rest = self.lines[self.row].split_off(offset);
+                self.row += 1;
+                self.col = 0;
+                self.lines.insert(self.row, rest);
+            }
+            KeyCode::Backspace if self.col > 0 => {
+                self.col -= 1;
+                let offset = self.offset();
+                self.lines[self.row].remove(offset);
+            }
+            KeyCode::Backspace if self.row > 0 => {
+                let line = self.lines.remove(self.row);
+                self.row -= 1;
+                self.col = self.line_len(self.row);
+                self.lines[self.row].push_str(&line);
+            }
+            KeyCode::Delete if self.col < self.line_len(self.row) => {
+                let offset = self.offset();
+                self.lines[self.row].remove(offset);
+            }
+            KeyCode::Delete if self.row + 1 < self.lines.len() => {
+                let line = self.lines.remove(self.row + 1);
+                self.lines[self.row].push_str(&line);
+            }
+            KeyCode::Left if self.col > 0 => self.col -= 1,
+            KeyCode::Left if self.row > 0 => {
+                self.row -= 1;
+                self.col = self.line_len(self.row);
+            }
+            KeyCode::Right if self.col < self.line_len(self.row) => self.col += 1,
+            KeyCode::Right if self.row + 1 < self.lines.len() => {
+                self.row += 1;
+                self.col = 0;
+            }
+            KeyCode::Up if self.row > 0 => {
+                self.row -= 1;
+                self.col = self.col.min(self.line_len(self.row));
+            }
+            KeyCode::Down if self.row + 1 < self.lines.len() => {
+                self.row += 1;
+                self.col = self.col.min(self.line_len(self.row));
+            }
+            KeyCode::Home => self.col = 0,
+            KeyCode::End => self.col = self.line_len(self.row),
+            _ => {}
+        }
+    }
+}
+
+/// A result set as text, browsed a row and a column at a time. The selected column can be
+/// sorted by, hidden, resized and pinned to the left without running the query again.
+#[derive(Debug, Default)]
+struct Results {
+    columns: Vec<String>,
+    /// The values of the rows as the query returned them, what's exported
+    values: Vec<Vec<Value>>,
+    /// The values as they're drawn
+    rows: Vec<Vec<String>>,
+    /// Whether the result had more than [`MAX_ROWS`] rows
+    truncated: bool,
+    /// Drawn width of each column
+    widths: Vec<u16>,
+    /// The rows in the order they're shown, indexes into `rows`
+    order: Vec<usize>,
+    sort: Option<Sort>,
+    hidden: Vec<bool>,
+    /// The columns drawn before the others, in the order they were pinned
+    pinned: Vec<usize>,
+    /// The selected column
+    column: usize,
+    /// The first of the columns that aren't pinned that is drawn, the ones before it are
+    /// scrolled out of view
+    first_column: usize,
+    state: TableState,
+}
+
+/// The column a result is sorted by.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Sort {
+    column: usize,
+    descending: bool,
+}
+
+impl Results {
+    /// Executes the statements in `sql` in order, returns the result of the last one that
+    /// returns columns.
+    fn load(db: &Connection, sql: &str, params: &Params) -> Result<Option<Results>, CustomError> {
+        let mut batch = Batch::new(db, sql);
+        let mut last = None;
+        while let Some(mut stmt) = batch.next()? {
+            params.bind(&mut stmt)?;
+            if stmt.column_count() == 0 {
+                stmt.raw_execute()?;
+                continue;
+            }
+            let columns = (stmt.column_names().into_iter())
+                .map(str::to_string)
+                .collect::<Vec<_>>();
+            let mut values = vec![];
+            let mut truncated = false;
+            let mut query = stmt.raw_query();
+            while let Some(row) = query.next()? {
+                if values.len() == MAX_ROWS {
+                    truncated = true;
+                    break;
+                }
+                values.push(
+                    (0..columns.len())
+                        .map(|i| Value::from(row.get_ref_unwrap(i)))
+                        .collect::<Vec<_>>(),
+                );
+            }
+            let rows = (values.iter())
+                .map(|row| {
+                    let cells = row.iter().map(|value| cell(value_to_string(value.into())));
+                    cells.collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+            let widths = (0..columns.len())
+                .map(|i| {
+                    let values = rows.iter().map(|row| row[i].chars().count());
+                    // Room for the arrow of the sort after the name
+                    let header = columns[i].chars().count() + 2;
+                    let widest = values.chain([header]).max();
+                    widest.unwrap_or_default().min(MAX_COLUMN_WIDTH) as u16
+                })
+                .collect();
+            let state = TableState::default().with_selected((!rows.is_empty()).then_some(0));
+            last = Some(Results {
+                order: (0..rows.len()).collect(),
+                hidden: vec![false; columns.len()],
+                columns,
+                values,
+                rows,
+                truncated,
+                widths,
+                sort: None,
+                pinned: vec![],
+                column: 0,
+                first_column: 0,
+                state,
+            });
+        }
+        Ok(last)
+    }
+
+    /// Sorts, hides, sizes and pins the columns like `old` and selects the same row, when it has
+    /// the same columns. A refreshed result is shown like it was.
+    fn keep_view(&mut self, old: &Results) {
+        if self.columns != old.columns {
+            return;
+        }
+        self.sort = old.sort;
+        self.hidden.clone_from(&old.hidden);
+        self.pinned.clone_from(&old.pinned);
+        self.widths.clone_from(&old.widths);
+        self.column = old.column;
+        self.first_column = old.first_column;
+        self.sort_rows();
+        let last = self.rows.len().checked_sub(1);
+        let selected = old
+            .state
+            .selected()
+            .zip(last)
+            .map(|(row, last)| row.min(last));
+        self.state.select(selected);
+    }
+
+    /// Left and Right select a column, `s` sorts by it, ascending, descending and back to the
+    /// order of the query. `h` hides it and `H` shows the hidden columns again, `<` and `>`
+    /// narrow and widen it and `p` pins it to the left or unpins it.
+    fn handle_key(&mut self, key: KeyEvent) {
+        let shown = self.shown_columns();
+        let at = shown.iter().position(|&column| column == self.column);
+        match key.code {
+            KeyCode::Left => {
+                let at = at.map_or(0, |at| at.saturating_sub(1));
+                self.column = shown.get(at).copied().unwrap_or_default();
+            }
+            KeyCode::Right => {
+                let at = at.map_or(0, |at| (at + 1).min(shown.len().saturating_sub(1)));
+                self.column = shown.get(at).copied().unwrap_or_default();
+            }
+            KeyCode::Char('s') if !self.columns.is_empty() => {
+                self.sort = match self.sort {
+                    Some(Sort {
+                        column,
+                        descending: false,
+                    }) if column == self.column => Some(Sort {
+                        column,
+                        descending: true,
+                    }),
+                    Some(Sort { column, .. }) if column == self.column => None,
+                    _ => Some(Sort {
+                        column: self.column,
+                        descending: false,
+                    }),
+                };
+                self.sort_rows();
+            }
+            // The last column stays
+            KeyCode::Char('h') if shown.len() > 1 => {
+                self.hidden[self.column] = true;
+                let at = at.unwrap_or_default().min(shown.len() - 2);
+                self.column = self.shown_columns()[at];
+            }
+            KeyCode::Char('H') => self.hidden.fill(false),
+            KeyCode::Char('<') if !self.columns.is_empty() => {
+                let width = &mut self.widths[self.column];
+                *width = width.saturating_sub(2).max(1);
+            }
+            KeyCode::Char('>') if !self.columns.is_empty() => {
+                let width = &mut self.widths[self.column];
+                *width = width.saturating_add(2);
+            }
+            KeyCode::Char('p') if !self.columns.is_empty() => {
+                match self.pinned.iter().position(|&column| column == self.column) {
+                    Some(at) => {
+                        self.pinned.remove(at);
+                    }
+                    None => self.pinned.push(self.column),
+                }
+            }
+            code => move_selection(&mut self.state, code, self.rows.len()),
+        }
+    }
+
+    /// The columns that aren't hidden, the pinned ones first.
+    fn shown_columns(&self) -> Vec<usize> {
+        let unpinned = (0..self.columns.len()).filter(|column| !self.pinned.contains(column));
+        let columns = self.pinned.iter().copied().chain(unpinned);
+        columns.filter(|&column| !self.hidden[column]).collect()
+    }
+
+    /// Orders the rows by the sorted column, numbers before text and empty values last. The
+    /// selected row stays selected.
+    fn sort_rows(&mut self) {
+        let selected = (self.state.selected()).and_then(|row| self.order.get(row).copied());
+        self.order = (0..self.rows.len()).collect();
+        if let Some(Sort { column, descending }) = self.sort {
+            let rows = &self.rows;
+            self.order.sort_by(|&a, &b| {
+                let ordering = compare_cells(&rows[a][column], &rows[b][column]);
+                match descending {
+                    true => ordering.reverse(),
+                    false => ordering,
+                }
+            });
+        }
+        let selected = selected.and_then(|row| self.order.iter().position(|&r| r == row));
+        self.state.select(selected);
+    }
+
+    /// Scrolls the columns that aren't pinned so the selected column fits in `width`.
+    fn scroll_to_column(&mut self, width: u16) {
+        let (pinned, unpinned) = self.pinned_and_unpinned();
+        self.first_column = self.first_column.min(unpinned.len().saturating_sub(1));
+        let Some(at) = unpinned.iter().position(|&column| column == self.column) else {
+            return;
+        };
+        self.first_column = self.first_column.min(at);
+        // Columns are a space apart
+        let drawn = |columns: &[usize]| -> u16 {
+            columns.iter().map(|&column| self.widths[column] + 1).sum()
+        };
+        while self.first_column < at
+            && drawn(&pinned) + drawn(&unpinned[self.first_column..=at]) > width
+        {
+            self.first_column += 1;
+        }
+    }
+
+    /// Writes the shown columns of the rows, in the order they're shown, to the file at `path`
+    /// in the format of its extension. Returns the number of rows written.
+    fn export(&self, path: &Path) -> Result<usize, CustomError> {
+        let extension = path.extension().and_then(|extension| extension.to_str());
+        let mode = match extension.map(str::to_lowercase).as_deref() {
+            Some("csv") => OutputMode::Csv,
+            Some("tsv") => OutputMode::Tsv,
+            Some("json") => OutputMode::Json,
+            Some("ndjson" | "jsonl") => OutputMode::Ndjson,
+            Some("md" | "markdown") => OutputMode::Markdown,
+            Some("html" | "htm") => OutputMode::Html,
+            Some("dot" | "gv") => OutputMode::Dot,
+            _ => {
+                return Err(CustomError::InvalidArgument(format!(
+                    "{} isn't a .csv, .tsv, .json, .ndjson, .md, .html or .dot file",
+                    path.display()
+                )))
+            }
+        };
+        let columns = self.shown_columns();
+        let names = (columns.iter())
+            .map(|&column| self.columns[column].clone())
+            .collect::<Vec<_>>();
+        let rows = self.order.iter().map(|&row| {
+            let values = columns.iter().map(|&column| &self.values[row][column]);
+            values.map(ValueRef::from).collect()
+        });
+        let options = OutputOptions {
+            mode,
+            ..OutputOptions::default()
+        };
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        write_rows(&names, rows, &mut file, &options)?;
+        file.flush()?;
+        Ok(self.order.len())
+    }
+
+    /// The shown columns that are pinned and the ones that aren't.
+    fn pinned_and_unpinned(&self) -> (Vec<usize>, Vec<usize>) {
+        (self.shown_columns().into_iter()).partition(|column| self.pinned.contains(column))
+    }
+
+    fn render(&mut self, frame: &mut Frame, area: Rect, block: Block, theme: &Theme) {
+        self.scroll_to_column(block.inner(area).width);
+        let (pinned, unpinned) = self.pinned_and_unpinned();
+        let drawn = (pinned.into_iter())
+            .chain(unpinned.into_iter().skip(self.first_column))
+            .collect::<Vec<_>>();
+
+        let header = drawn.iter().map(|&column| {
+            let arrow = match self.sort {
+                Some(Sort { column: sorted, .. }) if sorted != column => "",
+                Some(Sort {
+                    descending: true, ..
+                }) => " ▼",
+                Some(Sort { .. }) => " ▲",
+                None => "",
+            };
+            let style = match column == self.column {
+                true => theme.header.patch(theme.selected),
+                false => theme.header,
+            };
+            Cell::from(format!("{}{}", self.columns[column], arrow)).style(style)
+        });
+        let rows = self.order.iter().map(|&row| {
+            let cells = drawn.iter().map(|&column| self.rows[row][column].clone());
+            Row::new(cells.collect::<Vec<_>>())
+        });
+        let widths = drawn
+            .iter()
+            .map(|&column| Constraint::Length(self.widths[column]));
+        let table = Table::new(rows, widths)
+            .header(Row::new(header.collect::<Vec<_>>()))
+            .row_highlight_style(theme.selected)
+            .block(block);
+        frame.render_stateful_widget(table, area, &mut self.state);
+    }
+}
+
+/// Numbers compare by value and before text, empty values, NULL among them, come last.
+fn compare_cells(a: &str, b: &str) -> std::cmp::Ordering {
+    let key = |cell: &str| match (cell.is_empty(), cell.parse::<f64>()) {
+        (true, _) => (2, 0.0),
+        (false, Ok(number)) => (0, number),
+        (false, Err(_)) => (1, 0.0),
+    };
+    let ((a_kind, a_number), (b_kind, b_number)) = (key(a), key(b));
+    a_kind
+        .cmp(&b_kind)
+        .then(a_number.total_cmp(&b_number))
+        .then_with(|| a.cmp(b))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BrowserFocus {
+    Search,
+    Commits,
+}
+
+/// A commit listed by the browser.
+#[derive(Debug, Clone, PartialEq)]
+struct ListedCommit {
+    hash: String,
+    when: String,
+    author: String,
+    subject: String,
+    parent: Option<String>,
+}
+
+/// The files a commit changed and its diff against its first parent.
+#[derive(Debug, Default)]
+struct Preview {
+    hash: String,
+    files: Vec<(String, i64, i64)>,
+    diff: Vec<Line<'static>>,
+    /// Lines of the diff scrolled out of view
+    scroll: u16,
+}
+
+/// Lists the commits matching a search, with the files and the diff of the selected one beside
+/// the list.
+struct Browser<'a> {
+    db: &'a Connection,
+    settings: &'a Settings,
+    search: Editor,
+    focus: BrowserFocus,
+    commits: Vec<ListedCommit>,
+    /// Whether the commits were listed yet
+    listed: bool,
+    state: TableState,
+    preview: Preview,
+    status: String,
+}
+
+impl<'a> Browser<'a> {
+    fn new(db: &'a Connection, settings: &'a Settings) -> Self {
+        Browser {
+            db,
+            settings,
+            search: Editor::default(),
+            focus: BrowserFocus::Commits,
+            commits: vec![],
+            listed: false,
+            state: TableState::default(),
+            preview: Preview::default(),
+            status: String::new(),
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Action {
+        if key.kind == KeyEventKind::Release {
+            return Action::None;
+        }
+        let run = match self.settings.binding(&key) {
+            Some(Binding::Quit) => return Action::Quit,
+            Some(Binding::Run) => true,
+            Some(Binding::NextPane) => {
+                self.switch_focus();
+                return Action::None;
+            }
+            Some(Binding::ScrollDown) => {
+                self.preview.scroll = self.preview.scroll.saturating_add(10);
+                return Action::None;
+            }
+            Some(Binding::ScrollUp) => {
+                self.preview.scroll = self.preview.scroll.saturating_sub(10);
+                return Action::None;
+            }
+            _ => false,
+        };
+        match (self.focus, key.code) {
+            _ if run => {
+                self.focus = BrowserFocus::Commits;
+                return Action::Execute;
+            }
+            (BrowserFocus::Search, KeyCode::Enter) => {
+                self.focus = BrowserFocus::Commits;
+                return Action::Execute;
+            }
+            (_, KeyCode::BackTab) | (BrowserFocus::Search, KeyCode::Esc) => self.switch_focus(),
+            (BrowserFocus::Commits, KeyCode::Char('/')) => self.focus = BrowserFocus::Search,
+            (BrowserFocus::Search, KeyCode::Up | KeyCode::Down) => {}
+            (BrowserFocus::Search, _) => self.search.handle_key(key),
+            (BrowserFocus::Commits, KeyCode::Char('b')) => {
+                let commit = self.state.selected().and_then(|i| self.commits.get(i));
+                if let (Some(commit), Some((path, _, _))) = (commit, self.preview.files.first()) {
+                    return Action::Blame {
+                        path: path.clone(),
+                        rev: commit.hash.clone(),
+                    };
+                }
+            }
+            (BrowserFocus::Commits, code) => {
+                move_selection(&mut self.state, code, self.commits.len());
+                self.load_preview();
+            }
+        }
+        Action::None
+    }
+
+    fn switch_focus(&mut self) {
+        self.focus = match self.focus {
+            BrowserFocus::Search => BrowserFocus::Commits,
+            BrowserFocus::Commits => BrowserFocus::Search,
+        };
+    }
+
+    /// Lists the commits matching the search.
+    fn execute(&mut self) {
+        let start = Instant::now();
+        let (sql, params) = commits_query(&self.search.text());
+        let listed = self.db.prepare(&sql).and_then(|mut stmt| {
+            let commits = stmt.query_map(rusqlite::params_from_iter(&params), |row| {
+                let message: Option<String> = row.get(3)?;
+                Ok(ListedCommit {
+                    hash: row.get(0)?,
+                    when: row.get(1)?,
+                    author: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                    subject: message
+                        .unwrap_or_default()
+                        .lines()
+                        .next()
+                        .unwrap_or_default()
+                        .to_string(),
+                    parent: row.get(4)?,
+                })
+            })?;
+            commits.collect::<rusqlite::Result<Vec<_>>>()
+        });
+        self.listed = true;
+        match listed {
+            Ok(commits) => {
+                self.status = format!("{} commits in {:.1?}", commits.len(), start.elapsed());
+                self.state =
+                    TableState::default().with_selected((!commits.is_empty()).then_some(0));
+                self.commits = commits;
+                self.preview = Preview::default();
+                self.load_preview();
+            }
+            Err(e) => self.status = format!("error: {}", e),
+        }
+    }
+
+    /// Reads the files and the diff of the selected commit, unless they're shown already.
+    fn load_preview(&mut self) {
+        let Some(commit) = self.state.selected().and_then(|i| self.commits.get(i)) else {
+            return;
+        };
+        if self.preview.hash == commit.hash {
+            return;
+        }
+        let files = self
+            .db
+            .prepare(
+                "SELECT file_name, additions, deletions FROM stats WHERE hash = ?
+                 ORDER BY file_name",
+            )
+            .and_then(|mut stmt| {
+                stmt.query_map([&commit.hash], |row| {
+                    Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()
+            });
+        let diff = match &commit.parent {
+            Some(parent) => self
+                .db
+                .query_row(
+                    "SELECT git_diff_text(?, ?)",
+                    [parent, &commit.hash],
+                    |row| row.get::<_, Option<String>>(0),
+                )
+                .map(|diff| self.settings.theme.diff(&diff.unwrap_or_default())),
+            None => Ok(vec![Line::raw(
+                "The root commit has no parent to diff against",
+            )]),
+        };
+        self.preview = match (files, diff) {
+            (Ok(files), Ok(diff)) => Preview {
+                hash: commit.hash.clone(),
+                files,
+                diff,
+                scroll: 0,
+            },
+            (Err(e), _) | (_, Err(e)) => Preview {
+                hash: commit.hash.clone(),
+                diff: vec![Line::raw(format!("error: {}", e))],
+                ..Preview::default()
+            },
+        };
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [search, main, status] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(3),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [list, preview] =
+            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .areas(main);
+        let [files, diff] =
+            Layout::vertical([Constraint::Percentage(30), Constraint::Percentage(70)])
+                .areas(preview);
+
+        let theme = &self.settings.theme;
+        let block = Block::bordered()
+            .title(SEARCH_TITLE)
+            .border_style(theme.border(self.focus == BrowserFocus::Search));
+        self.search
+            .render(frame, search, block, self.focus == BrowserFocus::Search);
+
+        let rows = self.commits.iter().map(|commit| {
+            Row::new([
+                commit.hash.chars().take(7).collect::<String>(),
+                commit.when.chars().take(10).collect(),
+                commit.author.clone(),
+                commit.subject.clone(),
+            ])
+        });
+        let widths = [
+            Constraint::Length(7),
+            Constraint::Length(10),
+            Constraint::Length(16),
+            Constraint::Min(10),
+        ];
+        let table = Table::new(rows, widths)
+            .row_highlight_style(theme.selected)
+            .block(
+                Block::bordered()
+                    .title(format!(
+                        " Commits: b blames a changed file, {}/{} scroll the diff ",
+                        self.settings.key_name(Binding::ScrollDown),
+                        self.settings.key_name(Binding::ScrollUp)
+                    ))
+                    .border_style(theme.border(self.focus == BrowserFocus::Commits)),
+            );
+        frame.render_stateful_widget(table, list, &mut self.state);
+
+        let rows = self
+            .preview
+            .files
+            .iter()
+            .map(|(file, additions, deletions)| {
+                Row::new([
+                    format!("+{}", additions),
+                    format!("-{}", deletions),
+                    file.clone(),
+                ])
+            });
+        let widths = [
+            Constraint::Length(7),
+            Constraint::Length(7),
+            Constraint::Min(10),
+        ];
+        let table = Table::new(rows, widths).block(Block::bordered().title(" Files "));
+        frame.render_widget(table, files);
+        // Only the lines in view are drawn, a big diff isn't copied on every key
+        let shown = (self.preview.diff.iter())
+            .skip(self.preview.scroll as usize)
+            .take(diff.height as usize);
+        frame.render_widget(
+            Paragraph::new(shown.cloned().collect::<Vec<_>>())
+                .block(Block::bordered().title(" Diff ")),
+            diff,
+        );
+        frame.render_widget(theme.status(&self.status), status);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BlameFocus {
+    File,
+    Lines,
+}
+
+/// A line of a file and the commit that last changed it.
+#[derive(Debug, Clone, PartialEq)]
+struct BlamedLine {
+    hash: String,
+    author: String,
+    when: String,
+    text: String,
+}
+
+/// The lines of a file at a revision, each with the commit, the author and the date of its last
+/// change in the gutter. Enter on a line shows its commit in the browser.
+struct Blame<'a> {
+    db: &'a Connection,
+    settings: &'a Settings,
+    /// The path of the file and optionally the revision, HEAD by default
+    file: Editor,
+    focus: BlameFocus,
+    lines: Vec<BlamedLine>,
+    /// The text of the lines in the colors of the file's syntax
+    highlighted: Vec<Line<'static>>,
+    state: TableState,
+    status: String,
+}
+
+impl<'a> Blame<'a> {
+    fn new(db: &'a Connection, settings: &'a Settings) -> Self {
+        Blame {
+            db,
+            settings,
+            file: Editor::default(),
+            focus: BlameFocus::File,
+            lines: vec![],
+            highlighted: vec![],
+            state: TableState::default(),
+            status: String::new(),
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Action {
+        if key.kind == KeyEventKind::Release {
+            return Action::None;
+        }
+        let binding = self.settings.binding(&key);
+        match (self.focus, key.code) {
+            _ if binding == Some(Binding::Quit) => return Action::Quit,
+            _ if binding == Some(Binding::Run) => return Action::Execute,
+            (BlameFocus::File, KeyCode::Enter) => return Action::Execute,
+            _ if binding == Some(Binding::NextPane) => {
+                self.focus = match self.focus {
+                    BlameFocus::File => BlameFocus::Lines,
+                    BlameFocus::Lines => BlameFocus::File,
+                };
+            }
+            (_, KeyCode::BackTab) | (BlameFocus::File, KeyCode::Esc) => {
+                self.focus = match self.focus {
+                    BlameFocus::File => BlameFocus::Lines,
+                    BlameFocus::Lines => BlameFocus::File,
+                };
+            }
+            (BlameFocus::Lines, KeyCode::Char('/')) => self.focus = BlameFocus::File,
+            (BlameFocus::File, KeyCode::Up | KeyCode::Down) => {}
+            (BlameFocus::File, _) => self.file.handle_key(key),
+            (BlameFocus::Lines, KeyCode::Enter) => {
+                if let Some(line) = self.state.selected().and_then(|i| self.lines.get(i)) {
+                    return Action::Show(line.hash.clone());
+                }
+            }
+            (BlameFocus::Lines, code) => move_selection(&mut self.state, code, self.lines.len()),
+        }
+        Action::None
+    }
+
+    /// Reads the file and its blame.
+    fn execute(&mut self) {
+        let text = self.file.text();
+        let mut words = text.split_whitespace();
+        let Some(path) = words.next() else {
+            self.status = "error: no file to blame".to_string();
+            return;
+        };
+        let rev = words.next().unwrap_or("HEAD");
+        let start = Instant::now();
+        match blame(self.db, path, rev) {
+            Ok(Some(lines)) => {
+                self.status = format!("{} lines in {:.1?}", lines.len(), start.elapsed());
+                self.state = TableState::default().with_selected((!lines.is_empty()).then_some(0));
+                let text = lines.iter().map(|line| line.text.as_str());
+                self.highlighted = self.settings.theme.file(path, text);
+                self.lines = lines;
+                self.focus = BlameFocus::Lines;
+            }
+            Ok(None) => self.status = format!("error: there's no {} in {}", path, rev),
+            Err(e) => self.status = format!("error: {}", e),
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [file, lines, status] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(3),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let theme = &self.settings.theme;
+        let title = format!(
+            " Blame: PATH [REV], Enter opens, {} goes back ",
+            self.settings.key_name(Binding::Blame)
+        );
+        let block = Block::bordered()
+            .title(title)
+            .border_style(theme.border(self.focus == BlameFocus::File));
+        self.file
+            .render(frame, file, block, self.focus == BlameFocus::File);
+
+        let rows = self.lines.iter().zip(&self.highlighted).enumerate();
+        let rows = rows.map(|(i, (line, text))| {
+            Row::new([
+                Cell::from(line.hash.chars().take(7).collect::<String>()),
+                Cell::from(line.when.chars().take(10).collect::<String>()),
+                Cell::from(line.author.clone()),
+                Cell::from((i + 1).to_string()),
+                Cell::from(text.clone()),
+            ])
+        });
+        let widths = [
+            Constraint::Length(7),
+            Constraint::Length(10),
+            Constraint::Length(16),
+            Constraint::Length(5),
+            Constraint::Min(10),
+        ];
+        let table = Table::new(rows, widths)
+            .row_highlight_style(theme.selected)
+            .block(
+                Block::bordered()
+                    .title(" Lines: Enter shows the commit of a line ")
+                    .border_style(theme.border(self.focus == BlameFocus::Lines)),
+            );
+        frame.render_stateful_widget(table, lines, &mut self.state);
+        frame.render_widget(theme.status(&self.status), status);
+    }
+}
+
+/// The lines of the file at `path` in `rev` with the commits that last changed them, None when
+/// the file doesn't exist. The file is blamed once with `git_blame_json` and its hunks spread
+/// over their lines.
+fn blame(db: &Connection, path: &str, rev: &str) -> rusqlite::Result<Option<Vec<BlamedLine>>> {
+    let content: Option<Value> =
+        db.query_row("SELECT git_blob_content(?, ?)", [rev, path], |row| {
+            row.get(0)
+        })?;
+    let text = match content {
+        Some(Value::Text(text)) => text,
+        Some(Value::Blob(bytes)) => String::from_utf8_lossy(&bytes).to_string(),
+        _ => return Ok(None),
+    };
+
+    let mut lines = text.lines().map(|line| BlamedLine {
+        hash: String::new(),
+        author: String::new(),
+        when: String::new(),
+        // Tabs would be drawn as a single space
+        text: line.replace('\t', "    "),
+    });
+    let mut blamed = vec![];
+    let mut stmt = db.prepare(
+        "SELECT json_extract(value, '$.hash'), json_extract(value, '$.author_name'),
+                json_extract(value, '$.author_when'), json_extract(value, '$.lines')
+         FROM json_each(git_blame_json(?, ?))",
+    )?;
+    let mut hunks = stmt.query([path, rev])?;
+    while let Some(hunk) = hunks.next()? {
+        let (hash, author, when): (String, String, String) =
+            (hunk.get(0)?, hunk.get(1)?, hunk.get(2)?);
+        for line in lines.by_ref().take(hunk.get(3)?) {
+            blamed.push(BlamedLine {
+                hash: hash.clone(),
+                author: author.clone(),
+                when: when.clone(),
+                ..line
+            });
+        }
+    }
+    // Lines the blame doesn't cover, if any, are shown without a commit
+    blamed.extend(lines);
+    Ok(Some(blamed))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum QueriesFocus {
+    List,
+    Name,
+}
+
+/// The queries saved in the config file, with the SQL and the parameters of the selected one.
+/// Enter runs a query in the workbench, `e` opens it there to edit it first.
+struct Queries<'a> {
+    settings: &'a Settings,
+    queries: BTreeMap<String, QueryTemplate>,
+    /// The config file queries are saved to
+    path: PathBuf,
+    state: TableState,
+    /// The name the workbench's query is saved as
+    name: Editor,
+    focus: QueriesFocus,
+    status: String,
+}
+
+impl<'a> Queries<'a> {
+    fn new(
+        queries: BTreeMap<String, QueryTemplate>,
+        path: PathBuf,
+        settings: &'a Settings,
+    ) -> Self {
+        Queries {
+            settings,
+            state: TableState::default().with_selected((!queries.is_empty()).then_some(0)),
+            queries,
+            status: format!("Queries are saved to {}", path.display()),
+            path,
+            name: Editor::default(),
+            focus: QueriesFocus::List,
+        }
+    }
+
+    fn selected(&self) -> Option<(&String, &QueryTemplate)> {
+        self.state
+            .selected()
+            .and_then(|i| self.queries.iter().nth(i))
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Action {
+        if key.kind == KeyEventKind::Release {
+            return Action::None;
+        }
+        let binding = self.settings.binding(&key);
+        match (self.focus, key.code) {
+            _ if binding == Some(Binding::Quit) => return Action::Quit,
+            _ if binding == Some(Binding::NextPane) => {
+                self.focus = match self.focus {
+                    QueriesFocus::List => QueriesFocus::Name,
+                    QueriesFocus::Name => QueriesFocus::List,
+                };
+            }
+            (_, KeyCode::BackTab) | (QueriesFocus::Name, KeyCode::Esc) => {
+                self.focus = match self.focus {
+                    QueriesFocus::List => QueriesFocus::Name,
+                    QueriesFocus::Name => QueriesFocus::List,
+                };
+            }
+            (QueriesFocus::Name, KeyCode::Enter) => {
+                let name = self.name.text().trim().to_string();
+                if name.is_empty() {
+                    self.status = "error: type the name to save the query as".to_string();
+                } else {
+                    self.focus = QueriesFocus::List;
+                    return Action::Save(name);
+                }
+            }
+            (QueriesFocus::Name, KeyCode::Up | KeyCode::Down) => {}
+            (QueriesFocus::Name, _) => self.name.handle_key(key),
+            (QueriesFocus::List, KeyCode::Enter | KeyCode::Char('e')) => {
+                if let Some((name, _)) = self.selected() {
+                    return Action::Open {
+                        name: name.clone(),
+                        run: key.code == KeyCode::Enter,
+                    };
+                }
+            }
+            (QueriesFocus::List, code) => move_selection(&mut self.state, code, self.queries.len()),
+        }
+        Action::None
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let theme = &self.settings.theme;
+        let [main, name, status] = Layout::vertical([
+            Constraint::Min(3),
+            Constraint::Length(3),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [list, query] =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)])
+                .areas(main);
+
+        let rows = self.queries.iter().map(|(name, template)| {
+            Row::new([
+                name.clone(),
+                template.description.clone().unwrap_or_default(),
+            ])
+        });
+        let table = Table::new(rows, [Constraint::Length(20), Constraint::Min(10)])
+            .row_highlight_style(theme.selected)
+            .block(
+                Block::bordered()
+                    .title(format!(
+                        " Saved queries: Enter runs, e edits, {} goes back ",
+                        self.settings.key_name(Binding::Queries)
+                    ))
+                    .border_style(theme.border(self.focus == QueriesFocus::List)),
+            );
+        frame.render_stateful_widget(table, list, &mut self.state);
+
+        let text = match self.selected() {
+            Some((_, template)) => {
+                let params = (template.params.iter())
+                    .map(|(name, value)| format!("\n:{} defaults to {}", name, value));
+                format!("{}\n{}", template.sql, params.collect::<String>())
+            }
+            None => String::new(),
+        };
+        frame.render_widget(
+            Paragraph::new(text).block(Block::bordered().title(" Query ")),
+            query,
+        );
+
+        let block = Block::bordered()
+            .title(" Save the workbench's query as, Enter saves ")
+            .border_style(theme.border(self.focus == QueriesFocus::Name));
+        self.name
+            .render(frame, name, block, self.focus == QueriesFocus::Name);
+        frame.render_widget(theme.status(&self.status), status);
+    }
+}
+
+/// A panel of the dashboard and the result of its query.
+struct DashboardPanel {
+    panel: Panel,
+    results: Results,
+    /// Why the query of the last refresh failed
+    error: Option<String>,
+}
+
+/// The panels of `[[tui.panels]]`, two side by side, their queries run again when something in
+/// the repository changes. Tab moves to the next panel, the keys of the workbench's result
+/// browse the focused one.
+struct Dashboard<'a> {
+    db: &'a Connection,
+    settings: &'a Settings,
+    panels: Vec<DashboardPanel>,
+    /// The panel that has the focus
+    focus: usize,
+    /// The repository whose changes refresh the panels
+    repository: PathBuf,
+    /// The changes of the repository, watched from the first refresh on
+    changes: Option<Changes>,
+    /// Whether the panels were filled
+    refreshed: bool,
+    status: String,
+}
+
+impl<'a> Dashboard<'a> {
+    fn new(
+        db: &'a Connection,
+        panels: Vec<Panel>,
+        repository: &Path,
+        settings: &'a Settings,
+    ) -> Self {
+        let panels = panels.into_iter().map(|panel| DashboardPanel {
+            panel,
+            results: Results::default(),
+            error: None,
+        });
+        Dashboard {
+            db,
+            settings,
+            panels: panels.collect(),
+            focus: 0,
+            repository: repository.to_path_buf(),
+            changes: None,
+            refreshed: false,
+            status: String::new(),
+        }
+    }
+
+    /// Runs the queries of the panels, the first refresh starts watching the repository.
+    fn execute(&mut self) {
+        let start = Instant::now();
+        for panel in &mut self.panels {
+            let params = (panel.panel.params.iter())
+                .map(|(name, value)| Param::Named(name.clone(), value.clone()))
+                .collect::<Vec<_>>();
+            match Results::load(self.db, &panel.panel.sql, &Params::from(params)) {
+                Ok(results) => {
+                    let mut results = results.unwrap_or_default();
+                    results.keep_view(&panel.results);
+                    panel.results = results;
+                    panel.error = None;
+                }
+                Err(e) => panel.error = Some(e.to_string()),
+            }
+        }
+        self.refreshed = true;
+        self.status = format!(
+            "Refreshed at {} in {:.1?}, {} refreshes, {} moves to the next panel",
+            chrono::Local::now().format("%T"),
+            start.elapsed(),
+            self.settings.key_name(Binding::Run),
+            self.settings.key_name(Binding::NextPane)
+        );
+        if self.changes.is_none() {
+            match Changes::watch(&self.repository) {
+                Ok(changes) => self.changes = Some(changes),
+                Err(e) => self.status = format!("error: changes aren't refreshed: {}", e),
+            }
+        }
+    }
+
+    /// Whether the repository changed since the panels were refreshed.
+    fn changed(&mut self) -> bool {
+        let Some(changes) = &self.changes else {
+            return false;
+        };
+        changes.changed().unwrap_or_else(|e| {
+            self.status = format!("error: changes aren't refreshed: {}", e);
+            self.changes = None;
+            false
+        })
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Action {
+        if key.kind == KeyEventKind::Release {
+            return Action::None;
+        }
+        let panels = self.panels.len().max(1);
+        match (self.settings.binding(&key), key.code) {
+            (Some(Binding::Quit), _) => return Action::Quit,
+            (Some(Binding::Run), _) => return Action::Execute,
+            (Some(Binding::NextPane), _) => self.focus = (self.focus + 1) % panels,
+            (_, KeyCode::BackTab) => self.focus = (self.focus + panels - 1) % panels,
+            _ => {
+                if let Some(panel) = self.panels.get_mut(self.focus) {
+                    panel.results.handle_key(key);
+                }
+            }
+        }
+        Action::None
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let theme = &self.settings.theme;
+        let [main, status] =
+            Layout::vertical([Constraint::Min(3), Constraint::Length(1)]).areas(frame.area());
+        if self.panels.is_empty() {
+            let help = "There are no panels, add them to the config like\n\n\
+                        [[tui.panels]]\n\
+                        title = \"Recent commits\"\n\
+                        sql = \"SELECT author_when, author_name, message FROM commits LIMIT 20\"";
+            let block = Block::bordered().title(format!(
+                " Dashboard: {} goes back ",
+                self.settings.key_name(Binding::Dashboard)
+            ));
+            frame.render_widget(Paragraph::new(help).block(block), main);
+        }
+        let rows = self.panels.len().div_ceil(2);
+        let rows =
+            Layout::vertical((0..rows).map(|_| Constraint::Ratio(1, rows as u32))).split(main);
+        for (row, (area, panels)) in rows.iter().zip(self.panels.chunks_mut(2)).enumerate() {
+            let columns = panels.len() as u32;
+            let areas = Layout::horizontal((0..columns).map(|_| Constraint::Ratio(1, columns)))
+                .split(*area);
+            for (column, (area, panel)) in areas.iter().zip(panels).enumerate() {
+                let title = format!(
+                    " {} ({} rows) ",
+                    panel.panel.title,
+                    panel.results.rows.len()
+                );
+                let focused = row * 2 + column == self.focus;
+                let block = Block::bordered()
+                    .title(title)
+                    .border_style(theme.border(focused));
+                match &panel.error {
+                    Some(e) => frame.render_widget(
+                        Paragraph::new(format!("error: {}", e))
+                            .style(theme.error)
+                            .wrap(Wrap { trim: false })
+                            .block(block),
+                        *area,
+                    ),
+                    None => panel.results.render(frame, *area, block, theme),
+                }
+            }
+        }
+        frame.render_widget(theme.status(&self.status), status);
+    }
+}
+
+/// Moves the selected row of a table with `rows` rows for the arrow, page, home and end keys.
+fn move_selection(state: &mut TableState, code: KeyCode, rows: usize) {
+    let page = 20;
+    let selected = state.selected().unwrap_or_default();
+    let last = rows.saturating_sub(1);
+    let row = match code {
+        KeyCode::Up => selected.saturating_sub(1),
+        KeyCode::Down => (selected + 1).min(last),
+        KeyCode::PageUp => selected.saturating_sub(page),
+        KeyCode::PageDown => (selected + page).min(last),
+        KeyCode::Home => 0,
+        KeyCode::End => last,
+        _ => selected,
+    };
+    if rows > 0 {
+        state.select(Some(row));
+    }
+}
+
+/// The query listing the commits a search matches and its parameters. `author:` terms match
+/// the name or the email of the author, `path:` terms the files a commit changes and `rev:` is
+/// the revision or range the walk starts from, e.g. `rev:v1.0..main`. Other words match the
+/// message.
+fn commits_query(search: &str) -> (String, Vec<String>) {
+    let mut sql = String::from(
+        "SELECT hash, author_when, author_name, message, parent_1 FROM commits c WHERE true",
+    );
+    let mut params = vec![];
+    for term in search.split_whitespace() {
+        match term.split_once(':') {
+            Some(("author", author)) => {
+                sql.push_str(" AND (author_name LIKE ? OR author_email LIKE ?)");
+                params.extend([format!("%{}%", author), format!("%{}%", author)]);
+            }
+            Some(("path", path)) => {
+                sql.push_str(
+                    " AND EXISTS (SELECT 1 FROM stats s WHERE s.hash = c.hash AND s.file_name LIKE ?)",
+                );
+                params.push(format!("%{}%", path));
+            }
+            Some(("rev", rev)) => {
+                sql.push_str(" AND c.ref = ?");
+                params.push(rev.to_string());
+            }
+            _ => {
+                sql.push_str(" AND message LIKE ?");
+                params.push(format!("%{}%", term));
+            }
+        }
+    }
+    sql.push_str(&format!(" LIMIT {}", MAX_COMMITS));
+    (sql, params)
+}
+
+/// A value on a single line, line breaks and tabs of messages would break up the table.
+fn cell(value: String) -> String {
+    if value.contains(['\n', '\r', '\t']) {
+        value.replace(['\n', '\r', '\t'], " ")
+    } else {
+        value
+    }
+}
+
+/// What the keys of `[tui.keys]` do.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Binding {
+    Quit,
+    Run,
+    Save,
+    NextPane,
+    Commits,
+    Blame,
+    Queries,
+    ScrollDown,
+    ScrollUp,
+    Stop,
+    Export,
+    Complete,
+    Dashboard,
+}
+
+/// The bindings by their name in the config, with their default keys.
+const BINDINGS: [(Binding, &str, &[&str]); 13] = [
+    (Binding::Quit, "quit", &["ctrl-q"]),
+    (Binding::Run, "run", &["f5", "ctrl-r"]),
+    (Binding::Save, "save", &["ctrl-s"]),
+    (Binding::NextPane, "next_pane", &["tab"]),
+    (Binding::Commits, "commits", &["f2"]),
+    (Binding::Blame, "blame", &["f3"]),
+    (Binding::Queries, "queries", &["f4"]),
+    (Binding::ScrollDown, "scroll_down", &["ctrl-d"]),
+    (Binding::ScrollUp, "scroll_up", &["ctrl-u"]),
+    (Binding::Stop, "stop", &["esc", "ctrl-c"]),
+    (Binding::Export, "export", &["ctrl-e"]),
+    (Binding::Complete, "complete", &["ctrl-space"]),
+    (Binding::Dashboard, "dashboard", &["f6"]),
+];
+
+/// A key and the modifiers held with it, written like `ctrl-q`, `f5`, `alt-enter` or `shift-tab`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl Key {
+    fn parse(key: &str) -> Result<Key, String> {
+        let (held, name) = match key.strip_suffix("--") {
+            Some(held) => (held, "-"),
+            None => key.rsplit_once('-').unwrap_or(("", key)),
+        };
+        let mut modifiers = KeyModifiers::NONE;
+        for modifier in held.split('-').filter(|held| !held.is_empty()) {
+            modifiers |= match modifier.to_lowercase().as_str() {
+                "ctrl" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(format!("{} has an unknown modifier {}", key, modifier)),
+            };
+        }
+        let mut chars = name.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match name.to_lowercase().as_str() {
+                "enter" => KeyCode::Enter,
+                "esc" => KeyCode::Esc,
+                "tab" => KeyCode::Tab,
+                "backspace" => KeyCode::Backspace,
+                "delete" => KeyCode::Delete,
+                "insert" => KeyCode::Insert,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "space" => KeyCode::Char(' '),
+                f => match f.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                    Some(n @ 1..=24) => KeyCode::F(n),
+                    _ => return Err(format!("{} is not a key", key)),
+                },
+            },
+        };
+        // Terminals send shifted characters and Shift-Tab as keys of their own
+        let shift = modifiers.contains(KeyModifiers::SHIFT);
+        let code = match code {
+            KeyCode::Char(c) if shift => KeyCode::Char(c.to_ascii_uppercase()),
+            KeyCode::Tab if shift => KeyCode::BackTab,
+            code => code,
+        };
+        let modifiers = match code {
+            KeyCode::Char(_) | KeyCode::BackTab => modifiers - KeyModifiers::SHIFT,
+            _ => modifiers,
+        };
+        Ok(Key { code, modifiers })
+    }
+
+    fn matches(&self, key: &KeyEvent) -> bool {
+        let modifiers = match key.code {
+            KeyCode::Char(_) | KeyCode::BackTab => key.modifiers - KeyModifiers::SHIFT,
+            _ => key.modifiers,
+        };
+        key.code == self.code && modifiers == self.modifiers
+    }
+}
+
+impl Display for Key {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (modifier, name) in [
+            (KeyModifiers::CONTROL, "Ctrl-"),
+            (KeyModifiers::ALT, "Alt-"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                f.write_str(name)?;
+            }
+        }
+        match self.code {
+            KeyCode::Char(' ') => f.write_str("Space"),
+            KeyCode::Char(c) if !self.modifiers.is_empty() => write!(f, "{}", c.to_uppercase()),
+            KeyCode::BackTab => f.write_str("Shift-Tab"),
+            code => write!(f, "{}", code),
+        }
+    }
+}
+
+/// The styles of the tui.
+#[derive(Debug, Clone)]
+struct Theme {
+    /// The border of the pane that has the focus
+    focused: Style,
+    /// The borders of the other panes
+    unfocused: Style,
+    /// The selected row of a table
+    selected: Style,
+    /// The column names of a result
+    header: Style,
+    /// The status line when it shows an error
+    error: Style,
+    /// Whether diffs and files are syntax highlighted
+    highlight: bool,
+}
+
+impl Theme {
+    fn colors() -> Theme {
+        Theme {
+            focused: Style::new().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+            unfocused: Style::new().fg(Color::DarkGray),
+            selected: Style::new().add_modifier(Modifier::REVERSED),
+            header: Style::new().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+            error: Style::new().fg(Color::Red),
+            highlight: true,
+        }
+    }
+
+    /// Bold, dim and reversed text only.
+    fn no_color() -> Theme {
+        Theme {
+            focused: Style::new().add_modifier(Modifier::BOLD),
+            unfocused: Style::new().add_modifier(Modifier::DIM),
+            selected: Style::new().add_modifier(Modifier::REVERSED),
+            header: Style::new().add_modifier(Modifier::BOLD),
+            error: Style::new(),
+            highlight: false,
+        }
+    }
+
+    fn border(&self, focused: bool) -> Style {
+        match focused {
+            true => self.focused,
+            false => self.unfocused,
+        }
+    }
+
+    /// The lines of a diff, highlighted unless the theme has no colors.
+    fn diff(&self, diff: &str) -> Vec<Line<'static>> {
+        match self.highlight {
+            true => Highlighter::get().diff(diff),
+            false => diff
+                .lines()
+                .map(|line| Line::raw(line.to_string()))
+                .collect(),
+        }
+    }
+
+    /// The lines of the file at `path`, highlighted unless the theme has no colors.
+    fn file<'t>(&self, path: &str, lines: impl Iterator<Item = &'t str>) -> Vec<Line<'static>> {
+        match self.highlight {
+            true => Highlighter::get().file(path, lines),
+            false => lines.map(|line| Line::raw(line.to_string())).collect(),
+        }
+    }
+
+    fn status<'s>(&self, status: &'s str) -> Paragraph<'s> {
+        let style = match status.starts_with("error") {
+            true => self.error,
+            false => Style::new(),
+        };
+        Paragraph::new(status).style(style)
+    }
+}
+
+/// The keys and the theme of the tui.
+#[derive(Debug)]
+struct Settings {
+    keys: Vec<(Binding, Vec<Key>)>,
+    theme: Theme,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings::new(&TuiConfig::default(), false).expect("the default keys are valid")
+    }
+}
+
+impl Settings {
+    /// The keys and the colors of `config` over the defaults. The default theme has colors,
+    /// unless `no_color` asks for the `no-color` theme and the config doesn't pick one.
+    fn new(config: &TuiConfig, no_color: bool) -> Result<Settings, CustomError> {
+        let invalid = |message: String| CustomError::InvalidArgument(format!("tui.{}", message));
+        if let Some(name) = (config.keys.keys()).find(|name| BINDINGS.iter().all(|b| b.1 != *name))
+        {
+            let names = BINDINGS.iter().map(|(_, name, _)| *name);
+            return Err(invalid(format!(
+                "keys: there is no {} to bind, the bindings are {}",
+                name,
+                names.collect::<Vec<_>>().join(", ")
+            )));
+        }
+        let mut keys = vec![];
+        for (binding, name, defaults) in BINDINGS {
+            let bound = match config.keys.get(name) {
+                Some(bound) => bound.keys().iter().map(String::as_str).collect(),
+                None => defaults.to_vec(),
+            };
+            let bound = (bound.into_iter())
+                .map(Key::parse)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| invalid(format!("keys.{}: {}", name, e)))?;
+            keys.push((binding, bound));
+        }
+        // The stop keys are only read while a query runs
+        let bound = keys.iter().filter(|(binding, _)| *binding != Binding::Stop);
+        let mut seen: Vec<(Key, Binding)> = vec![];
+        for (binding, key) in bound.flat_map(|(b, keys)| keys.iter().map(move |key| (b, key))) {
+            if let Some((_, other)) = seen.iter().find(|(seen, _)| seen == key) {
+                return Err(invalid(format!(
+                    "keys: {} is bound to both {} and {}",
+                    key,
+                    BINDINGS.iter().find(|b| b.0 == *other).unwrap().1,
+                    BINDINGS.iter().find(|b| b.0 == *binding).unwrap().1
+                )));
+            }
+            seen.push((*key, *binding));
+        }
+
+        let mut theme = match config.theme.as_deref() {
+            Some("default") => Theme::colors(),
+            Some("no-color") => Theme::no_color(),
+            None if no_color => Theme::no_color(),
+            None => Theme::colors(),
+            Some(theme) => {
+                return Err(invalid(format!(
+                    "theme: there is no theme {}, use default or no-color",
+                    theme
+                )))
+            }
+        };
+        for (element, color) in &config.colors {
+            let color = (color.parse::<Color>())
+                .map_err(|_| invalid(format!("colors.{}: {} is not a color", element, color)))?;
+            match element.as_str() {
+                "focused" => theme.focused = theme.focused.fg(color),
+                "unfocused" => theme.unfocused = theme.unfocused.fg(color),
+                "selected" => theme.selected = Style::new().bg(color),
+                "header" => theme.header = theme.header.fg(color),
+                "error" => theme.error = theme.error.fg(color),
+                _ => {
+                    return Err(invalid(format!(
+                        "colors: there is no {} to color, the elements are focused, unfocused, \
+                         selected, header and error",
+                        element
+                    )))
+                }
+            }
+        }
+        Ok(Settings { keys, theme })
+    }
+
+    /// What a key that was pressed does, if it's bound to anything.
+    fn binding(&self, key: &KeyEvent) -> Option<Binding> {
+        if key.kind == KeyEventKind::Release {
+            return None;
+        }
+        let mut bound = self.keys.iter();
+        bound
+            .find(|(_, keys)| keys.iter().any(|bound| bound.matches(key)))
+            .map(|(binding, _)| *binding)
+    }
+
+    fn keys(&self, binding: Binding) -> &[Key] {
+        let bound = self.keys.iter().find(|(b, _)| *b == binding);
+        bound.map_or(&[], |(_, keys)| keys)
+    }
+
+    /// The first key of `binding`, as shown in titles.
+    fn key_name(&self, binding: Binding) -> String {
+        match self.keys(binding).first() {
+            Some(key) => key.to_string(),
+            None => "(unbound)".to_string(),
+        }
+    }
+}
+
+/// While it lives the stop keys, Ctrl-C and Esc by default, interrupt the running statement. The
+/// terminal is in raw mode, Ctrl-C is read as a key instead of sending SIGINT.
+struct StopKeys {
+    done: Arc<AtomicBool>,
+    watcher: Option<thread::JoinHandle<()>>,
+}
+
+impl StopKeys {
+    fn interrupting(interrupt: &Interrupt, keys: Vec<Key>) -> StopKeys {
+        let done = Arc::new(AtomicBool::new(false));
+        let watcher = {
+            let (done, interrupt) = (done.clone(), interrupt.clone());
+            thread::spawn(move || {
+                while !done.load(Ordering::SeqCst) {
+                    if !event::poll(POLL).unwrap_or(false) {
+                        continue;
+                    }
+                    let Ok(Event::Key(key)) = event::read() else {
+                        continue;
+                    };
+                    if keys.iter().any(|stop| stop.matches(&key)) {
+                        interrupt.interrupt();
+                    }
+                }
+            })
+        };
+        StopKeys {
+            done,
+            watcher: Some(watcher),
+        }
+    }
+}
+
+impl Drop for StopKeys {
+    fn drop(&mut self) {
+        self.done.store(true, Ordering::SeqCst);
+        if let Some(watcher) = self.watcher.take() {
+            let _ = watcher.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::{Config, KeyList, Panel, QueryTemplate, TuiConfig};
+    use crate::params::Params;
+    use crate::test::{commit_file, temp_repository};
+    use crate::tui::{
+        commits_query, Action, App, Binding, Editor, Key, Queries, Results, Screen, Settings, Sort,
+        Workbench,
+    };
+    use crate::SqliteGit;
+    use ratatui::backend::TestBackend;
+    use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use ratatui::style::{Color, Modifier};
+    use ratatui::Terminal;
+    use rusqlite::Connection;
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+
+    #[test]
+    fn runs_the_editor_query_and_browses_the_result() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("tui")?;
+        commit_file(&repo, "file.txt", "one\n", "first")?;
+        commit_file(&repo, "file.txt", "two\n", "second\n\nwith a body")?;
+
+        let db = Connection::open_in_memory()?;
+        SqliteGit::new()
+            .with_all()
+            .repository(&path)
+            .register(&db)?;
+        let settings = Settings::default();
+        let mut workbench = Workbench::new(&db, &settings);
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        for c in "SELECT message\nFROM commits;".chars() {
+            let code = if c == '\n' {
+                KeyCode::Enter
+            } else {
+                KeyCode::Char(c)
+            };
+            assert_eq!(workbench.handle_key(key(code)), Action::None);
+        }
+        assert_eq!(workbench.handle_key(key(KeyCode::F(5))), Action::Execute);
+        workbench.execute();
+        workbench.handle_key(key(KeyCode::BackTab));
+        workbench.handle_key(key(KeyCode::Down));
+        let mut terminal = Terminal::new(TestBackend::new(60, 20))?;
+        terminal.draw(|frame| workbench.draw(frame))?;
+        let screen = format!("{:?}", terminal.backend().buffer());
+        let quit = KeyEvent::new(KeyCode::Char('q'), KeyModifiers::CONTROL);
+        std::fs::remove_dir_all(&path)?;
+
+        assert!(
+            workbench.status.starts_with("2 rows in"),
+            "{}",
+            workbench.status
+        );
+        assert_eq!(workbench.results.state.selected(), Some(1));
+        assert!(screen.contains("SELECT message"));
+        assert!(screen.contains("second  with a body"));
+        assert!(screen.contains("first"));
+        assert_eq!(workbench.handle_key(quit), Action::Quit);
+
+        Ok(())
+    }
+
+    #[test]
+    fn sorts_hides_resizes_and_pins_result_columns() -> Result<(), Box<dyn std::error::Error>> {
+        let db = Connection::open_in_memory()?;
+        let sql = "SELECT * FROM (VALUES (10, 'b'), (2, NULL), (1, 'c'))";
+        let mut results = Results::load(&db, sql, &Params::default())?.unwrap();
+        let key = |results: &mut Results, code| {
+            results.handle_key(KeyEvent::new(code, KeyModifiers::NONE));
+        };
+        let column = |results: &Results, column: usize| {
+            let rows = results
+                .order
+                .iter()
+                .map(|&row| results.rows[row][column].clone());
+            rows.collect::<Vec<_>>()
+        };
+        let draw = |results: &mut Results| -> Result<String, Box<dyn std::error::Error>> {
+            let mut terminal = Terminal::new(TestBackend::new(30, 6))?;
+            let theme = Settings::default().theme;
+            terminal.draw(|frame| {
+                let area = frame.area();
+                results.render(frame, area, ratatui::widgets::Block::bordered(), &theme)
+            })?;
+            let buffer = terminal.backend().buffer();
+            let line = (0..buffer.area.width).map(|x| buffer[(x, 1)].symbol().to_string());
+            Ok(line.collect())
+        };
+
+        // Numbers sort by value, the selected row stays selected
+        key(&mut results, KeyCode::Down);
+        key(&mut results, KeyCode::Char('s'));
+        assert_eq!(column(&results, 0), ["1", "2", "10"]);
+        assert_eq!(results.state.selected(), Some(1));
+        key(&mut results, KeyCode::Char('s'));
+        assert_eq!(column(&results, 0), ["10", "2", "1"]);
+        key(&mut results, KeyCode::Char('s'));
+        assert_eq!(column(&results, 0), ["10", "2", "1"]);
+        assert_eq!(results.sort, None);
+
+        // Empty values sort last
+        key(&mut results, KeyCode::Right);
+        key(&mut results, KeyCode::Char('s'));
+        assert_eq!(column(&results, 1), ["b", "c", ""]);
+        assert!(draw(&mut results)?.contains("column2 ▲"));
+
+        key(&mut results, KeyCode::Char('p'));
+        let header = draw(&mut results)?;
+        assert!(
+            header.find("column2") < header.find("column1"),
+            "{}",
+            header
+        );
+
+        key(&mut results, KeyCode::Char('h'));
+        let header = draw(&mut results)?;
+        assert!(!header.contains("column2"), "{}", header);
+        // The last column stays
+        key(&mut results, KeyCode::Char('h'));
+        assert!(draw(&mut results)?.contains("column1"));
+        key(&mut results, KeyCode::Char('H'));
+        assert!(draw(&mut results)?.contains("column2"));
+
+        let width = results.widths[results.column];
+        key(&mut results, KeyCode::Char('>'));
+        assert_eq!(results.widths[results.column], width + 2);
+        for _ in 0..10 {
+            key(&mut results, KeyCode::Char('<'));
+        }
+        assert_eq!(results.widths[results.column], 1);
+        assert_eq!(
+            results.sort,
+            Some(Sort {
+                column: 1,
+                descending: false
+            })
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn exports_the_result_as_it_is_shown() -> Result<(), Box<dyn std::error::Error>> {
+        let db = Connection::open_in_memory()?;
+        let settings = Settings::default();
+        let mut workbench = Workbench::new(&db, &settings);
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        let export = KeyEvent::new(KeyCode::Char('e'), KeyModifiers::CONTROL);
+        let dir = std::env::temp_dir().join(format!("sqlitegit-tui-export-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+
+        workbench.handle_key(export);
+        assert!(workbench.status.starts_with("error: there's no result"));
+
+        workbench.editor = Editor::with_text(
+            "SELECT * FROM (VALUES (2, 'two', 'a long' || char(10) || 'cell'), (1, 'one', NULL))",
+        );
+        workbench.execute();
+        workbench.handle_key(key(KeyCode::BackTab));
+        workbench.handle_key(key(KeyCode::Char('s')));
+        workbench.handle_key(key(KeyCode::Right));
+        workbench.handle_key(key(KeyCode::Char('h')));
+        let mut export_to = |file: &str| -> Result<String, Box<dyn std::error::Error>> {
+            workbench.handle_key(export);
+            workbench.export = Some(Editor::with_text(&dir.join(file).to_string_lossy()));
+            workbench.handle_key(key(KeyCode::Enter));
+            assert!(
+                workbench.status.starts_with("Exported 2 rows"),
+                "{}",
+                workbench.status
+            );
+            Ok(std::fs::read_to_string(dir.join(file))?)
+        };
+        let csv = export_to("result.csv")?;
+        let markdown = export_to("result.md")?;
+        workbench.handle_key(export);
+        workbench.export = Some(Editor::with_text("result.xlsx"));
+        workbench.handle_key(key(KeyCode::Enter));
+        std::fs::remove_dir_all(&dir)?;
+
+        assert_eq!(csv, "column1,column3\r\n1,\r\n2,\"a long\ncell\"\r\n");
+        assert_eq!(
+            markdown,
+            "| column1 | column3 |\n| --- | --- |\n| 1 |  |\n| 2 | a long<br>cell |\n"
+        );
+        assert!(
+            workbench.status.contains("isn't a .csv"),
+            "{}",
+            workbench.status
+        );
+        assert!(workbench.export.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn completes_names_in_the_sql_editor() -> Result<(), Box<dyn std::error::Error>> {
+        let db = Connection::open_in_memory()?;
+        SqliteGit::new().with_all().register(&db)?;
+        let settings = Settings::default();
+        let mut workbench = Workbench::new(&db, &settings);
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        let complete = KeyEvent::new(KeyCode::Char(' '), KeyModifiers::CONTROL);
+        let type_text = |workbench: &mut Workbench, text: &str| {
+            for c in text.chars() {
+                workbench.handle_key(key(KeyCode::Char(c)));
+            }
+        };
+
+        // A single completion replaces the word, the arguments of the table are shown
+        type_text(&mut workbench, "SELECT * FROM comm");
+        workbench.handle_key(complete);
+        assert_eq!(workbench.editor.text(), "SELECT * FROM commits");
+        assert_eq!(workbench.status, "commits(repository, ref, sample)");
+
+        type_text(&mut workbench, " c WHERE c.author_");
+        workbench.handle_key(complete);
+        let mut terminal = Terminal::new(TestBackend::new(80, 20))?;
+        terminal.draw(|frame| workbench.draw(frame))?;
+        let screen = format!("{:?}", terminal.backend().buffer());
+        assert!(screen.contains("author_name"), "{}", screen);
+        assert!(screen.contains("author_email"));
+        // The list follows the word
+        type_text(&mut workbench, "w");
+        let listed = workbench.completions.as_ref().map(|c| c.completions.len());
+        assert_eq!(listed, Some(1));
+        workbench.handle_key(key(KeyCode::Backspace));
+        workbench.handle_key(key(KeyCode::Down));
+        workbench.handle_key(key(KeyCode::Enter));
+        assert_eq!(
+            workbench.editor.text(),
+            "SELECT * FROM commits c WHERE c.author_email"
+        );
+        assert!(workbench.completions.is_none());
+        assert_eq!(Key::parse("ctrl-space")?.to_string(), "Ctrl-Space");
+
+        Ok(())
+    }
+
+    #[test]
+    fn browses_the_commits_a_search_matches() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("tui_browser")?;
+        commit_file(&repo, "other.txt", "one\n", "first")?;
+        commit_file(&repo, "file.txt", "two\n", "second")?;
+        commit_file(&repo, "file.txt", "three\n", "third")?;
+
+        let db = Connection::open_in_memory()?;
+        SqliteGit::new()
+            .with_all()
+            .repository(&path)
+            .register(&db)?;
+        let settings = Settings::default();
+        let queries = Queries::new(BTreeMap::new(), PathBuf::new(), &settings);
+        let mut app = App::new(&db, queries, vec![], &path, &settings);
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        assert_eq!(app.handle_key(key(KeyCode::F(2))), Action::Execute);
+        assert_eq!(app.screen, Screen::Browser);
+        app.browser.execute();
+        let listed = app.browser.commits.len();
+        app.handle_key(key(KeyCode::Char('/')));
+        for c in "path:file.txt".chars() {
+            app.handle_key(key(KeyCode::Char(c)));
+        }
+        assert_eq!(app.handle_key(key(KeyCode::Enter)), Action::Execute);
+        app.browser.execute();
+        let subjects = (app.browser.commits.iter())
+            .map(|commit| commit.subject.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(subjects, vec!["third", "second"]);
+        app.handle_key(key(KeyCode::Down));
+        let mut terminal = Terminal::new(TestBackend::new(100, 20))?;
+        terminal.draw(|frame| app.draw(frame))?;
+        let screen = format!("{:?}", terminal.backend().buffer());
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!(listed, 3);
+        assert_eq!(
+            app.browser.preview.files,
+            vec![("file.txt".to_string(), 1, 0)]
+        );
+        assert!(screen.contains("+two"), "{}", screen);
+        assert_eq!(app.handle_key(key(KeyCode::F(2))), Action::None);
+        assert_eq!(app.screen, Screen::Workbench);
+
+        Ok(())
+    }
+
+    #[test]
+    fn translates_search_terms_to_constraints() {
+        let (sql, params) = commits_query("author:ann rev:v1.0.. fix");
+
+        assert!(sql.contains("(author_name LIKE ? OR author_email LIKE ?) AND c.ref = ?"));
+        assert!(sql.ends_with("AND message LIKE ? LIMIT 1000"));
+        assert_eq!(params, vec!["%ann%", "%ann%", "v1.0..", "%fix%"]);
+    }
+
+    #[test]
+    fn blames_a_file_and_shows_the_commit_of_a_line() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("tui_blame")?;
+        let first = commit_file(&repo, "file.txt", "one\ntwo\n", "first")?;
+        let second = commit_file(&repo, "file.txt", "zero\none\ntwo\n", "second")?;
+        commit_file(&repo, "other.txt", "other\n", "third")?;
+
+        let db = Connection::open_in_memory()?;
+        SqliteGit::new()
+            .with_all()
+            .repository(&path)
+            .register(&db)?;
+        let settings = Settings::default();
+        let queries = Queries::new(BTreeMap::new(), PathBuf::new(), &settings);
+        let mut app = App::new(&db, queries, vec![], &path, &settings);
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        app.handle_key(key(KeyCode::F(2)));
+        app.browser.execute();
+        app.handle_key(key(KeyCode::Down));
+        assert_eq!(app.handle_key(key(KeyCode::Char('b'))), Action::Execute);
+        assert_eq!(app.screen, Screen::Blame);
+        app.blame.execute();
+        let blamed = (app.blame.lines.iter())
+            .map(|line| (line.hash.clone(), line.text.clone()))
+            .collect::<Vec<_>>();
+        let mut terminal = Terminal::new(TestBackend::new(80, 10))?;
+        terminal.draw(|frame| app.draw(frame))?;
+        let screen = format!("{:?}", terminal.backend().buffer());
+        app.handle_key(key(KeyCode::Down));
+        assert_eq!(app.handle_key(key(KeyCode::Enter)), Action::Execute);
+        assert_eq!(app.screen, Screen::Browser);
+        app.browser.execute();
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!(
+            blamed,
+            vec![
+                (second.to_string(), "zero".to_string()),
+                (first.to_string(), "one".to_string()),
+                (first.to_string(), "two".to_string())
+            ]
+        );
+        assert!(screen.contains("Someone"), "{}", screen);
+        assert_eq!(app.browser.commits[0].hash, first.to_string());
+
+        Ok(())
+    }
+
+    #[test]
+    fn runs_and_saves_queries_of_the_config() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("tui_queries")?;
+        commit_file(&repo, "file.txt", "one\n", "first")?;
+        commit_file(&repo, "file.txt", "two\n", "second")?;
+        let config = std::env::temp_dir().join("sqlitegit_tui_queries.toml");
+        std::fs::write(&config, "# Shared queries\n")?;
+
+        let db = Connection::open_in_memory()?;
+        SqliteGit::new()
+            .with_all()
+            .repository(&path)
+            .register(&db)?;
+        let template = QueryTemplate {
+            sql: "SELECT message FROM commits WHERE message LIKE :word".to_string(),
+            description: Some("Commits mentioning a word".to_string()),
+            params: BTreeMap::from([("word".to_string(), "first".to_string())]),
+        };
+        let queries = BTreeMap::from([("mentions".to_string(), template)]);
+        let settings = Settings::default();
+        let queries = Queries::new(queries, config.clone(), &settings);
+        let mut app = App::new(&db, queries, vec![], &path, &settings);
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        assert_eq!(app.handle_key(key(KeyCode::F(4))), Action::None);
+        assert_eq!(app.handle_key(key(KeyCode::Enter)), Action::Execute);
+        assert_eq!(app.screen, Screen::Workbench);
+        app.workbench.execute();
+        let opened = app.workbench.results.rows.clone();
+        app.workbench.params = Editor::with_text("word=sec%");
+        let save = KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert_eq!(app.handle_key(save), Action::None);
+        assert_eq!(app.screen, Screen::Queries);
+        for c in "-second".chars() {
+            app.handle_key(key(KeyCode::Char(c)));
+        }
+        app.handle_key(key(KeyCode::Enter));
+        let saved = Config::load(Some(&config), &path)?;
+        let content = std::fs::read_to_string(&config)?;
+        std::fs::remove_file(&config)?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!(opened, vec![vec!["first".to_string()]]);
+        assert_eq!(app.screen, Screen::Workbench);
+        assert_eq!(app.workbench.saved_as.as_deref(), Some("mentions-second"));
+        assert!(content.starts_with("# Shared queries\n"), "{}", content);
+        let second = &saved.queries["mentions-second"];
+        assert_eq!(second.params["word"], "sec%");
+        assert_eq!(second.description.as_deref(), None);
+        assert_eq!(app.queries.queries.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn refreshes_the_panels_of_the_dashboard() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("tui_dashboard")?;
+        commit_file(&repo, "file.txt", "one\n", "first")?;
+
+        let db = Connection::open_in_memory()?;
+        SqliteGit::new()
+            .with_all()
+            .repository(&path)
+            .register(&db)?;
+        let panel = |title: &str, sql: &str| Panel {
+            title: title.to_string(),
+            sql: sql.to_string(),
+            params: BTreeMap::new(),
+        };
+        let panels = vec![
+            panel("Messages", "SELECT message FROM commits"),
+            panel("Broken", "SELECT nope FROM commits"),
+        ];
+        let settings = Settings::default();
+        let queries = Queries::new(BTreeMap::new(), PathBuf::new(), &settings);
+        let mut app = App::new(&db, queries, panels, &path, &settings);
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        assert_eq!(app.handle_key(key(KeyCode::F(6))), Action::Execute);
+        app.dashboard.execute();
+        // Sorted descending, which the refresh keeps
+        app.handle_key(key(KeyCode::Char('s')));
+        app.handle_key(key(KeyCode::Char('s')));
+        commit_file(&repo, "file.txt", "two\n", "second")?;
+        assert_eq!(app.handle_key(key(KeyCode::F(5))), Action::Execute);
+        app.dashboard.execute();
+        app.handle_key(key(KeyCode::Tab));
+        let mut terminal = Terminal::new(TestBackend::new(80, 10))?;
+        terminal.draw(|frame| app.draw(frame))?;
+        let screen = format!("{:?}", terminal.backend().buffer());
+        std::fs::remove_dir_all(&path)?;
+
+        let messages = &app.dashboard.panels[0].results;
+        let order = (messages.order.iter()).map(|&row| messages.rows[row][0].as_str());
+        assert_eq!(order.collect::<Vec<_>>(), ["second", "first"]);
+        assert_eq!(app.dashboard.focus, 1);
+        assert!(screen.contains("Messages (2 rows)"), "{}", screen);
+        assert!(screen.contains("error: no such column: nope"), "{}", screen);
+        assert_eq!(app.handle_key(key(KeyCode::F(6))), Action::None);
+        assert_eq!(app.screen, Screen::Workbench);
+
+        Ok(())
+    }
+
+    #[test]
+    fn reads_keys_and_colors_of_the_config() -> Result<(), Box<dyn std::error::Error>> {
+        let key = |code, modifiers| KeyEvent::new(code, modifiers);
+        let config = TuiConfig {
+            keys: BTreeMap::from([
+                ("quit".to_string(), KeyList::One("ctrl-x".to_string())),
+                (
+                    "next_pane".to_string(),
+                    KeyList::Many(vec!["alt-n".to_string()]),
+                ),
+            ]),
+            colors: BTreeMap::from([("focused".to_string(), "#00ff00".to_string())]),
+            ..TuiConfig::default()
+        };
+        let settings = Settings::new(&config, false)?;
+        let no_color = Settings::new(&TuiConfig::default(), true)?;
+        let conflict = TuiConfig {
+            keys: BTreeMap::from([("save".to_string(), KeyList::One("F5".to_string()))]),
+            ..TuiConfig::default()
+        };
+        let conflict = Settings::new(&conflict, false).unwrap_err().to_string();
+
+        let ctrl_x = key(KeyCode::Char('x'), KeyModifiers::CONTROL);
+        assert_eq!(settings.binding(&ctrl_x), Some(Binding::Quit));
+        let ctrl_q = key(KeyCode::Char('q'), KeyModifiers::CONTROL);
+        assert_eq!(settings.binding(&ctrl_q), None);
+        let alt_n = key(KeyCode::Char('n'), KeyModifiers::ALT);
+        assert_eq!(settings.binding(&alt_n), Some(Binding::NextPane));
+        assert_eq!(
+            settings.binding(&key(KeyCode::Tab, KeyModifiers::NONE)),
+            None
+        );
+        assert_eq!(settings.key_name(Binding::Run), "F5");
+        assert_eq!(settings.theme.focused.fg, Some(Color::Rgb(0, 255, 0)));
+        assert_eq!(no_color.theme.focused.fg, None);
+        assert!(no_color.theme.focused.add_modifier.contains(Modifier::BOLD));
+        assert_eq!(conflict, "tui.keys: F5 is bound to both run and save");
+        let back_tab = key(KeyCode::BackTab, KeyModifiers::SHIFT);
+        assert!(Key::parse("shift-tab")?.matches(&back_tab));
+        assert_eq!(Key::parse("ctrl-alt-x")?.to_string(), "Ctrl-Alt-X");
+        assert!(Key::parse("hyper-x").is_err());
+
+        Ok(())
+    }
+}
@@ -1,225 +1,502 @@
+use crate::output::{values_to_json, OutputOptions, Table};
+use crate::params::Params;
+use crate::{CustomError, Profiler};
 use itertools::Itertools;
-use rusqlite::types::Type;
-use rusqlite::{Connection, Statement};
-
-/**
-hash            text, 0
-message         text, 1
-author_name     text, 2
-author_email    text, 3
-author_when     DATETIME, 4
-committer_name  text, 5
-committer_email text, 6
-committer_when  DATETIME, 7
-is_merge        bool, 8
-parent_1        text, 9
-parent_2        text, 10
-repository      hidden, 11
-ref             hidden 12
- */
-pub fn list_all_comits(db: &Connection) {
-    let sql = r#"
-    SELECT hash, message, author_when
-    FROM commits('/home/rdp/dixa/listing-service')
-    "#;
-    let mut stmt = db.prepare(sql).unwrap();
-
-    execute_and_pretty_print(&mut stmt);
+use rusqlite::types::ValueRef;
+use rusqlite::{Batch, Connection, Row, Statement};
+use std::collections::HashMap;
+use std::io::Write;
+use std::time::Instant;
+
+/// Writes the result of `stmt` to `out` in the mode of `options`. Past the limit of `options`
+/// the rows are counted and the count is written after the result. DATETIME columns are written
+/// in the time zone and the date format of `options`.
+pub fn execute_and_write(
+    stmt: &mut Statement,
+    out: &mut dyn Write,
+    options: &OutputOptions,
+) -> Result<(), CustomError> {
+    let columns = column_names(stmt);
+    let datetimes = datetime_columns(stmt, options);
+    let mut writer = options.writer(&columns);
+    writer.start(out)?;
+    let limit = options.limit.map_or(usize::MAX, |limit| limit.rows);
+    let mut rows = stmt.raw_query();
+    let mut written = 0;
+    while written < limit {
+        let Some(row) = rows.next()? else {
+            break;
+        };
+        let values = row_values(row, columns.len());
+        match &datetimes {
+            Some(datetimes) => {
+                let converted = (values.iter().zip(datetimes))
+                    .map(|(value, &datetime)| match (value, datetime) {
+                        (ValueRef::Text(text), true) => std::str::from_utf8(text)
+                            .ok()
+                            .and_then(|text| options.datetime(text)),
+                        _ => None,
+                    })
+                    .collect_vec();
+                let values = (values.iter().zip(&converted))
+                    .map(|(value, converted)| match converted {
+                        Some(text) => ValueRef::Text(text.as_bytes()),
+                        None => *value,
+                    })
+                    .collect_vec();
+                writer.row(out, &values)?;
+            }
+            None => writer.row(out, &values)?,
+        }
+        written += 1;
+    }
+    // The rows left out are counted without formatting them
+    let mut more = 0;
+    if written == limit {
+        while rows.next()?.is_some() {
+            more += 1;
+        }
+    }
+    writer.finish(out)?;
+    if let (Some(limit), 1..) = (options.limit, more) {
+        writeln!(out, "{} more rows, use {}", more, limit.hint)?;
+    }
+    Ok(())
 }
 
-pub fn execute_and_format(stmt: &mut Statement) -> Vec<String> {
-    let col_count = stmt.column_count();
-    let result_rows = stmt
-        .query_map([], |row| {
-            let mut row_array: Vec<String> = vec![];
-            (0..col_count).for_each(|i| {
-                let col_ref = row.get_ref_unwrap(i);
-                match col_ref.data_type() {
-                    Type::Null => {
-                        row_array.push("NULL".to_string());
-                        //row_str.push_str("NULL");
-                    }
-                    Type::Integer => {
-                        row_array.push(col_ref.as_i64().unwrap().to_string());
-                    }
-                    Type::Real => {
-                        row_array.push(col_ref.as_f64().unwrap().to_string());
-                    }
-                    Type::Text => {
-                        row_array.push(col_ref.as_str().unwrap().to_string().lines().join(""));
-                    }
-                    Type::Blob => {
-                        row_array.push(
-                            String::from_utf8(Vec::from(col_ref.as_blob().unwrap())).unwrap(),
-                        );
-                    }
-                };
-            });
-            Ok(row_array)
-        })
-        .unwrap()
-        .map(|r| r.unwrap())
-        .collect_vec();
+/// Executes every statement in `sql` in order, writing the result set of each statement that
+/// returns columns to `out`. Statements without columns (DDL, inserts, ...) are executed
+/// silently.
+///
+/// With a `profiler` the query plan, the time spent preparing and executing and what the git
+/// tables did are written to stderr after each statement.
+pub fn execute_all_and_write(
+    db: &Connection,
+    sql: &str,
+    params: &Params,
+    options: &OutputOptions,
+    profiler: Option<&Profiler>,
+    out: &mut dyn Write,
+) -> Result<(), CustomError> {
+    let mut batch = Batch::new(db, sql);
+    let mut printed_any = false;
+    let mut preparing = Instant::now();
+    while let Some(mut stmt) = batch.next()? {
+        let prepared = preparing.elapsed();
+        params.bind(&mut stmt)?;
+        let plan = profiler.map(|_| query_plan(db, &stmt));
+        if let Some(profiler) = profiler {
+            profiler.start();
+        }
+        let executing = Instant::now();
+        let result = if stmt.column_count() == 0 {
+            stmt.raw_execute().map(drop).map_err(CustomError::from)
+        } else {
+            let written = match printed_any {
+                true => writeln!(out).map_err(CustomError::from),
+                false => Ok(()),
+            };
+            printed_any = true;
+            written.and_then(|()| execute_and_write(&mut stmt, out, options))
+        };
+        if let (Some(profiler), Some(plan)) = (profiler, plan) {
+            let executed = executing.elapsed();
+            eprint!("{}", plan);
+            eprintln!("prepare {:.1?}, execute {:.1?}", prepared, executed);
+            eprint!("{}", profiler.finish());
+        }
+        result?;
+        preparing = Instant::now();
+    }
+    Ok(())
+}
 
-    let mut init = (0..col_count).map(|_| 0).collect_vec();
-    let col_names = stmt
-        .column_names()
-        .iter()
-        .map(|str| str.to_string())
-        .collect_vec();
-    let col_names_and_rows = [vec![col_names.to_owned()], result_rows.to_owned()].concat();
-    let max_size = col_names_and_rows.iter().fold(init, |mut acc, vec| {
-        (0..col_count).for_each(|i| {
-            if acc[i] < vec[i].len() {
-                acc[i] = std::cmp::min(vec[i].len(), 50)
-            }
-        });
-        acc
-    });
-
-    let headers = {
-        (0..col_count)
-            .map(|i| {
-                let max_size = max_size[i];
-                let mut str: String = col_names[i].to_owned();
-                let length = std::cmp::min(std::cmp::max(max_size, str.len()), 50);
-                str.truncate(length);
-                format!("{:width$}", str, width = length as usize)
-            })
-            .join(" | ")
+/// The EXPLAIN QUERY PLAN of `stmt` as an indented tree, empty when SQLite can't explain it.
+fn query_plan(db: &Connection, stmt: &Statement) -> String {
+    let explain = |sql: String| -> rusqlite::Result<String> {
+        let mut explain = db.prepare(&format!("EXPLAIN QUERY PLAN {}", sql))?;
+        let mut depths = HashMap::new();
+        let mut plan = String::from("QUERY PLAN\n");
+        let mut rows = explain.query([])?;
+        while let Some(row) = rows.next()? {
+            let (id, parent, detail): (i64, i64, String) = (row.get(0)?, row.get(1)?, row.get(3)?);
+            let depth = depths.get(&parent).map_or(1, |depth| depth + 1);
+            depths.insert(id, depth);
+            plan.push_str(&format!("{}{}\n", "  ".repeat(depth), detail));
+        }
+        Ok(plan)
     };
+    stmt.expanded_sql()
+        .and_then(|sql| explain(sql).ok())
+        .unwrap_or_default()
+}
 
-    let line = {
-        let lenth =
-            (0..col_count).fold(0, |acc, next| acc + max_size[next]) + 2 + (col_count * 3) - 1;
-        format!(
-            "{}",
-            String::from((0..lenth).map(|_| '-').collect::<String>())
-        )
-    };
+/// The result of `stmt` as a table, see [`Table`], with the number of rows in it.
+pub(crate) fn execute_into_table(
+    stmt: &mut Statement,
+    options: &OutputOptions,
+) -> rusqlite::Result<(Table, usize)> {
+    let mut table = Table::new(&column_names(stmt), options);
+    let mut count = 0;
+    let mut rows = stmt.raw_query();
+    while let Some(row) = rows.next()? {
+        table.push(&row_values(row, table.columns()));
+        count += 1;
+    }
+    Ok((table, count))
+}
 
-    let formatted_rows = result_rows
+pub fn column_names(stmt: &Statement) -> Vec<String> {
+    stmt.column_names()
         .iter()
-        .enumerate()
-        .flat_map(|(i, row_vec)| {
-            print!("| ");
-            let cols = (0..col_count)
-                .map(|(i)| {
-                    let max_size = max_size[i];
-                    let mut str: String = row_vec[i].to_owned();
-                    let length = std::cmp::min(std::cmp::max(max_size, str.len()), 50);
-                    str.truncate(length);
-                    format!("{:width$}", str, width = length as usize)
-                })
-                .join(" | ");
-            println!("");
-            if i == 0 {
-                let lenth =
-                    (0..col_count).fold(0, |acc, next| acc + max_size[next]) + 2 + (col_count * 3)
-                        - 1;
-                println!(
-                    "{}",
-                    String::from((0..lenth).map(|_| '-').collect::<String>())
-                );
-            }
+        .map(|str| str.to_string())
+        .collect_vec()
+}
 
-            let line = format!("|{}|", cols);
+pub fn row_to_json(row: &Row, col_names: &[String]) -> serde_json::Value {
+    values_to_json(&row_values(row, col_names.len()), col_names)
+}
 
-            vec![line]
+/// Which columns of `stmt` are declared DATETIME, None when none are or `options` leave their
+/// values as the tables return them.
+fn datetime_columns(stmt: &Statement, options: &OutputOptions) -> Option<Vec<bool>> {
+    let datetimes = (stmt.columns().iter())
+        .map(|column| {
+            let decl = column.decl_type().unwrap_or_default().to_ascii_uppercase();
+            decl.contains("DATETIME") || decl.contains("TIMESTAMP")
         })
         .collect_vec();
+    let converted = options.formats_datetimes() && datetimes.contains(&true);
+    converted.then_some(datetimes)
+}
 
-    [vec![headers], vec![line], formatted_rows].concat()
+/// The first `columns` values of `row`.
+fn row_values<'r>(row: &'r Row, columns: usize) -> Vec<ValueRef<'r>> {
+    (0..columns).map(|i| row.get_ref_unwrap(i)).collect()
 }
 
-pub fn execute_and_pretty_print(stmt: &mut Statement) {
-    let col_count = stmt.column_count();
-    let result_rows = stmt
-        .query_map([], |row| {
-            let mut row_array: Vec<String> = vec![];
-            (0..col_count).for_each(|i| {
-                let col_ref = row.get_ref_unwrap(i);
-                match col_ref.data_type() {
-                    Type::Null => {
-                        row_array.push("NULL".to_string());
-                        //row_str.push_str("NULL");
-                    }
-                    Type::Integer => {
-                        row_array.push(col_ref.as_i64().unwrap().to_string());
-                    }
-                    Type::Real => {
-                        row_array.push(col_ref.as_f64().unwrap().to_string());
-                    }
-                    Type::Text => {
-                        row_array.push(col_ref.as_str().unwrap().to_string().lines().join(""));
-                    }
-                    Type::Blob => {
-                        row_array.push(
-                            String::from_utf8(Vec::from(col_ref.as_blob().unwrap())).unwrap(),
-                        );
-                    }
-                };
-            });
-            Ok(row_array)
-        })
-        .unwrap()
-        .map(|r| r.unwrap())
-        .collect_vec();
+#[cfg(test)]
+mod test {
+    use crate::output::{
+        ColumnWidth, DateFormat, OutputMode, OutputOptions, RowLimit, TimeZoneChoice,
+    };
+    use crate::utils::{execute_and_write, execute_into_table};
+    use rusqlite::{Connection, Statement};
 
-    let mut init = (0..col_count).map(|_| 0).collect_vec();
-    let col_names = stmt
-        .column_names()
-        .iter()
-        .map(|str| str.to_string())
-        .collect_vec();
-    let col_names_and_rows = [vec![col_names], result_rows].concat();
-    let max_size = col_names_and_rows.iter().fold(init, |mut acc, vec| {
-        (0..col_count).for_each(|i| {
-            if acc[i] < vec[i].len() {
-                acc[i] = std::cmp::min(vec[i].len(), 50)
-            }
-        });
-        acc
-    });
+    /// The lines of the result of `stmt` as a table.
+    fn execute_and_format(
+        stmt: &mut Statement,
+        options: &OutputOptions,
+    ) -> rusqlite::Result<Vec<String>> {
+        Ok(execute_into_table(stmt, options)?.0.lines())
+    }
 
-    col_names_and_rows
-        .iter()
-        .enumerate()
-        .for_each(|(i, row_vec)| {
-            print!("| ");
-            (0..col_count).for_each(|i| {
-                let max_size = max_size[i];
-                let mut str: String = row_vec[i].to_owned();
-                let length = std::cmp::min(std::cmp::max(max_size, str.len()), 50);
-                str.truncate(length);
-                print!("{}", format!("{:width$}", str, width = length as usize));
-                print!(" | ");
-            });
-            println!("");
-            if i == 0 {
-                let lenth =
-                    (0..col_count).fold(0, |acc, next| acc + max_size[next]) + 2 + (col_count * 3)
-                        - 1;
-                println!(
-                    "{}",
-                    String::from((0..lenth).map(|_| '-').collect::<String>())
-                );
-            }
-        });
+    #[test]
+    fn csv_quoting() -> Result<(), rusqlite::Error> {
+        let db = Connection::open_in_memory()?;
+        let mut stmt = db.prepare(
+            r#"SELECT 'a,b' AS "x,y", 'say "hi"' AS quote, 'l1' || char(10) || 'l2' AS lines, NULL AS n"#,
+        )?;
+        let options = OutputOptions {
+            mode: OutputMode::Csv,
+            headers: true,
+            ..OutputOptions::default()
+        };
+        let mut out = vec![];
+        execute_and_write(&mut stmt, &mut out, &options).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\"x,y\",quote,lines,n\r\n\"a,b\",\"say \"\"hi\"\"\",\"l1\nl2\",\r\n"
+        );
+
+        Ok(())
+    }
 
-    //println!("{:#?}", wut);
+    #[test]
+    fn markdown_escaping() -> Result<(), rusqlite::Error> {
+        let db = Connection::open_in_memory()?;
+        let mut stmt = db
+            .prepare("SELECT 'a|b' AS \"x|y\", 'l1' || char(13, 10) || 'l2' AS lines, NULL AS n")?;
+        let options = OutputOptions {
+            mode: OutputMode::Markdown,
+            headers: true,
+            ..OutputOptions::default()
+        };
+        let mut out = vec![];
+        execute_and_write(&mut stmt, &mut out, &options).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "| x\\|y | lines | n |\n| --- | --- | --- |\n| a\\|b | l1<br>l2 |  |\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn colored_table() -> Result<(), rusqlite::Error> {
+        let db = Connection::open_in_memory()?;
+        let mut stmt = db.prepare(
+            "SELECT * FROM (VALUES (1, 'x', NULL), (22, 'yy', 3.5), (3, 'zzz', 1)) AS t",
+        )?;
+        let colored = OutputOptions {
+            color: true,
+            ..OutputOptions::default()
+        };
+        let lines = execute_and_format(&mut stmt, &colored)?;
+
+        assert_eq!(
+            lines,
+            [
+                "| \x1b[1mcolumn1\x1b[22m | \x1b[1mcolumn2\x1b[22m | \x1b[1mcolumn3\x1b[22m |",
+                "-------------------------------",
+                "|       1 | x       | \x1b[2mNULL   \x1b[22m |",
+                "\x1b[48;5;236m|      22 | yy      |     3.5 |\x1b[49m",
+                "|       3 | zzz     |       1 |",
+            ]
+        );
+        let mut stmt = db.prepare("SELECT NULL AS n")?;
+        assert_eq!(
+            execute_and_format(&mut stmt, &OutputOptions::default())?[2],
+            "| NULL |"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn unicode_widths() -> Result<(), rusqlite::Error> {
+        let db = Connection::open_in_memory()?;
+        let sql = "SELECT * FROM (VALUES ('修复提交', 'ok'), ('👨‍👩‍👧 family', 'a')) AS t";
+        let mut stmt = db.prepare(sql)?;
+
+        assert_eq!(
+            execute_and_format(&mut stmt, &OutputOptions::default())?,
+            [
+                "| column1   | column2 |",
+                "-----------------------",
+                "| 修复提交  | ok      |",
+                "| 👨‍👩‍👧 family | a       |",
+            ]
+        );
+        let options = OutputOptions {
+            max_width: 0,
+            widths: vec!["column1=6".parse().unwrap()],
+            ..OutputOptions::default()
+        };
+        assert_eq!(
+            execute_and_format(&mut stmt, &options)?,
+            [
+                "| colum… | column2 |",
+                "--------------------",
+                "| 修复…  | ok      |",
+                "| 👨‍👩‍👧 fa… | a       |",
+            ]
+        );
+        assert_eq!(
+            "message=0".parse(),
+            Ok(ColumnWidth {
+                column: "message".to_string(),
+                width: 0
+            })
+        );
+        assert!("message".parse::<ColumnWidth>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn expanded_records() -> Result<(), rusqlite::Error> {
+        let db = Connection::open_in_memory()?;
+        let mut stmt = db.prepare(
+            "SELECT 'a1b2' AS hash, 'Fix' || char(10) || 'the parser' AS message, NULL AS parents
+             UNION ALL SELECT 'c3d4', 'Add tests', 2",
+        )?;
+        let options = OutputOptions {
+            mode: OutputMode::Expanded,
+            ..OutputOptions::default()
+        };
+        let mut out = vec![];
+        execute_and_write(&mut stmt, &mut out, &options).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            r#"-[ RECORD 1 ]
+hash    | a1b2
+message | Fix
+        | the parser
+parents | NULL
+-[ RECORD 2 ]
+hash    | c3d4
+message | Add tests
+parents | 2
+"#
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn plain_values() -> Result<(), rusqlite::Error> {
+        let db = Connection::open_in_memory()?;
+        let mut stmt = db.prepare(
+            "SELECT 'a1b2' AS hash, 'Fix' || char(9) || 'it' AS message, NULL AS n
+             UNION ALL SELECT 'c3d4', 'Add' || char(10) || 'tests', 2",
+        )?;
+        let options = OutputOptions {
+            mode: OutputMode::Plain,
+            ..OutputOptions::default()
+        };
+        let mut out = vec![];
+        execute_and_write(&mut stmt, &mut out, &options).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "a1b2\tFix\\tit\t\nc3d4\tAdd\\ntests\t2\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn html_report() -> Result<(), rusqlite::Error> {
+        let db = Connection::open_in_memory()?;
+        let mut stmt = db.prepare("SELECT '<b> & \"c\"' AS \"a<b\", 2 AS n, NULL AS z")?;
+        let options = OutputOptions {
+            mode: OutputMode::Html,
+            ..OutputOptions::default()
+        };
+        let mut out = vec![];
+        execute_and_write(&mut stmt, &mut out, &options).unwrap();
+        let html = String::from_utf8(out).unwrap();
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<th>a&lt;b</th>\n<th>n</th>\n<th>z</th>\n</tr>\n</thead>\n<tbody>"));
+        assert!(html.contains(
+            "<tr>\n<td>&lt;b&gt; &amp; &quot;c&quot;</td>\n<td class=\"number\">2</td>\n\
+             <td class=\"null\">NULL</td>\n</tr>\n</tbody>"
+        ));
+        assert!(html.ends_with("</html>\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn dot_graph() -> Result<(), rusqlite::Error> {
+        let db = Connection::open_in_memory()?;
+        let mut stmt = db.prepare(
+            "SELECT 'c3' AS hash, 'c2' AS parent, 'Merge \"x\"' AS message
+             UNION ALL SELECT 'c3', 'b1', 'Merge \"x\"'
+             UNION ALL SELECT 'c1', NULL, 'Initial' || char(10) || 'commit'",
+        )?;
+        let options = OutputOptions {
+            mode: OutputMode::Dot,
+            ..OutputOptions::default()
+        };
+        let mut out = vec![];
+        execute_and_write(&mut stmt, &mut out, &options).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            r#"digraph {
+  node [shape=box];
+  "c3" -> "c2";
+  "c3" [label="Merge \"x\""];
+  "c3" -> "b1";
+  "c3" [label="Merge \"x\""];
+  "c1";
+  "c1" [label="Initial\ncommit"];
 }
+"#
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn row_limit() -> Result<(), rusqlite::Error> {
+        let db = Connection::open_in_memory()?;
+        let sql = "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 5)
+                   SELECT i FROM n";
+        let write = |rows| {
+            let options = OutputOptions {
+                mode: OutputMode::Plain,
+                limit: Some(RowLimit {
+                    rows,
+                    hint: "--no-limit",
+                }),
+                ..OutputOptions::default()
+            };
+            let mut out = vec![];
+            execute_and_write(&mut db.prepare(sql)?, &mut out, &options).unwrap();
+            Ok::<_, rusqlite::Error>(String::from_utf8(out).unwrap())
+        };
+
+        assert_eq!(write(2)?, "1\n2\n3 more rows, use --no-limit\n");
+        assert_eq!(write(5)?, "1\n2\n3\n4\n5\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn datetimes_in_time_zone() -> Result<(), rusqlite::Error> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch(
+            "CREATE TABLE t(at DATETIME, text TEXT);
+             INSERT INTO t VALUES ('2022-06-23 16:00:00+02:00', '2022-06-23 16:00:00+02:00'),
+                                  ('2022-06-23 16:00:00+00:00', 'none');",
+        )?;
+        let write = |timezone| {
+            let options = OutputOptions {
+                mode: OutputMode::Plain,
+                timezone,
+                ..OutputOptions::default()
+            };
+            let mut out = vec![];
+            execute_and_write(&mut db.prepare("SELECT * FROM t")?, &mut out, &options).unwrap();
+            Ok::<_, rusqlite::Error>(String::from_utf8(out).unwrap())
+        };
+
+        assert_eq!(
+            write(TimeZoneChoice::Utc)?,
+            "2022-06-23 14:00:00+00:00\t2022-06-23 16:00:00+02:00\n\
+             2022-06-23 16:00:00+00:00\tnone\n"
+        );
+        assert_eq!(
+            write(TimeZoneChoice::Original)?,
+            "2022-06-23 16:00:00+02:00\t2022-06-23 16:00:00+02:00\n\
+             2022-06-23 16:00:00+00:00\tnone\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn date_formats_by_mode() -> Result<(), Box<dyn std::error::Error>> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch(
+            "CREATE TABLE t(at DATETIME);
+             INSERT INTO t VALUES ('2022-06-23 16:00:00+02:00');",
+        )?;
+        let write = |mode, date_format: Option<&str>| -> Result<_, Box<dyn std::error::Error>> {
+            let options = OutputOptions {
+                mode,
+                headers: false,
+                date_format: date_format.map(str::parse).transpose()?,
+                date_formats: [(OutputMode::Csv, "%d %b %Y %H:%M".parse()?)].into(),
+                ..OutputOptions::default()
+            };
+            let mut out = vec![];
+            execute_and_write(&mut db.prepare("SELECT at FROM t")?, &mut out, &options)?;
+            Ok(String::from_utf8(out)?)
+        };
+
+        assert_eq!(write(OutputMode::Csv, None)?, "23 Jun 2022 14:00\r\n");
+        assert_eq!(write(OutputMode::Tsv, None)?, "2022-06-23 14:00:00+00:00\n");
+        assert_eq!(write(OutputMode::Tsv, Some("%s"))?, "1655992800\n");
+        assert!("%Q".parse::<DateFormat>().is_err());
 
-pub fn list_commits_with_stats(db: &Connection) {
-    let sql = r#"
-    SELECT commits.hash, stats.file_name, SUM(stats.additions), SUM(stats.deletions)
-    FROM commits('/home/rdp/dixa/listing-service') left outer join stats() on commits.hash = stats.hash
-    WHERE commits.is_merge is true
-    group by commits.hash, stats.file_name
-    "#;
-    let mut stmt = db.prepare(sql).unwrap();
-    let start = std::time::Instant::now();
-    execute_and_pretty_print(&mut stmt);
-
-    //println!("{:#?}", iter.collect_vec());
+        Ok(())
+    }
 }
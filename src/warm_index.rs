@@ -0,0 +1,179 @@
+use crate::intern::Interner;
+use crate::{walk_commits, CommitShadow, CustomError, TableConfig};
+use git2::{Oid, Repository};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::Instant;
+use tracing::{debug, info};
+
+/// Histories with more commits aren't indexed, the index would take hundreds of megabytes.
+const MAX_COMMITS: usize = 250_000;
+
+/// The commits of the default repository, read on a thread of their own when the tables are
+/// registered, so the queries of a long-lived connection don't walk the history again.
+///
+/// The index is used while HEAD points where it did when the index was built. Once HEAD moves a
+/// new index is built, the queries walk the history until it's ready.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct WarmIndex {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    warmed: Option<Arc<Warmed>>,
+    building: bool,
+}
+
+/// The commits reachable from HEAD when the index was built.
+#[derive(Debug)]
+pub(crate) struct Warmed {
+    git_dir: PathBuf,
+    head: Oid,
+    /// In the order the `commits` table walks them
+    pub(crate) commits: Vec<CommitShadow>,
+    positions: HashMap<Oid, usize>,
+}
+
+impl WarmIndex {
+    /// Starts building the index of the default repository of `config`, unless it's built or
+    /// being built already.
+    pub(crate) fn warm(&self, config: &TableConfig) {
+        {
+            let mut state = self.lock();
+            if state.building || state.warmed.is_some() {
+                return;
+            }
+            state.building = true;
+        }
+        let index = self.clone();
+        let config = config.clone();
+        thread::spawn(move || {
+            let start = Instant::now();
+            let built = config
+                .open_repository(None)
+                .and_then(|repo| Warmed::build(&repo));
+            let mut state = index.lock();
+            state.building = false;
+            match built {
+                Ok(warmed) => {
+                    info!(commits = warmed.commits.len(), elapsed = ?start.elapsed(), "warmed index");
+                    state.warmed = Some(Arc::new(warmed));
+                }
+                Err(e) => debug!(error = %e, "not warming the index"),
+            }
+        });
+    }
+
+    /// The index of `repo` when it's ready and HEAD hasn't moved since it was built. A moved HEAD
+    /// starts building a new index.
+    pub(crate) fn get(&self, repo: &Repository, config: &TableConfig) -> Option<Arc<Warmed>> {
+        let warmed = self.lock().warmed.clone()?;
+        if warmed.git_dir != repo.path() {
+            return None;
+        }
+        if repo.head().ok().and_then(|head| head.target()) == Some(warmed.head) {
+            return Some(warmed);
+        }
+        self.lock().warmed = None;
+        self.warm(config);
+        None
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Warmed {
+    fn build(repo: &Repository) -> Result<Warmed, CustomError> {
+        let head = repo.head()?.peel_to_commit()?.id();
+        let mut interner = Interner::default();
+        let mut commits = vec![];
+        walk_commits(repo, None, |commit| {
+            if commits.len() == MAX_COMMITS {
+                return Err(CustomError::InvalidArgument(format!(
+                    "the history has more than {} commits",
+                    MAX_COMMITS
+                )));
+            }
+            commits.push(CommitShadow::new(&commit, &mut interner));
+            Ok(true)
+        })?;
+        let positions = commits
+            .iter()
+            .enumerate()
+            .filter_map(|(i, commit)| Some((Oid::from_str(&commit.hash).ok()?, i)))
+            .collect();
+        Ok(Warmed {
+            git_dir: repo.path().to_path_buf(),
+            head,
+            commits,
+            positions,
+        })
+    }
+
+    /// The commit `oid` when HEAD reaches it.
+    pub(crate) fn commit(&self, oid: Oid) -> Option<&CommitShadow> {
+        self.positions.get(&oid).map(|&i| &self.commits[i])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test::{commit_file, temp_repository};
+    use crate::SqliteGit;
+    use rusqlite::Connection;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn serves_commits_until_head_moves() -> Result<(), Box<dyn std::error::Error>> {
+        let (path, repo) = temp_repository("warm_index")?;
+        let first = commit_file(&repo, "file.txt", "one\n", "first")?;
+        commit_file(&repo, "file.txt", "two\n", "second")?;
+
+        let git = SqliteGit::new()
+            .with_commits()
+            .repository(&path)
+            .with_warm_index();
+        let db = Connection::open_in_memory()?;
+        git.register(&db)?;
+        let index = git.config.warm_index.clone().ok_or("no index")?;
+        let start = Instant::now();
+        while index.get(&repo, &git.config).is_none() && start.elapsed() < Duration::from_secs(10) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let warmed = index
+            .get(&repo, &git.config)
+            .ok_or("the index wasn't built")?;
+        let count = |db: &Connection| {
+            db.query_row("SELECT count(*) FROM commits", [], |row| {
+                row.get::<_, i64>(0)
+            })
+        };
+        let warm_count = count(&db)?;
+        let message: String = db.query_row(
+            "SELECT message FROM commits WHERE hash = ?",
+            [first.to_string()],
+            |row| row.get(0),
+        )?;
+        commit_file(&repo, "file.txt", "three\n", "third")?;
+        let moved = index.get(&repo, &git.config);
+        let walked_count = count(&db)?;
+        std::fs::remove_dir_all(&path)?;
+
+        assert_eq!(warmed.commits.len(), 2);
+        assert_eq!(
+            warmed.commit(first).map(|c| c.hash.clone()),
+            Some(first.to_string())
+        );
+        assert_eq!(warm_count, 2);
+        assert_eq!(message, "first");
+        assert!(moved.is_none());
+        assert_eq!(walked_count, 3);
+
+        Ok(())
+    }
+}
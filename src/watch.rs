@@ -0,0 +1,114 @@
+use crate::output::OutputOptions;
+use crate::params::Params;
+use crate::stats_cache::CACHE_DIR;
+use crate::utils::execute_all_and_write;
+use crate::CustomError;
+use git2::Repository;
+use notify::event::{EventKind, ModifyKind};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use rusqlite::Connection;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// Changes arriving within this window of each other are handled by a single re-run, a single
+/// git operation touches many files under `.git`.
+const DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Runs `sql`, then clears the screen and re-runs it every time something below the `.git`
+/// directory of the repository at `repository` changes. Runs until the process is interrupted.
+pub fn run(
+    db: &Connection,
+    repository: &Path,
+    sql: &str,
+    params: &Params,
+    output: &OutputOptions,
+) -> Result<(), CustomError> {
+    let changes = Changes::watch(repository)?;
+    loop {
+        print!("\x1b[2J\x1b[H");
+        println!(
+            "Every change to {}: {}\n",
+            changes.git_dir.display(),
+            chrono::Local::now().format("%F %T")
+        );
+        if let Err(e) = execute_all_and_write(db, sql, params, output, None, &mut std::io::stdout())
+        {
+            eprintln!("error: {}", e.diagnose(db, sql));
+        }
+        std::io::stdout().flush()?;
+
+        if !changes.wait()? {
+            return Ok(());
+        }
+    }
+}
+
+/// The changes below the `.git` directory of a repository, the ones a git operation
+/// makes at once count as a single change.
+pub(crate) struct Changes {
+    git_dir: PathBuf,
+    events: mpsc::Receiver<notify::Result<notify::Event>>,
+    _watcher: RecommendedWatcher,
+}
+
+impl Changes {
+    pub(crate) fn watch(repository: &Path) -> Result<Changes, CustomError> {
+        let git_dir = Repository::discover(repository)?.path().to_path_buf();
+        let (tx, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(tx)?;
+        watcher.watch(&git_dir, RecursiveMode::Recursive)?;
+        Ok(Changes {
+            git_dir,
+            events,
+            _watcher: watcher,
+        })
+    }
+
+    /// Waits for the next change, false when the repository is no longer watched.
+    pub(crate) fn wait(&self) -> Result<bool, CustomError> {
+        loop {
+            match self.events.recv() {
+                Ok(event) => {
+                    if is_change(&event?, &self.git_dir) {
+                        break;
+                    }
+                }
+                Err(_) => return Ok(false),
+            }
+        }
+        self.settle();
+        Ok(true)
+    }
+
+    /// Whether something changed since the last call, without waiting for a change.
+    #[cfg(feature = "tui")]
+    pub(crate) fn changed(&self) -> Result<bool, CustomError> {
+        let mut changed = false;
+        while let Ok(event) = self.events.try_recv() {
+            changed |= is_change(&event?, &self.git_dir);
+        }
+        if changed {
+            self.settle();
+        }
+        Ok(changed)
+    }
+
+    /// Waits for the rest of the changes of a git operation.
+    fn settle(&self) {
+        while self.events.recv_timeout(DEBOUNCE).is_ok() {}
+    }
+}
+
+/// Reading the repository to answer the query must not trigger another run, neither must the
+/// stats cache it writes.
+fn is_change(event: &notify::Event, git_dir: &Path) -> bool {
+    let cache_dir = git_dir.join(CACHE_DIR);
+    let is_read = matches!(
+        event.kind,
+        EventKind::Access(_) | EventKind::Modify(ModifyKind::Metadata(_)) | EventKind::Other
+    );
+    let is_cache = !event.paths.is_empty() && event.paths.iter().all(|p| p.starts_with(&cache_dir));
+    !is_read && !is_cache
+}
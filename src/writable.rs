@@ -0,0 +1,377 @@
+use crate::{to_utc, trace_index_info, CustomError, TableConfig};
+use git2::{Repository, Time};
+use itertools::Itertools;
+use rusqlite::types::{Value, ValueRef};
+use rusqlite::vtab::{
+    sqlite3_vtab, sqlite3_vtab_cursor, Context, CreateVTab, IndexConstraintOp, IndexInfo,
+    UpdateVTab, VTab, VTabConnection, VTabCursor, VTabKind, Values,
+};
+use std::os::raw::c_int;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+use tracing::{debug, debug_span, info};
+
+/// The values of a row of a writable table, in the order of its columns, without the hidden
+/// repository column.
+pub(crate) type Row = Vec<Value>;
+
+/// Changes the first row, as it was listed, into the second one.
+pub(crate) type Update = fn(&Repository, &Row, &Row) -> Result<(), CustomError>;
+
+/// A table over something a repository keeps, like its tags, that statements can change.
+/// SELECT lists the rows of a repository, INSERT, UPDATE and DELETE change the repository row
+/// by row as the statement runs.
+///
+/// The tables have no transactions of their own: a change is made in the repository when its
+/// row is written, a ROLLBACK doesn't undo it.
+#[derive(Debug)]
+pub(crate) struct Table {
+    pub(crate) name: &'static str,
+    /// The name and declared type of each column, the hidden `repo` column comes after them
+    pub(crate) columns: &'static [(&'static str, &'static str)],
+    /// The rows of a repository
+    pub(crate) rows: fn(&Repository) -> Result<Vec<Row>, CustomError>,
+    /// Adds the row, the columns the INSERT leaves out are NULL
+    pub(crate) insert: fn(&Repository, &Row) -> Result<(), CustomError>,
+    /// None for tables whose rows can't be changed, only deleted and inserted
+    pub(crate) update: Option<Update>,
+    /// Removes the row, as it was listed
+    pub(crate) delete: fn(&Repository, &Row) -> Result<(), CustomError>,
+}
+
+impl Table {
+    fn schema(&self) -> String {
+        let columns = self.columns.iter();
+        let mut columns = columns.map(|(name, declared)| format!("{} {}", name, declared));
+        format!(
+            "create table {}({}, repo hidden)",
+            self.name,
+            columns.join(", ")
+        )
+    }
+}
+
+/// The rows the scans of a table returned, by rowid. SQLite picks the rows a DELETE or UPDATE
+/// changes by scanning the table, and passes their rowids back to be changed.
+#[derive(Debug, Default)]
+struct Scanned {
+    /// The rowid the next scan starts at, rowids aren't reused
+    next: i64,
+    /// The first rowid of each scan, the repository argument it read and its rows
+    scans: Vec<(i64, Option<String>, Arc<Vec<Row>>)>,
+}
+
+impl Scanned {
+    fn add(&mut self, repo_param: Option<String>, rows: Arc<Vec<Row>>) -> i64 {
+        let first = self.next;
+        self.next += rows.len().max(1) as i64;
+        self.scans.push((first, repo_param, rows));
+        first
+    }
+
+    fn get(&self, rowid: i64) -> Option<(Option<&str>, &Row)> {
+        self.scans.iter().find_map(|(first, repo_param, rows)| {
+            let row = rows.get(usize::try_from(rowid - first).ok()?)?;
+            Some((repo_param.as_deref(), row))
+        })
+    }
+}
+
+#[repr(C)]
+pub(crate) struct WritableTable {
+    base: sqlite3_vtab,
+    config: TableConfig,
+    table: &'static Table,
+    /// Shared with the cursors, which add the rows they scan
+    scanned: Arc<Mutex<Scanned>>,
+}
+
+impl WritableTable {
+    fn scanned(&self) -> MutexGuard<'_, Scanned> {
+        self.scanned.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The repository argument and the values of the row SQLite passes `rowid` of.
+    fn scanned_row(&self, rowid: i64) -> Result<(Option<String>, Row), CustomError> {
+        let scanned = self.scanned();
+        let (repo_param, row) = scanned.get(rowid).ok_or_else(|| {
+            CustomError::InvalidArgument(format!(
+                "{} has no row {}, rows can only be changed by the statement that read them",
+                self.table.name, rowid
+            ))
+        })?;
+        Ok((repo_param.map(str::to_string), row.clone()))
+    }
+
+    fn delete_row(&mut self, rowid: i64) -> Result<(), CustomError> {
+        let (repo_param, row) = self.scanned_row(rowid)?;
+        let repo = self.config.open_repository(repo_param.as_deref())?;
+        info!(table = self.table.name, ?row, "delete");
+        (self.table.delete)(&repo, &row)
+    }
+
+    fn insert_row(&mut self, args: &Values<'_>) -> Result<i64, CustomError> {
+        let (repo_param, row) = self.values(args)?;
+        let repo = self.config.open_repository(repo_param.as_deref())?;
+        info!(table = self.table.name, ?row, "insert");
+        (self.table.insert)(&repo, &row)?;
+        Ok(self.scanned().add(repo_param, Arc::new(vec![row])))
+    }
+
+    fn update_row(&mut self, args: &Values<'_>) -> Result<(), CustomError> {
+        let Some(update) = self.table.update else {
+            return Err(CustomError::InvalidArgument(format!(
+                "{} can't be updated, delete the row and insert the new one",
+                self.table.name
+            )));
+        };
+        let (repo_param, old) = self.scanned_row(args.get(0)?)?;
+        let (new_repo_param, new) = self.values(args)?;
+        if new_repo_param != repo_param {
+            return Err(CustomError::InvalidArgument(format!(
+                "the rows of {} can't move to another repository",
+                self.table.name
+            )));
+        }
+        let repo = self.config.open_repository(repo_param.as_deref())?;
+        info!(table = self.table.name, ?old, ?new, "update");
+        update(&repo, &old, &new)
+    }
+
+    /// The repository argument and the values of the row an INSERT or UPDATE writes, which
+    /// follow the old and the new rowid.
+    fn values(&self, args: &Values<'_>) -> Result<(Option<String>, Row), CustomError> {
+        let mut values = args.iter().skip(2).map(Value::from).collect_vec();
+        let repo_param = match values.pop() {
+            None | Some(Value::Null) => None,
+            Some(Value::Text(repo)) => Some(repo),
+            Some(value) => {
+                return Err(CustomError::InvalidArgument(format!(
+                    "the repository must be TEXT, got {}",
+                    value.data_type()
+                )))
+            }
+        };
+        Ok((repo_param, values))
+    }
+}
+
+unsafe impl<'a> VTab<'a> for WritableTable {
+    type Aux = (TableConfig, &'static Table);
+    type Cursor = WritableCursor;
+
+    fn connect(
+        _db: &mut VTabConnection,
+        aux: Option<&Self::Aux>,
+        _args: &[&[u8]],
+    ) -> rusqlite::Result<(String, Self)> {
+        let Some((config, table)) = aux.cloned() else {
+            return Err(rusqlite::Error::ModuleError(
+                "the writable tables are registered with their table".to_string(),
+            ));
+        };
+        Ok((
+            table.schema(),
+            WritableTable {
+                base: sqlite3_vtab::default(),
+                config,
+                table,
+                scanned: Arc::default(),
+            },
+        ))
+    }
+
+    fn best_index(&self, info: &mut IndexInfo) -> rusqlite::Result<()> {
+        trace_index_info(self.table.name, info);
+        let repo = self.table.columns.len() as c_int;
+        let usable = info.constraints().position(|constraint| {
+            constraint.column() == repo
+                && constraint.is_usable()
+                && constraint.operator() == IndexConstraintOp::SQLITE_INDEX_CONSTRAINT_EQ
+        });
+        let idx_num = match usable {
+            Some(i) => {
+                let mut usage = info.constraint_usage(i);
+                usage.set_argv_index(1);
+                usage.set_omit(true);
+                1
+            }
+            None => 0,
+        };
+        debug!(table = self.table.name, idx_num, "best_index");
+        info.set_idx_num(idx_num);
+        // Every scan lists the whole repository, however it's picked
+        info.set_estimated_cost(1000.0);
+        info.set_estimated_rows(100);
+        Ok(())
+    }
+
+    fn open(&mut self) -> rusqlite::Result<WritableCursor> {
+        // The rowids of statements that finished aren't passed back anymore
+        if Arc::strong_count(&self.scanned) == 1 {
+            self.scanned().scans.clear();
+        }
+        Ok(WritableCursor {
+            base: Default::default(),
+            config: self.config.clone(),
+            table: self.table,
+            scanned: self.scanned.clone(),
+            rows: Arc::default(),
+            first: 0,
+            i: 0,
+            repo_param: None,
+            scan: None,
+        })
+    }
+}
+
+impl CreateVTab<'_> for WritableTable {
+    const KIND: VTabKind = VTabKind::EponymousOnly;
+}
+
+impl UpdateVTab<'_> for WritableTable {
+    fn delete(&mut self, arg: ValueRef<'_>) -> rusqlite::Result<()> {
+        let rowid = arg.as_i64()?;
+        Ok(self.delete_row(rowid)?)
+    }
+
+    fn insert(&mut self, args: &Values<'_>) -> rusqlite::Result<i64> {
+        Ok(self.insert_row(args)?)
+    }
+
+    fn update(&mut self, args: &Values<'_>) -> rusqlite::Result<()> {
+        Ok(self.update_row(args)?)
+    }
+}
+
+#[repr(C)]
+pub(crate) struct WritableCursor {
+    base: sqlite3_vtab_cursor,
+    config: TableConfig,
+    table: &'static Table,
+    scanned: Arc<Mutex<Scanned>>,
+    rows: Arc<Vec<Row>>,
+    /// The rowid of the first row
+    first: i64,
+    i: usize,
+    repo_param: Option<String>,
+    /// The scan of `--profile` the rows count towards
+    scan: Option<usize>,
+}
+
+impl WritableCursor {
+    fn init(&mut self, idx_num: c_int, vals: &[ValueRef]) -> Result<(), CustomError> {
+        self.i = 0;
+        self.rows = Arc::default();
+        self.repo_param = match (idx_num, vals.first()) {
+            (1, Some(ValueRef::Null)) => return Ok(()),
+            (1, Some(value)) => match value.as_str() {
+                Ok(repo) => Some(repo.to_string()),
+                Err(_) => {
+                    return Err(CustomError::InvalidArgument(format!(
+                        "the repository must be TEXT, got {}",
+                        value.data_type()
+                    )))
+                }
+            },
+            _ => None,
+        };
+        let repo = self.config.open_repository(self.repo_param.as_deref())?;
+        self.rows = Arc::new((self.table.rows)(&repo)?);
+        let mut scanned = self.scanned.lock().unwrap_or_else(PoisonError::into_inner);
+        self.first = scanned.add(self.repo_param.clone(), self.rows.clone());
+        Ok(())
+    }
+}
+
+unsafe impl VTabCursor for WritableCursor {
+    fn filter(
+        &mut self,
+        idx_num: c_int,
+        _idx_str: Option<&str>,
+        args: &Values<'_>,
+    ) -> rusqlite::Result<()> {
+        let vals = args.iter().collect_vec();
+        let name = self.table.name;
+        let _span = debug_span!("filter", table = name, idx_num, ?vals).entered();
+        let start = Instant::now();
+        self.init(idx_num, &vals)?;
+        let row = !self.eof();
+        self.scan = (self.config.profiler).filter(name, &vals, start.elapsed(), row);
+        Ok(())
+    }
+
+    fn next(&mut self) -> rusqlite::Result<()> {
+        self.i += 1;
+        let row = !self.eof();
+        self.config.profiler.next(self.scan, Duration::ZERO, row);
+        Ok(())
+    }
+
+    fn eof(&self) -> bool {
+        self.i >= self.rows.len()
+    }
+
+    fn column(&self, ctx: &mut Context, i: c_int) -> rusqlite::Result<()> {
+        match self.rows[self.i].get(i as usize) {
+            Some(value) => ctx.set_result(value),
+            None => ctx.set_result(&self.repo_param),
+        }
+    }
+
+    fn rowid(&self) -> rusqlite::Result<i64> {
+        Ok(self.first + self.i as i64)
+    }
+}
+
+/// The text of column `i` of `row`, None when it's NULL.
+pub(crate) fn text<'r>(
+    table: &Table,
+    row: &'r Row,
+    i: usize,
+) -> Result<Option<&'r str>, CustomError> {
+    match &row[i] {
+        Value::Null => Ok(None),
+        Value::Text(text) => Ok(Some(text)),
+        value => Err(CustomError::InvalidArgument(format!(
+            "{}.{} must be TEXT, got {}",
+            table.name,
+            table.columns[i].0,
+            value.data_type()
+        ))),
+    }
+}
+
+/// The text of column `i` of `row`, which the row can't do without.
+pub(crate) fn required<'r>(table: &Table, row: &'r Row, i: usize) -> Result<&'r str, CustomError> {
+    text(table, row, i)?.ok_or_else(|| {
+        CustomError::InvalidArgument(format!(
+            "{}.{} can't be NULL",
+            table.name, table.columns[i].0
+        ))
+    })
+}
+
+/// Whether column `i` of `row` is true, NULL is false.
+pub(crate) fn flag(table: &Table, row: &Row, i: usize) -> Result<bool, CustomError> {
+    match &row[i] {
+        Value::Null => Ok(false),
+        Value::Integer(value) => Ok(*value != 0),
+        value => Err(CustomError::InvalidArgument(format!(
+            "{}.{} must be a boolean, got {}",
+            table.name,
+            table.columns[i].0,
+            value.data_type()
+        ))),
+    }
+}
+
+/// A git time as a DATETIME value, like the ones of `commits`.
+pub(crate) fn timestamp(time: Time) -> Value {
+    Value::Text(to_utc(time).format("%F %T%:z").to_string())
+}
+
+/// Text as a value, NULL for None.
+pub(crate) fn optional(text: Option<impl Into<String>>) -> Value {
+    text.map_or(Value::Null, |text| Value::Text(text.into()))
+}